@@ -0,0 +1,9 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use fenwick_bit_tree::benchmarks::compare_trees;
+
+fn trees(c: &mut Criterion) {
+    compare_trees(c, &[16, 64, 1_000, 10_000, 100_000], |i| (i % 100) as i64);
+}
+
+criterion_group!(benches, trees);
+criterion_main!(benches);
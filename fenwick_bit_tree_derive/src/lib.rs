@@ -0,0 +1,123 @@
+//! Derive macros for `fenwick-bit-tree` value traits. Use them through the `derive`
+//! feature of the main crate.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, parse_quote, Data, DeriveInput, Fields, Index, Type};
+
+/// Derives `FenwickTreeValue` for structs which fields are all value types. Values
+/// are aggregated field by field, are identity or valid if all fields are, and cancel
+/// if any field does.
+#[proc_macro_derive(FenwickTreeValue)]
+pub fn derive_fenwick_tree_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(
+        input,
+        quote!(::fenwick_bit_tree::FenwickTreeValue),
+        |fields, types| {
+            let stores = fields
+                .iter()
+                .map(|field| quote!(self.#field.store_value(&other.#field);));
+            let absorbs = fields
+                .iter()
+                .map(|field| quote!(self.#field.absorb(other.#field);));
+            quote! {
+                const CANCELS: bool =
+                    false #(|| <#types as ::fenwick_bit_tree::FenwickTreeValue>::CANCELS)*;
+
+                fn store_value(&mut self, other: &Self) {
+                    #(#stores)*
+                }
+
+                fn absorb(&mut self, other: Self) {
+                    #(#absorbs)*
+                }
+
+                fn is_identity(&self) -> bool {
+                    true #(&& self.#fields.is_identity())*
+                }
+
+                fn is_valid(&self) -> bool {
+                    true #(&& self.#fields.is_valid())*
+                }
+            }
+        },
+    )
+    .unwrap_or_else(|err| err.to_compile_error())
+    .into()
+}
+
+/// Derives `InvertibleValue` for structs which fields are all invertible value types.
+/// Values are substracted field by field.
+#[proc_macro_derive(InvertibleValue)]
+pub fn derive_invertible_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(
+        input,
+        quote!(::fenwick_bit_tree::InvertibleValue),
+        |fields, _| {
+            let substractions = fields
+                .iter()
+                .map(|field| quote!(#field: self.#field.substract(other.#field),));
+            quote! {
+                fn substract(self, other: Self) -> Self {
+                    Self { #(#substractions)* }
+                }
+            }
+        },
+    )
+    .unwrap_or_else(|err| err.to_compile_error())
+    .into()
+}
+
+/// Generates trait implementation, requiring the same trait from every type parameter
+/// and passing field accessors and types to the `body` generator.
+fn expand(
+    mut input: DeriveInput,
+    trait_path: TokenStream2,
+    body: impl Fn(&[TokenStream2], &[Type]) -> TokenStream2,
+) -> syn::Result<TokenStream2> {
+    let (fields, types): (Vec<TokenStream2>, Vec<Type>) = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => fields
+                .named
+                .iter()
+                .map(|field| {
+                    let name = field.ident.as_ref().unwrap();
+                    (quote!(#name), field.ty.clone())
+                })
+                .unzip(),
+            Fields::Unnamed(fields) => fields
+                .unnamed
+                .iter()
+                .enumerate()
+                .map(|(idx, field)| {
+                    let idx = Index::from(idx);
+                    (quote!(#idx), field.ty.clone())
+                })
+                .unzip(),
+            Fields::Unit => (vec![], vec![]),
+        },
+        _ => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "Value traits can only be derived for structs.",
+            ))
+        }
+    };
+
+    for param in input.generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#trait_path));
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let body = body(&fields, &types);
+
+    Ok(quote! {
+        impl #impl_generics #trait_path for #name #ty_generics #where_clause {
+            #body
+        }
+    })
+}
@@ -0,0 +1,162 @@
+//! Classic algorithms built on top of Fenwick trees.
+
+use crate::{FixedSizeFenwickTree, Max};
+
+/// Returns, for every item, how many of the items before it are smaller, in
+/// `O(n log n)`. Items are compressed to their ranks first, so any [`Ord`] type works.
+///
+/// Summing the result gives the number of ordered pairs, thus the number of
+/// inversions is `n * (n - 1) / 2` minus that sum and the number of ties.
+///
+/// ```
+/// use fenwick_bit_tree::algorithms::rank_array;
+///
+/// assert_eq!(rank_array(&[3, 1, 4, 1, 5]), vec![0, 0, 2, 0, 4]);
+/// assert_eq!(rank_array(&["b", "a", "c"]), vec![0, 0, 2]);
+/// ```
+pub fn rank_array<T: Ord>(items: &[T]) -> Vec<usize> {
+    let ranks = compress(items);
+    let mut seen = FixedSizeFenwickTree::<usize>::new(ranks.len());
+
+    ranks
+        .into_iter()
+        .map(|rank| {
+            let smaller = match rank {
+                0 => 0,
+                rank => seen.prefix(rank - 1),
+            };
+            seen.add(rank, 1);
+            smaller
+        })
+        .collect()
+}
+
+/// Returns length of the longest strictly increasing subsequence of `items`, in
+/// `O(n log n)`.
+///
+/// ```
+/// use fenwick_bit_tree::algorithms::longest_increasing_subsequence;
+///
+/// assert_eq!(longest_increasing_subsequence(&[3, 1, 4, 1, 5, 9, 2, 6]), 4);
+/// ```
+pub fn longest_increasing_subsequence<T: Ord>(items: &[T]) -> usize {
+    longest_increasing_subsequence_indices(items).len()
+}
+
+/// Returns ascending indexes of items forming one of the longest strictly increasing
+/// subsequences of `items`, in `O(n log n)`.
+///
+/// Subsequence length ending at every item is the prefix maximum over smaller items
+/// plus one, so lengths are kept in a tree of [`Max`] values indexed by item ranks,
+/// paired with the index of the item ending the subsequence.
+///
+/// ```
+/// use fenwick_bit_tree::algorithms::longest_increasing_subsequence_indices;
+///
+/// let items = [3, 1, 4, 1, 5, 9, 2, 6];
+/// let indices = longest_increasing_subsequence_indices(&items);
+/// let subsequence: Vec<_> = indices.iter().map(|idx| items[*idx]).collect();
+/// assert_eq!(subsequence, vec![1, 4, 5, 6]);
+/// ```
+pub fn longest_increasing_subsequence_indices<T: Ord>(items: &[T]) -> Vec<usize> {
+    let ranks = compress(items);
+    let mut best = FixedSizeFenwickTree::<Max<(usize, usize)>>::new(ranks.len());
+    let mut previous = vec![None; items.len()];
+    let mut longest = Max((0, 0));
+
+    for (idx, rank) in ranks.into_iter().enumerate() {
+        let Max((len, end)) = match rank {
+            0 => Max((0, 0)),
+            rank => best.prefix(rank - 1),
+        };
+        if len > 0 {
+            previous[idx] = Some(end);
+        }
+        let ending_here = Max((len + 1, idx));
+        best.add(rank, ending_here);
+        longest = longest.max(ending_here);
+    }
+
+    let Max((len, end)) = longest;
+    if len == 0 {
+        return Vec::new();
+    }
+    let mut indices: Vec<usize> = std::iter::successors(Some(end), |idx| previous[*idx]).collect();
+    indices.reverse();
+    indices
+}
+
+/// Replaces every item with its position among distinct items sorted.
+fn compress<T: Ord>(items: &[T]) -> Vec<usize> {
+    let mut distinct: Vec<&T> = items.iter().collect();
+    distinct.sort_unstable();
+    distinct.dedup();
+    items
+        .iter()
+        .map(|item| distinct.partition_point(|other| *other < item))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::algorithms::{
+        longest_increasing_subsequence, longest_increasing_subsequence_indices, rank_array,
+    };
+    use crate::workload::Workload;
+
+    #[test]
+    fn rank_array_matches_naive() {
+        let items: Vec<i32> = Workload::new(1, 1)
+            .deltas(-20..20)
+            .updates()
+            .map(|(_, item)| item as i32)
+            .take(200)
+            .collect();
+        let naive: Vec<usize> = (0..items.len())
+            .map(|i| items[..i].iter().filter(|item| **item < items[i]).count())
+            .collect();
+
+        assert_eq!(rank_array(&items), naive);
+    }
+
+    #[test]
+    fn longest_increasing_subsequence_matches_naive() {
+        for seed in 0..20 {
+            let items: Vec<u8> = Workload::new(seed, 30)
+                .updates()
+                .map(|(item, _)| item as u8)
+                .take(50)
+                .collect();
+            let mut naive = vec![1; items.len()];
+            for i in 0..items.len() {
+                for j in 0..i {
+                    if items[j] < items[i] {
+                        naive[i] = naive[i].max(naive[j] + 1);
+                    }
+                }
+            }
+
+            let indices = longest_increasing_subsequence_indices(&items);
+            assert_eq!(indices.len(), *naive.iter().max().unwrap());
+            assert!(indices.windows(2).all(|pair| pair[0] < pair[1]));
+            assert!(indices
+                .windows(2)
+                .all(|pair| items[pair[0]] < items[pair[1]]));
+        }
+    }
+
+    #[test]
+    fn longest_increasing_subsequence_edge_cases() {
+        assert_eq!(longest_increasing_subsequence::<u8>(&[]), 0);
+        assert_eq!(longest_increasing_subsequence(&[5, 5, 5]), 1);
+        assert_eq!(longest_increasing_subsequence_indices(&[3, 2, 1]), vec![2]);
+    }
+
+    #[test]
+    fn rank_array_edge_cases() {
+        assert_eq!(rank_array::<u8>(&[]), Vec::<usize>::new());
+        assert_eq!(rank_array(&[7, 7, 7]), vec![0, 0, 0]);
+        assert_eq!(rank_array(&[1, 2, 3]), vec![0, 1, 2]);
+        assert_eq!(rank_array(&[3, 2, 1]), vec![0, 0, 0]);
+    }
+}
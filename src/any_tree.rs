@@ -0,0 +1,105 @@
+use crate::{
+    FenwickReader, FenwickTreeValue, FenwickWriter, FixedSizeFenwickTree, GrowingFenwickTree,
+    TreeError,
+};
+
+/// Tree which implementation is picked at runtime, e.g. from configuration, without
+/// boxing or making consuming code generic.
+pub enum AnyFenwickTree<T: FenwickTreeValue> {
+    FixedSize(FixedSizeFenwickTree<T>),
+    Growing(GrowingFenwickTree<T>),
+}
+
+impl<T: FenwickTreeValue> From<FixedSizeFenwickTree<T>> for AnyFenwickTree<T> {
+    fn from(tree: FixedSizeFenwickTree<T>) -> Self {
+        AnyFenwickTree::FixedSize(tree)
+    }
+}
+
+impl<T: FenwickTreeValue> From<GrowingFenwickTree<T>> for AnyFenwickTree<T> {
+    fn from(tree: GrowingFenwickTree<T>) -> Self {
+        AnyFenwickTree::Growing(tree)
+    }
+}
+
+impl<T: FenwickTreeValue> FenwickReader for AnyFenwickTree<T> {
+    type Value = T;
+
+    fn query(&self, idx: usize) -> Result<T, TreeError> {
+        match self {
+            AnyFenwickTree::FixedSize(tree) => tree.query(idx),
+            AnyFenwickTree::Growing(tree) => tree.query(idx),
+        }
+    }
+
+    fn find_prefix(&self, target: T) -> Option<usize>
+    where
+        T: PartialOrd,
+    {
+        match self {
+            AnyFenwickTree::FixedSize(tree) => tree.find_prefix(target),
+            AnyFenwickTree::Growing(tree) => tree.find_prefix(target),
+        }
+    }
+}
+
+impl<T: FenwickTreeValue> FenwickWriter for AnyFenwickTree<T> {
+    fn update(&mut self, idx: usize, value: T) -> Result<(), TreeError> {
+        match self {
+            AnyFenwickTree::FixedSize(tree) => tree.update(idx, value),
+            AnyFenwickTree::Growing(tree) => tree.update(idx, value),
+        }
+    }
+
+    fn update_target(&self, idx: usize) -> Result<Option<usize>, TreeError> {
+        match self {
+            AnyFenwickTree::FixedSize(tree) => tree.update_target(idx),
+            AnyFenwickTree::Growing(tree) => tree.update_target(idx),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::any_tree::AnyFenwickTree;
+    use crate::{
+        FenwickReader, FenwickWriter, FixedSizeFenwickTree, GrowingFenwickTree, TreeError,
+    };
+
+    fn build(growing: bool) -> AnyFenwickTree<i32> {
+        if growing {
+            GrowingFenwickTree::new(0).into()
+        } else {
+            FixedSizeFenwickTree::new(16).into()
+        }
+    }
+
+    #[test]
+    fn delegates_to_fixed_size_tree() {
+        let mut tree = build(false);
+        tree.update(3, 1).unwrap();
+        tree.update(10, 2).unwrap();
+
+        assert_eq!(tree.query(15).unwrap(), 3);
+        assert_eq!(tree.range_query(3, 10).unwrap(), 2);
+        assert_eq!(
+            tree.update(20, 1),
+            Err(TreeError::IndexOutOfBounds {
+                index: 20,
+                bound: 16
+            })
+        );
+        assert!(matches!(tree, AnyFenwickTree::FixedSize(_)));
+    }
+
+    #[test]
+    fn delegates_to_growing_tree() {
+        let mut tree = build(true);
+        tree.update(3, 1).unwrap();
+        tree.update(100, 2).unwrap();
+
+        assert_eq!(tree.query(1_000).unwrap(), 3);
+        assert_eq!(tree.range_query(3, 100).unwrap(), 2);
+        assert!(matches!(tree, AnyFenwickTree::Growing(_)));
+    }
+}
@@ -0,0 +1,179 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::{mpsc, oneshot};
+
+use crate::{FenwickWriter, InvertibleValue, TreeError};
+
+type Read<F> = Box<dyn FnOnce(&F) + Send>;
+
+/// Handle of a tokio task owning a tree, so async services query it without holding
+/// locks across executor threads. Updates are consumed from an [`mpsc::Receiver`]
+/// passed to [`AsyncFenwickTree::spawn`], queries are sent to the task and awaited.
+///
+/// Pending updates are applied before queries, so a query awaited after an update was
+/// sent observes it. Updates rejected by the tree are dropped and counted with
+/// [`AsyncFenwickTree::rejected_updates`]. The task stops once all handles are dropped.
+///
+/// ```
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+/// runtime.block_on(async {
+///     let (updates, receiver) = tokio::sync::mpsc::channel(64);
+///     let tree = AsyncFenwickTree::spawn(FixedSizeFenwickTree::<i64>::new(16), receiver);
+///
+///     updates.send((3, 5)).await.unwrap();
+///     assert_eq!(tree.query(15).await.unwrap(), 5);
+/// });
+/// ```
+pub struct AsyncFenwickTree<F: FenwickWriter> {
+    reads: mpsc::UnboundedSender<Read<F>>,
+    rejected: Arc<AtomicU64>,
+}
+
+impl<F: FenwickWriter> Clone for AsyncFenwickTree<F> {
+    fn clone(&self) -> Self {
+        Self {
+            reads: self.reads.clone(),
+            rejected: Arc::clone(&self.rejected),
+        }
+    }
+}
+
+impl<F> AsyncFenwickTree<F>
+where
+    F: FenwickWriter + Send + 'static,
+    F::Value: Send,
+{
+    /// Spawns the task owning `tree` and applying `updates` to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a tokio runtime.
+    pub fn spawn(tree: F, updates: mpsc::Receiver<(usize, F::Value)>) -> Self {
+        let (reads, receiver) = mpsc::unbounded_channel();
+        let rejected = Arc::new(AtomicU64::new(0));
+        tokio::spawn(serve(tree, updates, receiver, Arc::clone(&rejected)));
+        Self { reads, rejected }
+    }
+
+    /// Runs `read` against the tree within the task, and returns its result.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the task has stopped.
+    ///
+    pub async fn read<R, Q>(&self, read: Q) -> Result<R, TreeError>
+    where
+        R: Send + 'static,
+        Q: FnOnce(&F) -> R + Send + 'static,
+    {
+        let (reply, result) = oneshot::channel();
+        self.reads
+            .send(Box::new(move |tree| {
+                let _ = reply.send(read(tree));
+            }))
+            .map_err(|_| TreeError::Disconnected)?;
+        result.await.map_err(|_| TreeError::Disconnected)
+    }
+
+    /// Same as [`crate::FenwickReader::query`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `idx` is out of bounds, or the task has
+    /// stopped.
+    ///
+    pub async fn query(&self, idx: usize) -> Result<F::Value, TreeError> {
+        self.read(move |tree| tree.query(idx)).await?
+    }
+
+    /// Same as [`crate::FenwickReader::range_query`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if range is invalid or out of bounds, or the
+    /// task has stopped.
+    ///
+    pub async fn range_query(&self, from: usize, to: usize) -> Result<F::Value, TreeError>
+    where
+        F::Value: InvertibleValue,
+    {
+        self.read(move |tree| tree.range_query(from, to)).await?
+    }
+
+    /// Returns number of updates dropped, as the tree rejected them.
+    pub fn rejected_updates(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+}
+
+async fn serve<F: FenwickWriter>(
+    mut tree: F,
+    mut updates: mpsc::Receiver<(usize, F::Value)>,
+    mut reads: mpsc::UnboundedReceiver<Read<F>>,
+    rejected: Arc<AtomicU64>,
+) {
+    let mut ingesting = true;
+    loop {
+        tokio::select! {
+            biased;
+            update = updates.recv(), if ingesting => match update {
+                Some((idx, value)) => {
+                    if tree.update(idx, value).is_err() {
+                        rejected.fetch_add(1, Ordering::Relaxed);
+                    }
+                }
+                None => ingesting = false,
+            },
+            read = reads.recv() => match read {
+                Some(read) => read(&tree),
+                None => return,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::mpsc;
+
+    use crate::async_tree::AsyncFenwickTree;
+    use crate::{FenwickReader, FixedSizeFenwickTree, GrowingFenwickTree, TreeError};
+
+    #[tokio::test]
+    async fn queries_see_sent_updates() {
+        let (updates, receiver) = mpsc::channel(4);
+        let tree = AsyncFenwickTree::spawn(GrowingFenwickTree::<i64>::new(0), receiver);
+
+        let producer = tokio::spawn(async move {
+            for idx in 0..100 {
+                updates.send((idx, 1)).await.unwrap();
+            }
+        });
+        producer.await.unwrap();
+
+        assert_eq!(tree.query(99).await.unwrap(), 100);
+        assert_eq!(tree.clone().range_query(49, 99).await.unwrap(), 50);
+        let doubled = tree.read(|tree| tree.query(9).map(|sum| sum * 2));
+        assert_eq!(doubled.await.unwrap(), Ok(20));
+    }
+
+    #[tokio::test]
+    async fn rejected_updates() {
+        let (updates, receiver) = mpsc::channel(4);
+        let tree = AsyncFenwickTree::spawn(FixedSizeFenwickTree::<i32>::new(4), receiver);
+
+        updates.send((4, 1)).await.unwrap();
+        updates.send((2, 3)).await.unwrap();
+        drop(updates);
+
+        assert_eq!(tree.query(3).await.unwrap(), 3);
+        assert_eq!(tree.rejected_updates(), 1);
+        assert_eq!(
+            tree.query(4).await,
+            Err(TreeError::IndexOutOfBounds { index: 4, bound: 4 })
+        );
+    }
+}
@@ -0,0 +1,189 @@
+//! Reusable [criterion] harness comparing tree implementations, available with
+//! `benchmarks` feature. Crate's own suite runs it for [`i64`] values, custom value
+//! types can be benchmarked the same way:
+//!
+//! ```rust,no_run
+//! use criterion::Criterion;
+//! use fenwick_bit_tree::benchmarks::compare_trees;
+//! use fenwick_bit_tree::prelude::*;
+//!
+//! let mut c = Criterion::default();
+//! compare_trees(&mut c, &[1_000, 100_000], |i| ModInt::<1_000_007>::new(i as u64));
+//! ```
+
+use criterion::{BenchmarkId, Criterion};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+use crate::{
+    FenwickReader, FenwickTree, FenwickTreeValue, FenwickWriter, FixedSizeFenwickTree,
+    GrowingFenwickTree, HybridFenwickTree, TreeError,
+};
+
+/// Order in which benchmarks visit indexes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessPattern {
+    Sequential,
+    Random,
+}
+
+impl AccessPattern {
+    fn indexes(self, size: usize) -> Vec<usize> {
+        let mut indexes: Vec<usize> = (0..size).collect();
+        if self == AccessPattern::Random {
+            indexes.shuffle(&mut StdRng::seed_from_u64(0));
+        }
+        indexes
+    }
+}
+
+/// Baseline keeping plain prefix sums: `O(1)` queries and `O(n)` updates.
+pub struct NaivePrefixSums<T> {
+    sums: Vec<T>,
+}
+
+impl<T: FenwickTreeValue> NaivePrefixSums<T> {
+    pub fn new(size: usize) -> Self {
+        Self {
+            sums: vec![T::default(); size],
+        }
+    }
+}
+
+impl<T: FenwickTreeValue> FenwickReader for NaivePrefixSums<T> {
+    type Value = T;
+
+    fn query(&self, idx: usize) -> Result<T, TreeError> {
+        self.sums
+            .get(idx)
+            .cloned()
+            .ok_or(TreeError::IndexOutOfBounds {
+                index: idx,
+                bound: self.sums.len(),
+            })
+    }
+}
+
+impl<T: FenwickTreeValue> FenwickWriter for NaivePrefixSums<T> {
+    fn update(&mut self, idx: usize, value: T) -> Result<(), TreeError> {
+        if idx >= self.sums.len() {
+            return Err(TreeError::IndexOutOfBounds {
+                index: idx,
+                bound: self.sums.len(),
+            });
+        }
+        for sum in &mut self.sums[idx..] {
+            sum.store_value(&value);
+        }
+        Ok(())
+    }
+}
+
+/// Benchmarks updates of trees created by `new_tree` for every size, storing `value(i)`
+/// at index `i`. Results of different implementations sharing the same `pattern` land
+/// in the same criterion group.
+pub fn bench_updates<F: FenwickTree>(
+    c: &mut Criterion,
+    name: &str,
+    pattern: AccessPattern,
+    sizes: &[usize],
+    new_tree: impl Fn(usize) -> F,
+    value: impl Fn(usize) -> F::Value,
+) {
+    let mut group = c.benchmark_group(format!("updates/{pattern:?}"));
+    for &size in sizes {
+        let indexes = pattern.indexes(size);
+        let mut tree = new_tree(size);
+        let mut step = 0;
+
+        group.bench_with_input(BenchmarkId::new(name, size), &size, |b, _| {
+            b.iter(|| {
+                let idx = indexes[step % size];
+                step += 1;
+                tree.update(idx, value(idx)).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Benchmarks queries of trees created by `new_tree` for every size and filled with
+/// `value(i)` at index `i`. Results of different implementations sharing the same
+/// `pattern` land in the same criterion group.
+pub fn bench_queries<F: FenwickTree>(
+    c: &mut Criterion,
+    name: &str,
+    pattern: AccessPattern,
+    sizes: &[usize],
+    new_tree: impl Fn(usize) -> F,
+    value: impl Fn(usize) -> F::Value,
+) {
+    let mut group = c.benchmark_group(format!("queries/{pattern:?}"));
+    for &size in sizes {
+        let indexes = pattern.indexes(size);
+        let mut tree = new_tree(size);
+        for idx in 0..size {
+            tree.update(idx, value(idx)).unwrap();
+        }
+        let mut step = 0;
+
+        group.bench_with_input(BenchmarkId::new(name, size), &size, |b, _| {
+            b.iter(|| {
+                let idx = indexes[step % size];
+                step += 1;
+                tree.query(idx).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+/// Compares [`FixedSizeFenwickTree`], [`GrowingFenwickTree`], [`HybridFenwickTree`]
+/// and [`NaivePrefixSums`] holding `value(i)` at index `i`, for every size and access
+/// pattern.
+pub fn compare_trees<T: FenwickTreeValue>(
+    c: &mut Criterion,
+    sizes: &[usize],
+    value: impl Fn(usize) -> T + Copy,
+) {
+    for pattern in [AccessPattern::Sequential, AccessPattern::Random] {
+        bench_updates(c, "fixed", pattern, sizes, FixedSizeFenwickTree::new, value);
+        bench_updates(c, "growing", pattern, sizes, GrowingFenwickTree::new, value);
+        bench_updates(c, "hybrid", pattern, sizes, HybridFenwickTree::new, value);
+        bench_updates(c, "naive", pattern, sizes, NaivePrefixSums::new, value);
+
+        bench_queries(c, "fixed", pattern, sizes, FixedSizeFenwickTree::new, value);
+        bench_queries(c, "growing", pattern, sizes, GrowingFenwickTree::new, value);
+        bench_queries(c, "hybrid", pattern, sizes, HybridFenwickTree::new, value);
+        bench_queries(c, "naive", pattern, sizes, NaivePrefixSums::new, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::benchmarks::{AccessPattern, NaivePrefixSums};
+    use crate::{FenwickReader, FenwickWriter, FixedSizeFenwickTree};
+
+    #[test]
+    fn naive_prefix_sums_match_tree() {
+        let mut naive = NaivePrefixSums::<i64>::new(50);
+        let mut tree = FixedSizeFenwickTree::<i64>::new(50);
+        for idx in AccessPattern::Random.indexes(50) {
+            naive.update(idx, idx as i64).unwrap();
+            tree.update(idx, idx as i64).unwrap();
+        }
+        for idx in 0..50 {
+            assert_eq!(naive.query(idx), tree.query(idx));
+        }
+        assert!(naive.query(50).is_err());
+        assert!(naive.update(50, 1).is_err());
+    }
+
+    #[test]
+    fn access_patterns_visit_every_index() {
+        let mut indexes = AccessPattern::Random.indexes(100);
+        indexes.sort();
+        assert_eq!(indexes, AccessPattern::Sequential.indexes(100));
+    }
+}
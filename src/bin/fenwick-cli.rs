@@ -0,0 +1,156 @@
+//! Loads `(index, value)` pairs from a CSV or JSON file into a tree and answers
+//! `query` and `range` commands read from stdin.
+//!
+//! ```bash
+//! cargo run --features cli --bin fenwick-cli -- points.csv
+//! ```
+//!
+//! CSV files hold `index,value` per line, JSON files hold an array of `[index, value]`
+//! pairs.
+
+use std::io::{self, BufRead, Write};
+use std::{env, fs, process};
+
+use fenwick_bit_tree::prelude::*;
+
+const HELP: &str = "\
+commands:
+  query <idx>        sum of values at indexes up to idx
+  range <from> <to>  sum of values at indexes from..=to
+  help               show this message
+  quit               exit";
+
+fn parse_csv(input: &str) -> Result<Vec<(usize, f64)>, String> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(number, line)| {
+            let (idx, value) = line
+                .split_once(',')
+                .ok_or_else(|| format!("line {}: expected `index,value`", number + 1))?;
+            let idx = idx
+                .trim()
+                .parse()
+                .map_err(|err| format!("line {}: {err}", number + 1))?;
+            let value = value
+                .trim()
+                .parse()
+                .map_err(|err| format!("line {}: {err}", number + 1))?;
+            Ok((idx, value))
+        })
+        .collect()
+}
+
+fn parse_json(input: &str) -> Result<Vec<(usize, f64)>, String> {
+    serde_json::from_str(input).map_err(|err| err.to_string())
+}
+
+fn load(path: &str) -> Result<GrowingFenwickTree<f64>, String> {
+    let input = fs::read_to_string(path).map_err(|err| format!("{path}: {err}"))?;
+    let points = if path.ends_with(".json") {
+        parse_json(&input)?
+    } else {
+        parse_csv(&input)?
+    };
+
+    let mut tree = GrowingFenwickTree::new(0);
+    for (idx, value) in points {
+        tree.update(idx, value).map_err(|err| err.to_string())?;
+    }
+    Ok(tree)
+}
+
+fn parse_index(arg: Option<&str>) -> Result<usize, String> {
+    let arg = arg.ok_or("missing index")?;
+    arg.parse().map_err(|err| format!("{arg}: {err}"))
+}
+
+/// Returns the answer to a command, or [`None`] when the session is over.
+fn execute(tree: &GrowingFenwickTree<f64>, line: &str) -> Option<Result<String, String>> {
+    let mut args = line.split_whitespace();
+    let res = match args.next() {
+        None => Ok(String::new()),
+        Some("quit" | "exit") => return None,
+        Some("help") => Ok(HELP.to_string()),
+        Some("query") => parse_index(args.next()).map(|idx| tree.query_clamped(idx).to_string()),
+        Some("range") => parse_index(args.next()).and_then(|from| {
+            let to = parse_index(args.next())?;
+            if from > to {
+                return Err(TreeError::InvalidRange { from, to }.to_string());
+            }
+            let before = from
+                .checked_sub(1)
+                .map_or(0.0, |idx| tree.query_clamped(idx));
+            Ok((tree.query_clamped(to) - before).to_string())
+        }),
+        Some(command) => Err(format!("unknown command `{command}`, try `help`")),
+    };
+    Some(res)
+}
+
+fn main() {
+    let Some(path) = env::args().nth(1) else {
+        eprintln!("usage: fenwick-cli <points.csv|points.json>");
+        process::exit(2);
+    };
+
+    let tree = load(&path).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        process::exit(1);
+    });
+
+    let stdin = io::stdin();
+    let mut stdout = io::stdout();
+    loop {
+        print!("> ");
+        let _ = stdout.flush();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) | Err(_) => break,
+            Ok(_) => {}
+        }
+
+        match execute(&tree, &line) {
+            None => break,
+            Some(Ok(answer)) if answer.is_empty() => {}
+            Some(Ok(answer)) => println!("{answer}"),
+            Some(Err(err)) => eprintln!("error: {err}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> GrowingFenwickTree<f64> {
+        let mut tree = GrowingFenwickTree::new(0);
+        for (idx, value) in parse_csv("0,1\n\n3, 2.5\n10,4\n").unwrap() {
+            tree.update(idx, value).unwrap();
+        }
+        tree
+    }
+
+    #[test]
+    fn parses_files() {
+        assert_eq!(
+            parse_json("[[0, 1.0], [3, 2]]").unwrap(),
+            vec![(0, 1.0), (3, 2.0)]
+        );
+        assert!(parse_csv("0,1\nnope").is_err());
+        assert!(parse_json("{}").is_err());
+    }
+
+    #[test]
+    fn answers_commands() {
+        let tree = tree();
+        assert_eq!(execute(&tree, "query 3"), Some(Ok("3.5".to_string())));
+        assert_eq!(execute(&tree, "range 3 10"), Some(Ok("6.5".to_string())));
+        assert_eq!(execute(&tree, "range 0 100"), Some(Ok("7.5".to_string())));
+        assert!(matches!(execute(&tree, "range 10 3"), Some(Err(_))));
+        assert!(matches!(execute(&tree, "sum 1"), Some(Err(_))));
+        assert_eq!(execute(&tree, "quit"), None);
+    }
+}
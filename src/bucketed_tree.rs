@@ -0,0 +1,253 @@
+use std::cmp::Ordering;
+
+use crate::{FenwickReader, FenwickWriter, FixedSizeFenwickTree, InvertibleValue, TreeError};
+
+/// Defines which edge of a bucket observations equal to a boundary belong to.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum BucketEdge {
+    /// Buckets are `[lower, upper)`, boundary belongs to the bucket it starts.
+    #[default]
+    Lower,
+    /// Buckets are `(lower, upper]`, boundary belongs to the bucket it ends, like
+    /// `le` buckets of Prometheus histograms.
+    Upper,
+}
+
+/// Defines what happens to observations below the first or beyond the last boundary.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum OutOfRangePolicy {
+    /// Observation is rejected with [`TreeError::OutOfRange`].
+    #[default]
+    Error,
+    /// Observation is counted in the first or the last bucket.
+    Clamp,
+    /// Observation is dropped.
+    Drop,
+}
+
+/// Histogram over caller supplied bucket boundaries, such as log-scale latency buckets
+/// or price bands. Observations are mapped to buckets with binary search and values
+/// of buckets are stored in a [`FixedSizeFenwickTree`], so cumulative values up to any
+/// observation are cheap.
+///
+/// `n` boundaries make `n - 1` buckets. Observations which can't be compared to
+/// boundaries (like `NaN`) are always rejected with [`TreeError::OutOfRange`].
+///
+/// ```
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut latencies = BucketedTree::<f64, u64>::new(vec![0.0, 1.0, 5.0, 25.0])
+///     .unwrap()
+///     .with_out_of_range(OutOfRangePolicy::Clamp);
+/// latencies.record(0.4, 1).unwrap();
+/// latencies.record(3.0, 1).unwrap();
+/// latencies.record(100.0, 1).unwrap();
+///
+/// assert_eq!(latencies.bucket_of(3.0).unwrap(), Some(1));
+/// assert_eq!(latencies.cumulative(4.0).unwrap(), 2);
+/// ```
+#[derive(Clone)]
+pub struct BucketedTree<K: PartialOrd + Copy, T: InvertibleValue> {
+    boundaries: Vec<K>,
+    tree: FixedSizeFenwickTree<T>,
+    edge: BucketEdge,
+    out_of_range: OutOfRangePolicy,
+}
+
+impl<K: PartialOrd + Copy, T: InvertibleValue> BucketedTree<K, T> {
+    /// Creates a histogram over `boundaries`, which must be increasing.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if less than two boundaries are passed, or
+    /// they are not increasing. [`TreeError::InvalidRange`] holds positions of the
+    /// first pair of boundaries out of order.
+    ///
+    pub fn new(boundaries: Vec<K>) -> Result<Self, TreeError> {
+        if boundaries.len() < 2 {
+            return Err(TreeError::InvalidRange {
+                from: 0,
+                to: boundaries.len(),
+            });
+        }
+        if let Some(from) = boundaries
+            .windows(2)
+            .position(|pair| pair[0].partial_cmp(&pair[1]) != Some(Ordering::Less))
+        {
+            return Err(TreeError::InvalidRange { from, to: from + 1 });
+        }
+
+        Ok(Self {
+            tree: FixedSizeFenwickTree::new(boundaries.len() - 1),
+            boundaries,
+            edge: BucketEdge::default(),
+            out_of_range: OutOfRangePolicy::default(),
+        })
+    }
+
+    pub fn with_edge(mut self, edge: BucketEdge) -> Self {
+        self.edge = edge;
+        self
+    }
+
+    pub fn with_out_of_range(mut self, policy: OutOfRangePolicy) -> Self {
+        self.out_of_range = policy;
+        self
+    }
+
+    pub fn buckets(&self) -> usize {
+        self.boundaries.len() - 1
+    }
+
+    /// Returns lower and upper boundaries of `bucket`.
+    pub fn bounds(&self, bucket: usize) -> Option<(K, K)> {
+        Some((
+            *self.boundaries.get(bucket)?,
+            *self.boundaries.get(bucket + 1)?,
+        ))
+    }
+
+    /// Returns bucket `key` is counted in, or [`None`] if it is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `key` is out of range and
+    /// [`OutOfRangePolicy::Error`] is set, or `key` can't be compared.
+    ///
+    pub fn bucket_of(&self, key: K) -> Result<Option<usize>, TreeError> {
+        let above = self.boundaries_up_to(key)?;
+        if (1..self.boundaries.len()).contains(&above) {
+            return Ok(Some(above - 1));
+        }
+
+        match self.out_of_range {
+            OutOfRangePolicy::Error => Err(TreeError::OutOfRange),
+            OutOfRangePolicy::Clamp => Ok(Some(above.clamp(1, self.buckets()) - 1)),
+            OutOfRangePolicy::Drop => Ok(None),
+        }
+    }
+
+    /// Adds `value` to the bucket of `key`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `key` is rejected by [`Self::bucket_of`].
+    ///
+    pub fn record(&mut self, key: K, value: T) -> Result<(), TreeError> {
+        match self.bucket_of(key)? {
+            Some(bucket) => self.tree.update(bucket, value),
+            None => Ok(()),
+        }
+    }
+
+    /// Returns sum of values in buckets up to and including the bucket of `key`.
+    /// Keys below the first boundary give default value, keys beyond the last one give
+    /// the total, whatever [`OutOfRangePolicy`] is set.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `key` can't be compared.
+    ///
+    pub fn cumulative(&self, key: K) -> Result<T, TreeError> {
+        match self.boundaries_up_to(key)? {
+            0 => Ok(T::default()),
+            above => self.tree.query(above.min(self.buckets()) - 1),
+        }
+    }
+
+    /// Returns the tree holding values of buckets.
+    pub fn tree(&self) -> &FixedSizeFenwickTree<T> {
+        &self.tree
+    }
+
+    /// Number of boundaries lying at or below `key`, with respect to bucket edge.
+    fn boundaries_up_to(&self, key: K) -> Result<usize, TreeError> {
+        if key.partial_cmp(&key).is_none() {
+            return Err(TreeError::OutOfRange);
+        }
+        Ok(match self.edge {
+            BucketEdge::Lower => self.boundaries.partition_point(|bound| *bound <= key),
+            BucketEdge::Upper => self.boundaries.partition_point(|bound| *bound < key),
+        })
+    }
+}
+
+impl<T: InvertibleValue> BucketedTree<f64, T> {
+    /// Creates a histogram over `count + 1` log-scale boundaries `start * factor^i`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if boundaries are not increasing, which
+    /// happens unless `start` is positive and `factor` is greater than one, or `count`
+    /// is zero.
+    ///
+    pub fn exponential(start: f64, factor: f64, count: usize) -> Result<Self, TreeError> {
+        let boundaries = std::iter::successors(Some(start), |bound| Some(bound * factor))
+            .take(count + 1)
+            .collect();
+        Self::new(boundaries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::bucketed_tree::{BucketEdge, BucketedTree, OutOfRangePolicy};
+    use crate::{FenwickReader, TreeError};
+
+    #[test]
+    fn boundary_validation() {
+        assert!(BucketedTree::<i32, i32>::new(vec![1, 2]).is_ok());
+        assert_eq!(
+            BucketedTree::<i32, i32>::new(vec![1]).err(),
+            Some(TreeError::InvalidRange { from: 0, to: 1 })
+        );
+        assert_eq!(
+            BucketedTree::<i32, i32>::new(vec![1, 3, 3]).err(),
+            Some(TreeError::InvalidRange { from: 1, to: 2 })
+        );
+        assert_eq!(
+            BucketedTree::<f64, i32>::new(vec![1.0, f64::NAN]).err(),
+            Some(TreeError::InvalidRange { from: 0, to: 1 })
+        );
+        assert!(BucketedTree::<f64, i32>::exponential(1.0, 0.5, 3).is_err());
+    }
+
+    #[test]
+    fn bucket_edges() {
+        let lower = BucketedTree::<i32, i32>::new(vec![0, 10, 20]).unwrap();
+        assert_eq!(lower.bucket_of(0), Ok(Some(0)));
+        assert_eq!(lower.bucket_of(10), Ok(Some(1)));
+        assert_eq!(lower.bucket_of(19), Ok(Some(1)));
+        assert_eq!(lower.bucket_of(20), Err(TreeError::OutOfRange));
+        assert_eq!(lower.bucket_of(-1), Err(TreeError::OutOfRange));
+
+        let upper = lower.with_edge(BucketEdge::Upper);
+        assert_eq!(upper.bucket_of(0), Err(TreeError::OutOfRange));
+        assert_eq!(upper.bucket_of(10), Ok(Some(0)));
+        assert_eq!(upper.bucket_of(20), Ok(Some(1)));
+        assert_eq!(upper.bounds(1), Some((10, 20)));
+        assert_eq!(upper.bounds(2), None);
+    }
+
+    #[test]
+    fn out_of_range_policies() {
+        let mut tree = BucketedTree::<f64, i64>::exponential(1.0, 10.0, 3)
+            .unwrap()
+            .with_out_of_range(OutOfRangePolicy::Clamp);
+        assert_eq!(tree.buckets(), 3);
+        tree.record(0.5, 1).unwrap();
+        tree.record(50.0, 1).unwrap();
+        tree.record(5000.0, 1).unwrap();
+        assert_eq!(tree.record(f64::NAN, 1), Err(TreeError::OutOfRange));
+        assert_eq!(tree.tree().query(0).unwrap(), 1);
+        assert_eq!(tree.tree().range_query(0, 2).unwrap(), 2);
+
+        let mut tree = tree.with_out_of_range(OutOfRangePolicy::Drop);
+        tree.record(0.1, 1).unwrap();
+        assert_eq!(tree.bucket_of(0.1), Ok(None));
+        assert_eq!(tree.cumulative(0.1).unwrap(), 0);
+        assert_eq!(tree.cumulative(1.0).unwrap(), 1);
+        assert_eq!(tree.cumulative(99.0).unwrap(), 2);
+        assert_eq!(tree.cumulative(f64::INFINITY).unwrap(), 3);
+    }
+}
@@ -0,0 +1,205 @@
+use crate::{
+    AnyFenwickTree, FenwickTreeValue, FenwickWriter, FixedSizeFenwickTree, GrowingFenwickTree,
+    Growth, OutOfBoundsPolicy, TreeError,
+};
+
+/// Builder consolidating all construction options of the trees.
+///
+/// Tree is fixed size by default, configuring [`FenwickTreeBuilder::growth`] makes it
+/// growing.
+///
+/// ```rust
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let tree = FenwickTreeBuilder::new()
+///     .capacity(16)
+///     .growth(Growth::NextPowerOfTwo)
+///     .max_size(1024)
+///     .with_points([(0, 1), (100, 2)])
+///     .build()
+///     .unwrap();
+///
+/// assert_eq!(tree.query(1000).unwrap(), 3);
+/// ```
+pub struct FenwickTreeBuilder<T: FenwickTreeValue> {
+    capacity: usize,
+    growth: Option<Growth>,
+    max_size: Option<usize>,
+    out_of_bounds: Option<OutOfBoundsPolicy>,
+    points: Vec<(usize, T)>,
+}
+
+impl<T: FenwickTreeValue> Default for FenwickTreeBuilder<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: FenwickTreeValue> FenwickTreeBuilder<T> {
+    pub fn new() -> Self {
+        Self {
+            capacity: 0,
+            growth: None,
+            max_size: None,
+            out_of_bounds: None,
+            points: vec![],
+        }
+    }
+
+    /// Number of indexes tree holds initially. Size of the tree for fixed size trees.
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    /// Makes tree growing on updates beyond the rightmost index, using `growth` policy.
+    pub fn growth(mut self, growth: Growth) -> Self {
+        self.growth = Some(growth);
+        self
+    }
+
+    /// Makes tree fixed size, which is the default.
+    pub fn fixed(mut self) -> Self {
+        self.growth = None;
+        self
+    }
+
+    /// Limits number of indexes growing tree can hold. Updates beyond it return an
+    /// error. Ignored by fixed size trees.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Behavior of queries and updates beyond the rightmost index. Defaults to
+    /// [`OutOfBoundsPolicy::Error`] for fixed size trees and to
+    /// [`OutOfBoundsPolicy::Grow`] for growing ones.
+    pub fn out_of_bounds(mut self, policy: OutOfBoundsPolicy) -> Self {
+        self.out_of_bounds = Some(policy);
+        self
+    }
+
+    /// Points added to the tree once it is built.
+    pub fn with_points(mut self, points: impl IntoIterator<Item = (usize, T)>) -> Self {
+        self.points.extend(points);
+        self
+    }
+
+    /// Builds the tree.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any point is out of bounds of the tree and
+    /// out of bounds policy rejects it.
+    ///
+    pub fn build(self) -> Result<AnyFenwickTree<T>, TreeError> {
+        let mut tree: AnyFenwickTree<T> = match self.growth {
+            None => {
+                let mut tree = FixedSizeFenwickTree::new(self.capacity);
+                if let Some(policy) = self.out_of_bounds {
+                    tree = tree.with_out_of_bounds(policy);
+                }
+                tree.into()
+            }
+            Some(growth) => {
+                let capacity = self
+                    .max_size
+                    .map_or(self.capacity, |max_size| self.capacity.min(max_size));
+                let mut tree = GrowingFenwickTree::new(capacity);
+                tree.growth = growth;
+                tree.max_size = self.max_size;
+                if let Some(policy) = self.out_of_bounds {
+                    tree.out_of_bounds = policy;
+                }
+                tree.into()
+            }
+        };
+
+        for (idx, value) in self.points {
+            tree.update(idx, value)?;
+        }
+
+        Ok(tree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::FenwickTreeBuilder;
+    use crate::{
+        AnyFenwickTree, FenwickReader, FenwickWriter, Growth, OutOfBoundsPolicy, TreeError,
+    };
+
+    #[test]
+    fn builds_fixed_size_tree_by_default() {
+        let tree = FenwickTreeBuilder::<i32>::new()
+            .capacity(8)
+            .build()
+            .unwrap();
+        assert!(matches!(tree, AnyFenwickTree::FixedSize(_)));
+        assert_eq!(
+            tree.query(8),
+            Err(TreeError::IndexOutOfBounds { index: 8, bound: 8 })
+        );
+    }
+
+    #[test]
+    fn fixed_size_tree_rejects_points_out_of_bounds() {
+        let res = FenwickTreeBuilder::new()
+            .capacity(8)
+            .with_points([(1, 1), (20, 1)])
+            .build();
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn builds_growing_tree() {
+        let tree = FenwickTreeBuilder::new()
+            .capacity(4)
+            .growth(Growth::Exact)
+            .with_points([(0, 1), (10, 2)])
+            .with_points([(10, 3)])
+            .build()
+            .unwrap();
+        assert!(matches!(tree, AnyFenwickTree::Growing(_)));
+        assert_eq!(tree.query(100).unwrap(), 6);
+    }
+
+    #[test]
+    fn out_of_bounds_policy() {
+        let tree = FenwickTreeBuilder::new()
+            .capacity(4)
+            .out_of_bounds(OutOfBoundsPolicy::ClampToLast)
+            .with_points([(1, 1), (9, 2)])
+            .build()
+            .unwrap();
+        assert!(matches!(tree, AnyFenwickTree::FixedSize(_)));
+        assert_eq!(tree.query(3).unwrap(), 3);
+        assert_eq!(tree.query(100).unwrap(), 3);
+
+        let tree = FenwickTreeBuilder::new()
+            .growth(Growth::Exact)
+            .out_of_bounds(OutOfBoundsPolicy::Error)
+            .with_points([(2, 1)])
+            .build();
+        assert!(tree.is_err());
+    }
+
+    #[test]
+    fn growing_tree_respects_max_size() {
+        let mut tree = FenwickTreeBuilder::new()
+            .capacity(64)
+            .growth(Growth::NextPowerOfTwo)
+            .max_size(32)
+            .build()
+            .unwrap();
+        tree.update(31, 1).unwrap();
+        assert_eq!(
+            tree.update(32, 1),
+            Err(TreeError::IndexOutOfBounds {
+                index: 32,
+                bound: 32
+            })
+        );
+    }
+}
@@ -0,0 +1,122 @@
+use std::cell::Cell;
+
+use crate::{least_significant_bit, FenwickReader, FenwickTreeValue, FenwickWriter, TreeError};
+
+/// Fixed size tree which updates take `&self`, as nodes are kept in [`Cell`]s. Handy
+/// for recursive single-threaded algorithms, where passing `&mut` down the call stack
+/// is painful.
+///
+/// Cells make the tree `!Sync`, so it can't be shared between threads:
+///
+/// ```compile_fail
+/// use fenwick_bit_tree::prelude::*;
+///
+/// fn assert_sync<T: Sync>(_: &T) {}
+/// assert_sync(&CellFenwickTree::<i32>::new(4));
+/// ```
+pub struct CellFenwickTree<T: FenwickTreeValue + Copy> {
+    data: Vec<Cell<T>>,
+}
+
+impl<T: FenwickTreeValue + Copy> CellFenwickTree<T> {
+    pub fn new(size: usize) -> Self {
+        Self {
+            data: (0..=size).map(|_| Cell::new(T::default())).collect(),
+        }
+    }
+
+    fn size(&self) -> usize {
+        self.data.len() - 1
+    }
+
+    /// Same as [`FenwickWriter::update`], but through a shared reference.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `idx` is out of bounds.
+    ///
+    pub fn update(&self, idx: usize, value: T) -> Result<(), TreeError> {
+        if idx >= self.size() {
+            return Err(TreeError::IndexOutOfBounds {
+                index: idx,
+                bound: self.size(),
+            });
+        }
+
+        if !value.is_valid() {
+            return Err(TreeError::InvalidValue);
+        }
+        if value.is_identity() {
+            return Ok(());
+        }
+
+        let mut position = idx + 1;
+        while position <= self.size() {
+            let cell = &self.data[position];
+            let mut node = cell.get();
+            node.store_value(&value);
+            cell.set(node);
+            position += least_significant_bit(position);
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: FenwickTreeValue + Copy> FenwickReader for CellFenwickTree<T> {
+    type Value = T;
+
+    fn query(&self, idx: usize) -> Result<T, TreeError> {
+        if idx >= self.size() {
+            return Err(TreeError::IndexOutOfBounds {
+                index: idx,
+                bound: self.size(),
+            });
+        }
+
+        let mut res = T::default();
+        let mut position = idx + 1;
+        while position > 0 {
+            res.store_value(&self.data[position].get());
+            position -= least_significant_bit(position);
+        }
+
+        Ok(res)
+    }
+}
+
+impl<T: FenwickTreeValue + Copy> FenwickWriter for CellFenwickTree<T> {
+    fn update(&mut self, idx: usize, value: T) -> Result<(), TreeError> {
+        CellFenwickTree::update(self, idx, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cell_tree::CellFenwickTree;
+    use crate::{FenwickReader, TreeError};
+
+    fn visit(tree: &CellFenwickTree<i32>, node: usize) {
+        if node >= 8 {
+            return;
+        }
+        tree.update(node, 1).unwrap();
+        visit(tree, 2 * node + 1);
+        visit(tree, 2 * node + 2);
+    }
+
+    #[test]
+    fn updates_through_shared_reference() {
+        let tree = CellFenwickTree::<i32>::new(8);
+        visit(&tree, 0);
+
+        assert_eq!(tree.query(0).unwrap(), 1);
+        assert_eq!(tree.query(7).unwrap(), 8);
+        assert_eq!(tree.range_query(3, 7).unwrap(), 4);
+        assert_eq!(
+            tree.update(8, 1),
+            Err(TreeError::IndexOutOfBounds { index: 8, bound: 8 })
+        );
+        assert!(tree.query(8).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+use crate::{
+    least_significant_bit, try_vec_of_defaults, FenwickReader, FenwickTreeValue, FenwickWriter,
+    InvertibleValue, TreeError,
+};
+
+/// Compact value type, which sums are accumulated in a wider [`Self::Accumulator`].
+pub trait WideningValue: FenwickTreeValue + Copy {
+    type Accumulator: InvertibleValue + Copy + From<Self> + TryInto<Self>;
+
+    /// Returns sum of both values, or [`None`] if it doesn't fit into the type.
+    fn checked_sum(self, other: Self) -> Option<Self>;
+}
+
+macro_rules! impl_widening_value {
+    ($(($t:ty, $acc:ty)),*) => {$(
+        impl WideningValue for $t {
+            type Accumulator = $acc;
+
+            fn checked_sum(self, other: Self) -> Option<Self> {
+                self.checked_add(other)
+            }
+        }
+    )*};
+}
+
+impl_widening_value!(
+    (i8, i64),
+    (i16, i64),
+    (i32, i64),
+    (u8, u64),
+    (u16, u64),
+    (u32, u64)
+);
+
+/// Fixed size tree of small values, which queries are accumulated in a wider type.
+///
+/// Half of the nodes hold the value of a single even index, and are stored in the
+/// compact type. Every other node aggregates at least two indexes and is stored in the
+/// accumulator type, so a tree of `u16` counters takes about 5 bytes per index instead
+/// of 8. Updates that would overflow a compact node are rejected with
+/// [`TreeError::Overflow`] and leave the tree unchanged, so values of even indexes
+/// have to fit into the compact type.
+///
+/// ```rust
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut tree = CompactFenwickTree::<u16>::new(1 << 16);
+/// for idx in 0..1 << 16 {
+///     tree.update(idx, 1_000).unwrap();
+/// }
+/// assert_eq!(tree.query((1 << 16) - 1).unwrap(), 65_536_000u64);
+/// ```
+pub struct CompactFenwickTree<T: WideningValue> {
+    /// Node at odd position `p` is stored at `p / 2`.
+    narrow: Vec<T>,
+    /// Node at even position `p` is stored at `p / 2`, so 0 is unused.
+    wide: Vec<T::Accumulator>,
+    size: usize,
+}
+
+impl<T: WideningValue> CompactFenwickTree<T> {
+    /// Creates tree of `size` indexes holding default values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory for the tree can't be allocated, use [`Self::try_new`] to
+    /// handle that case.
+    pub fn new(size: usize) -> Self {
+        Self::try_new(size).unwrap_or_else(|err| panic!("tree of {size} indexes: {err}"))
+    }
+
+    /// Same as [`Self::new`], but doesn't panic.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if number of nodes overflows [`usize`] or
+    /// memory for them can't be allocated.
+    ///
+    pub fn try_new(size: usize) -> Result<Self, TreeError> {
+        Ok(Self {
+            narrow: try_vec_of_defaults(size / 2 + size % 2)?,
+            wide: try_vec_of_defaults(size / 2 + 1)?,
+            size,
+        })
+    }
+
+    fn size(&self) -> usize {
+        self.size
+    }
+
+    fn check_bounds(&self, idx: usize) -> Result<(), TreeError> {
+        if idx >= self.size() {
+            return Err(TreeError::IndexOutOfBounds {
+                index: idx,
+                bound: self.size(),
+            });
+        }
+        Ok(())
+    }
+}
+
+fn is_wide(position: usize) -> bool {
+    position % 2 == 0
+}
+
+impl<T: WideningValue> FenwickReader for CompactFenwickTree<T> {
+    type Value = T::Accumulator;
+
+    fn query(&self, idx: usize) -> Result<T::Accumulator, TreeError> {
+        self.check_bounds(idx)?;
+
+        let mut res = T::Accumulator::default();
+        let mut position = idx + 1;
+        while position > 0 {
+            if is_wide(position) {
+                res.store_value(&self.wide[position / 2]);
+            } else {
+                res.store_value(&self.narrow[position / 2].into());
+            }
+            position -= least_significant_bit(position);
+        }
+
+        Ok(res)
+    }
+}
+
+impl<T: WideningValue> FenwickWriter for CompactFenwickTree<T> {
+    fn update(&mut self, idx: usize, value: T::Accumulator) -> Result<(), TreeError> {
+        self.check_bounds(idx)?;
+
+        if !value.is_valid() {
+            return Err(TreeError::InvalidValue);
+        }
+        if value.is_identity() {
+            return Ok(());
+        }
+        let compact: T = value.try_into().map_err(|_| TreeError::Overflow)?;
+
+        // Only the first node on the update path can be narrow, so it's the only one
+        // which can overflow.
+        let mut position = idx + 1;
+        if !is_wide(position) {
+            let node = &mut self.narrow[position / 2];
+            *node = node.checked_sum(compact).ok_or(TreeError::Overflow)?;
+            position += 1;
+        }
+        while position <= self.size {
+            self.wide[position / 2].store_value(&value);
+            position += least_significant_bit(position);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::compact_tree::CompactFenwickTree;
+    use crate::workload::Workload;
+    use crate::{FenwickReader, FenwickWriter, TreeError};
+
+    #[test]
+    fn matches_naive_sums() {
+        let mut tree = CompactFenwickTree::<i8>::new(300);
+        let mut points = vec![0i64; 300];
+
+        for (idx, value) in Workload::new(1, 300).deltas(-3..4).updates().take(1000) {
+            tree.update(idx, value).unwrap();
+            points[idx] += value;
+        }
+
+        let mut sum = 0;
+        for (idx, point) in points.iter().enumerate() {
+            sum += point;
+            assert_eq!(tree.query(idx).unwrap(), sum);
+        }
+        assert_eq!(
+            tree.range_query(10, 200).unwrap(),
+            points[11..=200].iter().sum::<i64>()
+        );
+    }
+
+    #[test]
+    fn rejects_overflowing_updates() {
+        let mut tree = CompactFenwickTree::<u8>::new(64);
+        assert_eq!(tree.update(0, 256), Err(TreeError::Overflow));
+
+        tree.update(0, 255).unwrap();
+        assert_eq!(tree.update(0, 1), Err(TreeError::Overflow));
+        assert_eq!(tree.query(1).unwrap(), 255);
+        assert_eq!(tree.query(63).unwrap(), 255);
+
+        // Only values of even indexes are held by compact nodes.
+        for idx in 1..64 {
+            tree.update(idx, 255).unwrap();
+        }
+        assert_eq!(tree.update(2, 1), Err(TreeError::Overflow));
+        tree.update(1, 1).unwrap();
+        assert_eq!(tree.query(1).unwrap(), 511);
+        assert_eq!(tree.query(63).unwrap(), 64 * 255 + 1);
+        assert!(tree.update(64, 1).is_err());
+    }
+
+    #[test]
+    fn try_new() {
+        assert_eq!(CompactFenwickTree::<u8>::try_new(20).unwrap().size(), 20);
+        assert!(matches!(
+            CompactFenwickTree::<u16>::try_new(usize::MAX),
+            Err(TreeError::AllocationFailed)
+        ));
+    }
+}
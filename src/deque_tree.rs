@@ -0,0 +1,110 @@
+use crate::{FenwickReader, FenwickWriter, GrowingFenwickTree, InvertibleValue, TreeError};
+
+/// Tree which key space grows in both directions from the `origin` passed to
+/// [`DequeFenwickTree::new`], so indexes lower than any seen before (e.g. late events
+/// with earlier timestamps) don't need the tree to be rebuilt.
+///
+/// Indexes from the origin up are kept in one growing tree, indexes below the origin
+/// are kept mirrored in another one, which grows downwards. Prefix sums of the lower
+/// part are computed as a difference from its total, which requires values to be
+/// invertible.
+pub struct DequeFenwickTree<T: InvertibleValue> {
+    origin: usize,
+    below: GrowingFenwickTree<T>,
+    above: GrowingFenwickTree<T>,
+}
+
+impl<T: InvertibleValue> DequeFenwickTree<T> {
+    pub fn new(origin: usize) -> Self {
+        Self {
+            origin,
+            below: GrowingFenwickTree::new(0),
+            above: GrowingFenwickTree::new(0),
+        }
+    }
+
+    pub fn origin(&self) -> usize {
+        self.origin
+    }
+
+    /// Returns the lowest index the tree currently holds.
+    pub fn lowest(&self) -> usize {
+        self.origin - self.below.resize_stats().len
+    }
+}
+
+impl<T: InvertibleValue> FenwickReader for DequeFenwickTree<T> {
+    type Value = T;
+
+    fn query(&self, idx: usize) -> Result<T, TreeError> {
+        let below_total = self.below.query_clamped(usize::MAX);
+
+        if idx >= self.origin {
+            let mut res = below_total;
+            res.absorb(self.above.query_clamped(idx - self.origin));
+            return Ok(res);
+        }
+
+        // Mirrored position `origin - 1 - idx` and all positions above it hold indexes
+        // lesser or equal than `idx`.
+        let mirrored = self.origin - 1 - idx;
+        if mirrored == 0 {
+            return Ok(below_total);
+        }
+        Ok(below_total.substract(self.below.query_clamped(mirrored - 1)))
+    }
+}
+
+impl<T: InvertibleValue> FenwickWriter for DequeFenwickTree<T> {
+    fn update(&mut self, idx: usize, value: T) -> Result<(), TreeError> {
+        if idx >= self.origin {
+            self.above.update(idx - self.origin, value)
+        } else {
+            self.below.update(self.origin - 1 - idx, value)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::deque_tree::DequeFenwickTree;
+    use crate::workload::Workload;
+    use crate::{FenwickReader, FenwickWriter};
+
+    #[test]
+    fn grows_below_origin() {
+        let mut tree = DequeFenwickTree::<i32>::new(100);
+        tree.update(100, 1).unwrap();
+        tree.update(105, 2).unwrap();
+        assert_eq!(tree.lowest(), 100);
+
+        tree.update(90, 4).unwrap();
+        tree.update(99, 8).unwrap();
+        assert_eq!(tree.lowest(), 90);
+        assert_eq!(tree.origin(), 100);
+
+        assert_eq!(tree.query(0).unwrap(), 0);
+        assert_eq!(tree.query(89).unwrap(), 0);
+        assert_eq!(tree.query(90).unwrap(), 4);
+        assert_eq!(tree.query(98).unwrap(), 4);
+        assert_eq!(tree.query(99).unwrap(), 12);
+        assert_eq!(tree.query(100).unwrap(), 13);
+        assert_eq!(tree.query(1_000).unwrap(), 15);
+        assert_eq!(tree.range_query(99, 105).unwrap(), 3);
+    }
+
+    #[test]
+    fn random_updates_match_naive_sum() {
+        let mut tree = DequeFenwickTree::<i64>::new(500);
+        let mut points = vec![0; 1000];
+
+        for (idx, value) in Workload::new(1, 1000).deltas(-100..100).updates().take(500) {
+            tree.update(idx, value).unwrap();
+            points[idx] += value;
+        }
+
+        for idx in 0..1000 {
+            assert_eq!(tree.query(idx).unwrap(), points[..=idx].iter().sum::<i64>());
+        }
+    }
+}
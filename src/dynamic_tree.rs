@@ -0,0 +1,187 @@
+use crate::{FenwickReader, FenwickTreeValue, FenwickWriter, TreeError};
+
+const KEY_BITS: usize = u64::BITS as usize;
+const NONE: usize = 0;
+
+#[derive(Debug, Clone, Default)]
+struct Node<T> {
+    sum: T,
+    children: [usize; 2],
+}
+
+/// Tree covering the whole [`u64`] key space without upfront sizing. Nodes of an
+/// implicit binary trie over key bits are allocated on first touch in a single arena
+/// and linked by indexes, like in a dynamic segment tree, so no hashing is involved.
+///
+/// Every update allocates at most 64 nodes and visits exactly 64, query visits at most
+/// 64. Queries of untouched parts of the key space don't allocate.
+///
+/// ```
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut tree = DynamicFenwickTree::<i64>::new();
+/// tree.update_key(u64::MAX, 3).unwrap();
+/// tree.update_key(1 << 50, 2).unwrap();
+///
+/// assert_eq!(tree.query_key(u64::MAX - 1), 2);
+/// assert_eq!(tree.query_key(u64::MAX), 5);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DynamicFenwickTree<T: FenwickTreeValue> {
+    nodes: Vec<Node<T>>,
+}
+
+impl<T: FenwickTreeValue> Default for DynamicFenwickTree<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: FenwickTreeValue> DynamicFenwickTree<T> {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![Node::default()],
+        }
+    }
+
+    /// Returns number of allocated nodes, root included.
+    pub fn node_count(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Returns sum of values stored at keys up to `key` inclusively.
+    pub fn query_key(&self, key: u64) -> T {
+        let mut res = T::default();
+        let mut node = 0;
+        for depth in 0..KEY_BITS {
+            let bit = key_bit(key, depth);
+            let children = self.nodes[node].children;
+            if bit == 1 && children[0] != NONE {
+                res.store_value(&self.nodes[children[0]].sum);
+            }
+            node = children[bit];
+            if node == NONE {
+                return res;
+            }
+        }
+        res.store_value(&self.nodes[node].sum);
+        res
+    }
+
+    /// Adds `value` at `key`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `value` is rejected by
+    /// [`FenwickTreeValue::is_valid`], or memory for new nodes can't be allocated, the
+    /// tree is left unchanged in both cases.
+    ///
+    pub fn update_key(&mut self, key: u64, value: T) -> Result<(), TreeError> {
+        if !value.is_valid() {
+            return Err(TreeError::InvalidValue);
+        }
+        if value.is_identity() {
+            return Ok(());
+        }
+
+        let mut allocated = 0;
+        let mut node = 0;
+        while allocated < KEY_BITS {
+            node = self.nodes[node].children[key_bit(key, allocated)];
+            if node == NONE {
+                break;
+            }
+            allocated += 1;
+        }
+        self.nodes
+            .try_reserve(KEY_BITS - allocated)
+            .map_err(|_| TreeError::AllocationFailed)?;
+
+        let mut node = 0;
+        self.nodes[node].sum.store_value(&value);
+        for depth in 0..KEY_BITS {
+            let bit = key_bit(key, depth);
+            let mut child = self.nodes[node].children[bit];
+            if child == NONE {
+                child = self.nodes.len();
+                self.nodes.push(Node::default());
+                self.nodes[node].children[bit] = child;
+            }
+            node = child;
+            self.nodes[node].sum.store_value(&value);
+        }
+
+        Ok(())
+    }
+}
+
+fn key_bit(key: u64, depth: usize) -> usize {
+    ((key >> (KEY_BITS - 1 - depth)) & 1) as usize
+}
+
+/// Indexes are keys, every [`usize`] fits into [`u64`].
+impl<T: FenwickTreeValue> FenwickReader for DynamicFenwickTree<T> {
+    type Value = T;
+
+    fn query(&self, idx: usize) -> Result<T, TreeError> {
+        Ok(self.query_key(idx as u64))
+    }
+}
+
+impl<T: FenwickTreeValue> FenwickWriter for DynamicFenwickTree<T> {
+    fn update(&mut self, idx: usize, value: T) -> Result<(), TreeError> {
+        self.update_key(idx as u64, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dynamic_tree::DynamicFenwickTree;
+    use crate::workload::{Pattern, Workload};
+    use crate::{FenwickReader, FixedSizeFenwickTree};
+
+    #[test]
+    fn matches_fixed_size_tree() {
+        let mut dynamic = DynamicFenwickTree::<i64>::new();
+        let mut fixed = FixedSizeFenwickTree::<i64>::new(100);
+        let workload = Workload::new(0, 100)
+            .pattern(Pattern::HotIndexes { hot: 5, share: 0.5 })
+            .deltas(-10..10);
+        for operation in workload.take(400) {
+            assert_eq!(operation.apply(&mut dynamic), operation.apply(&mut fixed));
+        }
+
+        for idx in 0..100 {
+            assert_eq!(dynamic.query(idx).unwrap(), fixed.query(idx).unwrap());
+        }
+    }
+
+    #[test]
+    fn whole_key_space() {
+        let mut tree = DynamicFenwickTree::<i32>::new();
+        assert_eq!(tree.query_key(u64::MAX), 0);
+
+        tree.update_key(0, 1).unwrap();
+        tree.update_key(u64::MAX, 2).unwrap();
+        tree.update_key(1 << 63, 4).unwrap();
+
+        assert_eq!(tree.query_key(0), 1);
+        assert_eq!(tree.query_key((1 << 63) - 1), 1);
+        assert_eq!(tree.query_key(1 << 63), 5);
+        assert_eq!(tree.query_key(u64::MAX - 1), 5);
+        assert_eq!(tree.query_key(u64::MAX), 7);
+    }
+
+    #[test]
+    fn allocates_on_touch() {
+        let mut tree = DynamicFenwickTree::<i32>::new();
+        tree.update_key(12345, 0).unwrap();
+        tree.query_key(u64::MAX);
+        assert_eq!(tree.node_count(), 1);
+
+        tree.update_key(12345, 1).unwrap();
+        assert_eq!(tree.node_count(), 65);
+        tree.update_key(12344, 1).unwrap();
+        assert_eq!(tree.node_count(), 66);
+    }
+}
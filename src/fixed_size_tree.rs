@@ -1,76 +1,843 @@
-use crate::{FenwickTree, FenwickTreeValue, TreeError, TreeIndex};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::RangeInclusive;
 
+use crate::hashing;
+use crate::iterators::{ChunkSums, NonZeroPoints, PrefixCursor, PrefixSums, WindowSums};
+use crate::search;
+use crate::snapshot_tree::SnapshotValue;
+use crate::values::NonNegative;
+use crate::{
+    add_to_nodes, append_data, check_permutation, count_of, data_from_fn, data_from_pairs,
+    data_from_points, fill_data, fill_new_nodes, least_significant_bit, len_for_keys,
+    points_into_data, points_of, remapped_points, store_to_nodes, try_vec_of_defaults,
+    DuplicatePolicy, FenwickReader, FenwickTreeValue, FenwickWriter, IndexedTree, InvertibleValue,
+    OutOfBoundsPolicy, TreeError,
+};
+
+/// Fenwick tree holding exactly `size` indexes passed to [`FixedSizeFenwickTree::new`].
+/// Both queries and updates accept indexes `0..size` and return
+/// [`TreeError::IndexOutOfBounds`] for any other index, unless configured otherwise
+/// with [`FixedSizeFenwickTree::with_out_of_bounds`].
 pub struct FixedSizeFenwickTree<T: FenwickTreeValue> {
-    data: Vec<T>,
+    pub(crate) data: Vec<T>,
+    pub(crate) out_of_bounds: OutOfBoundsPolicy,
+}
+
+impl<T: FenwickTreeValue> Clone for FixedSizeFenwickTree<T> {
+    fn clone(&self) -> Self {
+        Self {
+            data: self.data.clone(),
+            out_of_bounds: self.out_of_bounds,
+        }
+    }
+
+    /// Reuses existing allocation of `self`, so repeatedly snapshotting a tree into the
+    /// same scratch copy doesn't allocate.
+    fn clone_from(&mut self, source: &Self) {
+        self.data.clone_from(&source.data);
+        self.out_of_bounds = source.out_of_bounds;
+    }
+}
+
+impl<T: FenwickTreeValue> TryFrom<BTreeMap<usize, T>> for FixedSizeFenwickTree<T> {
+    type Error = TreeError;
+
+    /// Creates tree sized to hold the largest key of `points`, in `O(n + k)`. Fails
+    /// with [`TreeError::Overflow`] for [`usize::MAX`] key.
+    fn try_from(points: BTreeMap<usize, T>) -> Result<Self, TreeError> {
+        Ok(Self {
+            data: data_from_points(len_for_keys(points.keys())?, points)?,
+            ..Self::new(0)
+        })
+    }
+}
+
+impl<T: FenwickTreeValue> TryFrom<HashMap<usize, T>> for FixedSizeFenwickTree<T> {
+    type Error = TreeError;
+
+    /// Creates tree sized to hold the largest key of `points`, in `O(n + k)`. Fails
+    /// with [`TreeError::Overflow`] for [`usize::MAX`] key.
+    fn try_from(points: HashMap<usize, T>) -> Result<Self, TreeError> {
+        Ok(Self {
+            data: data_from_points(len_for_keys(points.keys())?, points)?,
+            ..Self::new(0)
+        })
+    }
+}
+
+/// Creates tree sized to hold the largest index of `pairs`, in `O(n + k)`. Values
+/// sharing an index are summed up.
+///
+/// # Panics
+///
+/// Panics if an index is [`usize::MAX`] or memory for the tree can't be allocated, use
+/// [`DuplicatePolicy::Sum`] conversion from a vector of pairs to handle those cases.
+impl<T: FenwickTreeValue> FromIterator<(usize, T)> for FixedSizeFenwickTree<T> {
+    fn from_iter<I: IntoIterator<Item = (usize, T)>>(pairs: I) -> Self {
+        let pairs: Vec<_> = pairs.into_iter().collect();
+        let data = len_for_keys(pairs.iter().map(|(idx, _)| idx))
+            .and_then(|len| data_from_points(len, pairs))
+            .unwrap_or_else(|err| panic!("tree can't hold all pairs: {err}"));
+        Self {
+            data,
+            ..Self::new(0)
+        }
+    }
+}
+
+impl<T: FenwickTreeValue> TryFrom<Vec<(usize, T)>> for FixedSizeFenwickTree<T> {
+    type Error = TreeError;
+
+    /// Creates tree sized to hold the largest index of `pairs`, in `O(n + k)`. Repeated
+    /// indexes are rejected, see [`DuplicatePolicy::Error`].
+    fn try_from(pairs: Vec<(usize, T)>) -> Result<Self, TreeError> {
+        Self::try_from((pairs, DuplicatePolicy::Error))
+    }
+}
+
+impl<T: FenwickTreeValue> TryFrom<(Vec<(usize, T)>, DuplicatePolicy)> for FixedSizeFenwickTree<T> {
+    type Error = TreeError;
+
+    /// Same as conversion from plain pairs, merging repeated indexes with `policy`.
+    fn try_from((pairs, policy): (Vec<(usize, T)>, DuplicatePolicy)) -> Result<Self, TreeError> {
+        Ok(Self {
+            data: data_from_pairs(pairs, policy)?,
+            ..Self::new(0)
+        })
+    }
 }
 
 impl<T: FenwickTreeValue> FixedSizeFenwickTree<T> {
+    /// Creates tree of `size` indexes holding default values.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory for the tree can't be allocated, use [`Self::try_new`] to
+    /// handle that case.
     pub fn new(size: usize) -> Self {
+        Self::try_new(size).unwrap_or_else(|err| panic!("tree of {size} indexes: {err}"))
+    }
+
+    /// Same as [`Self::new`], but doesn't panic.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if number of nodes overflows [`usize`] or
+    /// memory for them can't be allocated.
+    ///
+    pub fn try_new(size: usize) -> Result<Self, TreeError> {
+        Ok(Self {
+            data: try_vec_of_defaults(size.checked_add(1).ok_or(TreeError::Overflow)?)?,
+            out_of_bounds: OutOfBoundsPolicy::Error,
+        })
+    }
+
+    /// Sets behavior of queries and updates beyond the rightmost index, which is
+    /// [`OutOfBoundsPolicy::Error`] by default. Tree configured to
+    /// [`OutOfBoundsPolicy::Grow`] grows exactly to the updated index.
+    pub fn with_out_of_bounds(mut self, policy: OutOfBoundsPolicy) -> Self {
+        self.out_of_bounds = policy;
+        self
+    }
+
+    /// Creates tree of `size` indexes holding `f(i)` at index `i`, in `O(n)`.
+    pub fn from_fn(size: usize, f: impl FnMut(usize) -> T) -> Self {
+        Self {
+            data: data_from_fn(size, f),
+            ..Self::new(0)
+        }
+    }
+
+    /// Creates tree holding `values[i]` at index `i`, in `O(n)`.
+    pub fn from_slice(values: &[T]) -> Self {
+        Self::from_fn(values.len(), |idx| values[idx].clone())
+    }
+
+    /// Same as [`Self::from_slice`], reusing allocation of `values` when it has spare
+    /// capacity. There is no `From<Vec<T>>` conversion, as pairs are values too and it
+    /// would make conversions from `(index, value)` pairs ambiguous.
+    pub fn from_vec(mut values: Vec<T>) -> Self {
+        values.push(T::default());
+        values.rotate_right(1);
+        points_into_data(&mut values);
         Self {
-            data: vec![T::default(); size + 1],
+            data: values,
+            ..Self::new(0)
         }
     }
 
+    /// Creates tree of `size` indexes holding default values, same as [`Self::new`].
+    pub fn zeros(size: usize) -> Self {
+        Self::new(size)
+    }
+
+    /// Creates tree of `size` indexes all holding `value`, in `O(n)`.
+    pub fn filled(size: usize, value: T) -> Self {
+        Self::from_fn(size, |_| value.clone())
+    }
+
+    /// Creates tree of `size` indexes holding `points`, in `O(n + k)`. Values sharing
+    /// an index are summed up.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any point is out of bounds.
+    ///
+    pub fn with_points(size: usize, points: &[(usize, T)]) -> Result<Self, TreeError> {
+        if let Some(&(index, _)) = points.iter().find(|(idx, _)| *idx >= size) {
+            return Err(TreeError::IndexOutOfBounds { index, bound: size });
+        }
+
+        Ok(Self {
+            data: data_from_points(size, points.iter().cloned())?,
+            ..Self::new(0)
+        })
+    }
+
+    /// Sets value of every index to `value`, in `O(n)`.
+    pub fn fill(&mut self, value: T) {
+        fill_data(&mut self.data, &value);
+    }
+
+    /// Returns the first index which prefix sum doesn't satisfy `pred`. Predicate has
+    /// to be monotone: `true` for some number of shortest prefixes and `false` for
+    /// the rest, e.g. `|sum| *sum < budget` for trees with non-negative values.
+    ///
+    /// Search descends the tree in `O(log n)` and works for values without inverse.
+    /// `size` is returned if the predicate holds for all prefixes.
+    pub fn partition_point(&self, pred: impl Fn(&T) -> bool) -> usize {
+        search::partition_point(&self.data, pred)
+    }
+
+    /// Returns the first index which prefix sum is greater or equal than `target`, or
+    /// [`None`] if the total sum is less than `target`. Values are expected to be
+    /// non-negative.
+    ///
+    /// When several indexes share the same prefix sum (indexes holding zeros), the
+    /// lowest of them is returned.
+    pub fn lower_bound(&self, target: T) -> Option<usize>
+    where
+        T: PartialOrd,
+    {
+        let idx = self.partition_point(|sum| *sum < target);
+        (idx < self.data.len() - 1).then_some(idx)
+    }
+
+    /// Returns the first index which prefix sum is strictly greater than `target`, or
+    /// [`None`] if the total sum doesn't exceed `target`. Values are expected to be
+    /// non-negative.
+    ///
+    /// All indexes which prefix sum equals `target` are skipped, so for a tree of
+    /// counters `upper_bound(k)` is the index holding `k + 1`-th item.
+    pub fn upper_bound(&self, target: T) -> Option<usize>
+    where
+        T: PartialOrd,
+    {
+        let idx = self.partition_point(|sum| *sum <= target);
+        (idx < self.data.len() - 1).then_some(idx)
+    }
+
+    /// Returns the `k`-th index (counting from 0) holding zero count, or [`None`] if
+    /// there are no more than `k` such indexes, in `O(log n)`. Indexes are expected to
+    /// hold counts of 0 or 1, such as allocated ids, so the number of zeros up to an
+    /// index is `idx + 1` without its prefix sum.
+    ///
+    /// ```
+    /// use fenwick_bit_tree::prelude::*;
+    ///
+    /// let mut ids = FixedSizeFenwickTree::<u32>::new(6);
+    /// ids.update(0, 1).unwrap();
+    /// ids.update(2, 1).unwrap();
+    /// assert_eq!(ids.kth_missing(0), Some(1));
+    /// assert_eq!(ids.kth_missing(3), Some(5));
+    /// assert_eq!(ids.kth_missing(4), None);
+    /// ```
+    pub fn kth_missing(&self, k: usize) -> Option<usize>
+    where
+        T: TryInto<usize>,
+    {
+        let (idx, _) = search::kth_missing(&self.data, k, count_of);
+        (idx < self.size()).then_some(idx)
+    }
+
+    /// For every target returns the first index which prefix sum reaches it, or
+    /// [`None`] if the total sum doesn't. Same as running prefix search for each target
+    /// separately, but targets share the descent through the tree, so grids of
+    /// quantiles are answered much faster. Values are expected to be non-negative.
+    pub fn select_many(&self, targets: &[T]) -> Vec<Option<usize>>
+    where
+        T: PartialOrd,
+    {
+        search::select_many(&self.data, targets, |sum| sum)
+    }
+
+    /// Returns cursor over `(index, prefix sum)` pairs from `from` up to the rightmost
+    /// index, taking `O(1)` amortized per index after `O(log n)` start. Nothing is
+    /// yielded if `from` lies beyond the rightmost index.
+    pub fn prefix_cursor(&self, from: usize) -> PrefixCursor<'_, T> {
+        PrefixCursor::new(&self.data, from)
+    }
+
     fn size(&self) -> usize {
         self.data.len() - 1
     }
+
+    /// Lends the tree to `f` as [`IndexedTree`], which checks indexes once and then
+    /// queries and updates them without bounds checks. Indexes checked within `f`
+    /// can't leave it.
+    ///
+    /// ```
+    /// use fenwick_bit_tree::prelude::*;
+    ///
+    /// let mut tree = FixedSizeFenwickTree::<i32>::new(8);
+    /// let sum = tree.indexed(|mut tree| {
+    ///     let idx = tree.checked_idx(5)?;
+    ///     tree.update_idx(idx, 3)?;
+    ///     Ok::<_, TreeError>(tree.query_idx(idx))
+    /// });
+    /// assert_eq!(sum, Ok(3));
+    /// ```
+    pub fn indexed<R>(&mut self, f: impl for<'t> FnOnce(IndexedTree<'t, T>) -> R) -> R {
+        f(IndexedTree::new(self))
+    }
+
+    /// Adds `value` to `idx`, which has to lie within the tree.
+    pub(crate) fn add(&mut self, idx: usize, value: T) {
+        if !value.is_identity() {
+            add_to_nodes(&mut self.data, idx + 1, value);
+        }
+    }
+
+    /// Returns prefix sum up to `idx`, which has to lie within the tree.
+    pub(crate) fn prefix(&self, idx: usize) -> T {
+        let mut res = T::default();
+        let mut position = idx + 1;
+        while position > 0 {
+            res.store_value(&self.data[position]);
+            position -= least_significant_bit(position);
+        }
+        res
+    }
+
+    pub(crate) fn grow(&mut self, idx: usize) -> Result<(), TreeError> {
+        let new_size = idx.checked_add(2).ok_or(TreeError::Overflow)?;
+        let size_before_grow = self.data.len();
+        self.data
+            .try_reserve(new_size - size_before_grow)
+            .map_err(|_| TreeError::AllocationFailed)?;
+        self.data.resize(new_size, T::default());
+        fill_new_nodes(&mut self.data, size_before_grow);
+        Ok(())
+    }
 }
 
-impl<T: FenwickTreeValue> std::ops::Index<TreeIndex> for FixedSizeFenwickTree<T> {
-    type Output = T;
+impl<T: FenwickTreeValue + PartialOrd> FixedSizeFenwickTree<NonNegative<T>> {
+    fn ensure_non_negative(&self) -> Result<(), TreeError> {
+        if search::total(&self.data).negative_applied() {
+            return Err(TreeError::NegativeValue);
+        }
+        Ok(())
+    }
+
+    /// Same as [`Self::lower_bound`], but checks that no negative value was ever stored.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any negative value was stored.
+    ///
+    pub fn checked_lower_bound(&self, target: T) -> Result<Option<usize>, TreeError> {
+        self.ensure_non_negative()?;
+        let idx = self.partition_point(|sum| *sum.value() < target);
+        Ok((idx < self.data.len() - 1).then_some(idx))
+    }
 
-    fn index(&self, index: TreeIndex) -> &Self::Output {
-        &self.data[*index.to_internal()]
+    /// Same as [`Self::upper_bound`], but checks that no negative value was ever stored.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any negative value was stored.
+    ///
+    pub fn checked_upper_bound(&self, target: T) -> Result<Option<usize>, TreeError> {
+        self.ensure_non_negative()?;
+        let idx = self.partition_point(|sum| *sum.value() <= target);
+        Ok((idx < self.data.len() - 1).then_some(idx))
+    }
+
+    /// Same as [`Self::select_many`], but checks that no negative value was ever stored.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any negative value was stored.
+    ///
+    pub fn checked_select_many(&self, targets: &[T]) -> Result<Vec<Option<usize>>, TreeError> {
+        self.ensure_non_negative()?;
+        Ok(search::select_many(&self.data, targets, NonNegative::value))
     }
 }
 
-impl<T: FenwickTreeValue> std::ops::IndexMut<TreeIndex> for FixedSizeFenwickTree<T> {
-    fn index_mut(&mut self, index: TreeIndex) -> &mut Self::Output {
-        &mut self.data[*index.to_internal()]
+impl<T: InvertibleValue> FixedSizeFenwickTree<T> {
+    /// Places indexes of `other` right after indexes of `self`, so index `i` of `other`
+    /// becomes `size + i`, in `O(n + m)`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if memory for indexes of `other` can't be
+    /// allocated. The tree is left unchanged then.
+    ///
+    pub fn append(&mut self, other: Self) -> Result<(), TreeError> {
+        append_data(&mut self.data, other.data)
+    }
+
+    /// Returns tree holding value of every index `i` at index `perm[i]`, in `O(n)`.
+    /// Values moved to the same index are summed up, so `perm` needn't be a
+    /// permutation.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if length of `perm` differs from the size
+    /// of the tree, or a new index is out of bounds.
+    ///
+    pub fn remap(&self, perm: &[usize]) -> Result<Self, TreeError> {
+        check_permutation(perm, self.size())?;
+        self.remap_with(|idx| perm[idx])
+    }
+
+    /// Same as [`Self::remap`], with value of every index `i` moved to index `f(i)`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a new index is out of bounds.
+    ///
+    pub fn remap_with(&self, f: impl FnMut(usize) -> usize) -> Result<Self, TreeError> {
+        let points = remapped_points(&self.data, self.size(), f)?;
+        Ok(Self {
+            data: data_from_points(self.size(), points)?,
+            out_of_bounds: self.out_of_bounds,
+        })
+    }
+
+    /// Plain values stored at every index, in `O(n)`.
+    pub(crate) fn to_points(&self) -> Vec<T> {
+        points_of(&self.data)
+    }
+
+    /// Returns sums of disjoint blocks of `k` indexes: `0..k`, `k..2 * k` and so on.
+    /// The last block ends at the rightmost index and may be shorter. Nothing is
+    /// yielded if `k` is 0.
+    pub fn chunk_sums(&self, k: usize) -> impl Iterator<Item = T> + '_ {
+        ChunkSums::new(&self.data, k)
+    }
+
+    /// Returns the shortest `(from, to)` range of indexes (including edges) which sum
+    /// reaches `target`, or [`None`] if the total sum doesn't, e.g. the shortest period
+    /// containing 1000 errors. The leftmost range is returned among equally short ones.
+    /// Takes `O(n log n)`, values are expected to be non-negative.
+    ///
+    /// ```
+    /// use fenwick_bit_tree::prelude::*;
+    ///
+    /// let errors = FixedSizeFenwickTree::from_fn(8, |i| [1, 0, 4, 1, 0, 0, 3, 3][i]);
+    /// assert_eq!(errors.min_window_with_sum(5), Some((2, 3)));
+    /// assert_eq!(errors.min_window_with_sum(6), Some((6, 7)));
+    /// assert_eq!(errors.min_window_with_sum(13), None);
+    /// ```
+    pub fn min_window_with_sum(&self, target: T) -> Option<(usize, usize)>
+    where
+        T: PartialOrd,
+    {
+        search::min_window_with_sum(&self.data, &target)
+    }
+
+    /// Returns `(index, prefix sum)` pairs of indexes within `range`, in
+    /// `O(k + log n)` for `k` indexes, which is cheaper than querying every index.
+    /// Indexes beyond the rightmost one are not yielded.
+    pub fn prefix_sums_in(
+        &self,
+        range: RangeInclusive<usize>,
+    ) -> impl Iterator<Item = (usize, T)> + '_ {
+        PrefixSums::new(&self.data, range)
+    }
+
+    /// Returns sums of every `k` consecutive indexes: `0..k`, `1..k + 1` and so on up
+    /// to the rightmost index. Nothing is yielded if `k` is 0 or exceeds the size
+    /// of the tree.
+    pub fn window_sums(&self, k: usize) -> impl Iterator<Item = T> + '_ {
+        WindowSums::new(&self.data, k)
+    }
+
+    /// Returns iterator over `(index, value)` pairs of points holding non-default
+    /// values, in ascending index order.
+    ///
+    /// Takes `O(n)` for values which can cancel each other out, see
+    /// [`FenwickTreeValue::CANCELS`], numeric types included: a range with default
+    /// aggregate may still hold non-default points, so every index of the tree is
+    /// visited, however sparse it is. Use [`crate::MirroredFenwickTree::iter_nonzero`]
+    /// to skip empty ranges of such trees.
+    ///
+    /// Other values, like [`crate::DurationValue`] or [`crate::Histogram`], skip ranges
+    /// with default aggregate as a whole, so the cost grows with the number of
+    /// non-default points rather than with the size of the tree.
+    pub fn iter_nonzero(&self) -> impl Iterator<Item = (usize, T)> + '_ {
+        NonZeroPoints::new(&self.data)
+    }
+
+    /// Returns number of indexes between `from` and `to` (including edges) holding
+    /// non-default values, walking the range like [`Self::iter_nonzero`] does.
+    /// [`crate::MirroredFenwickTree::count_nonzero_between`] counts in `O(log n)`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `from` is greater than `to` or `to` is out
+    /// of bounds.
+    ///
+    pub fn count_nonzero_between(&self, from: usize, to: usize) -> Result<usize, TreeError> {
+        if from > to {
+            return Err(TreeError::InvalidRange { from, to });
+        }
+        if to >= self.size() {
+            return Err(TreeError::IndexOutOfBounds {
+                index: to,
+                bound: self.size(),
+            });
+        }
+        Ok(NonZeroPoints::in_range(&self.data, from, to).count())
     }
 }
 
-impl<T: FenwickTreeValue> FenwickTree for FixedSizeFenwickTree<T> {
-    type Value = T;
+impl<T: InvertibleValue + SnapshotValue> FixedSizeFenwickTree<T> {
+    /// Returns hash of values stored at every index, in `O(n)`. Indexes holding default
+    /// values don't contribute, so trees storing the same values hash the same
+    /// regardless of their sizes.
+    ///
+    /// Hash function is fixed and indexes and values are hashed as little-endian bytes,
+    /// indexes as [`u64`] and values in their [`SnapshotValue`] encoding, so hashes
+    /// can be compared between processes and platforms.
+    pub fn content_hash(&self) -> u64 {
+        hashing::content_hash(&self.data)
+    }
 
-    fn query(&self, idx: usize) -> Result<T, TreeError> {
-        let idx: TreeIndex = idx.into();
+    /// Returns [`Self::content_hash`] of every block of `k` indexes: `0..k`, `k..2 * k`
+    /// and so on, which localizes divergence of two trees to the blocks which hashes
+    /// differ. Nothing is returned if `k` is 0.
+    pub fn chunk_hashes(&self, k: usize) -> Vec<u64> {
+        hashing::chunk_hashes(&self.data, k)
+    }
+}
 
-        if *idx >= self.size() {
-            return Err(TreeError::IndexOutOfBounds(*idx));
+/// Applies every `(idx, value)` pair with [`FenwickWriter::update`].
+///
+/// # Panics
+///
+/// Panics if any update fails, e.g. `idx` is out of bounds.
+impl<T: FenwickTreeValue> Extend<(usize, T)> for FixedSizeFenwickTree<T> {
+    fn extend<I: IntoIterator<Item = (usize, T)>>(&mut self, pairs: I) {
+        for (idx, value) in pairs {
+            if let Err(err) = self.update(idx, value) {
+                panic!("update of index {idx} failed: {err}");
+            }
         }
+    }
+}
 
-        let mut res = T::default();
-        for data_position in idx.lsb_descending() {
-            let data_position = data_position.to_internal();
-            res.store_value(&self[data_position]);
+impl<T: FenwickTreeValue> FenwickReader for FixedSizeFenwickTree<T> {
+    type Value = T;
+
+    fn query(&self, mut idx: usize) -> Result<T, TreeError> {
+        if idx >= self.size() {
+            match self.out_of_bounds.query_index(idx, self.size())? {
+                Some(last) => idx = last,
+                None => return Ok(T::default()),
+            }
         }
 
-        Ok(res)
+        Ok(self.prefix(idx))
+    }
+
+    /// Same as [`Self::lower_bound`].
+    fn find_prefix(&self, target: T) -> Option<usize>
+    where
+        T: PartialOrd,
+    {
+        self.lower_bound(target)
     }
+}
 
+impl<T: FenwickTreeValue> FenwickWriter for FixedSizeFenwickTree<T> {
     fn update(&mut self, idx: usize, value: Self::Value) -> Result<(), TreeError> {
-        let idx: TreeIndex = idx.into();
+        if let Some(position) = self.update_position(idx, &value)? {
+            add_to_nodes(&mut self.data, position, value);
+        }
+        Ok(())
+    }
 
-        if *idx > self.data.len() {
-            return Err(TreeError::IndexOutOfBounds(*idx));
+    fn update_ref(&mut self, idx: usize, value: &Self::Value) -> Result<(), TreeError> {
+        if let Some(position) = self.update_position(idx, value)? {
+            store_to_nodes(&mut self.data, position, value);
         }
+        Ok(())
+    }
 
-        for data_position in idx.lsb_ascending(self.size()) {
-            let data_position = data_position.to_internal();
-            self[data_position].store_value(&value);
+    fn update_target(&self, idx: usize) -> Result<Option<usize>, TreeError> {
+        match idx < self.size() {
+            true => Ok(Some(idx)),
+            false => self.out_of_bounds.update_index(idx, self.size()),
         }
+    }
+}
 
-        Ok(())
+impl<T: FenwickTreeValue> FixedSizeFenwickTree<T> {
+    /// Returns node position update of `idx` with `value` starts at, growing the tree
+    /// if its policy asks to, or [`None`] if update is skipped.
+    pub(crate) fn update_position(&mut self, mut idx: usize, value: &T) -> Result<Option<usize>, TreeError> {
+        if idx >= self.size() {
+            match self.out_of_bounds.update_index(idx, self.size())? {
+                Some(target) => idx = target,
+                None => return Ok(None),
+            }
+        }
+
+        if !value.is_valid() {
+            return Err(TreeError::InvalidValue);
+        }
+        if value.is_identity() {
+            return Ok(None);
+        }
+
+        if idx >= self.size() {
+            self.grow(idx)?;
+        }
+
+        Ok(Some(idx + 1))
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::{BTreeMap, HashMap};
+
     use crate::fixed_size_tree::FixedSizeFenwickTree;
-    use crate::{FenwickTree, TreeError};
-    use rand::seq::SliceRandom;
-    use rand::Rng;
+    use crate::values::NonNegative;
+    use crate::workload::Workload;
+    use crate::{DuplicatePolicy, FenwickReader, FenwickWriter, OutOfBoundsPolicy, TreeError};
+
+    /// Seeded values within `0..100` of `size` indexes, and shuffled indexes to update
+    /// them in.
+    fn random_input(seed: u64, size: usize) -> (Vec<i32>, Vec<usize>) {
+        let input = Workload::new(seed, size)
+            .deltas(0..100)
+            .updates()
+            .map(|(_, value)| value as i32)
+            .take(size)
+            .collect();
+        let mut order = Workload::new(seed, usize::MAX).updates();
+        let mut indexes: Vec<usize> = (0..size).collect();
+        indexes.sort_by_cached_key(|_| order.next());
+        (input, indexes)
+    }
+
+    #[test]
+    fn from_maps() {
+        let points = [(7, 3), (2, 1), (0, 5)];
+        let tree = FixedSizeFenwickTree::try_from(BTreeMap::from(points)).unwrap();
+        assert_eq!(tree.size(), 8);
+        assert_eq!(tree.query(1).unwrap(), 5);
+        assert_eq!(tree.query(7).unwrap(), 9);
+
+        let tree = FixedSizeFenwickTree::try_from(HashMap::from(points)).unwrap();
+        assert_eq!(tree.query(6).unwrap(), 6);
+        assert!(tree.query(8).is_err());
+
+        let tree = FixedSizeFenwickTree::<i32>::try_from(HashMap::new()).unwrap();
+        assert_eq!(tree.size(), 0);
+
+        assert_eq!(
+            FixedSizeFenwickTree::try_from(BTreeMap::from([(usize::MAX, 1)])).err(),
+            Some(TreeError::Overflow)
+        );
+        assert_eq!(
+            FixedSizeFenwickTree::try_from(vec![(usize::MAX, 1)]).err(),
+            Some(TreeError::Overflow)
+        );
+        assert_eq!(
+            FixedSizeFenwickTree::<i32>::with_points(usize::MAX, &[]).err(),
+            Some(TreeError::Overflow)
+        );
+    }
+
+    #[test]
+    fn huge_keys_fail_allocation() {
+        let huge = usize::MAX / 2;
+        assert_eq!(
+            FixedSizeFenwickTree::try_from(BTreeMap::from([(huge, 1)])).err(),
+            Some(TreeError::AllocationFailed)
+        );
+        assert_eq!(
+            FixedSizeFenwickTree::try_from(vec![(huge, 1)]).err(),
+            Some(TreeError::AllocationFailed)
+        );
+        assert_eq!(
+            FixedSizeFenwickTree::<i32>::with_points(huge, &[]).err(),
+            Some(TreeError::AllocationFailed)
+        );
+    }
+
+    #[test]
+    fn try_from_pairs() {
+        let pairs = vec![(3, 1), (1, 2), (3, 4)];
+        assert_eq!(
+            FixedSizeFenwickTree::try_from(pairs.clone()).err(),
+            Some(TreeError::DuplicateIndex { index: 3 })
+        );
+
+        let tree = FixedSizeFenwickTree::try_from((pairs.clone(), DuplicatePolicy::Sum)).unwrap();
+        assert_eq!(tree.size(), 4);
+        assert_eq!(tree.query(3).unwrap(), 7);
+
+        let tree = FixedSizeFenwickTree::try_from((pairs, DuplicatePolicy::Overwrite)).unwrap();
+        assert_eq!(tree.query(3).unwrap(), 6);
+
+        let tree = FixedSizeFenwickTree::try_from(vec![(0, 1), (2, 0), (1, 1)]).unwrap();
+        assert_eq!(tree.query(2).unwrap(), 2);
+    }
+
+    #[test]
+    fn constructors() {
+        let tree = FixedSizeFenwickTree::<i32>::zeros(4);
+        assert_eq!(tree.query(3).unwrap(), 0);
+
+        let tree = FixedSizeFenwickTree::filled(5, 2);
+        assert_eq!(tree.query(4).unwrap(), 10);
+
+        let tree = FixedSizeFenwickTree::with_points(4, &[(1, 2), (3, 1), (1, 1)]).unwrap();
+        assert_eq!(tree.query(2).unwrap(), 3);
+        assert_eq!(tree.query(3).unwrap(), 4);
+        assert_eq!(
+            FixedSizeFenwickTree::with_points(4, &[(4, 1)]).err(),
+            Some(TreeError::IndexOutOfBounds { index: 4, bound: 4 })
+        );
+    }
+
+    #[test]
+    fn fill() {
+        let mut tree = FixedSizeFenwickTree::from_fn(10, |i| i as i32);
+        tree.fill(3);
+        for i in 0..10 {
+            assert_eq!(tree.query(i).unwrap(), 3 * (i as i32 + 1));
+        }
+    }
+
+    #[test]
+    fn content_hash() {
+        let mut tree = FixedSizeFenwickTree::<i32>::new(16);
+        let mut other = FixedSizeFenwickTree::<i32>::new(32);
+        assert_eq!(tree.content_hash(), other.content_hash());
+
+        tree.update(3, 1).unwrap();
+        tree.update(12, 5).unwrap();
+        other.update(12, 5).unwrap();
+        assert_ne!(tree.content_hash(), other.content_hash());
+        assert_eq!(tree.content_hash(), tree.clone().content_hash());
+
+        let differ: Vec<bool> = tree
+            .chunk_hashes(4)
+            .iter()
+            .zip(other.chunk_hashes(4).iter())
+            .map(|(hash, other_hash)| hash != other_hash)
+            .collect();
+        assert_eq!(differ, vec![true, false, false, false]);
+
+        other.update(3, 1).unwrap();
+        assert_eq!(tree.content_hash(), other.content_hash());
+        assert_ne!(
+            tree.content_hash(),
+            FixedSizeFenwickTree::<i32>::new(4).content_hash()
+        );
+        assert!(tree.chunk_hashes(0).is_empty());
+
+        // FNV-1a of index 3 as little-endian `u64` followed by 1 as little-endian `i32`.
+        let tree = FixedSizeFenwickTree::from_fn(4, |i| i32::from(i == 3));
+        assert_eq!(tree.content_hash(), 0xe9a5_e082_ce14_59d7);
+    }
+
+    #[test]
+    fn out_of_bounds_policies() {
+        let mut tree = FixedSizeFenwickTree::<i32>::new(4);
+        tree.update(1, 1).unwrap();
+        assert!(tree.query(4).is_err());
+
+        let mut tree = tree.with_out_of_bounds(OutOfBoundsPolicy::ClampToLast);
+        tree.update(10, 2).unwrap();
+        assert_eq!(tree.query(3).unwrap(), 3);
+        assert_eq!(tree.query(10).unwrap(), 3);
+
+        let mut tree = tree.with_out_of_bounds(OutOfBoundsPolicy::TreatAsZero);
+        tree.update(10, 2).unwrap();
+        assert_eq!(tree.query(10).unwrap(), 0);
+        assert_eq!(tree.query(3).unwrap(), 3);
+
+        let mut tree = tree.with_out_of_bounds(OutOfBoundsPolicy::Grow);
+        tree.update(10, 4).unwrap();
+        assert_eq!(tree.size(), 11);
+        assert_eq!(tree.query(9).unwrap(), 3);
+        assert_eq!(tree.query(10).unwrap(), 7);
+        assert_eq!(tree.query(100).unwrap(), 7);
+
+        let mut empty =
+            FixedSizeFenwickTree::<i32>::new(0).with_out_of_bounds(OutOfBoundsPolicy::ClampToLast);
+        assert_eq!(empty.query(0).unwrap(), 0);
+        assert_eq!(
+            empty.update(0, 1),
+            Err(TreeError::IndexOutOfBounds { index: 0, bound: 0 })
+        );
+    }
+
+    #[test]
+    fn remap() {
+        let mut tree = FixedSizeFenwickTree::<i32>::new(4);
+        for idx in 0..4 {
+            tree.update(idx, idx as i32 + 1).unwrap();
+        }
+
+        let reversed = tree.remap(&[3, 2, 1, 0]).unwrap();
+        assert_eq!(reversed.to_points(), vec![4, 3, 2, 1]);
+        let merged = tree.remap_with(|idx| idx / 2).unwrap();
+        assert_eq!(merged.to_points(), vec![3, 7, 0, 0]);
+
+        assert_eq!(
+            tree.remap(&[0, 1, 2]).err(),
+            Some(TreeError::IndexOutOfBounds { index: 3, bound: 4 })
+        );
+        assert_eq!(
+            tree.remap_with(|idx| idx + 1).err(),
+            Some(TreeError::IndexOutOfBounds { index: 4, bound: 4 })
+        );
+    }
+
+    #[test]
+    fn checked_indexes() {
+        let mut tree = FixedSizeFenwickTree::<i32>::new(8);
+        tree.indexed(|mut tree| {
+            let idx = tree.checked_idx(5).unwrap();
+            tree.update_idx(idx, 3).unwrap();
+            let last = tree.checked_idx(7).unwrap();
+            tree.update_idx(last, 1).unwrap();
+
+            assert_eq!(tree.query_idx(idx), 3);
+            assert_eq!(tree.tree().query(7).unwrap(), 4);
+            assert_eq!(usize::from(idx), 5);
+            assert_eq!(
+                tree.checked_idx(8),
+                Err(TreeError::IndexOutOfBounds { index: 8, bound: 8 })
+            );
+        });
+
+        let mut tree = FixedSizeFenwickTree::<f64>::new(4);
+        tree.indexed(|mut tree| {
+            let idx = tree.checked_idx(1).unwrap();
+            assert_eq!(tree.update_idx(idx, f64::NAN), Err(TreeError::InvalidValue));
+            assert_eq!(tree.query_idx(idx), 0.0);
+        });
+    }
 
     #[test]
     fn edge_case() {
@@ -97,118 +864,286 @@ mod tests {
     fn simple_tree_generation_with_queries() {
         let mut tree = FixedSizeFenwickTree::<i32>::new(32);
         for i in 0..32 {
-            if let Err(_) = tree.update(i, 1) {
-                assert!(false)
-            }
+            tree.update(i, 1).unwrap();
         }
         assert_eq!(tree.query(4).unwrap(), 5); // points at [0, 1, 2, 3, 4]
         assert_eq!(tree.query(0).unwrap(), 1);
         assert_eq!(tree.query(31).unwrap(), 32);
     }
 
+    #[test]
+    fn from_fn_matches_updates() {
+        let tree = FixedSizeFenwickTree::from_fn(100, |i| i as i64 * 3 - 50);
+        let mut expected = FixedSizeFenwickTree::new(100);
+        for i in 0..100 {
+            expected.update(i, i as i64 * 3 - 50).unwrap();
+        }
+
+        for i in 0..100 {
+            assert_eq!(tree.query(i), expected.query(i));
+        }
+        assert!(FixedSizeFenwickTree::from_fn(0, |_| 1).query(0).is_err());
+    }
+
+    #[test]
+    fn from_slice_and_vec_match_updates() {
+        let values: Vec<i64> = (0..77).map(|i| i * 7 % 11 - 5).collect();
+        let mut expected = FixedSizeFenwickTree::new(values.len());
+        for (idx, value) in values.iter().enumerate() {
+            expected.update(idx, *value).unwrap();
+        }
+
+        let from_slice = FixedSizeFenwickTree::from_slice(&values);
+        let from_vec = FixedSizeFenwickTree::from_vec(values.clone());
+        for idx in 0..=values.len() {
+            assert_eq!(from_slice.query(idx), expected.query(idx));
+            assert_eq!(from_vec.query(idx), expected.query(idx));
+        }
+        assert!(FixedSizeFenwickTree::<i64>::from_vec(Vec::new()).query(0).is_err());
+    }
+
+    #[test]
+    fn collect_and_extend_pairs() {
+        let mut tree: FixedSizeFenwickTree<i32> =
+            [(4, 2), (1, 3), (4, 1)].into_iter().collect();
+        assert_eq!(tree.size(), 5);
+        assert_eq!(tree.query(3).unwrap(), 3);
+        assert_eq!(tree.query(4).unwrap(), 6);
+
+        tree.extend((0..5).map(|idx| (idx, 1)));
+        assert_eq!(tree.query(4).unwrap(), 11);
+        assert_eq!(std::iter::empty().collect::<FixedSizeFenwickTree<i32>>().size(), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn extend_beyond_bounds() {
+        let mut tree = FixedSizeFenwickTree::<i32>::new(4);
+        tree.extend([(1, 1), (4, 1)]);
+    }
+
+    #[test]
+    fn update_bounds() {
+        let mut tree = FixedSizeFenwickTree::<i32>::new(4);
+        tree.update(3, 1).unwrap();
+        assert_eq!(
+            tree.update(4, 1),
+            Err(TreeError::IndexOutOfBounds { index: 4, bound: 4 })
+        );
+        assert_eq!(
+            tree.update(5, 1),
+            Err(TreeError::IndexOutOfBounds { index: 5, bound: 4 })
+        );
+        assert_eq!(
+            tree.update(4, 0),
+            Err(TreeError::IndexOutOfBounds { index: 4, bound: 4 })
+        );
+        assert_eq!(tree.query(3).unwrap(), 1);
+
+        let mut tree = FixedSizeFenwickTree::<i32>::new(0);
+        assert_eq!(
+            tree.update(0, 1),
+            Err(TreeError::IndexOutOfBounds { index: 0, bound: 0 })
+        );
+    }
+
+    #[test]
+    fn huge_indexes_dont_panic() {
+        let mut tree = FixedSizeFenwickTree::<i32>::new(4);
+        assert_eq!(
+            tree.query(usize::MAX),
+            Err(TreeError::IndexOutOfBounds {
+                index: usize::MAX,
+                bound: 4
+            })
+        );
+        assert_eq!(
+            tree.update(usize::MAX, 1),
+            Err(TreeError::IndexOutOfBounds {
+                index: usize::MAX,
+                bound: 4
+            })
+        );
+    }
+
+    #[test]
+    fn window_sums() {
+        let tree = FixedSizeFenwickTree::from_fn(6, |i| i as i32);
+
+        assert_eq!(
+            tree.window_sums(1).collect::<Vec<_>>(),
+            vec![0, 1, 2, 3, 4, 5]
+        );
+        assert_eq!(tree.window_sums(3).collect::<Vec<_>>(), vec![3, 6, 9, 12]);
+        assert_eq!(tree.window_sums(6).collect::<Vec<_>>(), vec![15]);
+        assert_eq!(tree.window_sums(7).count(), 0);
+        assert_eq!(tree.window_sums(0).count(), 0);
+    }
+
+    #[test]
+    fn append() {
+        let mut tree = FixedSizeFenwickTree::from_fn(5, |i| i as i32);
+        tree.append(FixedSizeFenwickTree::from_fn(7, |i| 10 * i as i32))
+            .unwrap();
+
+        for i in 0..12 {
+            let expected: i32 = (0..=i as i32)
+                .map(|i| if i < 5 { i } else { 10 * (i - 5) })
+                .sum();
+            assert_eq!(tree.query(i).unwrap(), expected);
+        }
+        assert!(tree.query(12).is_err());
+
+        tree.append(FixedSizeFenwickTree::new(0)).unwrap();
+        assert!(tree.query(12).is_err());
+    }
+
+    #[test]
+    fn try_new() {
+        assert_eq!(
+            FixedSizeFenwickTree::<i64>::try_new(3).unwrap().query(2),
+            Ok(0)
+        );
+        assert!(matches!(
+            FixedSizeFenwickTree::<i64>::try_new(usize::MAX),
+            Err(TreeError::Overflow)
+        ));
+        assert!(matches!(
+            FixedSizeFenwickTree::<i64>::try_new(usize::MAX / 2),
+            Err(TreeError::AllocationFailed)
+        ));
+    }
+
+    #[test]
+    fn chunk_sums() {
+        let tree = FixedSizeFenwickTree::from_fn(7, |i| i as i32);
+
+        assert_eq!(tree.chunk_sums(3).collect::<Vec<_>>(), vec![3, 12, 6]);
+        assert_eq!(tree.chunk_sums(7).collect::<Vec<_>>(), vec![21]);
+        assert_eq!(tree.chunk_sums(usize::MAX).collect::<Vec<_>>(), vec![21]);
+        assert_eq!(tree.chunk_sums(0).count(), 0);
+        assert_eq!(FixedSizeFenwickTree::<i32>::new(0).chunk_sums(2).count(), 0);
+    }
+
+    #[test]
+    fn prefix_sums_in_matches_queries() {
+        let tree = FixedSizeFenwickTree::from_fn(37, |i| (i as i32 % 5) - 2);
+        for from in 0..37 {
+            for to in from..37 {
+                let expected: Vec<_> = (from..=to).map(|i| (i, tree.query(i).unwrap())).collect();
+                assert_eq!(tree.prefix_sums_in(from..=to).collect::<Vec<_>>(), expected);
+            }
+        }
+
+        assert_eq!(tree.prefix_sums_in(35..=usize::MAX).count(), 2);
+        assert_eq!(tree.prefix_sums_in(37..=40).count(), 0);
+        #[allow(clippy::reversed_empty_ranges)]
+        let reversed = tree.prefix_sums_in(5..=4);
+        assert_eq!(reversed.count(), 0);
+        assert_eq!(
+            FixedSizeFenwickTree::<i32>::new(0)
+                .prefix_sums_in(0..=0)
+                .count(),
+            0
+        );
+    }
+
     // TODO: #[should_panic]?
     #[test]
     fn tree_indexing_overflow() {
         let tree = FixedSizeFenwickTree::<i32>::new(0);
 
-        match tree.query(1) {
-            Ok(_) => assert!(false),
-            Err(message) => assert_eq!(message, TreeError::IndexOutOfBounds(1)),
-        }
+        assert_eq!(
+            tree.query(1),
+            Err(TreeError::IndexOutOfBounds { index: 1, bound: 0 })
+        );
     }
 
     #[test]
     fn update_existent_value() {
         let mut tree = FixedSizeFenwickTree::<i32>::new(32);
         for _i in 0..32 {
-            if let Err(_) = tree.update(0, 1) {
-                assert!(false)
-            }
+            tree.update(0, 1).unwrap();
         }
         let res = tree.query(1).unwrap();
         assert_eq!(res, 32);
     }
 
     #[test]
-    fn random_100_point_data() {
-        let size = 100;
-        let mut input = vec![];
-        let mut rng = rand::thread_rng();
+    fn clone_from_reuses_allocation() {
+        let mut tree = FixedSizeFenwickTree::<i32>::new(100);
+        let mut snapshot = tree.clone();
+        let allocation = snapshot.data.as_ptr();
 
-        for _i in 0..size {
-            input.push((rng.gen::<f32>() * 100.0) as i32);
+        for i in 0..10 {
+            tree.update(i * 10, 1).unwrap();
+            snapshot.clone_from(&tree);
+            assert_eq!(snapshot.data.as_ptr(), allocation);
+            assert_eq!(snapshot.query(99).unwrap(), i as i32 + 1);
         }
+    }
+
+    #[test]
+    fn identity_update_is_still_bounds_checked() {
+        let mut tree = FixedSizeFenwickTree::<i32>::new(4);
+        tree.update(3, 0).unwrap();
+        assert_eq!(tree.query(3).unwrap(), 0);
+        assert_eq!(
+            tree.update(100, 0),
+            Err(TreeError::IndexOutOfBounds {
+                index: 100,
+                bound: 4
+            })
+        );
+    }
+
+    #[test]
+    fn random_100_point_data() {
+        let size = 100;
+        let (input, _) = random_input(1, size);
 
         let mut tree = FixedSizeFenwickTree::<i32>::new(size);
         for i in 0..size {
-            if let Err(_) = tree.update(i, *input.get(i).unwrap()) {
-                assert!(false)
-            }
+            tree.update(i, *input.get(i).unwrap()).unwrap();
         }
 
         let mut sum = 0;
         for i in 0..size {
             sum += *input.get(i).unwrap();
 
-            if let Ok(res) = tree.query(i) {
-                assert_eq!(res, sum);
-            } else {
-                assert!(false)
-            }
+            assert_eq!(tree.query(i).unwrap(), sum);
         }
     }
 
     #[test]
     fn random_100_point_data_with_random_update_order() {
         let size = 100;
-        let mut input = vec![];
-        let mut rng = rand::thread_rng();
-
-        for _i in 0..size {
-            input.push((rng.gen::<f32>() * 100.0) as i32);
-        }
+        let (input, random_indexes) = random_input(2, size);
 
         let mut tree = FixedSizeFenwickTree::<i32>::new(size);
 
-        let mut random_indexes: Vec<usize> = (0..size).collect();
-        random_indexes.shuffle(&mut rng);
         for i in random_indexes {
-            if let Err(_) = tree.update(i, *input.get(i).unwrap()) {
-                assert!(false)
-            }
+            tree.update(i, *input.get(i).unwrap()).unwrap();
         }
 
         let mut sum = 0;
         for i in 0..size {
             sum += *input.get(i).unwrap();
-            if let Ok(res) = tree.query(i) {
-                assert_eq!(res, sum);
-            } else {
-                assert!(false);
-            }
+            assert_eq!(tree.query(i).unwrap(), sum);
         }
     }
 
     #[test]
     fn random_100_point_data_with_random_update_order_with_intermediate_asserts() {
         let size = 100;
-        let mut input = vec![];
-        let mut rng = rand::thread_rng();
-
-        for _i in 0..size {
-            input.push((rng.gen::<f32>() * 100.0) as i32);
-        }
+        let (input, random_indexes) = random_input(3, size);
 
         let mut tree = FixedSizeFenwickTree::<i32>::new(size);
 
-        let mut random_indexes: Vec<usize> = (0..size).collect();
-        random_indexes.shuffle(&mut rng);
         for i in random_indexes {
             let sum_before_update = tree.query(i).unwrap();
             let value_to_update = *input.get(i).unwrap();
-            if let Err(_) = tree.update(i, value_to_update) {
-                assert!(false)
-            }
+            tree.update(i, value_to_update).unwrap();
             let sum_after_update = tree.query(i).unwrap();
             assert_eq!(sum_after_update - sum_before_update, value_to_update)
         }
@@ -217,103 +1152,292 @@ mod tests {
         for i in 0..size {
             sum += *input.get(i).unwrap();
 
-            if let Ok(res) = tree.query(i) {
-                assert_eq!(res, sum);
-            } else {
-                assert!(false)
-            }
+            assert_eq!(tree.query(i).unwrap(), sum);
         }
     }
-}
 
-#[cfg(all(feature = "benchmarks", test))]
-mod benchmarks {
-    extern crate test;
-    use rand::seq::SliceRandom;
-    use rand::Rng;
-    use test::Bencher;
+    #[test]
+    fn partition_point_on_empty_tree() {
+        let tree = FixedSizeFenwickTree::<u32>::new(0);
+        assert_eq!(tree.partition_point(|sum| *sum < 1), 0);
+    }
 
-    use crate::prelude::*;
+    #[test]
+    fn partition_point_by_budget() {
+        let mut tree = FixedSizeFenwickTree::<u32>::new(100);
+        for (idx, cost) in [(3, 10), (10, 5), (11, 5), (50, 30), (99, 1)] {
+            tree.update(idx, cost).unwrap();
+        }
 
-    fn bench_update(b: &mut Bencher, size: usize) {
-        let mut input = vec![];
-        let mut rng = rand::thread_rng();
+        assert_eq!(tree.partition_point(|cost| *cost == 0), 3);
+        assert_eq!(tree.partition_point(|cost| *cost <= 10), 10);
+        assert_eq!(tree.partition_point(|cost| *cost <= 15), 11);
+        assert_eq!(tree.partition_point(|cost| *cost <= 20), 50);
+        assert_eq!(tree.partition_point(|cost| *cost < 51), 99);
+        assert_eq!(tree.partition_point(|cost| *cost <= 51), 100);
+    }
 
-        for _i in 0..size {
-            input.push((rng.gen::<f32>() * 100.0) as i32);
+    #[test]
+    fn partition_point_matches_linear_scan() {
+        let mut tree = FixedSizeFenwickTree::<u32>::new(100);
+        for (i, (_, value)) in Workload::new(4, 100).updates().take(100).enumerate() {
+            tree.update(i, value as u32).unwrap();
         }
 
-        let mut tree = FixedSizeFenwickTree::<i32>::new(size);
+        for budget in 0..500 {
+            let expected = (0..100)
+                .find(|i| tree.query(*i).unwrap() >= budget)
+                .unwrap_or(100);
+            assert_eq!(tree.partition_point(|sum| *sum < budget), expected);
+        }
+    }
 
-        let random_indexes: Vec<usize> = (0..size).collect();
+    #[test]
+    fn lower_and_upper_bounds_with_ties() {
+        let mut tree = FixedSizeFenwickTree::<u32>::new(100);
+        for (idx, count) in [(3, 10), (10, 5), (11, 5), (50, 30), (99, 1)] {
+            tree.update(idx, count).unwrap();
+        }
 
-        b.iter(|| {
-            let i = *random_indexes.choose(&mut rng).unwrap();
-            let value_to_update = *input.get(i).unwrap();
-            tree.update(i, value_to_update).unwrap()
-        });
+        assert_eq!(tree.lower_bound(0), Some(0));
+        assert_eq!(tree.upper_bound(0), Some(3));
+        assert_eq!(tree.lower_bound(10), Some(3));
+        assert_eq!(tree.upper_bound(10), Some(10));
+        assert_eq!(tree.lower_bound(15), Some(10));
+        assert_eq!(tree.upper_bound(15), Some(11));
+        assert_eq!(tree.lower_bound(51), Some(99));
+        assert_eq!(tree.upper_bound(50), Some(99));
+        assert_eq!(tree.upper_bound(51), None);
+        assert_eq!(tree.lower_bound(52), None);
     }
 
-    fn bench_reads(b: &mut Bencher, size: usize) {
-        let mut input = vec![];
-        let mut rng = rand::thread_rng();
+    #[test]
+    fn checked_searches_detect_negative_values() {
+        let mut tree = FixedSizeFenwickTree::<NonNegative<i32>>::new(100);
+        tree.update(3, NonNegative::new(10)).unwrap();
+        tree.update(50, NonNegative::new(5)).unwrap();
+
+        assert_eq!(tree.checked_lower_bound(10), Ok(Some(3)));
+        assert_eq!(tree.checked_upper_bound(10), Ok(Some(50)));
+        assert_eq!(
+            tree.checked_select_many(&[1, 15, 16]),
+            Ok(vec![Some(3), Some(50), None])
+        );
 
-        for _i in 0..size {
-            input.push((rng.gen::<f32>() * 100.0) as i32);
+        tree.update(60, NonNegative::new(-5)).unwrap();
+        tree.update(60, NonNegative::new(5)).unwrap();
+
+        assert_eq!(tree.checked_lower_bound(10), Err(TreeError::NegativeValue));
+        assert_eq!(tree.checked_upper_bound(10), Err(TreeError::NegativeValue));
+        assert_eq!(
+            tree.checked_select_many(&[1]),
+            Err(TreeError::NegativeValue)
+        );
+    }
+
+    #[test]
+    fn select_many_thresholds() {
+        let mut tree = FixedSizeFenwickTree::<u32>::new(100);
+        for (idx, count) in [(3, 10), (10, 5), (11, 5), (50, 30), (99, 1)] {
+            tree.update(idx, count).unwrap();
         }
 
-        let mut tree = FixedSizeFenwickTree::<i32>::new(size);
-        let random_indexes: Vec<usize> = (0..size).collect();
+        assert_eq!(
+            tree.select_many(&[51, 0, 10, 11, 21, 15, 52, 1]),
+            vec![
+                Some(99),
+                Some(0),
+                Some(3),
+                Some(10),
+                Some(50),
+                Some(10),
+                None,
+                Some(3)
+            ]
+        );
+        assert_eq!(tree.select_many(&[]), vec![]);
+    }
 
-        for _i in 0..size {
-            let i = *random_indexes.choose(&mut rng).unwrap();
-            let value_to_update = *input.get(i).unwrap();
-            tree.update(i, value_to_update).unwrap()
+    #[test]
+    fn select_many_matches_partition_point() {
+        let mut tree = FixedSizeFenwickTree::<u32>::new(100);
+        for (i, (_, value)) in Workload::new(5, 100).updates().take(100).enumerate() {
+            tree.update(i, value as u32).unwrap();
         }
 
-        b.iter(|| {
-            let i = *random_indexes.choose(&mut rng).unwrap();
-            tree.query(i).unwrap();
-        });
+        let targets: Vec<u32> = Workload::new(6, 500)
+            .updates()
+            .map(|(target, _)| target as u32)
+            .take(100)
+            .collect();
+        let expected: Vec<Option<usize>> = targets
+            .iter()
+            .map(|target| Some(tree.partition_point(|sum| sum < target)).filter(|idx| *idx < 100))
+            .collect();
+        assert_eq!(tree.select_many(&targets), expected);
     }
 
-    #[bench]
-    fn bench_1000_writes(b: &mut Bencher) {
-        bench_update(b, 1000);
+    #[test]
+    fn min_window_with_sum_matches_naive() {
+        for seed in 0..20 {
+            let values: Vec<u32> = Workload::new(seed, 30)
+                .deltas(0..4)
+                .updates()
+                .map(|(_, value)| value as u32)
+                .take(30)
+                .collect();
+            let tree = FixedSizeFenwickTree::from_fn(values.len(), |i| values[i]);
+            for target in 0..=values.iter().sum::<u32>() + 1 {
+                let naive = (0..values.len())
+                    .flat_map(|len| (len..values.len()).map(move |to| (to - len, to)))
+                    .find(|(from, to)| values[*from..=*to].iter().sum::<u32>() >= target);
+                assert_eq!(tree.min_window_with_sum(target), naive);
+            }
+        }
+        assert_eq!(
+            FixedSizeFenwickTree::<u32>::new(0).min_window_with_sum(0),
+            None
+        );
     }
 
-    #[bench]
-    fn bench_10000_writes(b: &mut Bencher) {
-        bench_update(b, 10000);
+    #[test]
+    fn kth_missing_matches_naive() {
+        for size in [0, 1, 5, 16, 37] {
+            let mut tree = FixedSizeFenwickTree::<i32>::new(size);
+            let mut missing = vec![];
+            let coins = Workload::new(size as u64, 1).deltas(0..2).updates();
+            for (idx, (_, coin)) in (0..size).zip(coins) {
+                match coin {
+                    1 => tree.update(idx, 1).unwrap(),
+                    _ => missing.push(idx),
+                }
+            }
+
+            for (k, idx) in missing.iter().enumerate() {
+                assert_eq!(tree.kth_missing(k), Some(*idx));
+            }
+            assert_eq!(tree.kth_missing(missing.len()), None);
+        }
     }
 
-    #[bench]
-    fn bench_100000_writes(b: &mut Bencher) {
-        bench_update(b, 100000);
+    #[test]
+    fn iter_nonzero_on_empty_tree() {
+        let tree = FixedSizeFenwickTree::<i32>::new(0);
+        assert_eq!(tree.iter_nonzero().next(), None);
     }
 
-    #[bench]
-    fn bench_10000000_writes(b: &mut Bencher) {
-        bench_update(b, 10000000);
+    #[test]
+    fn iter_nonzero_skips_default_points() {
+        let mut tree = FixedSizeFenwickTree::<i32>::new(1000);
+        for (idx, value) in [(999, 1), (0, 2), (512, 3), (511, 4), (7, 5)] {
+            tree.update(idx, value).unwrap();
+        }
+
+        assert_eq!(
+            tree.iter_nonzero().collect::<Vec<_>>(),
+            vec![(0, 2), (7, 5), (511, 4), (512, 3), (999, 1)]
+        );
     }
 
-    #[bench]
-    fn bench_1000_reads(b: &mut Bencher) {
-        bench_reads(b, 1000);
+    #[test]
+    fn iter_nonzero_reports_cancelling_points() {
+        let mut tree = FixedSizeFenwickTree::<i32>::new(8);
+        tree.update(0, 5).unwrap();
+        tree.update(1, -5).unwrap();
+        tree.update(4, 2).unwrap();
+        tree.update(7, -2).unwrap();
+
+        assert_eq!(
+            tree.iter_nonzero().collect::<Vec<_>>(),
+            vec![(0, 5), (1, -5), (4, 2), (7, -2)]
+        );
+        assert_eq!(tree.count_nonzero_between(0, 7).unwrap(), 4);
+        assert_eq!(tree.count_nonzero_between(1, 6).unwrap(), 2);
+
+        // Unsigned values can't cancel, so empty ranges are pruned.
+        let tree = FixedSizeFenwickTree::<u32>::with_points(1 << 20, &[(3, 1), (70_000, 2)]).unwrap();
+        assert_eq!(tree.iter_nonzero().collect::<Vec<_>>(), vec![(3, 1), (70_000, 2)]);
     }
 
-    #[bench]
-    fn bench_10000_reads(b: &mut Bencher) {
-        bench_reads(b, 10000);
+    #[test]
+    fn count_nonzero_between() {
+        let mut tree = FixedSizeFenwickTree::<i32>::new(1000);
+        for (idx, value) in [(999, 1), (0, 2), (512, 3), (511, 4), (7, 5)] {
+            tree.update(idx, value).unwrap();
+        }
+
+        assert_eq!(tree.count_nonzero_between(0, 999).unwrap(), 5);
+        assert_eq!(tree.count_nonzero_between(1, 998).unwrap(), 3);
+        assert_eq!(tree.count_nonzero_between(7, 511).unwrap(), 2);
+        assert_eq!(tree.count_nonzero_between(8, 510).unwrap(), 0);
+        assert_eq!(tree.count_nonzero_between(512, 512).unwrap(), 1);
+        assert_eq!(
+            tree.count_nonzero_between(600, 10),
+            Err(TreeError::InvalidRange { from: 600, to: 10 })
+        );
+        assert_eq!(
+            tree.count_nonzero_between(0, 1000),
+            Err(TreeError::IndexOutOfBounds {
+                index: 1000,
+                bound: 1000
+            })
+        );
     }
 
-    #[bench]
-    fn bench_100000_reads(b: &mut Bencher) {
-        bench_reads(b, 100000);
+    #[test]
+    fn iter_nonzero_matches_random_points() {
+        let size = 100;
+        let mut input = vec![0; size];
+        let mut tree = FixedSizeFenwickTree::<i32>::new(size);
+
+        for (idx, value) in Workload::new(7, size).deltas(1..100).updates().take(20) {
+            let value = value as i32;
+            input[idx] += value;
+            tree.update(idx, value).unwrap();
+        }
+
+        let expected: Vec<(usize, i32)> = input
+            .into_iter()
+            .enumerate()
+            .filter(|(_, value)| *value != 0)
+            .collect();
+        assert_eq!(tree.iter_nonzero().collect::<Vec<_>>(), expected);
     }
 
-    #[bench]
-    fn bench_10000000_reads(b: &mut Bencher) {
-        bench_reads(b, 10000000);
+    #[test]
+    fn prefix_cursor_matches_queries() {
+        use std::cell::Cell;
+
+        use crate::{FenwickTreeValue, Max};
+
+        let tree = FixedSizeFenwickTree::from_fn(45, |i| (i as i32 % 7) - 3);
+        for from in 0..47 {
+            let expected: Vec<_> = (from..45).map(|i| (i, tree.query(i).unwrap())).collect();
+            let cursor = tree.prefix_cursor(from);
+            assert_eq!(cursor.len(), expected.len());
+            assert_eq!(cursor.collect::<Vec<_>>(), expected);
+        }
+
+        let tree = FixedSizeFenwickTree::from_fn(30, |i| Max((i * 7 % 11) as u8));
+        for (idx, max) in tree.prefix_cursor(3) {
+            assert_eq!(max, tree.query(idx).unwrap());
+        }
+
+        thread_local!(static STORED: Cell<usize> = const { Cell::new(0) });
+
+        #[derive(Debug, Default, Clone, PartialEq)]
+        struct Counted(u64);
+
+        impl FenwickTreeValue for Counted {
+            fn store_value(&mut self, other: &Self) {
+                STORED.with(|stored| stored.set(stored.get() + 1));
+                self.0 += other.0;
+            }
+        }
+
+        let tree = FixedSizeFenwickTree::from_fn(1 << 12, |_| Counted(1));
+        STORED.with(|stored| stored.set(0));
+        assert_eq!(tree.prefix_cursor(0).last(), Some((4095, Counted(4096))));
+        assert_eq!(STORED.with(Cell::get), 4096);
     }
 }
@@ -0,0 +1,334 @@
+use std::collections::BTreeMap;
+
+use crate::{least_significant_bit, try_vec_of_defaults, TreeError};
+
+/// Grid of `rows * cols` cells supporting rectangle updates, cell reads and rectangle
+/// sums, all in `O(log rows * log cols)`, so heatmap-style accumulation over regions
+/// doesn't need per-cell loops.
+///
+/// Classic construction of four 2D trees holding rectangle corner changes, plain and
+/// weighted by their row, column and both. Cells are addressed with `(row, col)`
+/// pairs, rectangles include both corners.
+///
+/// ```
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut heatmap = GridFenwickTree::new(4, 4);
+/// heatmap.add_rectangle((0, 0), (2, 2), 1).unwrap();
+/// heatmap.add_rectangle((1, 1), (3, 3), 2).unwrap();
+///
+/// assert_eq!(heatmap.get((2, 2)).unwrap(), 3);
+/// assert_eq!(heatmap.rectangle_sum((0, 0), (3, 3)).unwrap(), 27);
+/// ```
+#[derive(Debug, Clone)]
+pub struct GridFenwickTree {
+    /// Nodes of four trees, row-major, including unused row 0 and column 0.
+    data: Vec<[i64; 4]>,
+    rows: usize,
+    cols: usize,
+}
+
+impl GridFenwickTree {
+    /// Creates grid of `rows * cols` cells holding 0.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory for the grid can't be allocated, use [`Self::try_new`] to
+    /// handle that case.
+    pub fn new(rows: usize, cols: usize) -> Self {
+        Self::try_new(rows, cols)
+            .unwrap_or_else(|err| panic!("grid of {rows} * {cols} cells: {err}"))
+    }
+
+    /// Same as [`Self::new`], but doesn't panic.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if number of nodes overflows [`usize`] or
+    /// memory for them can't be allocated.
+    ///
+    pub fn try_new(rows: usize, cols: usize) -> Result<Self, TreeError> {
+        let len = rows
+            .checked_add(1)
+            .zip(cols.checked_add(1))
+            .and_then(|(rows, cols)| rows.checked_mul(cols))
+            .ok_or(TreeError::Overflow)?;
+        Ok(Self {
+            data: try_vec_of_defaults(len)?,
+            rows,
+            cols,
+        })
+    }
+
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Adds `value` to every cell between `from` and `to` corners (including edges).
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `from` lies after `to` in any dimension,
+    /// `to` is out of bounds, or weighted changes overflow. Nothing is changed then.
+    ///
+    pub fn add_rectangle(
+        &mut self,
+        from: (usize, usize),
+        to: (usize, usize),
+        value: i64,
+    ) -> Result<(), TreeError> {
+        self.check_rectangle(from, to)?;
+        if value == 0 {
+            return Ok(());
+        }
+
+        // Corners share nodes, so all of them are summed up and checked before any
+        // node is written.
+        let (rows, cols) = ((from.0, to.0 + 1), (from.1, to.1 + 1));
+        let mut nodes = BTreeMap::new();
+        self.add_corner(&mut nodes, rows.0, cols.0, value)?;
+        self.add_corner(&mut nodes, rows.0, cols.1, -value)?;
+        self.add_corner(&mut nodes, rows.1, cols.0, -value)?;
+        self.add_corner(&mut nodes, rows.1, cols.1, value)?;
+
+        for (position, node) in nodes {
+            self.data[position] = node;
+        }
+        Ok(())
+    }
+
+    /// Adds `value` to a single cell.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `cell` is out of bounds.
+    ///
+    pub fn update(&mut self, cell: (usize, usize), value: i64) -> Result<(), TreeError> {
+        self.add_rectangle(cell, cell, value)
+    }
+
+    /// Returns value of a single cell.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `cell` is out of bounds, or the cell
+    /// value overflows.
+    ///
+    pub fn get(&self, cell: (usize, usize)) -> Result<i64, TreeError> {
+        self.check_rectangle(cell, cell)?;
+        Ok(self.prefix_nodes(cell.0 + 1, cell.1 + 1)?[0])
+    }
+
+    /// Returns sum of cells between `from` and `to` corners (including edges).
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `from` lies after `to` in any dimension,
+    /// `to` is out of bounds, or the sum overflows.
+    ///
+    pub fn rectangle_sum(
+        &self,
+        from: (usize, usize),
+        to: (usize, usize),
+    ) -> Result<i64, TreeError> {
+        self.check_rectangle(from, to)?;
+        let (rows, cols) = ((from.0, to.0 + 1), (from.1, to.1 + 1));
+        let outer = self.prefix_sum(rows.1, cols.1)?;
+        let above = self.prefix_sum(rows.0, cols.1)?;
+        let left = self.prefix_sum(rows.1, cols.0)?;
+        let corner = self.prefix_sum(rows.0, cols.0)?;
+        outer
+            .checked_sub(above)
+            .and_then(|sum| sum.checked_sub(left))
+            .and_then(|sum| sum.checked_add(corner))
+            .ok_or(TreeError::Overflow)
+    }
+
+    /// Sum of cells in the first `rows` rows and `cols` columns.
+    fn prefix_sum(&self, rows: usize, cols: usize) -> Result<i64, TreeError> {
+        let [plain, by_row, by_col, by_both] = self.prefix_nodes(rows, cols)?;
+        let rows = i64::try_from(rows + 1).map_err(|_| TreeError::Overflow)?;
+        let cols = i64::try_from(cols + 1).map_err(|_| TreeError::Overflow)?;
+        plain
+            .checked_mul(rows)
+            .and_then(|sum| sum.checked_mul(cols))
+            .and_then(|sum| sum.checked_sub(by_row.checked_mul(cols)?))
+            .and_then(|sum| sum.checked_sub(by_col.checked_mul(rows)?))
+            .and_then(|sum| sum.checked_add(by_both))
+            .ok_or(TreeError::Overflow)
+    }
+
+    fn prefix_nodes(&self, rows: usize, cols: usize) -> Result<[i64; 4], TreeError> {
+        let mut res = [0i64; 4];
+        let mut row = rows;
+        while row > 0 {
+            let mut col = cols;
+            while col > 0 {
+                let node = &self.data[row * (self.cols + 1) + col];
+                for (sum, value) in res.iter_mut().zip(node) {
+                    *sum = sum.checked_add(*value).ok_or(TreeError::Overflow)?;
+                }
+                col -= least_significant_bit(col);
+            }
+            row -= least_significant_bit(row);
+        }
+        Ok(res)
+    }
+
+    /// Adds `value` to the corner of cells starting at 0-based `(row, col)`, collecting
+    /// new values of affected nodes in `nodes` keyed by their positions. Corners beyond
+    /// the grid don't affect any cell and are skipped.
+    fn add_corner(
+        &self,
+        nodes: &mut BTreeMap<usize, [i64; 4]>,
+        row: usize,
+        col: usize,
+        value: i64,
+    ) -> Result<(), TreeError> {
+        if row >= self.rows || col >= self.cols {
+            return Ok(());
+        }
+        let (row, col) = (row + 1, col + 1);
+        let row_weight = i64::try_from(row).map_err(|_| TreeError::Overflow)?;
+        let col_weight = i64::try_from(col).map_err(|_| TreeError::Overflow)?;
+        let by_row = value.checked_mul(row_weight).ok_or(TreeError::Overflow)?;
+        let by_col = value.checked_mul(col_weight).ok_or(TreeError::Overflow)?;
+        let by_both = by_row.checked_mul(col_weight).ok_or(TreeError::Overflow)?;
+        let change = [value, by_row, by_col, by_both];
+
+        let mut position_row = row;
+        while position_row <= self.rows {
+            let mut position_col = col;
+            while position_col <= self.cols {
+                let position = position_row * (self.cols + 1) + position_col;
+                let node = nodes.entry(position).or_insert(self.data[position]);
+                for (sum, value) in node.iter_mut().zip(change) {
+                    *sum = sum.checked_add(value).ok_or(TreeError::Overflow)?;
+                }
+                position_col += least_significant_bit(position_col);
+            }
+            position_row += least_significant_bit(position_row);
+        }
+        Ok(())
+    }
+
+    fn check_rectangle(&self, from: (usize, usize), to: (usize, usize)) -> Result<(), TreeError> {
+        if from.0 > to.0 {
+            return Err(TreeError::InvalidRange {
+                from: from.0,
+                to: to.0,
+            });
+        }
+        if from.1 > to.1 {
+            return Err(TreeError::InvalidRange {
+                from: from.1,
+                to: to.1,
+            });
+        }
+        if to.0 >= self.rows {
+            return Err(TreeError::IndexOutOfBounds {
+                index: to.0,
+                bound: self.rows,
+            });
+        }
+        if to.1 >= self.cols {
+            return Err(TreeError::IndexOutOfBounds {
+                index: to.1,
+                bound: self.cols,
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::grid_tree::GridFenwickTree;
+    use crate::workload::Workload;
+    use crate::TreeError;
+
+    #[test]
+    fn matches_naive_grid() {
+        let (rows, cols) = (9, 13);
+        let mut tree = GridFenwickTree::new(rows, cols);
+        let mut naive = vec![vec![0i64; cols]; rows];
+
+        let row_ranges = Workload::new(1, rows).ranges();
+        let col_ranges = Workload::new(2, cols).ranges();
+        let values = Workload::new(3, 1).deltas(-5..5).updates();
+        for (((top, bottom), (left, right)), (_, value)) in
+            row_ranges.zip(col_ranges).zip(values).take(50)
+        {
+            tree.add_rectangle((top, left), (bottom, right), value)
+                .unwrap();
+            for row in &mut naive[top..=bottom] {
+                row[left..=right].iter_mut().for_each(|cell| *cell += value);
+            }
+        }
+
+        for top in 0..rows {
+            for left in 0..cols {
+                assert_eq!(tree.get((top, left)).unwrap(), naive[top][left]);
+                for bottom in top..rows {
+                    for right in left..cols {
+                        let expected: i64 = naive[top..=bottom]
+                            .iter()
+                            .map(|row| row[left..=right].iter().sum::<i64>())
+                            .sum();
+                        let sum = tree.rectangle_sum((top, left), (bottom, right));
+                        assert_eq!(sum.unwrap(), expected);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn bounds() {
+        let mut tree = GridFenwickTree::new(2, 3);
+        tree.update((1, 2), 4).unwrap();
+        assert_eq!(tree.rectangle_sum((0, 0), (1, 2)).unwrap(), 4);
+        assert_eq!(
+            tree.get((2, 0)),
+            Err(TreeError::IndexOutOfBounds { index: 2, bound: 2 })
+        );
+        assert_eq!(
+            tree.add_rectangle((0, 2), (1, 1), 1),
+            Err(TreeError::InvalidRange { from: 2, to: 1 })
+        );
+        assert_eq!(
+            tree.rectangle_sum((0, 0), (0, 3)),
+            Err(TreeError::IndexOutOfBounds { index: 3, bound: 3 })
+        );
+
+        assert_eq!(GridFenwickTree::try_new(0, 4).unwrap().cols(), 4);
+        assert!(matches!(
+            GridFenwickTree::try_new(usize::MAX, 0),
+            Err(TreeError::Overflow)
+        ));
+        assert!(matches!(
+            GridFenwickTree::try_new(1 << 31, 1 << 31),
+            Err(TreeError::AllocationFailed)
+        ));
+    }
+
+    #[test]
+    fn overflow_leaves_grid_untouched() {
+        let mut tree = GridFenwickTree::new(10, 10);
+        assert_eq!(
+            tree.add_rectangle((0, 0), (2, 2), i64::MAX / 2),
+            Err(TreeError::Overflow)
+        );
+        assert_eq!(tree.get((5, 5)).unwrap(), 0);
+        assert_eq!(tree.rectangle_sum((0, 0), (9, 9)).unwrap(), 0);
+
+        tree.update((0, 0), i64::MAX / 4).unwrap();
+        assert_eq!(tree.rectangle_sum((0, 0), (9, 9)).unwrap(), i64::MAX / 4);
+        assert_eq!(tree.update((1, 1), i64::MAX / 4), Err(TreeError::Overflow));
+        assert_eq!(tree.get((1, 1)).unwrap(), 0);
+    }
+}
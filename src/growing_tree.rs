@@ -1,130 +1,959 @@
-use crate::{FenwickTree, FenwickTreeValue, TreeError, TreeIndex};
+use std::collections::{BTreeMap, HashMap};
+use std::ops::RangeInclusive;
 
+use crate::hashing;
+use crate::iterators::{ChunkSums, NonZeroPoints, PrefixCursor, PrefixSums, WindowSums};
+use crate::search;
+use crate::snapshot_tree::SnapshotValue;
+use crate::values::NonNegative;
+use crate::{
+    add_to_nodes, append_data, check_permutation, count_of, data_from_fn, data_from_pairs,
+    data_from_points, data_into_points, fill_data, fill_new_nodes, least_significant_bit,
+    len_for_keys, points_into_data, remapped_points, store_to_nodes, try_vec_of_defaults,
+    DuplicatePolicy, FenwickReader, FenwickTreeValue, FenwickWriter, InvertibleValue,
+    OutOfBoundsPolicy, TreeError,
+};
+
+/// Fenwick tree growing on updates beyond the rightmost index, queries beyond it
+/// return the total sum. Both can be configured with
+/// [`GrowingFenwickTree::with_out_of_bounds`].
+///
+/// Any index up to `usize::MAX - 2` can be addressed, as long as memory for the
+/// tree holding it can be allocated. Updates of the two topmost indexes return
+/// [`TreeError::Overflow`] and updates needing more memory than available return
+/// [`TreeError::AllocationFailed`], queries accept the whole [`usize`] range.
 pub struct GrowingFenwickTree<T> {
     data: Vec<T>,
+    resizes: usize,
+    elements_copied: usize,
+    pub(crate) growth: Growth,
+    pub(crate) max_size: Option<usize>,
+    pub(crate) out_of_bounds: OutOfBoundsPolicy,
+}
+
+/// Defines how many indexes [`GrowingFenwickTree`] adds when an update goes beyond
+/// the rightmost index.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Growth {
+    /// Grow just enough to hold the updated index.
+    #[default]
+    Exact,
+    /// Grow to the closest power of two holding the updated index, so that sequential
+    /// updates trigger logarithmic number of resizes.
+    NextPowerOfTwo,
+}
+
+/// Growth statistics of [`GrowingFenwickTree`], see [`GrowingFenwickTree::resize_stats`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ResizeStats {
+    /// Number of times the tree was grown.
+    pub resizes: usize,
+    /// Number of elements moved to new allocations while growing.
+    pub elements_copied: usize,
+    /// Number of indexes the tree currently holds.
+    pub len: usize,
+    /// Number of indexes the tree can hold without reallocation.
+    pub capacity: usize,
+}
+
+impl<T: Clone> Clone for GrowingFenwickTree<T> {
+    fn clone(&self) -> Self {
+        Self {
+            data: self.data.clone(),
+            resizes: self.resizes,
+            elements_copied: self.elements_copied,
+            growth: self.growth,
+            max_size: self.max_size,
+            out_of_bounds: self.out_of_bounds,
+        }
+    }
+
+    /// Reuses existing allocation of `self`, so repeatedly snapshotting a tree into the
+    /// same scratch copy doesn't allocate once the copy is big enough.
+    fn clone_from(&mut self, source: &Self) {
+        self.data.clone_from(&source.data);
+        self.resizes = source.resizes;
+        self.elements_copied = source.elements_copied;
+        self.growth = source.growth;
+        self.max_size = source.max_size;
+        self.out_of_bounds = source.out_of_bounds;
+    }
+}
+
+impl<T: FenwickTreeValue> TryFrom<BTreeMap<usize, T>> for GrowingFenwickTree<T> {
+    type Error = TreeError;
+
+    /// Creates tree sized to hold the largest key of `points`, in `O(n + k)`. Fails
+    /// with [`TreeError::Overflow`] for [`usize::MAX`] key.
+    fn try_from(points: BTreeMap<usize, T>) -> Result<Self, TreeError> {
+        Ok(Self {
+            data: data_from_points(len_for_keys(points.keys())?, points)?,
+            ..Self::new(0)
+        })
+    }
+}
+
+impl<T: FenwickTreeValue> TryFrom<HashMap<usize, T>> for GrowingFenwickTree<T> {
+    type Error = TreeError;
+
+    /// Creates tree sized to hold the largest key of `points`, in `O(n + k)`. Fails
+    /// with [`TreeError::Overflow`] for [`usize::MAX`] key.
+    fn try_from(points: HashMap<usize, T>) -> Result<Self, TreeError> {
+        Ok(Self {
+            data: data_from_points(len_for_keys(points.keys())?, points)?,
+            ..Self::new(0)
+        })
+    }
+}
+
+/// Creates tree sized to hold the largest index of `pairs`, in `O(n + k)`. Values
+/// sharing an index are summed up.
+///
+/// # Panics
+///
+/// Panics if an index is [`usize::MAX`] or memory for the tree can't be allocated, use
+/// [`DuplicatePolicy::Sum`] conversion from a vector of pairs to handle those cases.
+impl<T: FenwickTreeValue> FromIterator<(usize, T)> for GrowingFenwickTree<T> {
+    fn from_iter<I: IntoIterator<Item = (usize, T)>>(pairs: I) -> Self {
+        let pairs: Vec<_> = pairs.into_iter().collect();
+        let data = len_for_keys(pairs.iter().map(|(idx, _)| idx))
+            .and_then(|len| data_from_points(len, pairs))
+            .unwrap_or_else(|err| panic!("tree can't hold all pairs: {err}"));
+        Self {
+            data,
+            ..Self::new(0)
+        }
+    }
+}
+
+impl<T: FenwickTreeValue> TryFrom<Vec<(usize, T)>> for GrowingFenwickTree<T> {
+    type Error = TreeError;
+
+    /// Creates tree sized to hold the largest index of `pairs`, in `O(n + k)`. Repeated
+    /// indexes are rejected, see [`DuplicatePolicy::Error`].
+    fn try_from(pairs: Vec<(usize, T)>) -> Result<Self, TreeError> {
+        Self::try_from((pairs, DuplicatePolicy::Error))
+    }
+}
+
+impl<T: FenwickTreeValue> TryFrom<(Vec<(usize, T)>, DuplicatePolicy)> for GrowingFenwickTree<T> {
+    type Error = TreeError;
+
+    /// Same as conversion from plain pairs, merging repeated indexes with `policy`.
+    fn try_from((pairs, policy): (Vec<(usize, T)>, DuplicatePolicy)) -> Result<Self, TreeError> {
+        Ok(Self {
+            data: data_from_pairs(pairs, policy)?,
+            ..Self::new(0)
+        })
+    }
 }
 
 impl<T: FenwickTreeValue> GrowingFenwickTree<T> {
+    /// Creates tree holding `size` indexes of default values up front.
+    ///
+    /// # Panics
+    ///
+    /// Panics if memory for the tree can't be allocated, use [`Self::try_new`] to
+    /// handle that case.
     pub fn new(size: usize) -> Self {
+        Self::try_new(size).unwrap_or_else(|err| panic!("tree of {size} indexes: {err}"))
+    }
+
+    /// Same as [`Self::new`], but doesn't panic.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if number of nodes overflows [`usize`] or
+    /// memory for them can't be allocated.
+    ///
+    pub fn try_new(size: usize) -> Result<Self, TreeError> {
+        Ok(Self {
+            data: try_vec_of_defaults(size.checked_add(1).ok_or(TreeError::Overflow)?)?,
+            resizes: 0,
+            elements_copied: 0,
+            growth: Growth::Exact,
+            max_size: None,
+            out_of_bounds: OutOfBoundsPolicy::Grow,
+        })
+    }
+
+    /// Sets behavior of queries and updates beyond the rightmost index, which is
+    /// [`OutOfBoundsPolicy::Grow`] by default.
+    pub fn with_out_of_bounds(mut self, policy: OutOfBoundsPolicy) -> Self {
+        self.out_of_bounds = policy;
+        self
+    }
+
+    /// Creates tree of `size` indexes holding `f(i)` at index `i`, in `O(n)`.
+    pub fn from_fn(size: usize, f: impl FnMut(usize) -> T) -> Self {
         Self {
-            data: vec![T::default(); size + 1],
+            data: data_from_fn(size, f),
+            ..Self::new(0)
+        }
+    }
+
+    /// Creates tree of `size` indexes holding default values, same as [`Self::new`].
+    pub fn zeros(size: usize) -> Self {
+        Self::new(size)
+    }
+
+    /// Creates tree of `size` indexes all holding `value`, in `O(n)`.
+    pub fn filled(size: usize, value: T) -> Self {
+        Self::from_fn(size, |_| value.clone())
+    }
+
+    /// Creates tree of `size` indexes holding `points`, in `O(n + k)`. Values sharing
+    /// an index are summed up.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any point is out of bounds.
+    ///
+    pub fn with_points(size: usize, points: &[(usize, T)]) -> Result<Self, TreeError> {
+        if let Some(&(index, _)) = points.iter().find(|(idx, _)| *idx >= size) {
+            return Err(TreeError::IndexOutOfBounds { index, bound: size });
+        }
+
+        Ok(Self {
+            data: data_from_points(size, points.iter().cloned())?,
+            ..Self::new(0)
+        })
+    }
+
+    /// Same as [`crate::FenwickReader::query`] without wrapping result into [`Result`], as
+    /// queries beyond the rightmost index return the total sum. Out of bounds policy of
+    /// the tree is deliberately bypassed, so the total is returned even if the policy
+    /// is [`crate::OutOfBoundsPolicy::Error`] or
+    /// [`crate::OutOfBoundsPolicy::TreatAsZero`].
+    pub fn query_clamped(&self, idx: usize) -> T {
+        let mut res = T::default();
+        let mut position = idx.saturating_add(1).min(self.size() - 1);
+        while position > 0 {
+            res.store_value(&self.data[position]);
+            position -= least_significant_bit(position);
+        }
+        res
+    }
+
+    /// Same as [`Self::query_clamped`], also telling whether `idx` lies beyond the
+    /// rightmost index and query was clamped, so wrongly computed indexes don't go
+    /// unnoticed. Bypasses out of bounds policy same as [`Self::query_clamped`], trees
+    /// which should reject such queries altogether can be configured with
+    /// [`crate::OutOfBoundsPolicy::Error`] and queried with
+    /// [`crate::FenwickReader::query`].
+    ///
+    /// ```
+    /// use fenwick_bit_tree::prelude::*;
+    ///
+    /// let mut tree = GrowingFenwickTree::<i32>::new(0);
+    /// tree.update(3, 2).unwrap();
+    /// assert_eq!(tree.query_checked(3), (2, false));
+    /// assert_eq!(tree.query_checked(10), (2, true));
+    /// ```
+    pub fn query_checked(&self, idx: usize) -> (T, bool) {
+        (self.query_clamped(idx), idx >= self.size() - 1)
+    }
+
+    /// Same as [`crate::FenwickWriter::update`] without wrapping result into [`Result`], as
+    /// tree grows to hold any index. Out of bounds policy of the tree is deliberately
+    /// bypassed, so the tree grows whatever the policy is.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `idx` is beyond max size configured via
+    /// [`crate::FenwickTreeBuilder::max_size`], is one of the two topmost indexes or memory
+    /// for it can't be allocated.
+    pub fn update_growing(&mut self, idx: usize, value: T) {
+        if let Err(err) = self.grow_and_update(idx, value) {
+            panic!("update of index {idx} failed: {err}");
+        }
+    }
+
+    /// Returns growth statistics, which allow detecting pathological growth patterns
+    /// and tuning initial size of the tree.
+    pub fn resize_stats(&self) -> ResizeStats {
+        ResizeStats {
+            resizes: self.resizes,
+            elements_copied: self.elements_copied,
+            len: self.data.len() - 1,
+            capacity: self.data.capacity() - 1,
         }
     }
 
+    /// Releases memory reserved for growth beyond the indexes currently held. Stored
+    /// values and prefix sums are not affected.
+    pub fn shrink_to_fit(&mut self) {
+        self.data.shrink_to_fit();
+    }
+
+    /// Sets value of every index to `value`, in `O(n)`.
+    pub fn fill(&mut self, value: T) {
+        fill_data(&mut self.data, &value);
+    }
+
+    /// Returns the first index which prefix sum doesn't satisfy `pred`. Predicate has
+    /// to be monotone: `true` for some number of shortest prefixes and `false` for
+    /// the rest, e.g. `|sum| *sum < budget` for trees with non-negative values.
+    ///
+    /// Search descends the tree in `O(log n)` and works for values without inverse.
+    /// Number of stored indexes is returned if the predicate holds for all of their
+    /// prefixes, as values beyond the rightmost index never change the aggregate.
+    pub fn partition_point(&self, pred: impl Fn(&T) -> bool) -> usize {
+        search::partition_point(&self.data, pred)
+    }
+
+    /// Returns the first index which prefix sum is greater or equal than `target`, or
+    /// [`None`] if the total sum is less than `target`. Values are expected to be
+    /// non-negative.
+    ///
+    /// When several indexes share the same prefix sum (indexes holding zeros), the
+    /// lowest of them is returned.
+    pub fn lower_bound(&self, target: T) -> Option<usize>
+    where
+        T: PartialOrd,
+    {
+        let idx = self.partition_point(|sum| *sum < target);
+        (idx < self.data.len() - 1).then_some(idx)
+    }
+
+    /// Returns the first index which prefix sum is strictly greater than `target`, or
+    /// [`None`] if the total sum doesn't exceed `target`. Values are expected to be
+    /// non-negative.
+    ///
+    /// All indexes which prefix sum equals `target` are skipped, so for a tree of
+    /// counters `upper_bound(k)` is the index holding `k + 1`-th item.
+    pub fn upper_bound(&self, target: T) -> Option<usize>
+    where
+        T: PartialOrd,
+    {
+        let idx = self.partition_point(|sum| *sum <= target);
+        (idx < self.data.len() - 1).then_some(idx)
+    }
+
+    /// Returns the `k`-th index (counting from 0) holding zero count, in `O(log n)`.
+    /// Indexes beyond the rightmost one hold zeros, so [`None`] is only returned if
+    /// the index doesn't fit [`usize`]. Indexes are expected to hold counts of 0 or 1,
+    /// such as allocated ids, so the number of zeros up to an index is `idx + 1`
+    /// without its prefix sum.
+    ///
+    /// ```
+    /// use fenwick_bit_tree::prelude::*;
+    ///
+    /// let mut ids = GrowingFenwickTree::<u32>::new(0);
+    /// ids.update(0, 1).unwrap();
+    /// ids.update(2, 1).unwrap();
+    /// assert_eq!(ids.kth_missing(0), Some(1));
+    /// assert_eq!(ids.kth_missing(10), Some(12));
+    /// ```
+    pub fn kth_missing(&self, k: usize) -> Option<usize>
+    where
+        T: TryInto<usize>,
+    {
+        let (idx, missing) = search::kth_missing(&self.data, k, count_of);
+        match idx < self.size() - 1 {
+            true => Some(idx),
+            false => idx.checked_add(k - missing),
+        }
+    }
+
+    /// For every target returns the first index which prefix sum reaches it, or
+    /// [`None`] if the total sum doesn't. Same as running prefix search for each target
+    /// separately, but targets share the descent through the tree, so grids of
+    /// quantiles are answered much faster. Values are expected to be non-negative.
+    pub fn select_many(&self, targets: &[T]) -> Vec<Option<usize>>
+    where
+        T: PartialOrd,
+    {
+        search::select_many(&self.data, targets, |sum| sum)
+    }
+
+    /// Returns cursor over `(index, prefix sum)` pairs from `from` up to the rightmost
+    /// index, taking `O(1)` amortized per index after `O(log n)` start. Nothing is
+    /// yielded if `from` lies beyond the rightmost index.
+    pub fn prefix_cursor(&self, from: usize) -> PrefixCursor<'_, T> {
+        PrefixCursor::new(&self.data, from)
+    }
+
     fn size(&self) -> usize {
         self.data.len()
     }
 
-    fn resize(&mut self, idx: &TreeIndex) -> Result<(), TreeError> {
+    fn grow(&mut self, position: usize) -> Result<(), TreeError> {
+        let mut new_len = match self.growth {
+            Growth::Exact => position,
+            Growth::NextPowerOfTwo => position.checked_next_power_of_two().unwrap_or(position),
+        };
+        if let Some(max_size) = self.max_size {
+            new_len = new_len.min(max_size);
+        }
+        self.resize(new_len)
+    }
+
+    /// Changes number of indexes held by the tree to `new_len`. Prefix sums of all
+    /// indexes below `new_len` stay the same, values of truncated indexes are dropped.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `new_len` exceeds configured max size or
+    /// memory for it can't be allocated.
+    ///
+    pub fn resize(&mut self, new_len: usize) -> Result<(), TreeError> {
+        if let Some(max_size) = self.max_size.filter(|max_size| new_len > *max_size) {
+            return Err(TreeError::IndexOutOfBounds {
+                index: new_len - 1,
+                bound: max_size,
+            });
+        }
+
         let size_before_resize = self.size();
+        let capacity_before_resize = self.data.capacity();
 
-        // TODO: resize should grow to the closest including power of 2
-        self.data.resize(*idx.to_internal() + 1, T::default());
+        let new_size = new_len.checked_add(1).ok_or(TreeError::Overflow)?;
+        self.data
+            .try_reserve(new_size.saturating_sub(size_before_resize))
+            .map_err(|_| TreeError::AllocationFailed)?;
 
-        if size_before_resize <= 1 {
-            return Ok(());
+        // Nodes only aggregate indexes to the left of them, so truncation keeps all
+        // surviving nodes intact.
+        self.data.resize(new_size, T::default());
+
+        // Stats only track growth, shrinking doesn't move anything.
+        if new_size > size_before_resize {
+            self.resizes += 1;
+            if self.data.capacity() != capacity_before_resize {
+                self.elements_copied += size_before_resize;
+            }
         }
 
-        let highest_index_before_resize = TreeIndex::Internal {
-            val: size_before_resize - 1,
-        };
+        fill_new_nodes(&mut self.data, size_before_resize.max(1));
 
-        let first_new_index = TreeIndex::Internal {
-            val: size_before_resize,
-        };
+        Ok(())
+    }
+}
 
-        let aggregate_from = if highest_index_before_resize.is_power_of_2() {
-            TreeIndex::Internal { val: 0 }
-        } else {
-            first_new_index
-                .lsb_descending()
-                .skip(1)
-                .chain([TreeIndex::Internal { val: 0 }])
-                .next()
-                .unwrap()
-        };
+impl<T: FenwickTreeValue + PartialOrd> GrowingFenwickTree<NonNegative<T>> {
+    fn ensure_non_negative(&self) -> Result<(), TreeError> {
+        if search::total(&self.data).negative_applied() {
+            return Err(TreeError::NegativeValue);
+        }
+        Ok(())
+    }
 
-        let sum_from = aggregate_from
-            .to_external()
-            .map_or(Ok(T::default()), |idx| self.query(*idx))?;
+    /// Same as [`Self::lower_bound`], but checks that no negative value was ever stored.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any negative value was stored.
+    ///
+    pub fn checked_lower_bound(&self, target: T) -> Result<Option<usize>, TreeError> {
+        self.ensure_non_negative()?;
+        let idx = self.partition_point(|sum| *sum.value() < target);
+        Ok((idx < self.data.len() - 1).then_some(idx))
+    }
 
-        let sum_till = highest_index_before_resize
-            .to_external()
-            .map_or(Ok(T::default()), |idx| self.query(*idx))?;
+    /// Same as [`Self::upper_bound`], but checks that no negative value was ever stored.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any negative value was stored.
+    ///
+    pub fn checked_upper_bound(&self, target: T) -> Result<Option<usize>, TreeError> {
+        self.ensure_non_negative()?;
+        let idx = self.partition_point(|sum| *sum.value() <= target);
+        Ok((idx < self.data.len() - 1).then_some(idx))
+    }
 
-        let value = sum_till.substract(sum_from);
+    /// Same as [`Self::select_many`], but checks that no negative value was ever stored.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any negative value was stored.
+    ///
+    pub fn checked_select_many(&self, targets: &[T]) -> Result<Vec<Option<usize>>, TreeError> {
+        self.ensure_non_negative()?;
+        Ok(search::select_many(&self.data, targets, NonNegative::value))
+    }
+}
 
-        for data_position in highest_index_before_resize
-            .lsb_ascending(self.size() - 1)
-            .skip(1)
-        {
-            let data_position = data_position.to_internal();
-            self[data_position].store_value(&value);
+impl<T: InvertibleValue> GrowingFenwickTree<T> {
+    /// Places indexes of `other` right after indexes currently held by `self`, so index
+    /// `i` of `other` becomes `len + i`, in `O(n + m)`. Growth configuration of `self`
+    /// is kept.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if combined indexes go beyond
+    /// [`crate::FenwickTreeBuilder::max_size`] or memory for indexes of `other` can't
+    /// be allocated. The tree is left unchanged then.
+    ///
+    pub fn append(&mut self, other: Self) -> Result<(), TreeError> {
+        let len = (self.data.len() - 1)
+            .checked_add(other.data.len() - 1)
+            .ok_or(TreeError::Overflow)?;
+        if let Some(max_size) = self.max_size.filter(|max_size| len > *max_size) {
+            return Err(TreeError::IndexOutOfBounds {
+                index: len - 1,
+                bound: max_size,
+            });
         }
+        append_data(&mut self.data, other.data)
+    }
 
-        Ok(())
+    /// Returns tree holding value of every index `i` at index `perm[i]`, in `O(n)`.
+    /// Values moved to the same index are summed up, so `perm` needn't be a
+    /// permutation. New tree grows to hold the highest new index, configuration of
+    /// `self` is kept.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if length of `perm` differs from the number
+    /// of indexes the tree holds, or a new index can't be held by a growing tree.
+    ///
+    pub fn remap(&self, perm: &[usize]) -> Result<Self, TreeError> {
+        check_permutation(perm, self.size() - 1)?;
+        self.remap_with(|idx| perm[idx])
+    }
+
+    /// Same as [`Self::remap`], with value of every index `i` moved to index `f(i)`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if a new index is beyond
+    /// [`crate::FenwickTreeBuilder::max_size`] or one of the two topmost indexes, or
+    /// memory for the new tree can't be allocated.
+    ///
+    pub fn remap_with(&self, f: impl FnMut(usize) -> usize) -> Result<Self, TreeError> {
+        let bound = self.max_size.unwrap_or(usize::MAX - 2);
+        let points = remapped_points(&self.data, bound, f)?;
+        let len = len_for_keys(points.iter().map(|(idx, _)| idx))?.max(self.size() - 1);
+
+        Ok(Self {
+            data: data_from_points(len, points)?,
+            resizes: 0,
+            elements_copied: 0,
+            growth: self.growth,
+            max_size: self.max_size,
+            out_of_bounds: self.out_of_bounds,
+        })
+    }
+
+    /// Drops trailing indexes holding default values and releases memory held by them.
+    /// Sums of all indexes stay the same.
+    pub fn compact(&mut self) {
+        let mut len = self.data.len() - 1;
+        while len > 0 {
+            let lsb = least_significant_bit(len);
+            let mut point = self.data[len].clone();
+            let mut step = 1;
+            while step < lsb {
+                point = point.substract(self.data[len - step].clone());
+                step <<= 1;
+            }
+            if !point.is_identity() {
+                break;
+            }
+            len -= 1;
+        }
+
+        self.data.truncate(len + 1);
+        self.data.shrink_to_fit();
+    }
+
+    /// Discards values of all indexes below `new_start` and shifts the rest down, so
+    /// index `new_start` becomes 0. Memory held by dropped indexes is released.
+    ///
+    /// Tree is rebuilt in `O(n)`, which makes sliding-window workloads pay for the
+    /// indexes they still need only.
+    pub fn advance_origin(&mut self, new_start: usize) {
+        if new_start == 0 {
+            return;
+        }
+
+        data_into_points(&mut self.data);
+        let dropped = new_start.min(self.data.len() - 1);
+        self.data.drain(1..=dropped);
+        points_into_data(&mut self.data);
+        self.data.shrink_to_fit();
+    }
+
+    /// Returns sums of disjoint blocks of `k` indexes: `0..k`, `k..2 * k` and so on.
+    /// The last block ends at the rightmost index and may be shorter. Nothing is
+    /// yielded if `k` is 0.
+    pub fn chunk_sums(&self, k: usize) -> impl Iterator<Item = T> + '_ {
+        ChunkSums::new(&self.data, k)
+    }
+
+    /// Returns the shortest `(from, to)` range of indexes (including edges) which sum
+    /// reaches `target`, or [`None`] if the total sum doesn't, e.g. the shortest period
+    /// containing 1000 errors. The leftmost range is returned among equally short ones.
+    /// Takes `O(n log n)`, values are expected to be non-negative.
+    ///
+    /// ```
+    /// use fenwick_bit_tree::prelude::*;
+    ///
+    /// let errors = GrowingFenwickTree::from_fn(8, |i| [1, 0, 4, 1, 0, 0, 3, 3][i]);
+    /// assert_eq!(errors.min_window_with_sum(5), Some((2, 3)));
+    /// assert_eq!(errors.min_window_with_sum(6), Some((6, 7)));
+    /// assert_eq!(errors.min_window_with_sum(13), None);
+    /// ```
+    pub fn min_window_with_sum(&self, target: T) -> Option<(usize, usize)>
+    where
+        T: PartialOrd,
+    {
+        search::min_window_with_sum(&self.data, &target)
+    }
+
+    /// Returns `(index, prefix sum)` pairs of indexes within `range`, in
+    /// `O(k + log n)` for `k` indexes, which is cheaper than querying every index.
+    /// Indexes beyond the rightmost one are not yielded.
+    pub fn prefix_sums_in(
+        &self,
+        range: RangeInclusive<usize>,
+    ) -> impl Iterator<Item = (usize, T)> + '_ {
+        PrefixSums::new(&self.data, range)
+    }
+
+    /// Returns sums of every `k` consecutive indexes: `0..k`, `1..k + 1` and so on up
+    /// to the rightmost index. Nothing is yielded if `k` is 0 or exceeds the size
+    /// of the tree.
+    pub fn window_sums(&self, k: usize) -> impl Iterator<Item = T> + '_ {
+        WindowSums::new(&self.data, k)
+    }
+
+    /// Returns iterator over `(index, value)` pairs of points holding non-default
+    /// values, in ascending index order.
+    ///
+    /// Takes `O(n)` for values which can cancel each other out, see
+    /// [`FenwickTreeValue::CANCELS`], numeric types included: a range with default
+    /// aggregate may still hold non-default points, so every index of the tree is
+    /// visited, however sparse it is. Use [`crate::MirroredFenwickTree::iter_nonzero`]
+    /// to skip empty ranges of such trees.
+    ///
+    /// Other values, like [`crate::DurationValue`] or [`crate::Histogram`], skip ranges
+    /// with default aggregate as a whole, so the cost grows with the number of
+    /// non-default points rather than with the size of the tree.
+    pub fn iter_nonzero(&self) -> impl Iterator<Item = (usize, T)> + '_ {
+        NonZeroPoints::new(&self.data)
+    }
+
+    /// Returns number of indexes between `from` and `to` (including edges) holding
+    /// non-default values, walking the range like [`Self::iter_nonzero`] does.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `from` is greater than `to`. Growing tree
+    /// truncates `to` beyond the rightmost index.
+    ///
+    pub fn count_nonzero_between(&self, from: usize, to: usize) -> Result<usize, TreeError> {
+        if from > to {
+            return Err(TreeError::InvalidRange { from, to });
+        }
+        Ok(NonZeroPoints::in_range(&self.data, from, to).count())
     }
 }
 
-impl<T> std::ops::Index<TreeIndex> for GrowingFenwickTree<T> {
-    type Output = T;
+impl<T: InvertibleValue + SnapshotValue> GrowingFenwickTree<T> {
+    /// Returns hash of values stored at every index, in `O(n)`. Indexes holding default
+    /// values don't contribute, so trees storing the same values hash the same
+    /// regardless of their sizes.
+    ///
+    /// Hash function is fixed and indexes and values are hashed as little-endian bytes,
+    /// indexes as [`u64`] and values in their [`SnapshotValue`] encoding, so hashes
+    /// can be compared between processes and platforms.
+    pub fn content_hash(&self) -> u64 {
+        hashing::content_hash(&self.data)
+    }
 
-    fn index(&self, index: TreeIndex) -> &Self::Output {
-        &self.data[*index.to_internal()]
+    /// Returns [`Self::content_hash`] of every block of `k` indexes: `0..k`, `k..2 * k`
+    /// and so on, which localizes divergence of two trees to the blocks which hashes
+    /// differ. Nothing is returned if `k` is 0.
+    pub fn chunk_hashes(&self, k: usize) -> Vec<u64> {
+        hashing::chunk_hashes(&self.data, k)
     }
 }
 
-impl<T> std::ops::IndexMut<TreeIndex> for GrowingFenwickTree<T> {
-    fn index_mut(&mut self, index: TreeIndex) -> &mut Self::Output {
-        &mut self.data[*index.to_internal()]
+/// `tree += (idx, value)` is the same as [`GrowingFenwickTree::update_growing`], and
+/// panics in the same cases.
+///
+/// ```
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut tree = GrowingFenwickTree::<i64>::new(0);
+/// tree += (3, 2);
+/// tree += vec![(10, 5), (3, 1)];
+/// tree += [(20, 1)];
+/// assert_eq!(tree.query(10).unwrap(), 8);
+/// ```
+impl<T: FenwickTreeValue> std::ops::AddAssign<(usize, T)> for GrowingFenwickTree<T> {
+    fn add_assign(&mut self, (idx, value): (usize, T)) {
+        self.update_growing(idx, value);
     }
 }
 
-impl<T: FenwickTreeValue> FenwickTree for GrowingFenwickTree<T> {
-    type Value = T;
+impl<T: FenwickTreeValue> std::ops::AddAssign<Vec<(usize, T)>> for GrowingFenwickTree<T> {
+    fn add_assign(&mut self, pairs: Vec<(usize, T)>) {
+        self.extend(pairs);
+    }
+}
 
-    fn query(&self, idx: usize) -> Result<T, TreeError> {
-        let mut idx: TreeIndex = idx.into();
+impl<T: FenwickTreeValue, const N: usize> std::ops::AddAssign<[(usize, T); N]>
+    for GrowingFenwickTree<T>
+{
+    fn add_assign(&mut self, pairs: [(usize, T); N]) {
+        self.extend(pairs);
+    }
+}
 
-        if self.size() <= *idx.to_internal() {
-            idx = TreeIndex::Internal {
-                val: self.size() - 1,
-            }
+/// Applies every `(idx, value)` pair with [`GrowingFenwickTree::update_growing`], and
+/// panics in the same cases.
+impl<T: FenwickTreeValue> Extend<(usize, T)> for GrowingFenwickTree<T> {
+    fn extend<I: IntoIterator<Item = (usize, T)>>(&mut self, pairs: I) {
+        for (idx, value) in pairs {
+            self.update_growing(idx, value);
         }
+    }
+}
 
-        let mut res = Self::Value::default();
+impl<T: FenwickTreeValue> FenwickReader for GrowingFenwickTree<T> {
+    type Value = T;
 
-        for data_position in idx.lsb_descending() {
-            let data_position = data_position.to_internal();
-            res.store_value(&self[data_position]);
+    fn query(&self, idx: usize) -> Result<T, TreeError> {
+        let len = self.size() - 1;
+        if idx >= len {
+            return match self.out_of_bounds.query_index(idx, len)? {
+                Some(_) => Ok(self.query_clamped(idx)),
+                None => Ok(T::default()),
+            };
         }
+        Ok(self.query_clamped(idx))
+    }
 
-        Ok(res)
+    /// Same as [`Self::lower_bound`].
+    fn find_prefix(&self, target: T) -> Option<usize>
+    where
+        T: PartialOrd,
+    {
+        self.lower_bound(target)
     }
+}
 
+impl<T: FenwickTreeValue> FenwickWriter for GrowingFenwickTree<T> {
     fn update(&mut self, idx: usize, value: Self::Value) -> Result<(), TreeError> {
-        let idx: TreeIndex = idx.into();
+        if let Some(position) = self.update_position(idx, &value)? {
+            add_to_nodes(&mut self.data, position, value);
+        }
+        Ok(())
+    }
 
-        if *idx.to_internal() > self.size() - 1 {
-            self.resize(&idx)?
+    fn update_ref(&mut self, idx: usize, value: &Self::Value) -> Result<(), TreeError> {
+        if let Some(position) = self.update_position(idx, value)? {
+            store_to_nodes(&mut self.data, position, value);
         }
+        Ok(())
+    }
 
-        for data_position in idx.lsb_ascending(self.size() - 1) {
-            let data_position = data_position.to_internal();
-            self[data_position].store_value(&value);
+    fn update_target(&self, idx: usize) -> Result<Option<usize>, TreeError> {
+        let len = self.size() - 1;
+        if idx >= len && self.out_of_bounds != OutOfBoundsPolicy::Grow {
+            return self.out_of_bounds.update_index(idx, len);
         }
+        Ok(Some(idx))
+    }
+}
 
+impl<T: FenwickTreeValue> GrowingFenwickTree<T> {
+    fn grow_and_update(&mut self, idx: usize, value: T) -> Result<(), TreeError> {
+        if let Some(position) = self.grow_for(idx, &value)? {
+            add_to_nodes(&mut self.data, position, value);
+        }
         Ok(())
     }
+
+    /// Returns node position update of `idx` with `value` starts at, applying out of
+    /// bounds policy, or [`None`] if update is skipped.
+    fn update_position(&mut self, idx: usize, value: &T) -> Result<Option<usize>, TreeError> {
+        let len = self.size() - 1;
+        if idx >= len && self.out_of_bounds != OutOfBoundsPolicy::Grow {
+            return match self.out_of_bounds.update_index(idx, len)? {
+                Some(idx) => self.grow_for(idx, value),
+                None => Ok(None),
+            };
+        }
+        self.grow_for(idx, value)
+    }
+
+    /// Same as [`Self::update_position`], growing the tree to hold `idx` whatever out
+    /// of bounds policy is.
+    pub(crate) fn grow_for(&mut self, idx: usize, value: &T) -> Result<Option<usize>, TreeError> {
+        if let Some(max_size) = self.max_size.filter(|max_size| idx >= *max_size) {
+            return Err(TreeError::IndexOutOfBounds {
+                index: idx,
+                bound: max_size,
+            });
+        }
+
+        if !value.is_valid() {
+            return Err(TreeError::InvalidValue);
+        }
+        if value.is_identity() {
+            return Ok(None);
+        }
+
+        let position = idx.checked_add(1).ok_or(TreeError::Overflow)?;
+
+        if position > self.size() - 1 {
+            self.grow(position)?
+        }
+
+        Ok(Some(position))
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use rand::seq::SliceRandom;
-    use rand::Rng;
+    use std::collections::{BTreeMap, HashMap};
+
+    use crate::growing_tree::{GrowingFenwickTree, Growth, ResizeStats};
+    use crate::values::NonNegative;
+    use crate::workload::Workload;
+    use crate::{DuplicatePolicy, FenwickReader, FenwickWriter, OutOfBoundsPolicy, TreeError};
 
-    use crate::growing_tree::GrowingFenwickTree;
-    use crate::FenwickTree;
+    /// Seeded values within `0..100` of `size` indexes, and shuffled indexes to update
+    /// them in.
+    fn random_input(seed: u64, size: usize) -> (Vec<i32>, Vec<usize>) {
+        let input = Workload::new(seed, size)
+            .deltas(0..100)
+            .updates()
+            .map(|(_, value)| value as i32)
+            .take(size)
+            .collect();
+        let mut order = Workload::new(seed, usize::MAX).updates();
+        let mut indexes: Vec<usize> = (0..size).collect();
+        indexes.sort_by_cached_key(|_| order.next());
+        (input, indexes)
+    }
+
+    #[test]
+    fn remap() {
+        let mut tree = GrowingFenwickTree::<i32>::new(3);
+        tree.update(0, 1).unwrap();
+        tree.update(2, 4).unwrap();
+
+        let remapped = tree.remap(&[2, 0, 0]).unwrap();
+        assert_eq!(remapped.query(0).unwrap(), 4);
+        assert_eq!(remapped.query(2).unwrap(), 5);
+
+        let remapped = tree.remap_with(|idx| idx * 10).unwrap();
+        assert_eq!(remapped.resize_stats().len, 21);
+        assert_eq!(remapped.query(19).unwrap(), 1);
+        assert_eq!(remapped.query(20).unwrap(), 5);
+
+        assert_eq!(
+            tree.remap(&[0, 1]).err(),
+            Some(TreeError::IndexOutOfBounds { index: 2, bound: 3 })
+        );
+        let mut limited = tree.clone();
+        limited.max_size = Some(10);
+        assert_eq!(
+            limited.remap_with(|idx| idx * 10).err(),
+            Some(TreeError::IndexOutOfBounds {
+                index: 10,
+                bound: 10
+            })
+        );
+    }
+
+    #[test]
+    fn from_maps() {
+        let points = [(9, 3), (4, 1)];
+        let tree = GrowingFenwickTree::try_from(BTreeMap::from(points)).unwrap();
+        assert_eq!(tree.resize_stats().len, 10);
+        assert_eq!(tree.query(8).unwrap(), 1);
+
+        let mut tree = GrowingFenwickTree::try_from(HashMap::from(points)).unwrap();
+        tree.update(20, 2).unwrap();
+        assert_eq!(tree.query(20).unwrap(), 6);
+
+        assert_eq!(
+            GrowingFenwickTree::try_from(HashMap::from([(usize::MAX, 1)])).err(),
+            Some(TreeError::Overflow)
+        );
+    }
+
+    #[test]
+    fn try_from_pairs() {
+        let pairs = vec![(5, 1), (5, 2)];
+        assert_eq!(
+            GrowingFenwickTree::try_from(pairs.clone()).err(),
+            Some(TreeError::DuplicateIndex { index: 5 })
+        );
+
+        let mut tree = GrowingFenwickTree::try_from((pairs, DuplicatePolicy::Sum)).unwrap();
+        tree.update(7, 1).unwrap();
+        assert_eq!(tree.query(6).unwrap(), 3);
+        assert_eq!(tree.query(7).unwrap(), 4);
+    }
+
+    #[test]
+    fn constructors() {
+        let mut tree = GrowingFenwickTree::filled(3, 1);
+        tree.update(5, 1).unwrap();
+        assert_eq!(tree.query(5).unwrap(), 4);
+
+        let tree = GrowingFenwickTree::with_points(8, &[(7, 2)]).unwrap();
+        assert_eq!(tree.resize_stats().len, 8);
+        assert_eq!(tree.query(7).unwrap(), 2);
+        assert!(GrowingFenwickTree::with_points(8, &[(8, 2)]).is_err());
+        assert_eq!(GrowingFenwickTree::<i32>::zeros(2).resize_stats().len, 2);
+    }
+
+    #[test]
+    fn fill() {
+        let mut tree = GrowingFenwickTree::<i32>::new(0);
+        tree.update(5, 7).unwrap();
+        tree.fill(1);
+        tree.update(7, 1).unwrap();
+        assert_eq!(tree.query(5).unwrap(), 6);
+        assert_eq!(tree.query(7).unwrap(), 7);
+    }
+
+    #[test]
+    fn content_hash_ignores_trailing_defaults() {
+        let mut tree = GrowingFenwickTree::<u64>::new(0);
+        let mut other = GrowingFenwickTree::<u64>::new(100);
+        tree.update(7, 2).unwrap();
+        other.update(7, 2).unwrap();
+        assert_eq!(tree.content_hash(), other.content_hash());
+
+        other.update(7, 1).unwrap();
+        assert_ne!(tree.content_hash(), other.content_hash());
+        assert_eq!(tree.chunk_hashes(4).len(), 2);
+    }
+
+    #[test]
+    fn out_of_bounds_policies() {
+        let mut tree =
+            GrowingFenwickTree::<i32>::new(4).with_out_of_bounds(OutOfBoundsPolicy::Error);
+        tree.update(1, 1).unwrap();
+        assert_eq!(
+            tree.update(4, 1),
+            Err(TreeError::IndexOutOfBounds { index: 4, bound: 4 })
+        );
+        assert!(tree.query(4).is_err());
+
+        let mut tree = tree.with_out_of_bounds(OutOfBoundsPolicy::ClampToLast);
+        tree.update(10, 2).unwrap();
+        assert_eq!(tree.query(3).unwrap(), 3);
+        assert_eq!(tree.query(10).unwrap(), 3);
+
+        let mut tree = tree.with_out_of_bounds(OutOfBoundsPolicy::TreatAsZero);
+        tree.update(10, 2).unwrap();
+        assert_eq!(tree.query(10).unwrap(), 0);
+        assert_eq!(tree.resize_stats().len, 4);
+
+        tree.update_growing(10, 2);
+        assert_eq!(tree.query(10).unwrap(), 5);
+    }
 
     #[test]
     fn empty_tree_query() {
@@ -170,13 +999,444 @@ mod tests {
         assert_eq!(tree.query(7).unwrap(), 2);
     }
 
+    #[test]
+    fn identity_update_does_not_resize() {
+        let mut tree = GrowingFenwickTree::<i32>::new(2);
+        let size_before_update = tree.size();
+        tree.update(1_000_000, 0).unwrap();
+        assert_eq!(tree.size(), size_before_update);
+    }
+
+    #[test]
+    fn huge_indexes_dont_panic() {
+        let mut tree = GrowingFenwickTree::<i32>::new(0);
+        tree.update(3, 1).unwrap();
+
+        assert!(tree.update(usize::MAX, 1).is_err());
+        assert_eq!(
+            tree.update(usize::MAX / 2, 1),
+            Err(TreeError::AllocationFailed)
+        );
+        assert_eq!(tree.resize(usize::MAX), Err(TreeError::Overflow));
+        assert_eq!(tree.query(usize::MAX).unwrap(), 1);
+
+        tree.growth = Growth::NextPowerOfTwo;
+        assert!(tree.update(usize::MAX / 2 + 10, 1).is_err());
+        assert_eq!(tree.query(usize::MAX).unwrap(), 1);
+    }
+
+    #[test]
+    fn max_index() {
+        let mut tree = GrowingFenwickTree::<u64>::new(0);
+        tree.update(10, 1).unwrap();
+
+        assert_eq!(tree.update(usize::MAX, 1), Err(TreeError::Overflow));
+        assert_eq!(tree.update(usize::MAX, 0), Ok(()));
+        assert_eq!(tree.update(usize::MAX - 1, 1), Err(TreeError::Overflow));
+        assert_eq!(
+            tree.update(usize::MAX - 2, 1),
+            Err(TreeError::AllocationFailed)
+        );
+        assert_eq!(tree.query(usize::MAX), Ok(1));
+        assert_eq!(tree.query(usize::MAX - 1), Ok(1));
+        assert_eq!(tree.resize_stats().len, 11);
+    }
+
+    #[test]
+    fn window_sums_cover_stored_indexes() {
+        let mut tree = GrowingFenwickTree::<i32>::new(0);
+        tree.update(1, 1).unwrap();
+        tree.update(4, 2).unwrap();
+
+        assert_eq!(tree.window_sums(2).collect::<Vec<_>>(), vec![1, 1, 0, 2]);
+        assert_eq!(tree.window_sums(usize::MAX).count(), 0);
+    }
+
+    #[test]
+    fn chunk_sums_cover_stored_indexes() {
+        let mut tree = GrowingFenwickTree::<i32>::new(0);
+        tree.update(1, 1).unwrap();
+        tree.update(4, 2).unwrap();
+
+        assert_eq!(tree.chunk_sums(2).collect::<Vec<_>>(), vec![1, 0, 2]);
+    }
+
+    #[test]
+    fn prefix_sums_in_cover_stored_indexes() {
+        let mut tree = GrowingFenwickTree::<i32>::new(0);
+        tree.update(1, 1).unwrap();
+        tree.update(4, 2).unwrap();
+
+        assert_eq!(
+            tree.prefix_sums_in(2..=10).collect::<Vec<_>>(),
+            vec![(2, 1), (3, 1), (4, 3)]
+        );
+    }
+
+    #[test]
+    fn kth_missing_beyond_stored_indexes() {
+        let mut ids = GrowingFenwickTree::<u8>::new(0);
+        assert_eq!(ids.kth_missing(0), Some(0));
+        assert_eq!(ids.kth_missing(usize::MAX), Some(usize::MAX));
+
+        for idx in [0, 1, 2, 4, 5] {
+            ids.update(idx, 1).unwrap();
+        }
+        assert_eq!(ids.kth_missing(0), Some(3));
+        assert_eq!(ids.kth_missing(1), Some(6));
+        assert_eq!(ids.kth_missing(5), Some(10));
+        assert_eq!(ids.kth_missing(usize::MAX), None);
+    }
+
+    #[test]
+    fn append_stitches_key_spaces() {
+        let mut day = GrowingFenwickTree::<i32>::new(0);
+        day.update(2, 1).unwrap();
+        let mut next_day = GrowingFenwickTree::<i32>::new(4);
+        next_day.update(0, 2).unwrap();
+        next_day.update(3, 4).unwrap();
+
+        day.append(next_day).unwrap();
+        assert_eq!(day.resize_stats().len, 7);
+        assert_eq!(day.query(2).unwrap(), 1);
+        assert_eq!(day.query(3).unwrap(), 3);
+        assert_eq!(day.query(5).unwrap(), 3);
+        assert_eq!(day.query(6).unwrap(), 7);
+
+        day.update(10, 8).unwrap();
+        assert_eq!(day.query(10).unwrap(), 15);
+    }
+
+    #[test]
+    fn append_respects_max_size() {
+        let mut tree = GrowingFenwickTree::<i32>::new(3);
+        tree.max_size = Some(5);
+        tree.update(1, 1).unwrap();
+
+        assert_eq!(
+            tree.append(GrowingFenwickTree::new(3)),
+            Err(TreeError::IndexOutOfBounds { index: 5, bound: 5 })
+        );
+        assert_eq!(tree.resize_stats().len, 3);
+        assert_eq!(tree.query(2).unwrap(), 1);
+
+        tree.append(GrowingFenwickTree::from_fn(2, |i| i as i32 + 1))
+            .unwrap();
+        assert_eq!(tree.query(4).unwrap(), 4);
+        assert!(tree.update(5, 1).is_err());
+    }
+
+    #[test]
+    fn try_new() {
+        assert_eq!(
+            GrowingFenwickTree::<i64>::try_new(3)
+                .unwrap()
+                .resize_stats()
+                .len,
+            3
+        );
+        assert!(matches!(
+            GrowingFenwickTree::<i64>::try_new(usize::MAX),
+            Err(TreeError::Overflow)
+        ));
+        assert!(matches!(
+            GrowingFenwickTree::<i64>::try_new(usize::MAX / 2),
+            Err(TreeError::AllocationFailed)
+        ));
+    }
+
+    #[test]
+    fn compact_drops_trailing_defaults() {
+        let mut tree = GrowingFenwickTree::<i32>::new(100);
+        tree.update(3, 1).unwrap();
+        tree.update(37, 2).unwrap();
+        tree.update(50, 4).unwrap();
+        tree.update(50, -4).unwrap();
+
+        tree.compact();
+        assert_eq!(tree.resize_stats().len, 38);
+        assert_eq!(tree.resize_stats().capacity, 38);
+        assert_eq!(tree.query(36).unwrap(), 1);
+        assert_eq!(tree.query(100).unwrap(), 3);
+
+        let mut tree = GrowingFenwickTree::<i32>::new(10);
+        tree.compact();
+        assert_eq!(tree.resize_stats().len, 0);
+    }
+
+    #[test]
+    fn advance_origin_drops_prefix() {
+        let mut tree = GrowingFenwickTree::from_fn(100, |i| i as i64);
+
+        tree.advance_origin(40);
+        assert_eq!(tree.resize_stats().len, 60);
+        assert_eq!(tree.resize_stats().capacity, 60);
+        for i in 0..60 {
+            assert_eq!(tree.query(i).unwrap(), (40..=40 + i as i64).sum::<i64>());
+        }
+
+        tree.update(70, 1).unwrap();
+        assert_eq!(tree.query(70).unwrap(), (40..100).sum::<i64>() + 1);
+
+        tree.advance_origin(1_000);
+        assert_eq!(tree.resize_stats().len, 0);
+        assert_eq!(tree.query(10).unwrap(), 0);
+    }
+
+    #[test]
+    fn infallible_api() {
+        let mut tree = GrowingFenwickTree::<i32>::new(0);
+        tree.update_growing(3, 2);
+        tree.update_growing(10, 5);
+
+        assert_eq!(tree.query_clamped(2), 0);
+        assert_eq!(tree.query_clamped(3), 2);
+        assert_eq!(tree.query_clamped(1000), 7);
+    }
+
+    #[test]
+    fn query_checked_reports_clamping() {
+        let mut tree = GrowingFenwickTree::<i32>::new(0);
+        assert_eq!(tree.query_checked(0), (0, true));
+
+        tree.update(3, 2).unwrap();
+        tree.update(7, 1).unwrap();
+        let len = tree.resize_stats().len;
+        assert_eq!(tree.query_checked(2), (0, false));
+        assert_eq!(tree.query_checked(len - 1), (3, false));
+        assert_eq!(tree.query_checked(len), (3, true));
+        assert_eq!(tree.query_checked(usize::MAX), (3, true));
+
+        for policy in [OutOfBoundsPolicy::Error, OutOfBoundsPolicy::TreatAsZero] {
+            let mut tree = tree.clone().with_out_of_bounds(policy);
+            assert!(tree.query(len) != Ok(3));
+            assert_eq!(tree.query_checked(len), (3, true));
+            assert_eq!(tree.query_clamped(len), 3);
+            tree.update_growing(len, 1);
+            assert_eq!(tree.query_clamped(len), 4);
+        }
+    }
+
+    #[test]
+    fn add_assign_pairs() {
+        let mut tree = GrowingFenwickTree::<i32>::new(0);
+        tree += (3, 2);
+        tree += vec![(10, 5), (3, 1)];
+        tree += [(0, 1), (40, 1)];
+        tree.extend((0..5).map(|idx| (idx, 1)));
+
+        assert_eq!(tree.query_clamped(2), 4);
+        assert_eq!(tree.query_clamped(3), 8);
+        assert_eq!(tree.query_clamped(1000), 15);
+    }
+
+    #[test]
+    fn collect_pairs() {
+        let mut tree: GrowingFenwickTree<i32> = [(4, 2), (1, 3), (4, 1)].into_iter().collect();
+        assert_eq!(tree.query(3).unwrap(), 3);
+        assert_eq!(tree.query(4).unwrap(), 6);
+
+        tree.extend([(9, 4)]);
+        assert_eq!(tree.query(9).unwrap(), 10);
+        assert_eq!(
+            std::iter::empty().collect::<GrowingFenwickTree<i32>>().query_checked(0),
+            (0, true)
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn add_assign_beyond_max_size() {
+        let mut tree = GrowingFenwickTree::<i32>::new(0);
+        tree.max_size = Some(4);
+        tree += (4, 1);
+    }
+
+    #[test]
+    #[should_panic]
+    fn update_growing_beyond_max_size() {
+        let mut tree = GrowingFenwickTree::<i32>::new(0);
+        tree.max_size = Some(4);
+        tree.update_growing(4, 1);
+    }
+
+    #[test]
+    fn from_fn_matches_updates() {
+        let mut tree = GrowingFenwickTree::from_fn(50, |i| i as i32 % 7);
+        let mut expected = GrowingFenwickTree::new(0);
+        for i in 0..50 {
+            expected.update(i, i as i32 % 7).unwrap();
+        }
+
+        tree.update(70, 1).unwrap();
+        expected.update(70, 1).unwrap();
+        for i in 0..80 {
+            assert_eq!(tree.query(i), expected.query(i));
+        }
+    }
+
+    #[test]
+    fn explicit_resize_keeps_prefix_sums() {
+        let mut tree = GrowingFenwickTree::from_fn(100, |i| i as i32);
+
+        tree.resize(37).unwrap();
+        assert_eq!(tree.resize_stats().len, 37);
+        for i in 0..37 {
+            assert_eq!(tree.query(i).unwrap(), (0..=i as i32).sum::<i32>());
+        }
+        assert_eq!(tree.query(99).unwrap(), (0..37).sum::<i32>());
+
+        tree.resize(200).unwrap();
+        tree.update(150, 1).unwrap();
+        assert_eq!(tree.resize_stats().len, 200);
+        assert_eq!(tree.query(149).unwrap(), (0..37).sum::<i32>());
+        assert_eq!(tree.query(150).unwrap(), (0..37).sum::<i32>() + 1);
+
+        tree.max_size = Some(200);
+        assert_eq!(
+            tree.resize(201),
+            Err(TreeError::IndexOutOfBounds {
+                index: 200,
+                bound: 200
+            })
+        );
+    }
+
+    #[test]
+    fn resize_stats_track_growth() {
+        let mut tree = GrowingFenwickTree::<i32>::new(3);
+        assert_eq!(
+            tree.resize_stats(),
+            ResizeStats {
+                resizes: 0,
+                elements_copied: 0,
+                len: 3,
+                capacity: 3,
+            }
+        );
+
+        tree.update(2, 1).unwrap();
+        assert_eq!(tree.resize_stats().resizes, 0);
+
+        tree.update(9, 1).unwrap();
+        let stats = tree.resize_stats();
+        assert_eq!(stats.resizes, 1);
+        assert_eq!(stats.elements_copied, 4);
+        assert_eq!(stats.len, 10);
+        assert!(stats.capacity >= 10);
+
+        tree.update(10, 1).unwrap();
+        assert_eq!(tree.resize_stats().resizes, 2);
+
+        tree.resize(5).unwrap();
+        tree.resize(5).unwrap();
+        let stats = tree.resize_stats();
+        assert_eq!(stats.resizes, 2);
+        assert_eq!(stats.len, 5);
+    }
+
+    #[test]
+    fn power_of_two_growth() {
+        let mut tree = GrowingFenwickTree::<i32>::new(0);
+        tree.growth = Growth::NextPowerOfTwo;
+
+        for i in 0..100 {
+            tree.update(i, 1).unwrap();
+        }
+
+        // grows to 1, 2, 4, ..., 128 indexes
+        assert_eq!(tree.resize_stats().resizes, 8);
+        assert_eq!(tree.resize_stats().len, 128);
+        assert_eq!(tree.query(99).unwrap(), 100);
+        assert_eq!(tree.query(200).unwrap(), 100);
+    }
+
+    #[test]
+    fn growth_is_limited_by_max_size() {
+        let mut tree = GrowingFenwickTree::<i32>::new(0);
+        tree.growth = Growth::NextPowerOfTwo;
+        tree.max_size = Some(100);
+
+        tree.update(70, 1).unwrap();
+        assert_eq!(tree.resize_stats().len, 100);
+        tree.update(99, 1).unwrap();
+        assert_eq!(tree.query(99).unwrap(), 2);
+        assert_eq!(
+            tree.update(100, 1),
+            Err(TreeError::IndexOutOfBounds {
+                index: 100,
+                bound: 100
+            })
+        );
+        assert_eq!(
+            tree.update(100, 0),
+            Err(TreeError::IndexOutOfBounds {
+                index: 100,
+                bound: 100
+            })
+        );
+    }
+
+    #[test]
+    fn shrink_to_fit_releases_reserved_capacity() {
+        let mut tree = GrowingFenwickTree::<i32>::new(0);
+        for i in 0..100 {
+            tree.update(i, 1).unwrap();
+        }
+
+        tree.shrink_to_fit();
+        let stats = tree.resize_stats();
+        assert_eq!(stats.capacity, stats.len);
+        assert_eq!(tree.query(99).unwrap(), 100);
+    }
+
+    #[test]
+    fn clone_from_reuses_allocation() {
+        let mut tree = GrowingFenwickTree::<i32>::new(100);
+        let mut snapshot = tree.clone();
+        let allocation = snapshot.data.as_ptr();
+
+        for i in 0..10 {
+            tree.update(i * 10, 1).unwrap();
+            snapshot.clone_from(&tree);
+            assert_eq!(snapshot.data.as_ptr(), allocation);
+            assert_eq!(snapshot.query(100).unwrap(), i as i32 + 1);
+        }
+    }
+
+    #[test]
+    fn resize_keeps_sums_of_scattered_points() {
+        let mut tree = GrowingFenwickTree::<i32>::new(0);
+        let points = [
+            (149, 1),
+            (5, 9),
+            (143, 8),
+            (36, 6),
+            (130, 7),
+            (11, 8),
+            (46, 2),
+            (177, 7),
+        ];
+        for (idx, value) in points {
+            tree.update(idx, value).unwrap();
+        }
+
+        let mut sum = 0;
+        for i in 0..200 {
+            sum += points
+                .iter()
+                .filter(|(idx, _)| *idx == i)
+                .map(|(_, value)| value)
+                .sum::<i32>();
+            assert_eq!(tree.query(i).unwrap(), sum);
+        }
+    }
+
     #[test]
     fn simple_tree_generation_with_queries() {
         let mut tree = GrowingFenwickTree::<i32>::new(11);
         for i in 0..32 {
-            if let Err(_) = tree.update(i, 1) {
-                assert!(false)
-            }
+            tree.update(i, 1).unwrap();
         }
         assert_eq!(tree.query(3).unwrap(), 4); // points at [0, 1, 2, 3, 4]
         assert_eq!(tree.query(0).unwrap(), 1);
@@ -187,28 +1447,18 @@ mod tests {
     fn test_range_queries() {
         let mut tree = GrowingFenwickTree::<i32>::new(0);
         for i in 0..=29 {
-            if let Err(_) = tree.update(i, 1) {
-                assert!(false)
-            }
+            tree.update(i, 1).unwrap();
         }
 
-        match tree.range_query(10, 20) {
-            Ok(10) => assert!(true),
-            _ => assert!(false),
-        }
-        match tree.range_query(8, 29) {
-            Ok(21) => assert!(true),
-            _ => assert!(false),
-        }
+        assert_eq!(tree.range_query(10, 20), Ok(10));
+        assert_eq!(tree.range_query(8, 29), Ok(21));
     }
 
     #[test]
     fn update_existent_value() {
         let mut tree = GrowingFenwickTree::<i32>::new(0);
         for _i in 0..32 {
-            if let Err(_) = tree.update(0, 1) {
-                assert!(false)
-            }
+            tree.update(0, 1).unwrap();
         }
         let res = tree.query(0).unwrap();
         assert_eq!(res, 32);
@@ -217,83 +1467,50 @@ mod tests {
     #[test]
     fn random_100_point_data() {
         let size = 100;
-        let mut input = vec![];
-        let mut rng = rand::thread_rng();
-
-        for _i in 0..size {
-            input.push((rng.gen::<f32>() * 100.0) as i32);
-        }
+        let (input, _) = random_input(1, size);
 
         let mut tree = GrowingFenwickTree::<i32>::new(0);
         for i in 0..size {
-            if let Err(_) = tree.update(i, *input.get(i).unwrap()) {
-                assert!(false)
-            }
+            tree.update(i, *input.get(i).unwrap()).unwrap();
         }
 
         let mut sum = 0;
         for i in 0..size {
             sum += *input.get(i).unwrap();
 
-            if let Ok(res) = tree.query(i) {
-                assert_eq!(res, sum);
-            } else {
-                assert!(false)
-            }
+            assert_eq!(tree.query(i).unwrap(), sum);
         }
     }
 
     #[test]
     fn random_100_point_data_with_random_update_order() {
         let size = 100;
-        let mut input = vec![];
-        let mut rng = rand::thread_rng();
-
-        for _i in 0..size {
-            input.push((rng.gen::<f32>() * 100.0) as i32);
-        }
+        let (input, random_indexes) = random_input(2, size);
 
         let mut tree = GrowingFenwickTree::<i32>::new(size);
 
-        let mut random_indexes: Vec<usize> = (0..size).collect();
-        random_indexes.shuffle(&mut rng);
         for i in random_indexes {
-            if let Err(_) = tree.update(i, *input.get(i).unwrap()) {
-                assert!(false)
-            }
+            tree.update(i, *input.get(i).unwrap()).unwrap();
         }
 
         let mut sum = 0;
         for i in 0..size {
             sum += *input.get(i).unwrap();
-            if let Ok(res) = tree.query(i) {
-                assert_eq!(res, sum);
-            } else {
-                assert!(false);
-            }
+            assert_eq!(tree.query(i).unwrap(), sum);
         }
     }
 
     #[test]
     fn random_100_point_data_with_random_update_order_with_intermediate_asserts() {
         let size = 100;
-        let mut input = vec![];
-        let mut rng = rand::thread_rng();
-
-        for _i in 0..size {
-            input.push((rng.gen::<f32>() * 100.0) as i32);
-        }
+        let (input, random_indexes) = random_input(3, size);
 
         let mut tree = GrowingFenwickTree::<i32>::new(size);
 
-        let mut random_indexes: Vec<usize> = (0..size).collect();
-        random_indexes.shuffle(&mut rng);
         for i in random_indexes {
             let sum_before_update = tree.query(i).unwrap();
             let value_to_update = *input.get(i).unwrap();
-            if let Err(_) = tree.update(i, value_to_update) {
-                assert!(false)
-            }
+            tree.update(i, value_to_update).unwrap();
             let sum_after_update = tree.query(i).unwrap();
             assert_eq!(sum_after_update - sum_before_update, value_to_update)
         }
@@ -302,11 +1519,218 @@ mod tests {
         for i in 0..size {
             sum += *input.get(i).unwrap();
 
-            if let Ok(res) = tree.query(i) {
-                assert_eq!(res, sum);
-            } else {
-                assert!(false)
-            }
+            assert_eq!(tree.query(i).unwrap(), sum);
+        }
+    }
+
+    #[test]
+    fn partition_point_on_empty_tree() {
+        let tree = GrowingFenwickTree::<u32>::new(0);
+        assert_eq!(tree.partition_point(|sum| *sum < 1), 0);
+    }
+
+    #[test]
+    fn partition_point_by_budget() {
+        let mut tree = GrowingFenwickTree::<u32>::new(0);
+        for (idx, cost) in [(3, 10), (10, 5), (11, 5), (50, 30), (99, 1)] {
+            tree.update(idx, cost).unwrap();
+        }
+
+        assert_eq!(tree.partition_point(|cost| *cost == 0), 3);
+        assert_eq!(tree.partition_point(|cost| *cost <= 10), 10);
+        assert_eq!(tree.partition_point(|cost| *cost <= 15), 11);
+        assert_eq!(tree.partition_point(|cost| *cost <= 20), 50);
+        assert_eq!(tree.partition_point(|cost| *cost < 51), 99);
+        assert_eq!(tree.partition_point(|cost| *cost <= 51), 100);
+    }
+
+    #[test]
+    fn partition_point_matches_linear_scan() {
+        let mut tree = GrowingFenwickTree::<u32>::new(100);
+        for (i, (_, value)) in Workload::new(4, 100).updates().take(100).enumerate() {
+            tree.update(i, value as u32).unwrap();
+        }
+
+        for budget in 0..500 {
+            let expected = (0..100)
+                .find(|i| tree.query(*i).unwrap() >= budget)
+                .unwrap_or(100);
+            assert_eq!(tree.partition_point(|sum| *sum < budget), expected);
+        }
+    }
+
+    #[test]
+    fn lower_and_upper_bounds_with_ties() {
+        let mut tree = GrowingFenwickTree::<u32>::new(0);
+        for (idx, count) in [(3, 10), (10, 5), (11, 5), (50, 30), (99, 1)] {
+            tree.update(idx, count).unwrap();
+        }
+
+        assert_eq!(tree.lower_bound(0), Some(0));
+        assert_eq!(tree.upper_bound(0), Some(3));
+        assert_eq!(tree.lower_bound(10), Some(3));
+        assert_eq!(tree.upper_bound(10), Some(10));
+        assert_eq!(tree.lower_bound(15), Some(10));
+        assert_eq!(tree.upper_bound(15), Some(11));
+        assert_eq!(tree.lower_bound(51), Some(99));
+        assert_eq!(tree.upper_bound(50), Some(99));
+        assert_eq!(tree.upper_bound(51), None);
+        assert_eq!(tree.lower_bound(52), None);
+    }
+
+    #[test]
+    fn checked_searches_detect_negative_values() {
+        let mut tree = GrowingFenwickTree::<NonNegative<i32>>::new(0);
+        tree.update(3, NonNegative::new(10)).unwrap();
+        tree.update(50, NonNegative::new(5)).unwrap();
+
+        assert_eq!(tree.checked_lower_bound(10), Ok(Some(3)));
+        assert_eq!(tree.checked_upper_bound(10), Ok(Some(50)));
+        assert_eq!(
+            tree.checked_select_many(&[1, 15, 16]),
+            Ok(vec![Some(3), Some(50), None])
+        );
+
+        tree.update(60, NonNegative::new(-5)).unwrap();
+        tree.update(60, NonNegative::new(5)).unwrap();
+
+        assert_eq!(tree.checked_lower_bound(10), Err(TreeError::NegativeValue));
+        assert_eq!(tree.checked_upper_bound(10), Err(TreeError::NegativeValue));
+        assert_eq!(
+            tree.checked_select_many(&[1]),
+            Err(TreeError::NegativeValue)
+        );
+    }
+
+    #[test]
+    fn select_many_thresholds() {
+        let mut tree = GrowingFenwickTree::<u32>::new(0);
+        for (idx, count) in [(3, 10), (10, 5), (11, 5), (50, 30), (99, 1)] {
+            tree.update(idx, count).unwrap();
+        }
+
+        assert_eq!(
+            tree.select_many(&[51, 0, 10, 11, 21, 15, 52, 1]),
+            vec![
+                Some(99),
+                Some(0),
+                Some(3),
+                Some(10),
+                Some(50),
+                Some(10),
+                None,
+                Some(3)
+            ]
+        );
+        assert_eq!(tree.select_many(&[]), vec![]);
+    }
+
+    #[test]
+    fn select_many_matches_partition_point() {
+        let mut tree = GrowingFenwickTree::<u32>::new(100);
+        for (i, (_, value)) in Workload::new(5, 100).updates().take(100).enumerate() {
+            tree.update(i, value as u32).unwrap();
+        }
+
+        let targets: Vec<u32> = Workload::new(6, 500)
+            .updates()
+            .map(|(target, _)| target as u32)
+            .take(100)
+            .collect();
+        let expected: Vec<Option<usize>> = targets
+            .iter()
+            .map(|target| Some(tree.partition_point(|sum| sum < target)).filter(|idx| *idx < 100))
+            .collect();
+        assert_eq!(tree.select_many(&targets), expected);
+    }
+
+    #[test]
+    fn iter_nonzero_on_empty_tree() {
+        let tree = GrowingFenwickTree::<i32>::new(0);
+        assert_eq!(tree.iter_nonzero().next(), None);
+    }
+
+    #[test]
+    fn iter_nonzero_skips_default_points() {
+        let mut tree = GrowingFenwickTree::<i32>::new(0);
+        for (idx, value) in [(999, 1), (0, 2), (512, 3), (511, 4), (7, 5)] {
+            tree.update(idx, value).unwrap();
+        }
+
+        assert_eq!(
+            tree.iter_nonzero().collect::<Vec<_>>(),
+            vec![(0, 2), (7, 5), (511, 4), (512, 3), (999, 1)]
+        );
+    }
+
+    #[test]
+    fn iter_nonzero_reports_cancelling_points() {
+        let mut tree = GrowingFenwickTree::<i32>::new(0);
+        tree.update(0, 5).unwrap();
+        tree.update(1, -5).unwrap();
+        tree.update(4, 2).unwrap();
+        tree.update(7, -2).unwrap();
+
+        assert_eq!(
+            tree.iter_nonzero().collect::<Vec<_>>(),
+            vec![(0, 5), (1, -5), (4, 2), (7, -2)]
+        );
+        assert_eq!(tree.count_nonzero_between(0, 7).unwrap(), 4);
+        assert_eq!(tree.count_nonzero_between(1, 6).unwrap(), 2);
+    }
+
+    #[test]
+    fn count_nonzero_between() {
+        let mut tree = GrowingFenwickTree::<i32>::new(0);
+        for (idx, value) in [(999, 1), (0, 2), (512, 3), (511, 4), (7, 5)] {
+            tree.update(idx, value).unwrap();
+        }
+
+        assert_eq!(tree.count_nonzero_between(0, 999).unwrap(), 5);
+        assert_eq!(tree.count_nonzero_between(1, 998).unwrap(), 3);
+        assert_eq!(tree.count_nonzero_between(7, 511).unwrap(), 2);
+        assert_eq!(tree.count_nonzero_between(8, 510).unwrap(), 0);
+        assert_eq!(tree.count_nonzero_between(512, 512).unwrap(), 1);
+        assert_eq!(
+            tree.count_nonzero_between(600, 10),
+            Err(TreeError::InvalidRange { from: 600, to: 10 })
+        );
+        assert_eq!(tree.count_nonzero_between(0, 1_000_000).unwrap(), 5);
+    }
+
+    #[test]
+    fn iter_nonzero_matches_random_points() {
+        let size = 100;
+        let mut input = vec![0; size];
+        let mut tree = GrowingFenwickTree::<i32>::new(size);
+
+        for (idx, value) in Workload::new(7, size).deltas(1..100).updates().take(20) {
+            let value = value as i32;
+            input[idx] += value;
+            tree.update(idx, value).unwrap();
+        }
+
+        let expected: Vec<(usize, i32)> = input
+            .into_iter()
+            .enumerate()
+            .filter(|(_, value)| *value != 0)
+            .collect();
+        assert_eq!(tree.iter_nonzero().collect::<Vec<_>>(), expected);
+    }
+
+    #[test]
+    fn prefix_cursor_covers_stored_indexes() {
+        let mut tree = GrowingFenwickTree::<i64>::new(0);
+        for idx in [3, 17, 4, 40] {
+            tree.update(idx, idx as i64).unwrap();
+        }
+        let sums: Vec<_> = tree.prefix_cursor(16).collect();
+        assert_eq!(sums.first(), Some(&(16, 7)));
+        assert_eq!(sums.last(), Some(&(tree.size() - 2, 64)));
+        for (idx, sum) in sums {
+            assert_eq!(sum, tree.query(idx).unwrap());
         }
+        assert_eq!(tree.prefix_cursor(usize::MAX).count(), 0);
+        assert_eq!(GrowingFenwickTree::<i64>::new(0).prefix_cursor(0).count(), 0);
     }
 }
@@ -0,0 +1,61 @@
+use std::hash::Hasher;
+
+use crate::snapshot_tree::SnapshotValue;
+use crate::{points_of, InvertibleValue};
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// FNV-1a hasher. Unlike [`std::collections::hash_map::DefaultHasher`] its output
+/// doesn't change between Rust releases, so hashes can be compared across processes.
+pub(crate) struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(FNV_OFFSET_BASIS)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+}
+
+/// Hash of non-default points with their indexes, starting at `offset`. Indexes are
+/// hashed as little-endian [`u64`] and points in their [`SnapshotValue`] encoding, so
+/// they hash the same on every target.
+fn hash_points<T: SnapshotValue>(points: &[T], offset: usize) -> u64 {
+    let mut hasher = Fnv1a::default();
+    let mut buf = vec![0; T::ENCODED_LEN];
+    for (idx, point) in points.iter().enumerate() {
+        if !point.is_identity() {
+            hasher.write(&((offset + idx) as u64).to_le_bytes());
+            point.encode(&mut buf);
+            hasher.write(&buf);
+        }
+    }
+    hasher.finish()
+}
+
+pub(crate) fn content_hash<T: InvertibleValue + SnapshotValue>(data: &[T]) -> u64 {
+    hash_points(&points_of(data), 0)
+}
+
+pub(crate) fn chunk_hashes<T: InvertibleValue + SnapshotValue>(data: &[T], k: usize) -> Vec<u64> {
+    if k == 0 {
+        return vec![];
+    }
+    points_of(data)
+        .chunks(k)
+        .enumerate()
+        .map(|(chunk, points)| hash_points(points, chunk * k))
+        .collect()
+}
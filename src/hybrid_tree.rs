@@ -0,0 +1,203 @@
+use crate::{
+    FenwickReader, FenwickTreeValue, FenwickWriter, FixedSizeFenwickTree, InvertibleValue,
+    TreeError,
+};
+
+/// Fixed size tree picking its layout by size: up to
+/// [`HybridFenwickTree::LINEAR_THRESHOLD`] indexes values are kept in a plain array,
+/// which is updated in `O(1)` and scanned by queries, bigger trees use
+/// [`FixedSizeFenwickTree`]. Scanning a few cache lines beats tree traversal for tiny
+/// trees, which matters when millions of them are kept.
+///
+/// ```
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut small = HybridFenwickTree::<i32>::new(16);
+/// let mut big = HybridFenwickTree::<i32>::new(1_000);
+/// assert!(small.is_linear() && !big.is_linear());
+///
+/// small.update(3, 5).unwrap();
+/// big.update(3, 5).unwrap();
+/// assert_eq!(small.range_query(2, 15).unwrap(), big.range_query(2, 15).unwrap());
+/// ```
+#[derive(Clone)]
+pub struct HybridFenwickTree<T: FenwickTreeValue> {
+    backend: Backend<T>,
+}
+
+#[derive(Clone)]
+enum Backend<T: FenwickTreeValue> {
+    Linear(Vec<T>),
+    Tree(FixedSizeFenwickTree<T>),
+}
+
+impl<T: FenwickTreeValue> HybridFenwickTree<T> {
+    /// Largest size [`HybridFenwickTree::new`] keeps plain values for.
+    pub const LINEAR_THRESHOLD: usize = 64;
+
+    pub fn new(size: usize) -> Self {
+        Self::with_threshold(size, Self::LINEAR_THRESHOLD)
+    }
+
+    /// Same as [`HybridFenwickTree::new`], keeping plain values for sizes up to
+    /// `threshold`, e.g. tuned for values much bigger or smaller than numbers.
+    pub fn with_threshold(size: usize, threshold: usize) -> Self {
+        let backend = match size <= threshold {
+            true => Backend::Linear(vec![T::default(); size]),
+            false => Backend::Tree(FixedSizeFenwickTree::new(size)),
+        };
+        Self { backend }
+    }
+
+    pub fn size(&self) -> usize {
+        match &self.backend {
+            Backend::Linear(points) => points.len(),
+            Backend::Tree(tree) => tree.data.len() - 1,
+        }
+    }
+
+    /// Returns whether values are kept in a plain array.
+    pub fn is_linear(&self) -> bool {
+        matches!(self.backend, Backend::Linear(_))
+    }
+}
+
+/// Returns aggregate of `points` from `start` up to `to` (including), which is empty
+/// for `start` past `to`. Fails if `to` is out of bounds.
+fn scan<T: FenwickTreeValue>(points: &[T], start: usize, to: usize) -> Result<T, TreeError> {
+    if to >= points.len() {
+        return Err(TreeError::IndexOutOfBounds {
+            index: to,
+            bound: points.len(),
+        });
+    }
+    let mut res = T::default();
+    for point in &points[start..=to] {
+        res.store_value(point);
+    }
+    Ok(res)
+}
+
+impl<T: FenwickTreeValue> FenwickReader for HybridFenwickTree<T> {
+    type Value = T;
+
+    fn query(&self, idx: usize) -> Result<T, TreeError> {
+        match &self.backend {
+            Backend::Linear(points) => scan(points, 0, idx),
+            Backend::Tree(tree) => tree.query(idx),
+        }
+    }
+
+    /// Plain values within the range are aggregated directly, without inverting
+    /// aggregation.
+    fn range_query(&self, from: usize, to: usize) -> Result<T, TreeError>
+    where
+        T: InvertibleValue,
+    {
+        match &self.backend {
+            Backend::Linear(_) if from > to => Err(TreeError::InvalidRange { from, to }),
+            Backend::Linear(points) => scan(points, from.saturating_add(1), to),
+            Backend::Tree(tree) => tree.range_query(from, to),
+        }
+    }
+
+    fn find_prefix(&self, target: T) -> Option<usize>
+    where
+        T: PartialOrd,
+    {
+        match &self.backend {
+            Backend::Linear(points) => {
+                let mut sum = T::default();
+                points.iter().position(|point| {
+                    sum.store_value(point);
+                    sum >= target
+                })
+            }
+            Backend::Tree(tree) => tree.lower_bound(target),
+        }
+    }
+}
+
+impl<T: FenwickTreeValue> FenwickWriter for HybridFenwickTree<T> {
+    fn update(&mut self, idx: usize, value: T) -> Result<(), TreeError> {
+        match &mut self.backend {
+            Backend::Linear(points) => {
+                let bound = points.len();
+                let point = points
+                    .get_mut(idx)
+                    .ok_or(TreeError::IndexOutOfBounds { index: idx, bound })?;
+                if !value.is_valid() {
+                    return Err(TreeError::InvalidValue);
+                }
+                point.absorb(value);
+                Ok(())
+            }
+            Backend::Tree(tree) => tree.update(idx, value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hybrid_tree::HybridFenwickTree;
+    use crate::workload::Workload;
+    use crate::{FenwickReader, FenwickWriter, FixedSizeFenwickTree, Max, TreeError};
+
+    #[test]
+    fn linear_and_tree_layouts_agree() {
+        for size in [0, 1, 7, 64, 65, 200] {
+            let mut hybrid = HybridFenwickTree::<i64>::new(size);
+            let mut tree = FixedSizeFenwickTree::<i64>::new(size);
+            assert_eq!(hybrid.size(), size);
+            assert_eq!(hybrid.is_linear(), size <= 64);
+
+            let updates = Workload::new(size as u64, size).deltas(-50..50).updates();
+            for (idx, value) in updates.take(size * 2) {
+                hybrid.update(idx, value).unwrap();
+                tree.update(idx, value).unwrap();
+            }
+            for to in 0..size {
+                assert_eq!(hybrid.query(to), tree.query(to));
+                for from in 0..=to {
+                    assert_eq!(hybrid.range_query(from, to), tree.range_query(from, to));
+                }
+            }
+            for (from, to) in [(0, size), (size, size), (1, 0)] {
+                assert_eq!(hybrid.range_query(from, to), tree.range_query(from, to));
+            }
+            assert_eq!(hybrid.query(size), tree.query(size));
+            assert_eq!(hybrid.update(size, 1), tree.update(size, 1));
+        }
+    }
+
+    #[test]
+    fn find_prefix_in_both_layouts() {
+        let mut linear = HybridFenwickTree::<u32>::new(10);
+        let mut tree = HybridFenwickTree::<u32>::with_threshold(10, 0);
+        for (idx, value) in [2, 0, 0, 4, 1, 0, 0, 0, 3, 0].into_iter().enumerate() {
+            linear.update(idx, value).unwrap();
+            tree.update(idx, value).unwrap();
+        }
+        for (target, expected) in [(0, Some(0)), (3, Some(3)), (7, Some(4)), (10, Some(8))] {
+            assert_eq!(linear.find_prefix(target), expected);
+            assert_eq!(tree.find_prefix(target), expected);
+        }
+        assert_eq!(linear.find_prefix(11), None);
+        assert_eq!(tree.find_prefix(11), None);
+    }
+
+    #[test]
+    fn linear_layout_checks_values() {
+        let mut tree = HybridFenwickTree::<f64>::with_threshold(100, 100);
+        assert!(tree.is_linear());
+        assert_eq!(tree.update(3, f64::NAN), Err(TreeError::InvalidValue));
+        tree.update(3, 0.5).unwrap();
+        assert_eq!(tree.query(99).unwrap(), 0.5);
+        assert!(!HybridFenwickTree::<f64>::with_threshold(100, 10).is_linear());
+
+        let mut tree = HybridFenwickTree::<Max<u8>>::new(8);
+        tree.update(2, Max(7)).unwrap();
+        tree.update(5, Max(3)).unwrap();
+        assert_eq!(tree.query(4).unwrap(), Max(7));
+    }
+}
@@ -0,0 +1,196 @@
+use std::hash::{Hash, Hasher};
+
+use crate::hashing::Fnv1a;
+use crate::FenwickTreeValue;
+
+/// HyperLogLog sketch estimating number of distinct items, with `2^P` registers.
+///
+/// Union of two sketches is an element-wise maximum of their registers, which can't be
+/// reverted, so trees storing sketches answer prefix queries only: an estimate of the
+/// number of distinct items seen on all indexes up to the queried one.
+///
+/// Empty sketch doesn't allocate registers, so untouched tree nodes stay cheap.
+///
+/// ```rust
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut tree = GrowingFenwickTree::<HyperLogLog<12>>::new(0);
+/// for (day, user) in [(0, "alice"), (0, "bob"), (1, "alice"), (2, "carol")] {
+///     tree.update(day, HyperLogLog::from_item(&user)).unwrap();
+/// }
+///
+/// assert_eq!(tree.query(1).unwrap().estimate().round(), 2.0);
+/// assert_eq!(tree.query(2).unwrap().estimate().round(), 3.0);
+/// ```
+///
+/// Items are hashed with a fixed hash function, so sketches built by different
+/// processes can be merged. Precision `P` must be within `4..=16`:
+///
+/// ```compile_fail
+/// use fenwick_bit_tree::prelude::*;
+///
+/// HyperLogLog::<0>::default().insert(&1);
+/// ```
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct HyperLogLog<const P: u8> {
+    registers: Vec<u8>,
+}
+
+impl<const P: u8> HyperLogLog<P> {
+    const VALID_PRECISION: () = assert!(P >= 4 && P <= 16, "Precision must be within 4..=16.");
+    const REGISTERS: usize = 1 << P;
+
+    pub fn new() -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID_PRECISION;
+        Self::default()
+    }
+
+    /// Creates sketch containing single `item`.
+    pub fn from_item<H: Hash + ?Sized>(item: &H) -> Self {
+        let mut res = Self::new();
+        res.insert(item);
+        res
+    }
+
+    pub fn insert<H: Hash + ?Sized>(&mut self, item: &H) {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::VALID_PRECISION;
+
+        let mut hasher = Fnv1a::default();
+        item.hash(&mut hasher);
+        let hash = finalize(hasher.finish());
+
+        let register = (hash >> (64 - P)) as usize;
+        let rank = ((hash << P) | (1 << (P - 1))).leading_zeros() as u8 + 1;
+
+        if self.registers.is_empty() {
+            self.registers = vec![0; Self::REGISTERS];
+        }
+        self.registers[register] = self.registers[register].max(rank);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.registers.is_empty()
+    }
+
+    /// Returns estimated number of distinct items inserted into the sketch.
+    pub fn estimate(&self) -> f64 {
+        if self.registers.is_empty() {
+            return 0.0;
+        }
+
+        let m = Self::REGISTERS as f64;
+        let alpha = match Self::REGISTERS {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|rank| 2f64.powi(-(*rank as i32)))
+            .sum();
+        let estimate = alpha * m * m / sum;
+
+        let empty_registers = self.registers.iter().filter(|rank| **rank == 0).count();
+        if estimate <= 2.5 * m && empty_registers > 0 {
+            return m * (m / empty_registers as f64).ln();
+        }
+
+        estimate
+    }
+}
+
+/// MurmurHash3 finalizer. FNV-1a barely mixes trailing bytes into high bits, which pick
+/// the register, so its hashes are finalized before use.
+fn finalize(mut hash: u64) -> u64 {
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    hash ^ (hash >> 33)
+}
+
+impl<const P: u8> FenwickTreeValue for HyperLogLog<P> {
+    const CANCELS: bool = false;
+
+    fn store_value(&mut self, other: &Self) {
+        if other.registers.is_empty() {
+            return;
+        }
+        if self.registers.is_empty() {
+            self.registers = other.registers.clone();
+            return;
+        }
+        for (rank, other_rank) in self.registers.iter_mut().zip(other.registers.iter()) {
+            *rank = (*rank).max(*other_rank);
+        }
+    }
+
+    fn absorb(&mut self, other: Self) {
+        if self.registers.is_empty() {
+            self.registers = other.registers;
+            return;
+        }
+        self.store_value(&other);
+    }
+
+    fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::hyperloglog::HyperLogLog;
+    use crate::{FenwickReader, FenwickTreeValue, FenwickWriter, GrowingFenwickTree};
+
+    #[test]
+    fn empty_sketch() {
+        let sketch = HyperLogLog::<10>::new();
+        assert!(sketch.is_empty());
+        assert_eq!(sketch.estimate(), 0.0);
+    }
+
+    #[test]
+    fn merge_with_empty_sketch() {
+        let mut sketch = HyperLogLog::<10>::from_item(&1);
+        let copy = sketch.clone();
+        sketch.store_value(&HyperLogLog::new());
+        assert_eq!(sketch, copy);
+
+        let mut empty = HyperLogLog::<10>::new();
+        empty.store_value(&copy);
+        assert_eq!(empty, copy);
+    }
+
+    #[test]
+    fn estimate_is_close_to_distinct_count() {
+        let mut sketch = HyperLogLog::<12>::new();
+        for i in 0..100_000u32 {
+            sketch.insert(&(i % 20_000));
+        }
+        let error = (sketch.estimate() - 20_000.0).abs() / 20_000.0;
+        assert!(error < 0.05, "estimate {} is too far", sketch.estimate());
+    }
+
+    #[test]
+    fn prefix_distinct_count() {
+        let mut tree = GrowingFenwickTree::<HyperLogLog<12>>::new(0);
+        for day in 0..10usize {
+            // every day 100 users are active and 100 of them are new
+            for user in day * 100..day * 100 + 200 {
+                tree.update(day, HyperLogLog::from_item(&user)).unwrap();
+            }
+        }
+
+        for day in 0..10usize {
+            let expected = (day + 2) as f64 * 100.0;
+            let error = (tree.query(day).unwrap().estimate() - expected).abs() / expected;
+            assert!(error < 0.05);
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use std::marker::PhantomData;
+
+use crate::{FenwickTreeValue, FixedSizeFenwickTree, TreeError};
+
+/// Invariant lifetime identifying a single [`IndexedTree`], so indexes checked by one
+/// of them can't be passed to another.
+type Brand<'t> = PhantomData<fn(&'t ()) -> &'t ()>;
+
+/// Index of a tree, checked against its bounds once with [`IndexedTree::checked_idx`].
+/// Checked indexes are passed to [`IndexedTree::query_idx`] and
+/// [`IndexedTree::update_idx`], which skip both bounds check and out of bounds policy.
+///
+/// Index is branded with the lifetime of the [`IndexedTree`] which checked it, so it
+/// can't outlive it or be passed to another tree.
+///
+/// ```compile_fail
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut small = FixedSizeFenwickTree::<i32>::new(4);
+/// let mut big = FixedSizeFenwickTree::<i32>::new(8);
+/// big.indexed(|big| {
+///     let idx = big.checked_idx(5).unwrap();
+///     small.indexed(|small| small.query_idx(idx));
+/// });
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Idx<'t> {
+    idx: usize,
+    brand: Brand<'t>,
+}
+
+impl Idx<'_> {
+    pub fn get(self) -> usize {
+        self.idx
+    }
+}
+
+impl From<Idx<'_>> for usize {
+    fn from(idx: Idx<'_>) -> Self {
+        idx.idx
+    }
+}
+
+impl std::fmt::Display for Idx<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.idx.fmt(f)
+    }
+}
+
+/// Tree lent by [`FixedSizeFenwickTree::indexed`], which checks indexes against its
+/// bounds once and then queries and updates them without further checks. Size of the
+/// tree can't change while it is lent, so checked indexes stay valid.
+pub struct IndexedTree<'t, T: FenwickTreeValue> {
+    tree: &'t mut FixedSizeFenwickTree<T>,
+    brand: Brand<'t>,
+}
+
+impl<'t, T: FenwickTreeValue> IndexedTree<'t, T> {
+    pub(crate) fn new(tree: &'t mut FixedSizeFenwickTree<T>) -> Self {
+        Self {
+            tree,
+            brand: PhantomData,
+        }
+    }
+
+    /// Returns `idx` as [`Idx`] checked against bounds of the tree.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `idx` is out of bounds.
+    ///
+    pub fn checked_idx(&self, idx: usize) -> Result<Idx<'t>, TreeError> {
+        let size = self.tree.data.len() - 1;
+        if idx >= size {
+            return Err(TreeError::IndexOutOfBounds {
+                index: idx,
+                bound: size,
+            });
+        }
+        Ok(Idx {
+            idx,
+            brand: self.brand,
+        })
+    }
+
+    /// Same as [`crate::FenwickReader::query`] for a checked index.
+    pub fn query_idx(&self, idx: Idx<'t>) -> T {
+        self.tree.prefix(idx.idx)
+    }
+
+    /// Same as [`crate::FenwickWriter::update`] for a checked index.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `value` is rejected by
+    /// [`FenwickTreeValue::is_valid`].
+    ///
+    pub fn update_idx(&mut self, idx: Idx<'t>, value: T) -> Result<(), TreeError> {
+        if !value.is_valid() {
+            return Err(TreeError::InvalidValue);
+        }
+        self.tree.add(idx.idx, value);
+        Ok(())
+    }
+
+    /// Returns the lent tree for reading.
+    pub fn tree(&self) -> &FixedSizeFenwickTree<T> {
+        self.tree
+    }
+}
@@ -0,0 +1,162 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{FenwickWriter, TreeError};
+
+type Progress<'a> = Box<dyn FnMut(usize) + 'a>;
+
+/// Options of [`FenwickWriter::import`]: progress reporting and cooperative
+/// cancellation of long loads.
+///
+/// ```
+/// use std::sync::atomic::AtomicBool;
+///
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let cancel = AtomicBool::new(false);
+/// let mut reported = vec![];
+/// let mut tree = GrowingFenwickTree::<i64>::new(0);
+///
+/// let options = ImportOptions::new()
+///     .progress(400, |applied| reported.push(applied))
+///     .cancel_on(&cancel);
+/// let applied = tree.import((0..1000).map(|idx| (idx, 1)), options).unwrap();
+///
+/// assert_eq!(applied, 1000);
+/// assert_eq!(reported, vec![400, 800]);
+/// ```
+#[derive(Default)]
+pub struct ImportOptions<'a> {
+    every: usize,
+    progress: Option<Progress<'a>>,
+    cancel: Option<&'a AtomicBool>,
+}
+
+impl<'a> ImportOptions<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Calls `progress` with the number of items applied so far after every `every`
+    /// items. Nothing is reported if `every` is 0.
+    pub fn progress(mut self, every: usize, progress: impl FnMut(usize) + 'a) -> Self {
+        self.every = every;
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// Stops import before the next item once `cancel` is set, e.g. from another thread
+    /// or from the progress callback.
+    pub fn cancel_on(mut self, cancel: &'a AtomicBool) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+}
+
+/// Error of [`FenwickWriter::import`]: update of item number `applied` failed with
+/// `error`, all items before it stay applied.
+#[derive(Debug, PartialEq)]
+pub struct ImportError {
+    pub applied: usize,
+    pub error: TreeError,
+}
+
+impl std::fmt::Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ImportError { applied, error } = self;
+        write!(f, "import failed after {applied} items: {error}")
+    }
+}
+
+impl std::error::Error for ImportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Implementation of [`FenwickWriter::import`].
+pub(crate) fn import<F, I>(
+    tree: &mut F,
+    items: I,
+    mut options: ImportOptions,
+) -> Result<usize, ImportError>
+where
+    F: FenwickWriter,
+    I: IntoIterator<Item = (usize, F::Value)>,
+{
+    let mut applied = 0;
+    for (idx, value) in items {
+        if options
+            .cancel
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+        {
+            break;
+        }
+        if let Err(error) = tree.update(idx, value) {
+            return Err(ImportError { applied, error });
+        }
+        applied += 1;
+        if let Some(progress) = options.progress.as_mut().filter(|_| options.every > 0) {
+            if applied % options.every == 0 {
+                progress(applied);
+            }
+        }
+    }
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use crate::import::{ImportError, ImportOptions};
+    use crate::{FenwickReader, FenwickWriter, FixedSizeFenwickTree, TreeError};
+
+    #[test]
+    fn import_without_options() {
+        let mut tree = FixedSizeFenwickTree::<i32>::new(10);
+        let applied = tree
+            .import((0..10).map(|idx| (idx, 2)), ImportOptions::new())
+            .unwrap();
+
+        assert_eq!(applied, 10);
+        assert_eq!(tree.query(9).unwrap(), 20);
+    }
+
+    #[test]
+    fn progress_callback_cancels() {
+        let cancel = AtomicBool::new(false);
+        let mut reported = vec![];
+        let mut tree = FixedSizeFenwickTree::<i32>::new(100);
+
+        let options = ImportOptions::new()
+            .progress(10, |applied| {
+                reported.push(applied);
+                if applied == 30 {
+                    cancel.store(true, Ordering::Relaxed);
+                }
+            })
+            .cancel_on(&cancel);
+        let applied = tree.import((0..100).map(|idx| (idx, 1)), options).unwrap();
+
+        assert_eq!(applied, 30);
+        assert_eq!(reported, vec![10, 20, 30]);
+        assert_eq!(tree.query(99).unwrap(), 30);
+    }
+
+    #[test]
+    fn failed_update_stops_import() {
+        let mut tree = FixedSizeFenwickTree::<i32>::new(4);
+        let mut reported = vec![];
+        let options = ImportOptions::new().progress(0, |applied| reported.push(applied));
+
+        assert_eq!(
+            tree.import([(0, 1), (1, 1), (4, 1), (2, 1)], options),
+            Err(ImportError {
+                applied: 2,
+                error: TreeError::IndexOutOfBounds { index: 4, bound: 4 }
+            })
+        );
+        assert!(reported.is_empty());
+        assert_eq!(tree.query(3).unwrap(), 2);
+    }
+}
@@ -0,0 +1,193 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+use crate::{FenwickWriter, TreeError};
+
+enum Message<F: FenwickWriter> {
+    Update(usize, F::Value),
+    Snapshot(Sender<F>),
+}
+
+/// Background thread owning a tree. Updates are sent over a channel with
+/// [`IngestSender::update`], which doesn't wait for them to be applied, so request
+/// threads don't pay for tree updates. Queries are answered by snapshots of the tree,
+/// cloned by the background thread on request.
+///
+/// Updates sent from one thread are applied in order, and a snapshot requested from a
+/// thread includes all updates that thread sent before. Updates rejected by the tree
+/// are dropped and counted with [`IngestHandle::rejected_updates`].
+///
+/// ```
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let ingest = IngestHandle::spawn(FixedSizeFenwickTree::<i64>::new(16));
+/// let sender = ingest.sender();
+/// std::thread::spawn(move || sender.update(3, 5).unwrap())
+///     .join()
+///     .unwrap();
+///
+/// let tree = ingest.finish().unwrap();
+/// assert_eq!(tree.query(15).unwrap(), 5);
+/// ```
+pub struct IngestHandle<F: FenwickWriter> {
+    sender: IngestSender<F>,
+    rejected: Arc<AtomicU64>,
+    thread: JoinHandle<F>,
+}
+
+/// Cloneable sending half of an [`IngestHandle`].
+pub struct IngestSender<F: FenwickWriter> {
+    sender: Sender<Message<F>>,
+}
+
+impl<F: FenwickWriter> Clone for IngestSender<F> {
+    fn clone(&self) -> Self {
+        Self {
+            sender: self.sender.clone(),
+        }
+    }
+}
+
+impl<F: FenwickWriter> IngestSender<F> {
+    /// Queues update of `idx` with `value`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the background thread has stopped.
+    ///
+    pub fn update(&self, idx: usize, value: F::Value) -> Result<(), TreeError> {
+        self.sender
+            .send(Message::Update(idx, value))
+            .map_err(|_| TreeError::Disconnected)
+    }
+}
+
+impl<F> IngestHandle<F>
+where
+    F: FenwickWriter + Clone + Send + 'static,
+    F::Value: Send,
+{
+    pub fn spawn(tree: F) -> Self {
+        let (sender, receiver) = mpsc::channel();
+        let rejected = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&rejected);
+        let thread = thread::spawn(move || ingest(tree, &receiver, &counter));
+        Self {
+            sender: IngestSender { sender },
+            rejected,
+            thread,
+        }
+    }
+
+    pub fn sender(&self) -> IngestSender<F> {
+        self.sender.clone()
+    }
+
+    /// Same as [`IngestSender::update`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the background thread has stopped.
+    ///
+    pub fn update(&self, idx: usize, value: F::Value) -> Result<(), TreeError> {
+        self.sender.update(idx, value)
+    }
+
+    /// Returns a copy of the tree with all updates queued so far from this thread.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the background thread has stopped.
+    ///
+    pub fn snapshot(&self) -> Result<F, TreeError> {
+        let (reply, snapshot) = mpsc::channel();
+        self.sender
+            .sender
+            .send(Message::Snapshot(reply))
+            .map_err(|_| TreeError::Disconnected)?;
+        snapshot.recv().map_err(|_| TreeError::Disconnected)
+    }
+
+    /// Returns number of updates dropped, as the tree rejected them.
+    pub fn rejected_updates(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Waits for the background thread to apply updates queued by all senders until
+    /// they are dropped, and returns the tree.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the background thread has panicked.
+    ///
+    pub fn finish(self) -> Result<F, TreeError> {
+        drop(self.sender);
+        self.thread.join().map_err(|_| TreeError::Disconnected)
+    }
+}
+
+fn ingest<F: FenwickWriter + Clone>(
+    mut tree: F,
+    receiver: &Receiver<Message<F>>,
+    rejected: &AtomicU64,
+) -> F {
+    for message in receiver {
+        match message {
+            Message::Update(idx, value) => {
+                if tree.update(idx, value).is_err() {
+                    rejected.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+            Message::Snapshot(reply) => {
+                let _ = reply.send(tree.clone());
+            }
+        }
+    }
+    tree
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::ingest::IngestHandle;
+    use crate::{FenwickReader, FixedSizeFenwickTree, GrowingFenwickTree};
+
+    #[test]
+    fn ingests_from_many_threads() {
+        let ingest = IngestHandle::spawn(GrowingFenwickTree::<i64>::new(0));
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let sender = ingest.sender();
+                thread::spawn(move || {
+                    for idx in 0..100 {
+                        sender.update(idx, 1).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let tree = ingest.finish().unwrap();
+        assert_eq!(tree.query(99).unwrap(), 400);
+        assert_eq!(tree.range_query(49, 99).unwrap(), 200);
+    }
+
+    #[test]
+    fn snapshots_and_rejected_updates() {
+        let ingest = IngestHandle::spawn(FixedSizeFenwickTree::<i32>::new(4));
+        ingest.update(1, 2).unwrap();
+        ingest.update(4, 1).unwrap();
+        let snapshot = ingest.snapshot().unwrap();
+        ingest.update(3, 5).unwrap();
+
+        assert_eq!(snapshot.query(3).unwrap(), 2);
+        assert_eq!(ingest.rejected_updates(), 1);
+        assert_eq!(ingest.snapshot().unwrap().query(3).unwrap(), 7);
+        assert_eq!(ingest.finish().unwrap().query(3).unwrap(), 7);
+    }
+}
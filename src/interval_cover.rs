@@ -0,0 +1,194 @@
+use crate::{FenwickReader, FenwickWriter, FixedSizeFenwickTree, TreeError};
+
+/// Half-open intervals `[from, to)` over positions `0..size`, such as bookings of a
+/// room over nights. Tells how many intervals cover a position and how much of a span
+/// is covered, both in `O(log n)`.
+///
+/// Classic construction of two trees: one holds coverage changes at interval edges,
+/// the other holds the same changes weighted by their position, which turns prefix
+/// sums of coverage into a difference of two prefix queries.
+///
+/// ```
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut rooms = IntervalCover::new(30);
+/// rooms.add_interval(3, 10).unwrap();
+/// rooms.add_interval(8, 12).unwrap();
+///
+/// assert_eq!(rooms.coverage_at(9).unwrap(), 2);
+/// assert_eq!(rooms.covered_length_between(0, 30).unwrap(), 11);
+/// ```
+pub struct IntervalCover {
+    changes: FixedSizeFenwickTree<i64>,
+    weighted_changes: FixedSizeFenwickTree<i64>,
+    size: usize,
+}
+
+impl IntervalCover {
+    pub fn new(size: usize) -> Self {
+        Self {
+            changes: FixedSizeFenwickTree::new(size + 1),
+            weighted_changes: FixedSizeFenwickTree::new(size + 1),
+            size,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Adds interval `[from, to)`. Empty intervals are ignored.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `from` is greater than `to`, or `to` is
+    /// beyond the size.
+    ///
+    pub fn add_interval(&mut self, from: usize, to: usize) -> Result<(), TreeError> {
+        self.change(from, to, 1)
+    }
+
+    /// Removes interval `[from, to)` added before. Removing intervals that weren't
+    /// added makes coverage negative.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `from` is greater than `to`, or `to` is
+    /// beyond the size.
+    ///
+    pub fn remove_interval(&mut self, from: usize, to: usize) -> Result<(), TreeError> {
+        self.change(from, to, -1)
+    }
+
+    /// Returns number of intervals covering `position`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `position` is out of bounds.
+    ///
+    pub fn coverage_at(&self, position: usize) -> Result<i64, TreeError> {
+        if position >= self.size() {
+            return Err(TreeError::IndexOutOfBounds {
+                index: position,
+                bound: self.size(),
+            });
+        }
+        self.changes.query(position)
+    }
+
+    /// Returns sum of coverage of every position within `[from, to)`: the total length
+    /// of interval parts lying there, overlapping parts counted once per interval.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `from` is greater than `to`, or `to` is
+    /// beyond the size.
+    ///
+    pub fn covered_length_between(&self, from: usize, to: usize) -> Result<i64, TreeError> {
+        self.check_range(from, to)?;
+        Ok(self.covered_length_before(to)? - self.covered_length_before(from)?)
+    }
+
+    /// Sum of coverage of positions `[0, position)`.
+    fn covered_length_before(&self, position: usize) -> Result<i64, TreeError> {
+        if position == 0 {
+            return Ok(0);
+        }
+        let position_value = i64::try_from(position).map_err(|_| TreeError::Overflow)?;
+        Ok(self.changes.query(position - 1)? * position_value
+            - self.weighted_changes.query(position - 1)?)
+    }
+
+    fn change(&mut self, from: usize, to: usize, value: i64) -> Result<(), TreeError> {
+        self.check_range(from, to)?;
+        if from == to {
+            return Ok(());
+        }
+        let from_value = i64::try_from(from).map_err(|_| TreeError::Overflow)?;
+        let to_value = i64::try_from(to).map_err(|_| TreeError::Overflow)?;
+
+        self.changes.update(from, value)?;
+        self.changes.update(to, -value)?;
+        self.weighted_changes.update(from, value * from_value)?;
+        self.weighted_changes.update(to, -value * to_value)
+    }
+
+    fn check_range(&self, from: usize, to: usize) -> Result<(), TreeError> {
+        if from > to {
+            return Err(TreeError::InvalidRange { from, to });
+        }
+        if to > self.size() {
+            return Err(TreeError::IndexOutOfBounds {
+                index: to,
+                bound: self.size(),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::interval_cover::IntervalCover;
+    use crate::workload::Workload;
+    use crate::TreeError;
+
+    #[test]
+    fn matches_naive_coverage() {
+        let mut cover = IntervalCover::new(50);
+        let mut naive = [0i64; 50];
+        let mut added = Vec::new();
+
+        let ranges = Workload::new(1, 51).ranges();
+        let coins = Workload::new(2, 10).deltas(0..100).updates();
+        for ((from, to), (coin, pick)) in ranges.zip(coins).take(100) {
+            if coin < 3 && !added.is_empty() {
+                let (from, to) = added.swap_remove(pick as usize % added.len());
+                cover.remove_interval(from, to).unwrap();
+                naive[from..to].iter_mut().for_each(|count| *count -= 1);
+            } else {
+                cover.add_interval(from, to).unwrap();
+                naive[from..to].iter_mut().for_each(|count| *count += 1);
+                added.push((from, to));
+            }
+        }
+
+        for (position, count) in naive.iter().enumerate() {
+            assert_eq!(cover.coverage_at(position).unwrap(), *count);
+        }
+        for from in 0..=50 {
+            for to in from..=50 {
+                let expected: i64 = naive[from..to].iter().sum();
+                assert_eq!(cover.covered_length_between(from, to).unwrap(), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn bounds() {
+        let mut cover = IntervalCover::new(10);
+        assert_eq!(cover.size(), 10);
+        cover.add_interval(0, 10).unwrap();
+        cover.add_interval(4, 4).unwrap();
+
+        assert_eq!(cover.coverage_at(9).unwrap(), 1);
+        assert_eq!(
+            cover.coverage_at(10),
+            Err(TreeError::IndexOutOfBounds {
+                index: 10,
+                bound: 10
+            })
+        );
+        assert_eq!(
+            cover.add_interval(5, 4),
+            Err(TreeError::InvalidRange { from: 5, to: 4 })
+        );
+        assert_eq!(
+            cover.add_interval(5, 11),
+            Err(TreeError::IndexOutOfBounds {
+                index: 11,
+                bound: 10
+            })
+        );
+    }
+}
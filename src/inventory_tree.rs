@@ -0,0 +1,217 @@
+use crate::{FenwickReader, FenwickWriter, FixedSizeFenwickTree, TreeError};
+
+/// Stock levels keyed by time bucket, which never go below zero.
+///
+/// Every index holds a change of the stock and the level at an index is the prefix
+/// sum up to it. Updates that would make the level negative at any index (the updated
+/// one or any later one) are rejected with [`TreeError::NegativePrefix`] and leave the
+/// tree unchanged.
+///
+/// Lowest levels are kept in a segment tree next to the Fenwick tree, so both checks
+/// and updates take `O(log n)`.
+pub struct InventoryTree {
+    changes: FixedSizeFenwickTree<i64>,
+    lowest: LowestLevels,
+}
+
+impl InventoryTree {
+    pub fn new(size: usize) -> Self {
+        Self {
+            changes: FixedSizeFenwickTree::new(size),
+            lowest: LowestLevels::new(size),
+        }
+    }
+
+    /// Returns the lowest stock level between `idx` and the rightmost index.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `idx` is out of bounds.
+    ///
+    pub fn lowest_level_from(&self, idx: usize) -> Result<i64, TreeError> {
+        self.check_bounds(idx)?;
+        Ok(self.lowest.min_from(1, 0, self.lowest.len, idx, 0))
+    }
+
+    fn check_bounds(&self, idx: usize) -> Result<(), TreeError> {
+        if idx >= self.lowest.len {
+            return Err(TreeError::IndexOutOfBounds {
+                index: idx,
+                bound: self.lowest.len,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl FenwickReader for InventoryTree {
+    type Value = i64;
+
+    fn query(&self, idx: usize) -> Result<i64, TreeError> {
+        self.changes.query(idx)
+    }
+}
+
+impl FenwickWriter for InventoryTree {
+    fn update(&mut self, idx: usize, value: i64) -> Result<(), TreeError> {
+        self.check_bounds(idx)?;
+
+        if value < 0 {
+            let threshold = value.checked_neg().ok_or(TreeError::Overflow)?;
+            let len = self.lowest.len;
+            if let Some(index) = self.lowest.first_below(1, 0, len, idx, threshold, 0) {
+                return Err(TreeError::NegativePrefix { index });
+            }
+        }
+
+        self.changes.update(idx, value)?;
+        self.lowest.add(1, 0, self.lowest.len, idx, value);
+        Ok(())
+    }
+}
+
+/// Segment tree holding minimum of stock levels within every node range. Additions
+/// always cover a suffix of indexes, they are stored in the highest nodes covered
+/// entirely and never pushed down.
+struct LowestLevels {
+    len: usize,
+    min: Vec<i64>,
+    added: Vec<i64>,
+}
+
+impl LowestLevels {
+    fn new(len: usize) -> Self {
+        Self {
+            len,
+            min: vec![0; 4 * len],
+            added: vec![0; 4 * len],
+        }
+    }
+
+    /// Adds `value` to all levels from `from`, within node covering `left..right`.
+    fn add(&mut self, node: usize, left: usize, right: usize, from: usize, value: i64) {
+        if right <= from {
+            return;
+        }
+        if from <= left {
+            self.min[node] += value;
+            self.added[node] += value;
+            return;
+        }
+
+        let middle = (left + right) / 2;
+        self.add(2 * node, left, middle, from, value);
+        self.add(2 * node + 1, middle, right, from, value);
+        self.min[node] = self.min[2 * node].min(self.min[2 * node + 1]) + self.added[node];
+    }
+
+    /// Lowest level from `from`, with `above` added by ancestors of `node`.
+    fn min_from(&self, node: usize, left: usize, right: usize, from: usize, above: i64) -> i64 {
+        if from <= left {
+            return self.min[node] + above;
+        }
+
+        let above = above + self.added[node];
+        let middle = (left + right) / 2;
+        let right_min = self.min_from(2 * node + 1, middle, right, from, above);
+        if from < middle {
+            right_min.min(self.min_from(2 * node, left, middle, from, above))
+        } else {
+            right_min
+        }
+    }
+
+    /// The first index from `from` which level is below `threshold`.
+    fn first_below(
+        &self,
+        node: usize,
+        left: usize,
+        right: usize,
+        from: usize,
+        threshold: i64,
+        above: i64,
+    ) -> Option<usize> {
+        if right <= from || self.min[node] + above >= threshold {
+            return None;
+        }
+        if right - left == 1 {
+            return Some(left);
+        }
+
+        let above = above + self.added[node];
+        let middle = (left + right) / 2;
+        self.first_below(2 * node, left, middle, from, threshold, above)
+            .or_else(|| self.first_below(2 * node + 1, middle, right, from, threshold, above))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::inventory_tree::InventoryTree;
+    use crate::workload::Workload;
+    use crate::{FenwickReader, FenwickWriter, TreeError};
+
+    #[test]
+    fn rejects_negative_levels() {
+        let mut tree = InventoryTree::new(10);
+        tree.update(2, 5).unwrap();
+        tree.update(6, -3).unwrap();
+
+        assert_eq!(
+            tree.update(4, -3),
+            Err(TreeError::NegativePrefix { index: 6 })
+        );
+        assert_eq!(
+            tree.update(0, -1),
+            Err(TreeError::NegativePrefix { index: 0 })
+        );
+        tree.update(4, -2).unwrap();
+
+        assert_eq!(tree.query(5).unwrap(), 3);
+        assert_eq!(tree.query(9).unwrap(), 0);
+        assert_eq!(tree.lowest_level_from(3).unwrap(), 0);
+        assert_eq!(tree.lowest_level_from(2).unwrap(), 0);
+        assert_eq!(tree.lowest_level_from(4).unwrap(), 0);
+        assert_eq!(tree.lowest_level_from(5).unwrap(), 0);
+        assert!(tree.update(10, 1).is_err());
+        assert!(tree.lowest_level_from(10).is_err());
+    }
+
+    #[test]
+    fn random_updates_match_naive_levels() {
+        let size = 37;
+        let mut tree = InventoryTree::new(size);
+        let mut changes = vec![0i64; size];
+
+        let updates = Workload::new(1, size).deltas(-10..10).updates();
+        let froms = Workload::new(2, size).updates();
+        for ((idx, value), (from, _)) in updates.zip(froms).take(1000) {
+            changes[idx] += value;
+            let levels: Vec<i64> = changes
+                .iter()
+                .scan(0, |level, change| {
+                    *level += change;
+                    Some(*level)
+                })
+                .collect();
+            let first_negative = levels.iter().position(|level| *level < 0);
+
+            match first_negative {
+                None => tree.update(idx, value).unwrap(),
+                Some(index) => {
+                    changes[idx] -= value;
+                    assert_eq!(
+                        tree.update(idx, value),
+                        Err(TreeError::NegativePrefix { index })
+                    );
+                }
+            }
+
+            let lowest = (from..size)
+                .map(|i| changes[..=i].iter().sum::<i64>())
+                .min()
+                .unwrap();
+            assert_eq!(tree.lowest_level_from(from).unwrap(), lowest);
+        }
+    }
+}
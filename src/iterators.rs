@@ -0,0 +1,302 @@
+use std::ops::RangeInclusive;
+
+use std::iter::FusedIterator;
+
+use crate::raw::lsb_descending;
+use crate::search::prefix;
+use crate::{least_significant_bit, FenwickTreeValue, InvertibleValue};
+
+enum NonZeroStep<T> {
+    Node(usize),
+    Point(usize, T),
+}
+
+/// Iterator over non-empty points of a tree, in ascending index order. Works on raw
+/// internal representation, where node `i` aggregates points in `(i - lsb(i), i]`
+/// range and node 0 is unused.
+///
+/// Nodes holding identity aggregates are skipped together with the whole range they
+/// cover, which is what makes sparse trees cheap to export. Only values which can't
+/// cancel each other out are skipped that way, see [`FenwickTreeValue::CANCELS`], others
+/// visit every node. [`NonZeroPoints::counting`] prunes regardless, for trees counting
+/// non-default points of another tree.
+///
+/// Nodes covering no indexes within `from..=to` range are skipped as well.
+pub(crate) struct NonZeroPoints<'a, T> {
+    data: &'a [T],
+    stack: Vec<NonZeroStep<T>>,
+    from: usize,
+    to: usize,
+    prune: bool,
+}
+
+impl<'a, T: InvertibleValue> NonZeroPoints<'a, T> {
+    pub(crate) fn new(data: &'a [T]) -> Self {
+        Self::in_range(data, 0, usize::MAX)
+    }
+
+    pub(crate) fn in_range(data: &'a [T], from: usize, to: usize) -> Self {
+        // Top level nodes cover the whole key space without overlapping. Pushing them
+        // from the highest one makes the lowest one processed first.
+        let mut stack = vec![];
+        let mut node = data.len().saturating_sub(1);
+        while node > 0 {
+            stack.push(NonZeroStep::Node(node));
+            node -= least_significant_bit(node);
+        }
+
+        Self {
+            data,
+            stack,
+            from,
+            to,
+            prune: !T::CANCELS,
+        }
+    }
+
+    /// Same as [`NonZeroPoints::new`], but skips every node holding identity aggregate.
+    /// Only correct for trees which points never cancel, like non-negative counts.
+    pub(crate) fn counting(data: &'a [T]) -> Self {
+        Self {
+            prune: true,
+            ..Self::new(data)
+        }
+    }
+
+    /// Children of the node, from the one closest to the node down to the lowest one.
+    fn children(node: usize) -> impl Iterator<Item = usize> {
+        let lsb = least_significant_bit(node);
+        (0..)
+            .map(|power| 1 << power)
+            .take_while(move |step| *step < lsb)
+            .map(move |step| node - step)
+    }
+}
+
+impl<T: InvertibleValue> Iterator for NonZeroPoints<'_, T> {
+    type Item = (usize, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(step) = self.stack.pop() {
+            match step {
+                NonZeroStep::Point(idx, value) => return Some((idx - 1, value)),
+                NonZeroStep::Node(node) => {
+                    let covered_from = node - least_significant_bit(node);
+                    if node - 1 < self.from || covered_from > self.to {
+                        continue;
+                    }
+
+                    if self.prune && self.data[node].is_identity() {
+                        continue;
+                    }
+
+                    let mut children_sum = T::default();
+                    for child in Self::children(node) {
+                        children_sum.store_value(&self.data[child]);
+                    }
+
+                    let point = self.data[node].clone().substract(children_sum);
+                    if !point.is_identity() && (self.from..=self.to).contains(&(node - 1)) {
+                        self.stack.push(NonZeroStep::Point(node, point));
+                    }
+                    self.stack
+                        .extend(Self::children(node).map(NonZeroStep::Node));
+                }
+            }
+        }
+        None
+    }
+}
+/// Iterator over sums of every `k` consecutive points, computed from prefix sums of
+/// raw internal representation.
+pub(crate) struct WindowSums<'a, T> {
+    data: &'a [T],
+    k: usize,
+    start: usize,
+}
+
+impl<'a, T> WindowSums<'a, T> {
+    pub(crate) fn new(data: &'a [T], k: usize) -> Self {
+        Self { data, k, start: 0 }
+    }
+}
+
+impl<T: InvertibleValue> Iterator for WindowSums<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let end = self.start.checked_add(self.k)?;
+        if self.k == 0 || end > self.data.len() - 1 {
+            return None;
+        }
+        let res = prefix(self.data, end).substract(prefix(self.data, self.start));
+        self.start += 1;
+        Some(res)
+    }
+}
+
+/// Iterator over sums of disjoint blocks of `k` points, the last block may be shorter.
+/// Every block needs a single prefix sum, as the previous one is kept.
+pub(crate) struct ChunkSums<'a, T> {
+    data: &'a [T],
+    k: usize,
+    start: usize,
+    before_start: T,
+}
+
+impl<'a, T: InvertibleValue> ChunkSums<'a, T> {
+    pub(crate) fn new(data: &'a [T], k: usize) -> Self {
+        Self {
+            data,
+            k,
+            start: 0,
+            before_start: T::default(),
+        }
+    }
+}
+
+impl<T: InvertibleValue> Iterator for ChunkSums<'_, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let points = self.data.len() - 1;
+        if self.k == 0 || self.start >= points {
+            return None;
+        }
+        let end = self.start.saturating_add(self.k).min(points);
+        let up_to_end = prefix(self.data, end);
+        let res = up_to_end.clone().substract(self.before_start.clone());
+        self.before_start = up_to_end;
+        self.start = end;
+        Some(res)
+    }
+}
+
+/// Iterator over `(index, prefix sum)` pairs of consecutive points. The first sum is a
+/// full prefix sum, every next one adds value of a single point: node value without
+/// nodes it aggregates, which takes `O(1)` amortized.
+pub(crate) struct PrefixSums<'a, T> {
+    data: &'a [T],
+    next: usize,
+    end: usize,
+    sum: T,
+}
+
+impl<'a, T: InvertibleValue> PrefixSums<'a, T> {
+    /// Iterates over points of `range` held by `data`, points beyond are skipped.
+    pub(crate) fn new(data: &'a [T], range: RangeInclusive<usize>) -> Self {
+        let next = *range.start();
+        let end = match range.is_empty() {
+            true => next,
+            false => range.end().saturating_add(1).min(data.len() - 1),
+        };
+        let sum = match next < end {
+            true => prefix(data, next),
+            false => T::default(),
+        };
+        Self {
+            data,
+            next,
+            end,
+            sum,
+        }
+    }
+}
+
+impl<T: InvertibleValue> Iterator for PrefixSums<'_, T> {
+    type Item = (usize, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next >= self.end {
+            return None;
+        }
+        let position = self.next + 1;
+        let mut point = self.data[position].clone();
+        let mut child = position - 1;
+        while child > position - least_significant_bit(position) {
+            point = point.substract(self.data[child].clone());
+            child -= least_significant_bit(child);
+        }
+        self.sum.absorb(point);
+        let idx = self.next;
+        self.next = position;
+        Some((idx, self.sum.clone()))
+    }
+}
+
+/// Cursor over `(index, prefix sum)` pairs of consecutive indexes of a tree, from the
+/// index it was created at up to the rightmost one. Sums up to every node of the last
+/// query path are kept, so each step combines a single node, which takes `O(1)`
+/// amortized and works for values which can't be inverted.
+///
+/// ```
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let tree = FixedSizeFenwickTree::from_fn(5, |i| Max([3, 1, 4, 1, 5][i]));
+/// let maxima: Vec<_> = tree.prefix_cursor(1).map(|(_, max)| max.0).collect();
+/// assert_eq!(maxima, vec![3, 4, 4, 5]);
+/// ```
+#[derive(Clone)]
+pub struct PrefixCursor<'a, T> {
+    data: &'a [T],
+    next: usize,
+    /// Positions of the last query path in ascending order, with sums up to them.
+    partial: Vec<(usize, T)>,
+}
+
+impl<'a, T: FenwickTreeValue> PrefixCursor<'a, T> {
+    /// Starts at index `from` of `data`, in `O(log n)`.
+    pub(crate) fn new(data: &'a [T], from: usize) -> Self {
+        let mut partial = vec![];
+        if from < data.len() {
+            let mut path: Vec<usize> = lsb_descending(from).collect();
+            path.reverse();
+            let mut sum = T::default();
+            for position in path {
+                sum.store_value(&data[position]);
+                partial.push((position, sum.clone()));
+            }
+        }
+        Self {
+            data,
+            next: from,
+            partial,
+        }
+    }
+}
+
+impl<T: FenwickTreeValue> Iterator for PrefixCursor<'_, T> {
+    type Item = (usize, T);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let position = self.next.checked_add(1)?;
+        if position >= self.data.len() {
+            return None;
+        }
+        // Path of `position` continues the previous one from its node covering
+        // everything left of `position`.
+        let covered = position - least_significant_bit(position);
+        while self.partial.last().is_some_and(|(last, _)| *last > covered) {
+            self.partial.pop();
+        }
+        let mut sum = match self.partial.last() {
+            Some((_, sum)) => sum.clone(),
+            None => T::default(),
+        };
+        sum.store_value(&self.data[position]);
+        self.partial.push((position, sum.clone()));
+
+        let idx = self.next;
+        self.next = position;
+        Some((idx, sum))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.data.len().saturating_sub(1).saturating_sub(self.next);
+        (len, Some(len))
+    }
+}
+
+impl<T: FenwickTreeValue> ExactSizeIterator for PrefixCursor<'_, T> {}
+
+impl<T: FenwickTreeValue> FusedIterator for PrefixCursor<'_, T> {}
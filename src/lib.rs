@@ -13,11 +13,95 @@
 //! Provides abstraction for Fenwick tree data structure and 2 implmentations:
 //!  - [`prelude::FixedSizeFenwickTree`]
 //!  - [`prelude::GrowingFenwickTree`]
+//!
+//! [`prelude::AnyFenwickTree`] allows picking one of them at runtime, for example with
+//! [`prelude::FenwickTreeBuilder`].
+//!
+//! Also provides helpers built on top of those trees:
+//!  - [`prelude::DequeFenwickTree`] growing below its origin as well
+//!  - [`prelude::CellFenwickTree`] updated through a shared reference
+//!  - [`prelude::CompactFenwickTree`] storing small values, summed in a wider type
+//!  - [`prelude::HybridFenwickTree`] scanning plain values of tiny trees
+//!  - [`prelude::MirroredFenwickTree`] keeping plain values for `O(1)` point reads
+//!  - [`prelude::DynamicFenwickTree`] allocating nodes on touch over the whole `u64` range
+//!  - [`prelude::OrderedSparseFenwickTree`] storing only populated nodes of huge key spaces
+//!  - [`prelude::VersionedFenwickTree`] syncing replicas by shipping only changes
+//!  - [`prelude::StampedFenwickTree`] recording when every index was last updated
+//!  - [`prelude::SnapshotFenwickTree`] saving snapshots and patches of nodes changed
+//!    since the last one
+//!  - [`prelude::MultiResolutionTree`]
+//!  - [`prelude::VwapTracker`]
+//!  - [`prelude::OrderBookDepth`]
+//!  - [`prelude::InventoryTree`] rejecting updates that make stock levels negative
+//!  - [`prelude::BucketedTree`] mapping observations to caller supplied buckets
+//!  - [`prelude::IntervalCover`] counting intervals covering positions
+//!  - [`prelude::OrderStatTree`] finding the `k`-th smallest key of a multiset
+//!  - [`prelude::GridFenwickTree`] adding values to rectangles of a 2D grid
+//!  - [`prelude::FenwickTreeNd`] summing boxes of points of `D` dimensions
+//!  - [`prelude::RangeAggTree`] segment tree answering arbitrary range aggregates of
+//!    non-invertible values
+//!  - [`prelude::RangeUpdateFenwickTree`] adding values to ranges, read per index
+//!  - [`prelude::RangeUpdateRangeQueryFenwickTree`] adding values to ranges and summing
+//!    ranges, both in `O(log n)`
+//!
+//! [`algorithms`] module solves classic problems with those trees, [`raw`] module
+//! exposes node traversal they are built on for custom operations. With `workload`
+//! feature, `workload` module generates reproducible random operation sequences for
+//! stress-testing trees and wrappers around them.
 //! 
 //! Key space for a tree lies within [`usize`] range. Tree support any value that 
 //! implements [`FenwickTreeValue`] trait. [`FenwickTreeValue`] is automatically 
-//! implmented for all primitive numeric types that support [`std::ops::AddAssign`], 
-//! [`std::ops::Sub`], [`core::cmp::PartialEq`] and [`Copy`] traits.
+//! implmented for all types that support [`std::ops::AddAssign`], [`std::ops::Sub`], 
+//! [`core::cmp::PartialEq`], [`Default`] and [`Copy`] traits, like primitive numeric
+//! types and [`std::num::Wrapping`] integers. That includes `glam` float vectors, so
+//! with `glam` feature positional deltas can be accumulated per tick and queried over
+//! frame ranges. Tuples of values (up to 4 elements) are aggregated component-wise
+//! when wrapped in [`prelude::Tuple`].
+//!
+//! Range queries require aggregation to be invertible, which is expressed by 
+//! [`InvertibleValue`] trait. Values that only implement [`FenwickTreeValue`] 
+//! (like gcd) can be stored in a tree, but only prefix queries are available for them.
+//!
+//! Ready-made value types with custom aggregation:
+//!  - [`prelude::ModInt`]
+//!  - [`prelude::XorValue`]
+//!  - [`prelude::NonNegative`] guarding prefix searches against negative values
+//!  - [`prelude::DurationValue`] elapsed time with saturating subtraction
+//!  - [`prelude::Tuple`] of values aggregated component-wise
+//!  - [`prelude::Histogram`] counts of `N` buckets aggregated bucket-wise
+//!  - [`prelude::Gcd`] (prefix queries only)
+//!  - [`prelude::Max`] (prefix queries only)
+//!  - [`prelude::AnyTrue`] and [`prelude::AllTrue`] (prefix queries only)
+//!  - [`prelude::BitsetValue`] (prefix queries only)
+//!  - `HyperLogLog` distinct count sketch (prefix queries only, `hyperloglog` feature)
+//!  - `TDigest` quantile sketch (prefix queries only, `tdigest` feature)
+//!  - `Money` amount in cents with overflow-checked sums (`money` feature)
+//!
+//! Tree operations don't panic, every failure is reported as [`TreeError`]. That
+//! includes indexes growing tree can't allocate memory for. Exceptions are few
+//! convenience methods which document their `# Panics` explicitly.
+//!
+//! Trees are [`Send`] and [`Sync`] when values are, but updates need `&mut` access, so
+//! sharing a tree between threads takes a lock. Under [`std::sync::RwLock`] a query
+//! observes either none or all of the nodes touched by an update, never a partially
+//! applied one. Types built for other sharing patterns guarantee the following:
+//!  - [`prelude::CellFenwickTree`] is updated through `&self`, as nodes are kept in
+//!    cells. It is `!Sync`, so it is never shared between threads.
+//!  - [`prelude::SyncFenwickTree`] shares a tree which is no longer updated, queries
+//!    take no lock.
+//!  - [`prelude::IngestHandle`] moves updates off latency sensitive threads to a
+//!    background thread owning the tree. Updates of one thread are applied in order,
+//!    and its queries are answered by snapshots including all updates it sent before.
+//!    `AsyncFenwickTree` does the same for tokio services with `tokio` feature.
+//!  - With `shared-memory` feature on unix, `SharedMemoryFenwickTree` shares [`i64`]
+//!    sums of a single writer process with reader processes under a sequence lock.
+//!    Readers never block the writer and observe either none or all of the nodes
+//!    touched by an update, retrying while one is in progress. If the writer dies in
+//!    the middle of an update, readers spin forever. The lock is model checked with
+//!    loom: `RUSTFLAGS="--cfg loom" cargo test --release --lib seqlock`.
+//!
+//! Custom structs which fields are all value types can derive both traits field-wise 
+//! with `#[derive(FenwickTreeValue, InvertibleValue)]` when `derive` feature is enabled.
 //!
 //! ## Installation  
 //!
@@ -33,10 +117,32 @@
 //! 
 //! ## Benchmarks
 //! 
+//! Criterion suite compares both trees with naive prefix sums. Its harness is public
+//! in `benchmarks` module, so custom value types can be benchmarked too.
+//!
 //! ```bash
 //! cargo bench --features benchmarks
 //! ```
 //! 
+//! ## Verification
+//!
+//! Kani proof harnesses check on bounded sizes that queries match naive sums after
+//! any update sequence, that operations don't panic and that resizing growing trees
+//! preserves prefix sums:
+//!
+//! ```bash
+//! cargo kani
+//! ```
+//!
+//! ## CLI
+//!
+//! `fenwick-cli` loads `(index, value)` pairs from a CSV or JSON file and answers
+//! `query` and `range` commands:
+//!
+//! ```bash
+//! cargo run --features cli --bin fenwick-cli -- points.csv
+//! ```
+//! 
 //! ## Basic usage:
 //! 
 //! ```rust
@@ -68,40 +174,475 @@
 //! assert_eq!(val, 10);
 //! ```
 
-#![forbid(unsafe_code)]
-#![feature(test)]
+// Shared memory can't be mapped without FFI, so the feature only denies unsafe code
+// outside of its module.
+#![cfg_attr(not(feature = "shared-memory"), forbid(unsafe_code))]
+#![cfg_attr(feature = "shared-memory", deny(unsafe_code))]
+
+use raw::least_significant_bit;
 
-use std::ops::{Deref, DerefMut};
+// Allows derive macros to refer to the crate by its name from within the crate itself.
+extern crate self as fenwick_bit_tree;
 
+pub mod algorithms;
+mod any_tree;
+#[cfg(feature = "tokio")]
+mod async_tree;
+#[cfg(feature = "benchmarks")]
+pub mod benchmarks;
+mod bucketed_tree;
+mod builder;
+mod cell_tree;
+mod compact_tree;
+mod deque_tree;
+mod dynamic_tree;
 mod fixed_size_tree;
+mod grid_tree;
 mod growing_tree;
+mod hashing;
+mod hybrid_tree;
+mod idx;
+mod import;
+mod ingest;
+mod interval_cover;
+mod inventory_tree;
+mod iterators;
+mod mirrored_tree;
+#[cfg(feature = "money")]
+mod money;
+#[cfg(feature = "hyperloglog")]
+mod hyperloglog;
+mod multi_resolution_tree;
+mod nd_tree;
+mod order_book_depth;
+mod order_stat_tree;
+mod ordered_sparse_tree;
+mod range_agg_tree;
+mod range_update_range_query_tree;
+mod range_update_tree;
+pub mod raw;
+mod search;
+#[cfg(any(loom, all(feature = "shared-memory", unix)))]
+mod seqlock;
+mod snapshot_tree;
+mod stamped_tree;
+#[cfg(all(feature = "shared-memory", unix, not(loom)))]
+#[allow(unsafe_code)]
+mod shared_memory_tree;
+mod sync_tree;
+#[cfg(feature = "tdigest")]
+mod tdigest;
+mod values;
+#[cfg(kani)]
+mod verification;
+mod versioned_tree;
+mod vwap_tracker;
+#[cfg(feature = "workload")]
+pub mod workload;
+#[cfg(all(test, not(feature = "workload")))]
+#[allow(dead_code)]
+mod workload;
 
+pub use any_tree::AnyFenwickTree;
+#[cfg(feature = "tokio")]
+pub use async_tree::AsyncFenwickTree;
+pub use bucketed_tree::{BucketEdge, BucketedTree, OutOfRangePolicy};
+pub use builder::FenwickTreeBuilder;
+pub use cell_tree::CellFenwickTree;
+pub use compact_tree::{CompactFenwickTree, WideningValue};
+pub use deque_tree::DequeFenwickTree;
+pub use dynamic_tree::DynamicFenwickTree;
 pub use fixed_size_tree::FixedSizeFenwickTree;
-pub use growing_tree::GrowingFenwickTree;
+pub use grid_tree::GridFenwickTree;
+pub use growing_tree::{GrowingFenwickTree, Growth, ResizeStats};
+pub use hybrid_tree::HybridFenwickTree;
+pub use idx::{Idx, IndexedTree};
+pub use import::{ImportError, ImportOptions};
+pub use ingest::{IngestHandle, IngestSender};
+pub use interval_cover::IntervalCover;
+pub use inventory_tree::InventoryTree;
+pub use iterators::PrefixCursor;
+pub use mirrored_tree::MirroredFenwickTree;
+pub use nd_tree::FenwickTreeNd;
+#[cfg(feature = "money")]
+pub use money::Money;
+#[cfg(feature = "derive")]
+pub use fenwick_bit_tree_derive::{FenwickTreeValue, InvertibleValue};
+#[cfg(feature = "hyperloglog")]
+pub use hyperloglog::HyperLogLog;
+pub use multi_resolution_tree::MultiResolutionTree;
+pub use order_book_depth::OrderBookDepth;
+pub use order_stat_tree::OrderStatTree;
+pub use ordered_sparse_tree::OrderedSparseFenwickTree;
+pub use range_agg_tree::RangeAggTree;
+pub use range_update_range_query_tree::RangeUpdateRangeQueryFenwickTree;
+pub use range_update_tree::RangeUpdateFenwickTree;
+#[cfg(all(feature = "shared-memory", unix))]
+pub use shared_memory_tree::{SharedMemoryFenwickTree, SharedMemoryReader};
+pub use snapshot_tree::{SnapshotFenwickTree, SnapshotValue};
+pub use stamped_tree::StampedFenwickTree;
+pub use sync_tree::SyncFenwickTree;
+#[cfg(feature = "tdigest")]
+pub use tdigest::TDigest;
+pub use values::{
+    AllTrue, AnyTrue, BitsetValue, DurationValue, Gcd, Histogram, Max, ModInt, NonNegative,
+    Tuple, XorValue,
+};
+pub use versioned_tree::{DeltaSet, VersionedFenwickTree};
+pub use vwap_tracker::VwapTracker;
 
 /// Contains all public types
 pub mod prelude {
     pub use crate::FenwickTreeValue;
+    pub use crate::InvertibleValue;
+    pub use crate::any_tree::AnyFenwickTree;
+    #[cfg(feature = "tokio")]
+    pub use crate::async_tree::AsyncFenwickTree;
+    pub use crate::bucketed_tree::{BucketEdge, BucketedTree, OutOfRangePolicy};
+    pub use crate::builder::FenwickTreeBuilder;
+    pub use crate::cell_tree::CellFenwickTree;
+    pub use crate::compact_tree::{CompactFenwickTree, WideningValue};
+    pub use crate::deque_tree::DequeFenwickTree;
+    pub use crate::dynamic_tree::DynamicFenwickTree;
     pub use crate::fixed_size_tree::FixedSizeFenwickTree;
-    pub use crate::growing_tree::GrowingFenwickTree;
-    pub use crate::FenwickTree;
-    pub use crate::TreeError;
+    pub use crate::grid_tree::GridFenwickTree;
+    pub use crate::growing_tree::{GrowingFenwickTree, Growth, ResizeStats};
+    pub use crate::hybrid_tree::HybridFenwickTree;
+    pub use crate::idx::{Idx, IndexedTree};
+    pub use crate::import::{ImportError, ImportOptions};
+    pub use crate::ingest::{IngestHandle, IngestSender};
+    pub use crate::interval_cover::IntervalCover;
+    pub use crate::inventory_tree::InventoryTree;
+    pub use crate::iterators::PrefixCursor;
+    pub use crate::mirrored_tree::MirroredFenwickTree;
+    pub use crate::nd_tree::FenwickTreeNd;
+    #[cfg(feature = "money")]
+    pub use crate::money::Money;
+    #[cfg(feature = "hyperloglog")]
+    pub use crate::hyperloglog::HyperLogLog;
+    pub use crate::multi_resolution_tree::MultiResolutionTree;
+    pub use crate::order_book_depth::OrderBookDepth;
+    pub use crate::order_stat_tree::OrderStatTree;
+    pub use crate::ordered_sparse_tree::OrderedSparseFenwickTree;
+    pub use crate::range_agg_tree::RangeAggTree;
+    pub use crate::range_update_range_query_tree::RangeUpdateRangeQueryFenwickTree;
+    pub use crate::range_update_tree::RangeUpdateFenwickTree;
+    #[cfg(all(feature = "shared-memory", unix))]
+    pub use crate::shared_memory_tree::{SharedMemoryFenwickTree, SharedMemoryReader};
+    pub use crate::snapshot_tree::{SnapshotFenwickTree, SnapshotValue};
+    pub use crate::stamped_tree::StampedFenwickTree;
+    pub use crate::sync_tree::SyncFenwickTree;
+    #[cfg(feature = "tdigest")]
+    pub use crate::tdigest::TDigest;
+    pub use crate::values::{
+        AllTrue, AnyTrue, BitsetValue, DurationValue, Gcd, Histogram, Max, ModInt, NonNegative,
+        Tuple, XorValue,
+    };
+    pub use crate::versioned_tree::{DeltaSet, VersionedFenwickTree};
+    pub use crate::vwap_tracker::VwapTracker;
+    pub use crate::{FenwickReader, FenwickTree, FenwickWriter};
+    pub use crate::{DuplicatePolicy, OutOfBoundsPolicy, TreeError};
 }
 
-fn least_significant_bit(idx: usize) -> usize {
-    let int_idx = idx as i32;
-    (int_idx & -int_idx) as usize
+/// Count stored in a node of a counting tree. Counts which don't fit [`usize`], such
+/// as negative ones, are considered 0.
+fn count_of<T: Clone + TryInto<usize>>(node: &T) -> usize {
+    node.clone().try_into().unwrap_or(0)
 }
 
-/// Types that implement that trait can be stored and aggregated within Fenwick tree.
-pub trait FenwickTreeValue:
-    Default + Clone //
-    + core::cmp::PartialEq 
+/// Same as [`add_to_nodes`], without taking `value`, so no node absorbs it.
+fn store_to_nodes<T: FenwickTreeValue>(data: &mut [T], mut position: usize, value: &T) {
+    while position < data.len() {
+        data[position].store_value(value);
+        position += least_significant_bit(position);
+    }
+}
+
+/// Adds `value` to tree data nodes from `position` upwards, as an update of index
+/// `position - 1` does. The last node absorbs `value` itself, saving a copy.
+fn add_to_nodes<T: FenwickTreeValue>(data: &mut [T], mut position: usize, value: T) {
+    let size = data.len() - 1;
+    loop {
+        let next = position + least_significant_bit(position);
+        if next > size {
+            data[position].absorb(value);
+            return;
+        }
+        data[position].store_value(&value);
+        position = next;
+    }
+}
+
+/// Builds tree data holding `f(i)` at every index in `O(n)`, by pushing every node
+/// into its parent once instead of updating each point separately.
+fn data_from_fn<T: FenwickTreeValue>(len: usize, mut f: impl FnMut(usize) -> T) -> Vec<T> {
+    let mut data = Vec::with_capacity(len + 1);
+    data.push(T::default());
+    data.extend((0..len).map(&mut f));
+    points_into_data(&mut data);
+    data
+}
+
+/// Allocates `len` default values, failing with [`TreeError::AllocationFailed`] instead
+/// of aborting when memory runs out.
+fn try_vec_of_defaults<T: Default + Clone>(len: usize) -> Result<Vec<T>, TreeError> {
+    let mut data = Vec::new();
+    data.try_reserve_exact(len)
+        .map_err(|_| TreeError::AllocationFailed)?;
+    data.resize(len, T::default());
+    Ok(data)
+}
+
+/// Builds tree data of `len` indexes holding `points`, in `O(n + k)`. Values sharing
+/// an index are summed up. Fails if `len` indexes and the unused node can't be
+/// counted in [`usize`] or allocated.
+fn data_from_points<T: FenwickTreeValue>(
+    len: usize,
+    points: impl IntoIterator<Item = (usize, T)>,
+) -> Result<Vec<T>, TreeError> {
+    let mut data: Vec<T> = try_vec_of_defaults(len.checked_add(1).ok_or(TreeError::Overflow)?)?;
+    for (idx, value) in points {
+        data[idx + 1].absorb(value);
+    }
+    points_into_data(&mut data);
+    Ok(data)
+}
+
+/// Builds tree data holding `pairs`, sized to the largest index, in `O(n + k)`.
+fn data_from_pairs<T: FenwickTreeValue>(
+    pairs: Vec<(usize, T)>,
+    policy: DuplicatePolicy,
+) -> Result<Vec<T>, TreeError> {
+    let len = len_for_keys(pairs.iter().map(|(idx, _)| idx))?;
+    let mut data: Vec<T> = try_vec_of_defaults(len.checked_add(1).ok_or(TreeError::Overflow)?)?;
+    let mut seen = try_vec_of_defaults(if policy == DuplicatePolicy::Error { len } else { 0 })?;
+
+    for (idx, value) in pairs {
+        match policy {
+            DuplicatePolicy::Sum => data[idx + 1].absorb(value),
+            DuplicatePolicy::Overwrite => data[idx + 1] = value,
+            DuplicatePolicy::Error => {
+                if seen[idx] {
+                    return Err(TreeError::DuplicateIndex { index: idx });
+                }
+                seen[idx] = true;
+                data[idx + 1] = value;
+            }
+        }
+    }
+
+    points_into_data(&mut data);
+    Ok(data)
+}
+
+/// Overwrites every point of tree data with `value`, in `O(n)`.
+fn fill_data<T: FenwickTreeValue>(data: &mut [T], value: &T) {
+    for node in data.iter_mut().skip(1) {
+        node.clone_from(value);
+    }
+    points_into_data(data);
+}
+
+/// Number of indexes needed to hold all `keys`. Fails for [`usize::MAX`] key, as that
+/// many indexes can't be counted in [`usize`].
+fn len_for_keys<'a>(keys: impl Iterator<Item = &'a usize>) -> Result<usize, TreeError> {
+    keys.max()
+        .map_or(Ok(0), |key| key.checked_add(1).ok_or(TreeError::Overflow))
+}
+
+/// Fills nodes from `from` position onwards, which hold no values of their own, with
+/// aggregates of the nodes they cover. Those are always located to the left, thus
+/// filling nodes in ascending order is enough.
+fn fill_new_nodes<T: FenwickTreeValue>(data: &mut [T], from: usize) {
+    for position in from..data.len() {
+        let covered_from = position - least_significant_bit(position);
+        let mut child = position - 1;
+        let (children, nodes) = data.split_at_mut(position);
+        while child > covered_from {
+            nodes[0].store_value(&children[child]);
+            child -= least_significant_bit(child);
+        }
+    }
+}
+
+/// Turns raw internal representation holding plain points (node 0 unused) into tree
+/// data in place, in `O(n)`.
+fn points_into_data<T: FenwickTreeValue>(data: &mut [T]) {
+    let len = data.len() - 1;
+    for position in 1..=len {
+        let parent = position + least_significant_bit(position);
+        if parent <= len {
+            let (children, parents) = data.split_at_mut(parent);
+            parents[0].store_value(&children[position]);
+        }
+    }
+}
+
+/// Reverse of [`points_into_data`]: restores plain points from tree data in place,
+/// in `O(n)`, by subtracting every node from its parent.
+fn data_into_points<T: InvertibleValue>(data: &mut [T]) {
+    let len = data.len() - 1;
+    for position in (1..=len).rev() {
+        let parent = position + least_significant_bit(position);
+        if parent <= len {
+            let node = data[position].clone();
+            data[parent] = data[parent].clone().substract(node);
+        }
+    }
+}
+
+/// Plain points of tree data, without the unused node 0, in `O(n)`.
+fn points_of<T: InvertibleValue>(data: &[T]) -> Vec<T> {
+    let mut points = data.to_vec();
+    data_into_points(&mut points);
+    points.remove(0);
+    points
+}
+
+/// Points of tree data paired with indexes `f` moves them to, in `O(n)`.
+fn remapped_points<T: InvertibleValue>(
+    data: &[T],
+    bound: usize,
+    mut f: impl FnMut(usize) -> usize,
+) -> Result<Vec<(usize, T)>, TreeError> {
+    points_of(data)
+        .into_iter()
+        .enumerate()
+        .map(|(idx, value)| match f(idx) {
+            new_idx if new_idx < bound => Ok((new_idx, value)),
+            new_idx => Err(TreeError::IndexOutOfBounds {
+                index: new_idx,
+                bound,
+            }),
+        })
+        .collect()
+}
+
+/// Checks that `perm` holds a new index for every one of `len` indexes.
+fn check_permutation(perm: &[usize], len: usize) -> Result<(), TreeError> {
+    if perm.len() != len {
+        return Err(TreeError::IndexOutOfBounds {
+            index: perm.len(),
+            bound: len,
+        });
+    }
+    Ok(())
+}
+
+/// Places points of `other` tree data right after points of `data`, in `O(n + m)`.
+/// Fails leaving `data` untouched if memory for them can't be allocated.
+fn append_data<T: InvertibleValue>(data: &mut Vec<T>, mut other: Vec<T>) -> Result<(), TreeError> {
+    data.try_reserve_exact(other.len() - 1)
+        .map_err(|_| TreeError::AllocationFailed)?;
+    data_into_points(data);
+    data_into_points(&mut other);
+    data.extend(other.into_iter().skip(1));
+    points_into_data(data);
+    Ok(())
+}
+
+/// Sum of values stored between `from` and `to` indexes (including edges).
+fn bucket_sum<F>(tree: &F, from: usize, to: usize) -> Result<F::Value, TreeError>
+where
+    F: FenwickReader,
+    F::Value: InvertibleValue,
 {
+    let to_sum = tree.query(to)?;
+    if from == 0 {
+        return Ok(to_sum);
+    }
+    let from_sum = tree.query(from - 1)?;
+    Ok(to_sum.substract(from_sum))
+}
+
+/// Types that implement that trait can be stored and aggregated within Fenwick tree.
+/// Aggregation is not required to be invertible, so such trees only answer prefix 
+/// queries. See [`InvertibleValue`] for values supporting range queries.
+pub trait FenwickTreeValue: Default + Clone {
+    /// Combines `other` into `self`. Called once per node visited by an update, so
+    /// heap-backed values should combine by reference (e.g. with `AddAssign<&Self>`)
+    /// rather than cloning `other`.
     fn store_value(&mut self, other: &Self);
+
+    /// Same as [`FenwickTreeValue::store_value`], but takes `other` by value, so heavy
+    /// values (big integers, sketches, histograms) can reuse its allocations instead
+    /// of copying them. Trees hand values they own over with it, e.g. to the last node
+    /// touched by an update.
+    fn absorb(&mut self, other: Self) {
+        self.store_value(&other)
+    }
+
+    /// Returns `true` if storing that value doesn't change an aggregate, so trees can
+    /// skip such updates altogether and leave such nodes out of exports. Default
+    /// implementation returns `false`, so nothing is skipped. Values of this crate
+    /// compare with [`Default::default`].
+    fn is_identity(&self) -> bool {
+        false
+    }
+
+    /// Whether values which aren't identity can aggregate into identity, like `5` and
+    /// `-5`. Sparse iteration only skips ranges with identity aggregates when they
+    /// can't, so the default is `true`.
+    const CANCELS: bool = true;
+
+    /// Returns `false` for values trees reject with [`TreeError::InvalidValue`], like
+    /// NaN floats, which would turn every sum covering them into NaN for good.
+    fn is_valid(&self) -> bool {
+        true
+    }
+}
+
+/// Values which aggregation can be reverted. Range queries and range updates are only
+/// available for trees storing such values.
+///
+/// Monoid-only aggregates (gcd, flags, bitset union, sketches) don't implement it, so
+/// subtracting their prefixes into meaningless ranges is rejected at compile time:
+///
+/// ```compile_fail
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let tree = FixedSizeFenwickTree::<BitsetValue<1>>::new(4);
+/// tree.range_query(0, 1).unwrap();
+/// ```
+pub trait InvertibleValue: FenwickTreeValue {
     fn substract(self, other: Self) -> Self;
 }
 
+/// Every `Copy` type which aggregates with `+=` and inverts with `-` is a value, that
+/// includes primitive numeric types, [`std::num::Wrapping`] integers, numeric newtypes
+/// and `glam` vectors. Values equal to [`Default::default`] are identities.
+///
+/// ```rust
+/// use fenwick_bit_tree::prelude::*;
+///
+/// #[derive(Debug, Default, Clone, Copy, PartialEq)]
+/// struct Meters(f64);
+///
+/// impl std::ops::AddAssign for Meters {
+///     fn add_assign(&mut self, other: Self) {
+///         self.0 += other.0;
+///     }
+/// }
+///
+/// impl std::ops::Sub for Meters {
+///     type Output = Self;
+///
+///     fn sub(self, other: Self) -> Self {
+///         Meters(self.0 - other.0)
+///     }
+/// }
+///
+/// let mut tree = FixedSizeFenwickTree::<Meters>::new(4);
+/// tree.update(1, Meters(2.5)).unwrap();
+/// tree.update(3, Meters(1.0)).unwrap();
+/// assert_eq!(tree.range_query(1, 3).unwrap(), Meters(1.0));
+/// assert_eq!(tree.update(2, Meters(f64::NAN)), Err(TreeError::InvalidValue));
+/// ```
+///
+/// Floats follow IEEE arithmetic, except that NaN updates are rejected with
+/// [`TreeError::InvalidValue`]. Infinite values are stored, but can't be removed by
+/// later updates, and range queries between two infinite sums of the same sign give
+/// NaN.
 impl<T> FenwickTreeValue for T 
 where T: Default + Copy //
     + std::ops::AddAssign
@@ -112,13 +653,30 @@ where T: Default + Copy //
         *self += *other
     }
 
+    fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+
+    // Only values holding NaN aren't equal to themselves.
+    #[allow(clippy::eq_op)]
+    fn is_valid(&self) -> bool {
+        self == self
+    }
+}
+
+impl<T> InvertibleValue for T 
+where T: Default + Copy //
+    + std::ops::AddAssign
+    + std::ops::Sub<Output = Self>
+    + core::cmp::PartialEq 
+{
     fn substract(self, other: Self) -> Self {
         self - other
     }
 }
 
-/// Fenwick tree trait, API of that data structure
-pub trait FenwickTree {
+/// Read half of Fenwick tree API, see [`FenwickTree`].
+pub trait FenwickReader {
     type Value: FenwickTreeValue;
 
     /// Returns sum of values across all indexes lesser or equal than `idx`.
@@ -129,274 +687,634 @@ pub trait FenwickTree {
     /// GrowingFenwick tree implementation never returns error.
     /// 
     fn query(&self, idx: usize) -> Result<Self::Value, TreeError>;
-    
-    /// Add new value to the `idx` stored value, which is 0 by default. 
-    ///
-    /// # Errors
-    ///
-    /// This function will return an error if idx is out of bounds.
-    /// GrowingFenwick tree implementation never returns error.
-    /// 
-    fn update(&mut self, idx: usize, value: Self::Value) -> Result<(), TreeError>;
 
-    /// Returns sum of values across all indexes in between `from` and `to` indexes 
-    /// (including edges).
+    /// Returns sum of values across all indexes after `from` up to `to` (including),
+    /// which is `query(to)` without `query(from)`. Equal indexes make an empty range.
     ///
     /// # Errors
     ///
-    /// This function will return an error if any index is out of bounds.
-    /// GrowingFenwick tree implementation never return error.
+    /// This function will return an error if `from` is greater than `to` or any index
+    /// is out of bounds. GrowingFenwick tree implementation only returns error for
+    /// reversed ranges.
     /// 
-    fn range_query(&self, from: usize, to: usize) -> Result<Self::Value, TreeError> {
+    fn range_query(&self, from: usize, to: usize) -> Result<Self::Value, TreeError>
+    where
+        Self::Value: InvertibleValue,
+    {
+        if from > to {
+            return Err(TreeError::InvalidRange { from, to });
+        }
         let from_sum = self.query(from)?;
         let to_sum = self.query(to)?;
         Ok(to_sum.substract(from_sum))
     }
-}
 
-/// For the sake of clarity Tree supports 2 types of indexing. [`TreeIndex::External`] is meant to be used 
-/// by library consumer. While [`TreeIndex::Internal`] is used for purposes to make tree reindexing code more
-/// understable and maintainable. [`usize`] can be automatically converted using `into()` into the [`TreeIndex::External`]
-#[derive(Debug, Clone, Copy)]
-enum TreeIndex {
-    Internal { val: usize },
-    External { val: usize },
-}
+    /// Returns the first index which prefix sum is greater or equal than `target`, or
+    /// [`None`] if no prefix sum reaches it. Values are expected to be non-negative.
+    ///
+    /// Trees of this crate descend their nodes in `O(log n)`, see
+    /// [`FixedSizeFenwickTree::lower_bound`]. Default implementation runs exponential
+    /// and then binary search over queries, in `O(log^2 n)`, indexes which queries
+    /// reject are considered to lie beyond the tree.
+    fn find_prefix(&self, target: Self::Value) -> Option<usize>
+    where
+        Self::Value: PartialOrd,
+    {
+        let reached = |idx: usize| self.query(idx).map_or(true, |sum| sum >= target);
 
-#[derive(Debug, PartialEq)]
-pub enum TreeError {
-    IndexOutOfBounds( usize )
+        let (mut lower, mut upper) = (0, 0);
+        while !reached(upper) {
+            if upper == usize::MAX {
+                return None;
+            }
+            lower = upper + 1;
+            upper = upper.saturating_mul(2).saturating_add(1);
+        }
+        while lower < upper {
+            let middle = lower + (upper - lower) / 2;
+            match reached(middle) {
+                true => upper = middle,
+                false => lower = middle + 1,
+            }
+        }
+
+        self.query(lower)
+            .is_ok_and(|sum| sum >= target)
+            .then_some(lower)
+    }
 }
 
-impl TreeIndex {
+/// Write half of Fenwick tree API, see [`FenwickTree`].
+pub trait FenwickWriter: FenwickReader {
+    /// Add new value to the `idx` stored value, which is 0 by default. 
+    /// Updates with identity values (see [`FenwickTreeValue::is_identity`]) are skipped.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if idx is out of bounds, or value is rejected
+    /// by [`FenwickTreeValue::is_valid`]. GrowingFenwick tree implementation only
+    /// returns error for indexes beyond configured max size and rejected values.
+    ///
+    /// Trees of this crate combine `value` into every visited node by reference with
+    /// [`FenwickTreeValue::store_value`], except the last one, which takes `value`
+    /// over with [`FenwickTreeValue::absorb`]. So `value` itself is never cloned, and
+    /// heap-backed values only allocate if their `store_value` does.
+    /// 
+    fn update(&mut self, idx: usize, value: Self::Value) -> Result<(), TreeError>;
 
-    fn to_internal(self) -> Self {
-        match self {
-            TreeIndex::Internal { val: _ } => self,
-            TreeIndex::External { val } => TreeIndex::Internal { val: val + 1 },
-        }
+    /// Same as [`FenwickWriter::update`], converting `value` first, so for example
+    /// [`u32`] values are added to an [`i64`] tree without casts.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if idx is out of bounds.
+    ///
+    fn update_from(&mut self, idx: usize, value: impl Into<Self::Value>) -> Result<(), TreeError>
+    where
+        Self: Sized,
+    {
+        self.update(idx, value.into())
     }
 
-    fn to_external(self) -> Result<Self, String> {
-        match self {
-            TreeIndex::Internal { val } => {
-                if val == 0 {
-                    return Err("Index is out of bounds.".to_string());
-                }
-                Ok(TreeIndex::External { val: val - 1 })
-            }
-            TreeIndex::External { val: _ } => Ok(self),
-        }
+    /// Same as [`FenwickWriter::update`], taking `value` by reference. Default
+    /// implementation clones `value`, trees of this crate combine it into nodes by
+    /// reference without cloning it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if idx is out of bounds.
+    ///
+    fn update_ref(&mut self, idx: usize, value: &Self::Value) -> Result<(), TreeError> {
+        self.update(idx, value.clone())
     }
 
-    /// Starts with the initial value and then moves down to zero returning result of
-    /// deduction of the least significant bit
-    fn lsb_descending(self) -> LeastSignificantBitDescentingChain {
-        LeastSignificantBitDescentingChain {
-            idx: self.to_internal(),
-        }
+    /// Returns index update of `idx` lands at under out of bounds policy of the tree,
+    /// or [`None`] if the update is dropped. Default implementation returns `idx`, as
+    /// trees without a policy never move updates.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the policy rejects updates of `idx`.
+    ///
+    fn update_target(&self, idx: usize) -> Result<Option<usize>, TreeError> {
+        Ok(Some(idx))
     }
 
-    /// Starts with the initial value and then moves up until upper bound is reached 
-    /// returning the result of deduction of the least significant bit
-    fn lsb_ascending(self, upper_bound: usize) -> LeastSignificantBitAscendingChain {
-        LeastSignificantBitAscendingChain {
-            idx: self.to_internal(),
-            max: upper_bound,
+    /// Applies `(idx, value)` pairs one by one, reporting progress and checking for
+    /// cancellation as configured with `options`. Returns number of pairs applied,
+    /// which is less than number of `items` if import was cancelled.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any update fails. Updates applied before
+    /// the failing one are kept, their number is reported in [`ImportError::applied`].
+    ///
+    fn import<I>(&mut self, items: I, options: ImportOptions) -> Result<usize, ImportError>
+    where
+        Self: Sized,
+        I: IntoIterator<Item = (usize, Self::Value)>,
+    {
+        import::import(self, items, options)
+    }
+
+    /// Adds `value` to every index after `from` up to `to` (including), which are the
+    /// indexes [`FenwickReader::range_query`] sums, so equal indexes make an empty
+    /// range. Default implementation updates indexes one by one in ascending order,
+    /// cloning `value` for every index but the last one.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `from` is greater than `to` or any update
+    /// fails. Default implementation keeps updates applied before the failing one, so
+    /// the range is left half-applied if `to` is out of bounds, if [`InventoryTree`]
+    /// rejects an update with [`TreeError::NegativePrefix`] or if a growing tree
+    /// reaches its maximum size.
+    ///
+    fn range_update(&mut self, from: usize, to: usize, value: Self::Value) -> Result<(), TreeError>
+    where
+        Self::Value: InvertibleValue,
+    {
+        if from > to {
+            return Err(TreeError::InvalidRange { from, to });
         }
+        if from == to {
+            return Ok(());
+        }
+        for idx in from + 1..to {
+            self.update(idx, value.clone())?;
+        }
+        self.update(to, value)
     }
+}
+
+/// Fenwick tree trait, API of that data structure. Implemented for every type which
+/// is both [`FenwickReader`] and [`FenwickWriter`], so APIs only querying a tree can
+/// accept read-only views by asking for the reader half.
+pub trait FenwickTree: FenwickReader + FenwickWriter {}
+
+impl<F: FenwickReader + FenwickWriter + ?Sized> FenwickTree for F {}
 
-    fn is_power_of_2(self) -> bool {
-        let idx = *self;
-        idx.is_power_of_two()
+impl<F: FenwickReader + ?Sized> FenwickReader for &F {
+    type Value = F::Value;
+
+    fn query(&self, idx: usize) -> Result<Self::Value, TreeError> {
+        (**self).query(idx)
+    }
+
+    fn range_query(&self, from: usize, to: usize) -> Result<Self::Value, TreeError>
+    where
+        Self::Value: InvertibleValue,
+    {
+        (**self).range_query(from, to)
     }
 
+    fn find_prefix(&self, target: Self::Value) -> Option<usize>
+    where
+        Self::Value: PartialOrd,
+    {
+        (**self).find_prefix(target)
+    }
 }
 
-impl From<usize> for TreeIndex {
-    fn from(value: usize) -> Self {
-        Self::External { val: value }
+impl<F: FenwickReader + ?Sized> FenwickReader for &mut F {
+    type Value = F::Value;
+
+    fn query(&self, idx: usize) -> Result<Self::Value, TreeError> {
+        (**self).query(idx)
+    }
+
+    fn range_query(&self, from: usize, to: usize) -> Result<Self::Value, TreeError>
+    where
+        Self::Value: InvertibleValue,
+    {
+        (**self).range_query(from, to)
+    }
+
+    fn find_prefix(&self, target: Self::Value) -> Option<usize>
+    where
+        Self::Value: PartialOrd,
+    {
+        (**self).find_prefix(target)
     }
 }
 
-impl Deref for TreeIndex {
-    type Target = usize;
+impl<F: FenwickWriter + ?Sized> FenwickWriter for &mut F {
+    fn update(&mut self, idx: usize, value: Self::Value) -> Result<(), TreeError> {
+        (**self).update(idx, value)
+    }
 
-    fn deref(&self) -> &Self::Target {
-        match self {
-            TreeIndex::External { val } => val,
-            TreeIndex::Internal { val } => val,
-        }
+    fn update_ref(&mut self, idx: usize, value: &Self::Value) -> Result<(), TreeError> {
+        (**self).update_ref(idx, value)
+    }
+
+    fn update_target(&self, idx: usize) -> Result<Option<usize>, TreeError> {
+        (**self).update_target(idx)
+    }
+
+    fn range_update(&mut self, from: usize, to: usize, value: Self::Value) -> Result<(), TreeError>
+    where
+        Self::Value: InvertibleValue,
+    {
+        (**self).range_update(from, to, value)
     }
 }
 
-impl PartialEq for TreeIndex {
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Self::Internal { val: l_val }, Self::Internal { val: r_val }) => l_val == r_val,
-            (Self::External { val: l_val }, Self::External { val: r_val }) => l_val == r_val,
-            _ => false,
-        }
+impl<F: FenwickReader + ?Sized> FenwickReader for Box<F> {
+    type Value = F::Value;
+
+    fn query(&self, idx: usize) -> Result<Self::Value, TreeError> {
+        (**self).query(idx)
+    }
+
+    fn range_query(&self, from: usize, to: usize) -> Result<Self::Value, TreeError>
+    where
+        Self::Value: InvertibleValue,
+    {
+        (**self).range_query(from, to)
+    }
+
+    fn find_prefix(&self, target: Self::Value) -> Option<usize>
+    where
+        Self::Value: PartialOrd,
+    {
+        (**self).find_prefix(target)
     }
 }
 
-impl DerefMut for TreeIndex {
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        match self {
-            TreeIndex::External { val } => val,
-            TreeIndex::Internal { val } => val,
-        }
+impl<F: FenwickWriter + ?Sized> FenwickWriter for Box<F> {
+    fn update(&mut self, idx: usize, value: Self::Value) -> Result<(), TreeError> {
+        (**self).update(idx, value)
     }
+
+    fn update_ref(&mut self, idx: usize, value: &Self::Value) -> Result<(), TreeError> {
+        (**self).update_ref(idx, value)
+    }
+
+    fn update_target(&self, idx: usize) -> Result<Option<usize>, TreeError> {
+        (**self).update_target(idx)
+    }
+
+    fn range_update(&mut self, from: usize, to: usize, value: Self::Value) -> Result<(), TreeError>
+    where
+        Self::Value: InvertibleValue,
+    {
+        (**self).range_update(from, to, value)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+#[non_exhaustive]
+pub enum TreeError {
+    /// `index` lies beyond `bound`, which is the size of the tree (or the origin for
+    /// time based helpers).
+    IndexOutOfBounds { index: usize, bound: usize },
+    /// Range starts after it ends.
+    InvalidRange { from: usize, to: usize },
+    /// Index or value arithmetic doesn't fit into its type.
+    Overflow,
+    /// Memory for growing the tree couldn't be allocated.
+    AllocationFailed,
+    /// Serialized tree can't be restored.
+    CorruptSnapshot,
+    /// Prefix search was requested from a tree where negative value was stored.
+    NegativeValue,
+    /// `index` appears more than once in points passed to a tree.
+    DuplicateIndex { index: usize },
+    /// Update was rejected, as prefix sum at `index` would become negative.
+    NegativePrefix { index: usize },
+    /// Changes of a versioned tree were requested or applied at version `found`, while
+    /// `expected` is the closest version accepted.
+    VersionMismatch { expected: u64, found: u64 },
+    /// Shared memory segment couldn't be created, opened or mapped, `errno` is the
+    /// OS error code.
+    SharedMemory { errno: i32 },
+    /// Background thread owning the tree has stopped.
+    Disconnected,
+    /// Observation lies outside of bucket boundaries, or can't be compared to them.
+    OutOfRange,
+    /// Value is rejected by [`FenwickTreeValue::is_valid`], like NaN floats.
+    InvalidValue,
+    /// Reading or writing serialized tree failed with I/O error of `kind`.
+    Io { kind: std::io::ErrorKind },
 }
 
-/// Iterator that implements changing value by deduction of the least significant bit and 
-/// returning result
-struct LeastSignificantBitDescentingChain {
-    idx: TreeIndex,
+/// Defines how points sharing an index are merged when a tree is created from a list
+/// of `(index, value)` pairs.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Values sharing an index are summed up.
+    Sum,
+    /// The last value for an index wins.
+    Overwrite,
+    /// Repeated index is rejected with [`TreeError::DuplicateIndex`].
+    #[default]
+    Error,
 }
 
-impl Iterator for LeastSignificantBitDescentingChain {
-    type Item = TreeIndex;
+/// Defines what happens to queries and updates of indexes beyond the rightmost one.
+/// Configured with `with_out_of_bounds` of both trees or with
+/// [`FenwickTreeBuilder::out_of_bounds`], so swapping tree implementations doesn't
+/// change the behavior silently.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutOfBoundsPolicy {
+    /// Both queries and updates return [`TreeError::IndexOutOfBounds`]. Default for
+    /// [`FixedSizeFenwickTree`].
+    Error,
+    /// Queries return the total sum and updates are applied to the rightmost index.
+    /// Updates of an empty tree return [`TreeError::IndexOutOfBounds`].
+    ClampToLast,
+    /// Queries return default value and updates are dropped.
+    TreatAsZero,
+    /// Queries return the total sum and updates grow the tree to hold the index.
+    /// Default for [`GrowingFenwickTree`].
+    Grow,
+}
 
-    fn next(&mut self) -> Option<Self::Item> {
-        if *self.idx == 0 {
-            return None;
+impl OutOfBoundsPolicy {
+    /// Index out of bounds query of `idx` is redirected to, or [`None`] if default value
+    /// is returned. `len` is the number of indexes tree holds.
+    fn query_index(self, idx: usize, len: usize) -> Result<Option<usize>, TreeError> {
+        match self {
+            OutOfBoundsPolicy::Error => Err(TreeError::IndexOutOfBounds {
+                index: idx,
+                bound: len,
+            }),
+            OutOfBoundsPolicy::ClampToLast | OutOfBoundsPolicy::Grow => Ok(len.checked_sub(1)),
+            OutOfBoundsPolicy::TreatAsZero => Ok(None),
         }
-        // TODO: implement COpy?
-        let res = TreeIndex::Internal { val: *self.idx };
-        *self.idx -= least_significant_bit(*self.idx);
-        Some(res)
     }
-}
 
-/// Iterator that implements changing value by addition of the least significant bit and 
-/// returning result
-struct LeastSignificantBitAscendingChain {
-    idx: TreeIndex,
-    max: usize,
+    /// Index out of bounds update of `idx` is redirected to, or [`None`] if update is
+    /// dropped. Growing is left to the caller.
+    fn update_index(self, idx: usize, len: usize) -> Result<Option<usize>, TreeError> {
+        let out_of_bounds = TreeError::IndexOutOfBounds {
+            index: idx,
+            bound: len,
+        };
+        match self {
+            OutOfBoundsPolicy::Error => Err(out_of_bounds),
+            OutOfBoundsPolicy::ClampToLast => len.checked_sub(1).ok_or(out_of_bounds).map(Some),
+            OutOfBoundsPolicy::TreatAsZero => Ok(None),
+            OutOfBoundsPolicy::Grow => Ok(Some(idx)),
+        }
+    }
 }
 
-impl Iterator for LeastSignificantBitAscendingChain {
-    type Item = TreeIndex;
-
-    fn next(&mut self) -> Option<Self::Item> {
-        if *self.idx > self.max {
-            return None;
+impl std::fmt::Display for TreeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TreeError::IndexOutOfBounds { index, bound } => {
+                write!(f, "index {index} is out of bounds {bound}")
+            }
+            TreeError::InvalidRange { from, to } => {
+                write!(f, "range start {from} is greater than range end {to}")
+            }
+            TreeError::Overflow => write!(f, "arithmetic overflow"),
+            TreeError::AllocationFailed => write!(f, "tree allocation failed"),
+            TreeError::CorruptSnapshot => write!(f, "corrupt tree snapshot"),
+            TreeError::NegativeValue => write!(f, "negative value stored in the tree"),
+            TreeError::DuplicateIndex { index } => write!(f, "index {index} is repeated"),
+            TreeError::NegativePrefix { index } => {
+                write!(f, "prefix sum at index {index} would become negative")
+            }
+            TreeError::VersionMismatch { expected, found } => {
+                write!(f, "version {found} doesn't match expected version {expected}")
+            }
+            TreeError::SharedMemory { errno } => {
+                write!(f, "shared memory operation failed with errno {errno}")
+            }
+            TreeError::Disconnected => write!(f, "tree owner thread has stopped"),
+            TreeError::OutOfRange => write!(f, "observation is out of bucket boundaries"),
+            TreeError::InvalidValue => write!(f, "value can't be stored in the tree"),
+            TreeError::Io { kind } => write!(f, "tree I/O failed: {kind}"),
         }
-        // TODO: implement COpy?
-        let res = TreeIndex::Internal { val: *self.idx };
-        *self.idx += least_significant_bit(*self.idx);
-        Some(res)
     }
 }
 
+impl std::error::Error for TreeError {}
+
 #[cfg(test)]
 mod tests {
 
     use pretty_assertions::assert_eq;
 
-    use crate::{least_significant_bit, TreeIndex};
+    use crate::{
+        least_significant_bit, FenwickReader, FenwickTree, FenwickWriter, FixedSizeFenwickTree,
+        GrowingFenwickTree, SyncFenwickTree, TreeError,
+    };
 
-    fn to_internal_index_vec(indexes: &[usize]) -> Vec<TreeIndex> {
-        indexes
-            .into_iter()
-            .map(|i| TreeIndex::Internal { val: *i })
-            .collect::<Vec<TreeIndex>>()
+    fn fill(mut tree: impl FenwickTree<Value = i32>) -> i32 {
+        for i in 0..8 {
+            tree.update(i, 1).unwrap();
+        }
+        tree.range_query(0, 7).unwrap()
     }
 
     #[test]
-    fn test_index_transform_from_internal_to_external_with_error() {
-        let idx = TreeIndex::Internal { val: 0 };
-        idx.to_external().expect_err("Index is out of bounds.");
+    fn test_delegation_to_borrowed_and_boxed_trees() {
+        let mut tree = FixedSizeFenwickTree::<i32>::new(8);
+        assert_eq!(fill(&mut tree), 7);
+        assert_eq!(tree.query(7).unwrap(), 8);
+
+        let boxed: Box<dyn FenwickTree<Value = i32>> = Box::new(GrowingFenwickTree::new(0));
+        assert_eq!(fill(boxed), 7);
+        assert_eq!(fill(Box::new(&mut tree)), 14);
+    }
+
+    fn total(tree: impl FenwickReader<Value = i32>) -> i32 {
+        tree.query(7).unwrap()
     }
 
     #[test]
-    fn test_index_transform_from_internal_to_external() {
-        for val in 1..100 {
-            let idx = TreeIndex::Internal { val: val };
-            assert_eq!(
-                idx.to_external().unwrap(),
-                TreeIndex::External { val: val - 1 }
-            );
-        }
+    fn test_read_only_views() {
+        let mut tree = FixedSizeFenwickTree::<i32>::new(8);
+        tree.update(3, 2).unwrap();
+
+        assert_eq!(total(&tree), 2);
+        assert_eq!(total(SyncFenwickTree::new(tree)), 2);
     }
 
     #[test]
-    fn test_index_transform_from_external_to_internal() {
-        for val in 0..100 {
-            let idx = TreeIndex::External { val: val };
-            assert_eq!(idx.to_internal(), TreeIndex::Internal { val: val + 1 });
-        }
+    fn test_range_update() {
+        let mut tree = FixedSizeFenwickTree::<i32>::new(8);
+        tree.range_update(2, 5, 3).unwrap();
+        assert_eq!(tree.query(2).unwrap(), 0);
+        assert_eq!(tree.query(5).unwrap(), 9);
+        assert_eq!(tree.range_query(2, 5).unwrap(), 9);
+        tree.range_update(4, 4, 1).unwrap();
+        assert_eq!(tree.query(7).unwrap(), 9);
+
+        assert_eq!(
+            tree.range_update(5, 2, 1),
+            Err(TreeError::InvalidRange { from: 5, to: 2 })
+        );
+        // Index 7 is updated before 8 is rejected.
+        assert!(tree.range_update(6, 8, 1).is_err());
+        assert_eq!(tree.query(7).unwrap(), 10);
+
+        let mut tree = GrowingFenwickTree::<i32>::new(0);
+        tree.range_update(10, 19, 1).unwrap();
+        assert_eq!(tree.query(100).unwrap(), 9);
     }
 
     #[test]
-    fn test_index_transform_to_itseld() {
-        for val in 0..100 {
-            let idx = TreeIndex::External { val: val };
-            assert_eq!(idx.to_external().unwrap(), TreeIndex::External { val });
+    fn test_update_from_and_ref() {
+        let mut fixed = FixedSizeFenwickTree::<i64>::new(8);
+        fixed.update_from(2, 3u32).unwrap();
+        fixed.update_from(5, -4i8).unwrap();
+        fixed.update_ref(5, &10).unwrap();
+        assert_eq!(fixed.query(4).unwrap(), 3);
+        assert_eq!(fixed.query(7).unwrap(), 9);
+        assert!(fixed.update_ref(8, &1).is_err());
+
+        let mut growing = GrowingFenwickTree::<i64>::new(0);
+        growing.update_ref(20, &2).unwrap();
+        growing.update_from(3, true).unwrap();
+        assert_eq!(growing.query(100).unwrap(), 3);
+
+        let mut boxed: Box<dyn FenwickTree<Value = i64>> = Box::new(growing);
+        boxed.update_ref(1, &4).unwrap();
+        assert_eq!(boxed.query(100).unwrap(), 7);
+    }
+
+    #[test]
+    fn test_range_update_moves_value() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use crate::{FenwickTreeValue, InvertibleValue};
+
+        static CLONES: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Debug, Default, PartialEq)]
+        struct Counted(i64);
+
+        impl Clone for Counted {
+            fn clone(&self) -> Self {
+                CLONES.fetch_add(1, Ordering::Relaxed);
+                Self(self.0)
+            }
         }
 
-        for val in 0..100 {
-            let idx = TreeIndex::Internal { val: val };
-            assert_eq!(idx.to_internal(), TreeIndex::Internal { val: val });
+        impl FenwickTreeValue for Counted {
+            fn store_value(&mut self, other: &Self) {
+                self.0 += other.0;
+            }
         }
+
+        impl InvertibleValue for Counted {
+            fn substract(self, other: Self) -> Self {
+                Self(self.0 - other.0)
+            }
+        }
+
+        let mut tree = FixedSizeFenwickTree::<Counted>::new(8);
+        let created = CLONES.load(Ordering::Relaxed);
+        let value = Counted(3);
+        tree.update_ref(7, &value).unwrap();
+        tree.update_ref(0, &value).unwrap();
+        assert_eq!(CLONES.load(Ordering::Relaxed), created);
+        tree.update(0, Counted(-3)).unwrap();
+        tree.update(7, Counted(-3)).unwrap();
+        tree.range_update(3, 3, Counted(1)).unwrap();
+        assert_eq!(CLONES.load(Ordering::Relaxed), created);
+        tree.range_update(2, 5, Counted(2)).unwrap();
+        assert_eq!(CLONES.load(Ordering::Relaxed), created + 2);
+        assert_eq!(tree.range_query(1, 5).unwrap(), Counted(6));
     }
 
     #[test]
-    fn test_ascending_lsb_chain() {
-        let idx: TreeIndex = 0.into();
-        assert_eq!(
-            idx.lsb_ascending(64).collect::<Vec<TreeIndex>>(),
-            to_internal_index_vec(&[1, 2, 4, 8, 16, 32, 64])
-        );
+    fn test_last_node_absorbs_update() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
 
-        let idx: TreeIndex = 1.into();
-        assert_eq!(
-            idx.lsb_ascending(64).collect::<Vec<TreeIndex>>(),
-            to_internal_index_vec(&[2, 4, 8, 16, 32, 64])
-        );
+        use crate::FenwickTreeValue;
 
-        let idx: TreeIndex = 6.into();
-        assert_eq!(
-            idx.lsb_ascending(64).collect::<Vec<TreeIndex>>(),
-            to_internal_index_vec(&[7, 8, 16, 32, 64])
-        );
+        static COPIES: AtomicUsize = AtomicUsize::new(0);
+
+        #[derive(Debug, Default, Clone, PartialEq)]
+        struct Histogram(Vec<u32>);
+
+        impl FenwickTreeValue for Histogram {
+            fn store_value(&mut self, other: &Self) {
+                COPIES.fetch_add(1, Ordering::Relaxed);
+                self.0.resize(self.0.len().max(other.0.len()), 0);
+                for (count, other_count) in self.0.iter_mut().zip(&other.0) {
+                    *count += other_count;
+                }
+            }
 
-        let idx: TreeIndex = 6.into();
-        assert_eq!(idx.lsb_ascending(0).collect::<Vec<TreeIndex>>(), vec![]);
+            fn absorb(&mut self, other: Self) {
+                if self.0.is_empty() {
+                    self.0 = other.0;
+                } else {
+                    self.store_value(&other);
+                }
+            }
+        }
+
+        let mut fixed = FixedSizeFenwickTree::<Histogram>::new(8);
+        fixed.update(7, Histogram(vec![1, 2])).unwrap();
+        let mut growing = GrowingFenwickTree::<Histogram>::new(4);
+        growing.update(3, Histogram(vec![4])).unwrap();
+        assert_eq!(COPIES.load(Ordering::Relaxed), 0);
+
+        // Nodes 5 and 6 copy the value, node 8 is not empty, so merges it.
+        fixed.update(4, Histogram(vec![1])).unwrap();
+        assert_eq!(COPIES.load(Ordering::Relaxed), 3);
+        assert_eq!(fixed.query(7).unwrap(), Histogram(vec![2, 2]));
+        assert_eq!(growing.query(3).unwrap(), Histogram(vec![4]));
     }
 
+    #[cfg(feature = "glam")]
     #[test]
-    fn test_descending_lsb_chain() {
-        let idx: TreeIndex = 5.into();
-        assert_eq!(idx, TreeIndex::External { val: 5 });
-        assert_eq!(
-            idx.lsb_descending().collect::<Vec<TreeIndex>>(),
-            to_internal_index_vec(&[6, 4])
-        );
+    fn test_glam_vectors() {
+        use glam::{Vec2, Vec3};
 
-        let idx: TreeIndex = 4.into();
-        assert_eq!(
-            idx.lsb_descending().collect::<Vec<TreeIndex>>(),
-            to_internal_index_vec(&[5, 4])
-        );
+        let mut positions = GrowingFenwickTree::<Vec2>::new(0);
+        positions.update(0, Vec2::new(1.0, 2.0)).unwrap();
+        positions.update(3, Vec2::new(0.5, -1.0)).unwrap();
+        positions.update(5, Vec2::X).unwrap();
+        assert_eq!(positions.query(4).unwrap(), Vec2::new(1.5, 1.0));
+        assert_eq!(positions.range_query(0, 5).unwrap(), Vec2::new(1.5, -1.0));
+
+        let mut velocities = FixedSizeFenwickTree::<Vec3>::new(4);
+        velocities.update(2, Vec3::ONE).unwrap();
+        assert_eq!(velocities.query(3).unwrap(), Vec3::ONE);
+    }
+
+    #[test]
+    fn test_range_query_validation() {
+        let mut tree = FixedSizeFenwickTree::<i32>::new(8);
+        tree.update(3, 1).unwrap();
+        tree.update(5, 2).unwrap();
 
-        let idx = TreeIndex::Internal { val: 3 };
         assert_eq!(
-            idx.lsb_descending().collect::<Vec<TreeIndex>>(),
-            to_internal_index_vec(&[3, 2])
+            tree.range_query(5, 3),
+            Err(TreeError::InvalidRange { from: 5, to: 3 })
         );
+        assert_eq!(tree.range_query(3, 3), Ok(0));
+        assert_eq!(tree.range_query(2, 3), Ok(1));
+        assert_eq!(tree.range_query(2, 7), Ok(3));
 
-        let idx = TreeIndex::Internal { val: 12 };
+        let tree = GrowingFenwickTree::<i32>::new(0);
         assert_eq!(
-            idx.lsb_descending().collect::<Vec<TreeIndex>>(),
-            to_internal_index_vec(&[12, 8])
+            tree.range_query(100, 10),
+            Err(TreeError::InvalidRange { from: 100, to: 10 })
         );
+        assert_eq!(tree.range_query(100, 100), Ok(0));
+    }
+
+    #[test]
+    fn test_error_display() {
+        let err = TreeError::IndexOutOfBounds { index: 10, bound: 4 };
+        assert_eq!(err.to_string(), "index 10 is out of bounds 4");
     }
 
     #[test]
     fn test_lsb() {
-        assert_eq!(least_significant_bit(12), 4)
+        assert_eq!(least_significant_bit(12), 4);
+        assert_eq!(least_significant_bit(0), 0);
+        assert_eq!(least_significant_bit(3 << 40), 1 << 40);
+        assert_eq!(least_significant_bit(usize::MAX), 1);
     }
 
     #[test]
@@ -404,4 +1322,85 @@ mod tests {
         assert_eq!(12usize.next_power_of_two(), 16);
         assert_eq!(12usize.next_power_of_two() >> 1, 8);
     }
+
+    #[test]
+    fn test_nan_is_rejected() {
+        let mut tree = FixedSizeFenwickTree::<f64>::new(4);
+        tree.update(1, 1.5).unwrap();
+        assert_eq!(tree.update(2, f64::NAN), Err(TreeError::InvalidValue));
+        assert_eq!(tree.query(3).unwrap(), 1.5);
+
+        let mut tree = GrowingFenwickTree::<f32>::new(0);
+        assert_eq!(tree.update(10, f32::NAN), Err(TreeError::InvalidValue));
+        assert_eq!(tree.query(10).unwrap(), 0.0);
+
+        tree.update(1, f32::INFINITY).unwrap();
+        assert_eq!(tree.query(3).unwrap(), f32::INFINITY);
+        assert!(tree.range_query(1, 3).unwrap().is_nan());
+    }
+
+    #[test]
+    fn test_value_without_partial_eq() {
+        use crate::FenwickTreeValue;
+
+        #[derive(Debug, Default, Clone)]
+        struct Samples(Vec<u8>);
+
+        impl FenwickTreeValue for Samples {
+            fn store_value(&mut self, other: &Self) {
+                self.0.extend_from_slice(&other.0);
+            }
+
+            fn is_identity(&self) -> bool {
+                self.0.is_empty()
+            }
+        }
+
+        let mut tree = FixedSizeFenwickTree::<Samples>::new(4);
+        tree.update(0, Samples(vec![1])).unwrap();
+        tree.update(2, Samples(vec![3, 4])).unwrap();
+        tree.update(3, Samples::default()).unwrap();
+        assert_eq!(tree.query(3).unwrap().0.len(), 3);
+        assert_eq!(tree.query(1).unwrap().0, vec![1]);
+    }
+
+    #[test]
+    fn test_find_prefix() {
+        /// Reader relying on default implementation.
+        struct Prefixes(Vec<u32>);
+
+        impl FenwickReader for Prefixes {
+            type Value = u32;
+
+            fn query(&self, idx: usize) -> Result<u32, TreeError> {
+                self.0.get(idx).copied().ok_or(TreeError::IndexOutOfBounds {
+                    index: idx,
+                    bound: self.0.len(),
+                })
+            }
+        }
+
+        let values = [0, 3, 0, 0, 1, 5, 0, 2, 2];
+        let mut fixed = FixedSizeFenwickTree::<u32>::new(values.len());
+        let mut growing = GrowingFenwickTree::<u32>::new(0);
+        for (idx, value) in values.into_iter().enumerate() {
+            fixed.update(idx, value).unwrap();
+            growing.update(idx, value).unwrap();
+        }
+        let prefixes = Prefixes((0..values.len()).map(|idx| fixed.query(idx).unwrap()).collect());
+
+        for target in 0..=14 {
+            let expected = fixed.lower_bound(target);
+            assert_eq!(fixed.find_prefix(target), expected);
+            assert_eq!(growing.find_prefix(target), expected);
+            assert_eq!(prefixes.find_prefix(target), expected);
+        }
+        assert_eq!(prefixes.find_prefix(0), Some(0));
+        assert_eq!(prefixes.find_prefix(4), Some(4));
+        assert_eq!(prefixes.find_prefix(14), None);
+        assert_eq!(Prefixes(Vec::new()).find_prefix(0), None);
+
+        let boxed: Box<dyn FenwickReader<Value = u32>> = Box::new(fixed);
+        assert_eq!(boxed.find_prefix(9), Some(5));
+    }
 }
@@ -0,0 +1,335 @@
+use crate::iterators::NonZeroPoints;
+use crate::{
+    store_to_nodes, FenwickReader, FenwickWriter, FixedSizeFenwickTree, InvertibleValue, TreeError,
+};
+
+/// Fixed size tree keeping a plain copy of the value stored at every index next to
+/// the tree. Point reads with [`MirroredFenwickTree::get`] and overwrites with
+/// [`MirroredFenwickTree::set`] don't need extra queries, at the cost of twice the
+/// memory. Indexes holding non-default values are counted by another tree, which
+/// follows points crossing the default value.
+pub struct MirroredFenwickTree<T: InvertibleValue> {
+    tree: FixedSizeFenwickTree<T>,
+    points: Vec<T>,
+    /// Holds 1 at every index which point isn't identity. Never smaller than `points`.
+    nonzero: FixedSizeFenwickTree<i64>,
+}
+
+impl<T: InvertibleValue> From<FixedSizeFenwickTree<T>> for MirroredFenwickTree<T> {
+    /// Restores plain values from the tree in `O(n)`.
+    fn from(tree: FixedSizeFenwickTree<T>) -> Self {
+        let points = tree.to_points();
+        Self {
+            nonzero: FixedSizeFenwickTree::from_fn(points.len(), |idx| {
+                i64::from(!points[idx].is_identity())
+            }),
+            points,
+            tree,
+        }
+    }
+}
+
+impl<T: InvertibleValue> MirroredFenwickTree<T> {
+    pub fn new(size: usize) -> Self {
+        Self {
+            tree: FixedSizeFenwickTree::new(size),
+            points: vec![T::default(); size],
+            nonzero: FixedSizeFenwickTree::new(size),
+        }
+    }
+
+    /// Returns value stored at `idx`, in `O(1)`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `idx` is out of bounds.
+    ///
+    pub fn get(&self, idx: usize) -> Result<&T, TreeError> {
+        self.points.get(idx).ok_or(TreeError::IndexOutOfBounds {
+            index: idx,
+            bound: self.points.len(),
+        })
+    }
+
+    /// Replaces value stored at `idx` with `value`. Only the difference with the
+    /// previous value is added to the tree. Indexes beyond the rightmost one follow out
+    /// of bounds policy of the tree, same as updates.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `idx` is out of bounds and the policy
+    /// rejects it.
+    ///
+    pub fn set(&mut self, idx: usize, value: T) -> Result<(), TreeError> {
+        let Some(target) = self.target(idx)? else {
+            return Ok(());
+        };
+        let previous = self.points.get(target).cloned().unwrap_or_default();
+        self.tree
+            .update(target, value.clone().substract(previous))?;
+        self.follow_tree()?;
+        self.change_point(target, |point| *point = value);
+        Ok(())
+    }
+
+    /// Returns number of indexes between `from` and `to` (including edges) holding
+    /// non-default values, in `O(log n)`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `from` is greater than `to` or `to` is out
+    /// of bounds.
+    ///
+    pub fn count_nonzero_between(&self, from: usize, to: usize) -> Result<usize, TreeError> {
+        if from > to {
+            return Err(TreeError::InvalidRange { from, to });
+        }
+        self.get(to)?;
+        let below = match from {
+            0 => 0,
+            _ => self.nonzero.query(from - 1)?,
+        };
+        Ok((self.nonzero.query(to)? - below) as usize)
+    }
+
+    /// Returns iterator over `(index, value)` pairs of points holding non-default
+    /// values, in ascending index order. Unlike [`FixedSizeFenwickTree::iter_nonzero`],
+    /// ranges without such points are skipped for any value type, guided by the
+    /// counting tree, so `k` points are visited in `O(k log n)`.
+    pub fn iter_nonzero(&self) -> impl Iterator<Item = (usize, &T)> + '_ {
+        NonZeroPoints::counting(&self.nonzero.data)
+            .filter_map(|(idx, _)| Some((idx, self.points.get(idx)?)))
+    }
+
+    pub fn into_inner(self) -> FixedSizeFenwickTree<T> {
+        self.tree
+    }
+}
+
+impl<T: InvertibleValue> FenwickReader for MirroredFenwickTree<T> {
+    type Value = T;
+
+    fn query(&self, idx: usize) -> Result<T, TreeError> {
+        self.tree.query(idx)
+    }
+}
+
+impl<T: InvertibleValue> MirroredFenwickTree<T> {
+    /// Returns index update of `idx` lands at under out of bounds policy of the tree,
+    /// or [`None`] if update is dropped. Plain values are reserved up to that index
+    /// before the tree is touched, so they never fall behind the tree.
+    fn target(&mut self, idx: usize) -> Result<Option<usize>, TreeError> {
+        let len = self.points.len();
+        let target = match idx < len {
+            true => idx,
+            false => match self.tree.out_of_bounds.update_index(idx, len)? {
+                Some(target) => target,
+                None => return Ok(None),
+            },
+        };
+        if target >= len {
+            let additional = target.checked_add(1).ok_or(TreeError::Overflow)? - len;
+            self.points
+                .try_reserve(additional)
+                .map_err(|_| TreeError::AllocationFailed)?;
+        }
+        if target >= self.nonzero.data.len() - 1 {
+            self.nonzero.grow(target)?;
+        }
+        Ok(Some(target))
+    }
+
+    /// Extends plain values to the size of the tree after it grew.
+    fn follow_tree(&mut self) -> Result<(), TreeError> {
+        let len = self.tree.data.len() - 1;
+        self.points
+            .try_reserve_exact(len.saturating_sub(self.points.len()))
+            .map_err(|_| TreeError::AllocationFailed)?;
+        self.points.resize(len, T::default());
+        Ok(())
+    }
+
+    /// Replaces point at `target` with `change` applied to it, counting the point when
+    /// it starts or stops holding the default value.
+    fn change_point(&mut self, target: usize, change: impl FnOnce(&mut T)) {
+        let Some(point) = self.points.get_mut(target) else {
+            return;
+        };
+        let was_identity = point.is_identity();
+        change(point);
+        let flag = match (was_identity, point.is_identity()) {
+            (true, false) => 1,
+            (false, true) => -1,
+            _ => return,
+        };
+        // Counting tree was grown to hold the target before any update.
+        store_to_nodes(&mut self.nonzero.data, target + 1, &flag);
+    }
+}
+
+impl<T: InvertibleValue> FenwickWriter for MirroredFenwickTree<T> {
+    fn update(&mut self, idx: usize, value: T) -> Result<(), TreeError> {
+        let Some(target) = self.target(idx)? else {
+            return Ok(());
+        };
+        self.tree.update(target, value.clone())?;
+        self.follow_tree()?;
+        // Identity values don't grow the tree, nor change plain values.
+        self.change_point(target, |point| point.absorb(value));
+        Ok(())
+    }
+
+    fn update_target(&self, idx: usize) -> Result<Option<usize>, TreeError> {
+        self.tree.update_target(idx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::mirrored_tree::MirroredFenwickTree;
+    use crate::{FenwickReader, FenwickWriter, FixedSizeFenwickTree, OutOfBoundsPolicy, TreeError};
+
+    #[test]
+    fn get_and_set() {
+        let mut tree = MirroredFenwickTree::<i32>::new(8);
+        tree.update(3, 5).unwrap();
+        tree.update(3, 2).unwrap();
+        tree.set(5, 4).unwrap();
+        tree.set(3, 1).unwrap();
+
+        assert_eq!(tree.get(3), Ok(&1));
+        assert_eq!(tree.get(5), Ok(&4));
+        assert_eq!(tree.get(0), Ok(&0));
+        assert_eq!(tree.query(7).unwrap(), 5);
+        assert_eq!(
+            tree.set(8, 1),
+            Err(TreeError::IndexOutOfBounds { index: 8, bound: 8 })
+        );
+        assert!(tree.update(8, 1).is_err());
+        assert_eq!(tree.into_inner().query(4).unwrap(), 1);
+    }
+
+    #[test]
+    fn update_follows_out_of_bounds_policy() {
+        let build = |policy| {
+            MirroredFenwickTree::from(FixedSizeFenwickTree::new(2).with_out_of_bounds(policy))
+        };
+
+        let mut tree = build(OutOfBoundsPolicy::Error);
+        assert_eq!(
+            tree.update(5, 1),
+            Err(TreeError::IndexOutOfBounds { index: 5, bound: 2 })
+        );
+        assert_eq!(tree.query(1).unwrap(), 0);
+
+        let mut tree = build(OutOfBoundsPolicy::ClampToLast);
+        tree.update(5, 3).unwrap();
+        assert_eq!(tree.get(1), Ok(&3));
+        assert_eq!(tree.query(1).unwrap(), 3);
+
+        let mut tree = build(OutOfBoundsPolicy::TreatAsZero);
+        tree.update(5, 3).unwrap();
+        assert_eq!(tree.get(1), Ok(&0));
+        assert_eq!(tree.query(1).unwrap(), 0);
+
+        let mut tree = build(OutOfBoundsPolicy::Grow);
+        tree.update(5, 3).unwrap();
+        tree.update(9, 0).unwrap();
+        assert_eq!(tree.get(5), Ok(&3));
+        assert_eq!(tree.get(4), Ok(&0));
+        assert!(tree.get(6).is_err());
+        assert_eq!(tree.query(5).unwrap(), 3);
+    }
+
+    #[test]
+    fn set_follows_out_of_bounds_policy() {
+        let build = |policy| {
+            let mut tree =
+                MirroredFenwickTree::from(FixedSizeFenwickTree::new(2).with_out_of_bounds(policy));
+            tree.set(1, 4).unwrap();
+            tree
+        };
+
+        let mut tree = build(OutOfBoundsPolicy::Error);
+        assert_eq!(
+            tree.set(5, 1),
+            Err(TreeError::IndexOutOfBounds { index: 5, bound: 2 })
+        );
+        assert_eq!(tree.get(1), Ok(&4));
+
+        let mut tree = build(OutOfBoundsPolicy::ClampToLast);
+        tree.set(5, 7).unwrap();
+        assert_eq!(tree.get(1), Ok(&7));
+        assert_eq!(tree.query(1).unwrap(), 7);
+
+        let mut tree = build(OutOfBoundsPolicy::TreatAsZero);
+        tree.set(5, 7).unwrap();
+        assert_eq!(tree.get(1), Ok(&4));
+        assert_eq!(tree.query(1).unwrap(), 4);
+
+        let mut tree = build(OutOfBoundsPolicy::Grow);
+        tree.set(5, 7).unwrap();
+        tree.set(9, 0).unwrap();
+        assert_eq!(tree.get(5), Ok(&7));
+        assert!(tree.get(6).is_err());
+        assert_eq!(tree.query(5).unwrap(), 11);
+    }
+
+    #[test]
+    fn count_nonzero_follows_points() {
+        let mut tree = MirroredFenwickTree::<i32>::new(8);
+        tree.update(0, 5).unwrap();
+        tree.update(1, -5).unwrap();
+        tree.set(4, 2).unwrap();
+        tree.update(6, 3).unwrap();
+        assert_eq!(tree.count_nonzero_between(0, 7).unwrap(), 4);
+        assert_eq!(tree.count_nonzero_between(1, 4).unwrap(), 2);
+        assert_eq!(
+            tree.count_nonzero_between(5, 2),
+            Err(TreeError::InvalidRange { from: 5, to: 2 })
+        );
+
+        tree.update(6, -3).unwrap();
+        tree.set(4, 0).unwrap();
+        tree.set(1, 1).unwrap();
+        assert_eq!(tree.count_nonzero_between(0, 7).unwrap(), 2);
+        assert_eq!(tree.count_nonzero_between(2, 7).unwrap(), 0);
+        assert_eq!(
+            tree.count_nonzero_between(0, 8),
+            Err(TreeError::IndexOutOfBounds { index: 8, bound: 8 })
+        );
+
+        let tree = MirroredFenwickTree::from(FixedSizeFenwickTree::from_fn(6, |i| i as i32 % 3));
+        assert_eq!(tree.count_nonzero_between(0, 5).unwrap(), 4);
+
+        let mut tree = MirroredFenwickTree::from(
+            FixedSizeFenwickTree::new(2).with_out_of_bounds(OutOfBoundsPolicy::Grow),
+        );
+        tree.update(9, 1).unwrap();
+        tree.update(20, 0).unwrap();
+        assert_eq!(tree.count_nonzero_between(0, 9).unwrap(), 1);
+    }
+
+    #[test]
+    fn iter_nonzero_follows_points() {
+        let mut tree = MirroredFenwickTree::<i32>::new(1 << 20);
+        tree.update(0, 5).unwrap();
+        tree.update(1, -5).unwrap();
+        tree.set(70_000, 2).unwrap();
+        tree.update(900_000, 3).unwrap();
+        tree.update(900_000, -3).unwrap();
+        assert_eq!(
+            tree.iter_nonzero().collect::<Vec<_>>(),
+            vec![(0, &5), (1, &-5), (70_000, &2)]
+        );
+        assert_eq!(MirroredFenwickTree::<i32>::new(0).iter_nonzero().next(), None);
+    }
+
+    #[test]
+    fn from_fixed_size_tree() {
+        let tree = MirroredFenwickTree::from(FixedSizeFenwickTree::from_fn(10, |i| i as i32));
+        for i in 0..10 {
+            assert_eq!(tree.get(i), Ok(&(i as i32)));
+        }
+    }
+}
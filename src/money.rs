@@ -0,0 +1,131 @@
+use crate::{FenwickTreeValue, InvertibleValue, TreeError};
+
+/// Amount of money in cents, aggregated with overflow-checked arithmetic.
+///
+/// Sums that don't fit into [`i64`] are not wrapped: amount saturates and remembers
+/// the overflow, so [`Money::cents`] of any aggregate it was part of returns
+/// [`TreeError::Overflow`] instead of a wrong balance.
+///
+/// ```rust
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut ledger = GrowingFenwickTree::<Money>::new(0);
+/// ledger.update(0, Money::from_cents(10_050)).unwrap();
+/// ledger.update(1, Money::from_cents(-2_599)).unwrap();
+/// assert_eq!(ledger.query(1).unwrap().cents(), Ok(7_451));
+/// assert_eq!(ledger.query(1).unwrap().to_string(), "74.51");
+///
+/// ledger.update(2, Money::from_cents(i64::MAX)).unwrap();
+/// assert_eq!(ledger.query(2).unwrap().cents(), Err(TreeError::Overflow));
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Money {
+    cents: i64,
+    overflow: bool,
+}
+
+impl Money {
+    pub fn from_cents(cents: i64) -> Self {
+        Self {
+            cents,
+            overflow: false,
+        }
+    }
+
+    /// Returns amount in cents.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if any sum this amount was part of overflowed.
+    ///
+    pub fn cents(&self) -> Result<i64, TreeError> {
+        if self.overflow {
+            return Err(TreeError::Overflow);
+        }
+        Ok(self.cents)
+    }
+
+    /// Returns `true` if any sum this amount was part of overflowed.
+    pub fn overflowed(&self) -> bool {
+        self.overflow
+    }
+
+    fn checked(cents: Option<i64>, saturated: i64, overflow: bool) -> Self {
+        match cents {
+            Some(cents) => Self { cents, overflow },
+            None => Self {
+                cents: saturated,
+                overflow: true,
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.overflow {
+            return write!(f, "overflow");
+        }
+        let sign = if self.cents < 0 { "-" } else { "" };
+        let cents = self.cents.unsigned_abs();
+        write!(f, "{sign}{}.{:02}", cents / 100, cents % 100)
+    }
+}
+
+impl FenwickTreeValue for Money {
+    fn store_value(&mut self, other: &Self) {
+        *self = Self::checked(
+            self.cents.checked_add(other.cents),
+            self.cents.saturating_add(other.cents),
+            self.overflow || other.overflow,
+        );
+    }
+
+    fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl InvertibleValue for Money {
+    fn substract(self, other: Self) -> Self {
+        Self::checked(
+            self.cents.checked_sub(other.cents),
+            self.cents.saturating_sub(other.cents),
+            self.overflow || other.overflow,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::money::Money;
+    use crate::{FenwickReader, FenwickWriter, FixedSizeFenwickTree, InvertibleValue, TreeError};
+
+    #[test]
+    fn range_sums_in_cents() {
+        let mut tree = FixedSizeFenwickTree::<Money>::new(8);
+        tree.update(1, Money::from_cents(1_000)).unwrap();
+        tree.update(4, Money::from_cents(-1_005)).unwrap();
+        tree.update(6, Money::from_cents(99)).unwrap();
+
+        assert_eq!(tree.range_query(1, 6).unwrap().cents(), Ok(-906));
+        assert_eq!(tree.range_query(1, 6).unwrap().to_string(), "-9.06");
+        assert_eq!(tree.query(3).unwrap().to_string(), "10.00");
+    }
+
+    #[test]
+    fn overflow_is_reported() {
+        let mut tree = FixedSizeFenwickTree::<Money>::new(4);
+        tree.update(0, Money::from_cents(i64::MIN)).unwrap();
+        assert_eq!(tree.query(3).unwrap().cents(), Ok(i64::MIN));
+
+        tree.update(1, Money::from_cents(-1)).unwrap();
+        let total = tree.query(3).unwrap();
+        assert!(total.overflowed());
+        assert_eq!(total.cents(), Err(TreeError::Overflow));
+        assert_eq!(total.to_string(), "overflow");
+
+        let difference = Money::from_cents(0).substract(Money::from_cents(i64::MIN));
+        assert_eq!(difference.cents(), Err(TreeError::Overflow));
+    }
+}
@@ -0,0 +1,254 @@
+use crate::{bucket_sum, FenwickWriter, GrowingFenwickTree, InvertibleValue, TreeError};
+
+const MINUTES_IN_HOUR: usize = 60;
+const HOURS_IN_DAY: usize = 24;
+const SECONDS_IN_MINUTE: u64 = 60;
+
+/// Time series aggregate that keeps linked minute, hour and day trees.
+///
+/// Every recorded value is routed to all three levels, so range sums can be answered
+/// by combining the coarsest buckets that fit into the requested range. Timestamps are
+/// seconds counted from the `origin` passed to [`MultiResolutionTree::new`], which keeps
+/// the underlying trees small for unix timestamps.
+pub struct MultiResolutionTree<T: InvertibleValue> {
+    origin: u64,
+    minutes: GrowingFenwickTree<T>,
+    hours: GrowingFenwickTree<T>,
+    days: GrowingFenwickTree<T>,
+}
+
+impl<T: InvertibleValue> MultiResolutionTree<T> {
+    pub fn new(origin: u64) -> Self {
+        Self {
+            origin,
+            minutes: GrowingFenwickTree::new(0),
+            hours: GrowingFenwickTree::new(0),
+            days: GrowingFenwickTree::new(0),
+        }
+    }
+
+    /// Returns minute bucket of `timestamp`, which can't precede the origin. Fails if
+    /// the bucket doesn't fit into [`usize`] on 32-bit targets.
+    fn minute_of(&self, timestamp: u64) -> Result<usize, TreeError> {
+        usize::try_from((timestamp - self.origin) / SECONDS_IN_MINUTE)
+            .map_err(|_| TreeError::Overflow)
+    }
+
+    /// Adds `value` to the minute, hour and day buckets containing `timestamp`. Either
+    /// all three levels are updated or none of them is.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `timestamp` is earlier than the origin,
+    /// its bucket doesn't fit into [`usize`], `value` is invalid, or memory for the
+    /// bucket can't be allocated.
+    ///
+    pub fn record(&mut self, timestamp: u64, value: T) -> Result<(), TreeError> {
+        if timestamp < self.origin {
+            return Err(TreeError::IndexOutOfBounds {
+                index: usize::try_from(timestamp).unwrap_or(usize::MAX),
+                bound: usize::try_from(self.origin).unwrap_or(usize::MAX),
+            });
+        }
+
+        let minute = self.minute_of(timestamp)?;
+        let hour = minute / MINUTES_IN_HOUR;
+        let day = hour / HOURS_IN_DAY;
+
+        // Growing keeps sums intact, so every level is validated and grown first, and
+        // updates of grown trees can't fail halfway through.
+        self.minutes.grow_for(minute, &value)?;
+        self.hours.grow_for(hour, &value)?;
+        self.days.grow_for(day, &value)?;
+
+        self.minutes.update(minute, value.clone())?;
+        self.hours.update(hour, value.clone())?;
+        self.days.update(day, value)
+    }
+
+    /// Returns sum of values recorded within minutes containing `t1` and `t2` and all
+    /// minutes in between (including edges).
+    ///
+    /// Empty value is returned if the whole range lies before the origin.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `t1` is greater than `t2`.
+    ///
+    pub fn sum_between(&self, t1: u64, t2: u64) -> Result<T, TreeError> {
+        if t1 > t2 {
+            return Err(TreeError::InvalidRange {
+                from: t1 as usize,
+                to: t2 as usize,
+            });
+        }
+        if t2 < self.origin {
+            return Ok(T::default());
+        }
+
+        let from = self.minute_of(t1.max(self.origin))?;
+        let to = self.minute_of(t2)?;
+
+        self.sum_minutes(from, to)
+    }
+
+    fn sum_minutes(&self, from: usize, to: usize) -> Result<T, TreeError> {
+        let first_full_hour = from.div_ceil(MINUTES_IN_HOUR);
+        let end_of_full_hours = (to + 1) / MINUTES_IN_HOUR;
+
+        if first_full_hour >= end_of_full_hours {
+            return bucket_sum(&self.minutes, from, to);
+        }
+
+        let mut res = self.sum_hours(first_full_hour, end_of_full_hours - 1)?;
+
+        if from < first_full_hour * MINUTES_IN_HOUR {
+            res.absorb(bucket_sum(
+                &self.minutes,
+                from,
+                first_full_hour * MINUTES_IN_HOUR - 1,
+            )?);
+        }
+
+        if end_of_full_hours * MINUTES_IN_HOUR <= to {
+            res.absorb(bucket_sum(
+                &self.minutes,
+                end_of_full_hours * MINUTES_IN_HOUR,
+                to,
+            )?);
+        }
+
+        Ok(res)
+    }
+
+    fn sum_hours(&self, from: usize, to: usize) -> Result<T, TreeError> {
+        let first_full_day = from.div_ceil(HOURS_IN_DAY);
+        let end_of_full_days = (to + 1) / HOURS_IN_DAY;
+
+        if first_full_day >= end_of_full_days {
+            return bucket_sum(&self.hours, from, to);
+        }
+
+        let mut res = bucket_sum(&self.days, first_full_day, end_of_full_days - 1)?;
+
+        if from < first_full_day * HOURS_IN_DAY {
+            res.absorb(bucket_sum(
+                &self.hours,
+                from,
+                first_full_day * HOURS_IN_DAY - 1,
+            )?);
+        }
+
+        if end_of_full_days * HOURS_IN_DAY <= to {
+            res.absorb(bucket_sum(
+                &self.hours,
+                end_of_full_days * HOURS_IN_DAY,
+                to,
+            )?);
+        }
+
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::multi_resolution_tree::MultiResolutionTree;
+    use crate::workload::Workload;
+    use crate::TreeError;
+
+    const ORIGIN: u64 = 1_700_000_000;
+
+    #[test]
+    fn record_before_origin() {
+        let mut tree = MultiResolutionTree::<i32>::new(ORIGIN);
+        assert_eq!(
+            tree.record(ORIGIN - 1, 1),
+            Err(TreeError::IndexOutOfBounds {
+                index: (ORIGIN - 1) as usize,
+                bound: ORIGIN as usize
+            })
+        );
+    }
+
+    #[test]
+    fn failed_record_leaves_levels_untouched() {
+        let mut tree = MultiResolutionTree::<f64>::new(ORIGIN);
+        tree.record(ORIGIN + 3_600, 1.0).unwrap();
+
+        assert_eq!(
+            tree.record(ORIGIN + 7_200, f64::NAN),
+            Err(TreeError::InvalidValue)
+        );
+        assert!(tree.record(u64::MAX, 1.0).is_err());
+
+        assert_eq!(tree.sum_between(ORIGIN, ORIGIN + 86_400).unwrap(), 1.0);
+        assert_eq!(
+            tree.sum_between(ORIGIN + 3_660, ORIGIN + 86_400).unwrap(),
+            0.0
+        );
+    }
+
+    #[test]
+    fn sum_within_single_minute() {
+        let mut tree = MultiResolutionTree::<i32>::new(ORIGIN);
+        tree.record(ORIGIN, 1).unwrap();
+        tree.record(ORIGIN + 59, 2).unwrap();
+        tree.record(ORIGIN + 60, 4).unwrap();
+
+        assert_eq!(tree.sum_between(ORIGIN, ORIGIN).unwrap(), 3);
+        assert_eq!(tree.sum_between(ORIGIN + 30, ORIGIN + 60).unwrap(), 7);
+        assert_eq!(
+            tree.sum_between(ORIGIN + 60, ORIGIN),
+            Err(TreeError::InvalidRange {
+                from: (ORIGIN + 60) as usize,
+                to: ORIGIN as usize
+            })
+        );
+    }
+
+    #[test]
+    fn sum_across_days() {
+        let mut tree = MultiResolutionTree::<i32>::new(ORIGIN);
+        let day = 86_400;
+        tree.record(ORIGIN + 10, 1).unwrap();
+        tree.record(ORIGIN + day + 3_600, 10).unwrap();
+        tree.record(ORIGIN + 3 * day - 1, 100).unwrap();
+        tree.record(ORIGIN + 3 * day, 1_000).unwrap();
+
+        assert_eq!(tree.sum_between(0, ORIGIN + 3 * day - 1).unwrap(), 111);
+        assert_eq!(
+            tree.sum_between(ORIGIN + 60, ORIGIN + 30 * day).unwrap(),
+            1_110
+        );
+    }
+
+    #[test]
+    fn random_records_match_naive_sum() {
+        let mut tree = MultiResolutionTree::<i64>::new(ORIGIN);
+        let mut records = vec![];
+
+        let updates = Workload::new(1, 10 * 86_400).deltas(0..100).updates();
+        for (offset, value) in updates.take(1000) {
+            let timestamp = ORIGIN + offset as u64;
+            tree.record(timestamp, value).unwrap();
+            records.push((timestamp, value));
+        }
+
+        let updates = Workload::new(2, 10 * 86_400).updates();
+        let lengths = Workload::new(3, 5 * 86_400).updates();
+        for ((offset, _), (length, _)) in updates.zip(lengths).take(100) {
+            let t1 = ORIGIN + offset as u64;
+            let t2 = t1 + length as u64;
+            let expected: i64 = records
+                .iter()
+                .filter(|(ts, _)| {
+                    let minute = (ts - ORIGIN) / 60;
+                    minute >= (t1 - ORIGIN) / 60 && minute <= (t2 - ORIGIN) / 60
+                })
+                .map(|(_, value)| value)
+                .sum();
+            assert_eq!(tree.sum_between(t1, t2).unwrap(), expected);
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use crate::raw::{lsb_ascending, lsb_descending};
+use crate::{FenwickTreeValue, InvertibleValue, TreeError};
+
+/// Fixed size tree over a `D` dimensional grid of points addressed with `[usize; D]`,
+/// such as voxels, with point updates in `O(log^D n)` and sums of boxes in
+/// `O(2^D log^D n)`. Nodes of every dimension are kept in a single vector, unlike
+/// nested trees.
+///
+/// ```
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut voxels = FenwickTreeNd::<u32, 3>::new([8, 8, 4]);
+/// voxels.update([1, 2, 3], 5).unwrap();
+/// voxels.update([4, 4, 0], 2).unwrap();
+///
+/// assert_eq!(voxels.query([7, 7, 3]).unwrap(), 7);
+/// assert_eq!(voxels.box_sum([1, 0, 1], [7, 7, 3]).unwrap(), 5);
+/// ```
+#[derive(Debug, Clone)]
+pub struct FenwickTreeNd<T: FenwickTreeValue, const D: usize> {
+    /// Row-major nodes, including unused position 0 of every dimension.
+    data: Vec<T>,
+    dims: [usize; D],
+    /// Distance between consecutive positions of every dimension within `data`.
+    strides: [usize; D],
+}
+
+impl<T: FenwickTreeValue, const D: usize> FenwickTreeNd<T, D> {
+    /// Creates tree of `dims[d]` points along every dimension `d`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the number of nodes overflows [`usize`].
+    pub fn new(dims: [usize; D]) -> Self {
+        let mut strides = [0; D];
+        let mut len = 1usize;
+        for d in (0..D).rev() {
+            strides[d] = len;
+            len = dims[d]
+                .checked_add(1)
+                .and_then(|positions| len.checked_mul(positions))
+                .expect("number of nodes overflows usize");
+        }
+        Self {
+            data: vec![T::default(); len],
+            dims,
+            strides,
+        }
+    }
+
+    pub fn dims(&self) -> [usize; D] {
+        self.dims
+    }
+
+    /// Adds `value` to `point`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `point` is out of bounds in any
+    /// dimension, or `value` is rejected by [`FenwickTreeValue::is_valid`].
+    ///
+    pub fn update(&mut self, point: [usize; D], value: T) -> Result<(), TreeError> {
+        self.check_point(&point)?;
+        if !value.is_valid() {
+            return Err(TreeError::InvalidValue);
+        }
+        if !value.is_identity() {
+            self.add_to_nodes(0, 0, &point, &value);
+        }
+        Ok(())
+    }
+
+    /// Returns sum of points between origin and `point` (including edges).
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `point` is out of bounds in any
+    /// dimension.
+    ///
+    pub fn query(&self, point: [usize; D]) -> Result<T, TreeError> {
+        self.check_point(&point)?;
+        let mut res = T::default();
+        self.prefix(0, 0, &point.map(|coord| coord + 1), &mut res);
+        Ok(res)
+    }
+
+    /// Returns sum of points of the box between `from` and `to` corners (including
+    /// edges), combining prefix sums of its `2^D` corners.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `from` lies after `to` in any dimension,
+    /// or `to` is out of bounds.
+    ///
+    pub fn box_sum(&self, from: [usize; D], to: [usize; D]) -> Result<T, TreeError>
+    where
+        T: InvertibleValue,
+    {
+        for (&from, &to) in from.iter().zip(&to) {
+            if from > to {
+                return Err(TreeError::InvalidRange { from, to });
+            }
+        }
+        self.check_point(&to)?;
+
+        let (mut added, mut removed) = (T::default(), T::default());
+        for corner in 0..1usize << D {
+            // Set bits pick the lower edge, which prefix excludes.
+            let positions: [usize; D] = std::array::from_fn(|d| match corner >> d & 1 {
+                1 => from[d],
+                _ => to[d] + 1,
+            });
+            if positions.contains(&0) {
+                continue;
+            }
+            match corner.count_ones() % 2 {
+                0 => self.prefix(0, 0, &positions, &mut added),
+                _ => self.prefix(0, 0, &positions, &mut removed),
+            }
+        }
+        Ok(added.substract(removed))
+    }
+
+    /// Aggregates into `res` nodes covering points below `positions` in dimensions
+    /// from `dim` on, within the slice of nodes starting at `offset`.
+    fn prefix(&self, dim: usize, offset: usize, positions: &[usize; D], res: &mut T) {
+        if dim == D {
+            res.store_value(&self.data[offset]);
+            return;
+        }
+        for position in lsb_descending(positions[dim]) {
+            self.prefix(
+                dim + 1,
+                offset + position * self.strides[dim],
+                positions,
+                res,
+            );
+        }
+    }
+
+    fn add_to_nodes(&mut self, dim: usize, offset: usize, point: &[usize; D], value: &T) {
+        if dim == D {
+            self.data[offset].store_value(value);
+            return;
+        }
+        for position in lsb_ascending(point[dim] + 1, self.dims[dim]) {
+            self.add_to_nodes(dim + 1, offset + position * self.strides[dim], point, value);
+        }
+    }
+
+    fn check_point(&self, point: &[usize; D]) -> Result<(), TreeError> {
+        for (&coord, &dim) in point.iter().zip(&self.dims) {
+            if coord >= dim {
+                return Err(TreeError::IndexOutOfBounds {
+                    index: coord,
+                    bound: dim,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::nd_tree::FenwickTreeNd;
+    use crate::workload::Workload;
+    use crate::{FenwickReader, FenwickWriter, FixedSizeFenwickTree, TreeError};
+
+    #[test]
+    fn matches_naive_voxels() {
+        let dims = [5, 3, 6];
+        let mut tree = FenwickTreeNd::<i64, 3>::new(dims);
+        let mut naive = [[[0i64; 6]; 3]; 5];
+        let mut coordinates = dims.map(|dim| Workload::new(dim as u64, dim).updates());
+        let values = Workload::new(0, 1).deltas(-10..10).updates();
+        for (_, value) in values.take(60) {
+            let point = [0, 1, 2].map(|axis| coordinates[axis].next().unwrap().0);
+            tree.update(point, value).unwrap();
+            naive[point[0]][point[1]][point[2]] += value;
+        }
+
+        for x in 0..5 {
+            for y in 0..3 {
+                for z in 0..6 {
+                    let from = [x / 2, y / 2, z / 3];
+                    let to = [x, y, z];
+                    let mut expected = 0;
+                    for plane in &naive[from[0]..=x] {
+                        for row in &plane[from[1]..=y] {
+                            expected += row[from[2]..=z].iter().sum::<i64>();
+                        }
+                    }
+                    assert_eq!(tree.box_sum(from, to).unwrap(), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn single_dimension_matches_tree() {
+        let mut tree = FenwickTreeNd::<i32, 1>::new([20]);
+        let mut expected = FixedSizeFenwickTree::<i32>::new(20);
+        for idx in 0..20 {
+            tree.update([idx], idx as i32 % 4).unwrap();
+            expected.update(idx, idx as i32 % 4).unwrap();
+        }
+        for idx in 0..20 {
+            assert_eq!(tree.query([idx]).unwrap(), expected.query(idx).unwrap());
+            assert_eq!(
+                tree.box_sum([idx], [19]).unwrap(),
+                expected.query(19).unwrap() - expected.query(idx).unwrap() + idx as i32 % 4
+            );
+        }
+    }
+
+    #[test]
+    fn bounds() {
+        let mut tree = FenwickTreeNd::<i32, 2>::new([3, 0]);
+        assert_eq!(tree.dims(), [3, 0]);
+        assert_eq!(
+            tree.update([1, 0], 1),
+            Err(TreeError::IndexOutOfBounds { index: 0, bound: 0 })
+        );
+
+        let mut tree = FenwickTreeNd::<f64, 2>::new([3, 4]);
+        assert_eq!(
+            tree.query([3, 0]),
+            Err(TreeError::IndexOutOfBounds { index: 3, bound: 3 })
+        );
+        assert_eq!(
+            tree.box_sum([0, 2], [2, 1]),
+            Err(TreeError::InvalidRange { from: 2, to: 1 })
+        );
+        assert_eq!(tree.update([0, 0], f64::NAN), Err(TreeError::InvalidValue));
+
+        let mut point = FenwickTreeNd::<i32, 0>::new([]);
+        point.update([], 3).unwrap();
+        assert_eq!(point.box_sum([], []).unwrap(), 3);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::{bucket_sum, FenwickReader, FenwickWriter, GrowingFenwickTree, TreeError};
+
+/// One side of an order book keyed by price tick.
+///
+/// Quantity resting at each tick is stored in a tree, so cumulative depth up to any
+/// price and the price at which given cumulative depth is reached are both cheap.
+pub struct OrderBookDepth {
+    depth: GrowingFenwickTree<i64>,
+    highest_price: Option<usize>,
+}
+
+impl Default for OrderBookDepth {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OrderBookDepth {
+    pub fn new() -> Self {
+        Self {
+            depth: GrowingFenwickTree::new(0),
+            highest_price: None,
+        }
+    }
+
+    /// Adds `qty` to the quantity resting at `price` tick.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if total quantity of the book doesn't fit
+    /// into [`i64`], or memory for `price` tick can't be allocated.
+    ///
+    pub fn add(&mut self, price: usize, qty: u64) -> Result<(), TreeError> {
+        if qty == 0 {
+            return Ok(());
+        }
+
+        let qty = i64::try_from(qty).map_err(|_| TreeError::Overflow)?;
+        self.total_qty()?
+            .checked_add(qty)
+            .ok_or(TreeError::Overflow)?;
+
+        self.depth.update(price, qty)?;
+        self.highest_price = self.highest_price.max(Some(price));
+        Ok(())
+    }
+
+    /// Removes up to `qty` from the quantity resting at `price` tick. Quantity never
+    /// goes below zero, so the amount that was actually removed is returned.
+    pub fn remove(&mut self, price: usize, qty: u64) -> Result<u64, TreeError> {
+        let removed = self.qty_at(price)?.min(qty);
+        if removed == 0 {
+            return Ok(0);
+        }
+
+        let delta = i64::try_from(removed).map_err(|_| TreeError::Overflow)?;
+        self.depth.update(price, -delta)?;
+
+        // The highest level got empty, so the highest one still holding quantity is
+        // where cumulative quantity reaches the total.
+        if self.highest_price == Some(price) && self.qty_at(price)? == 0 {
+            let total = self.depth.query(price)?;
+            self.highest_price = if total > 0 {
+                self.depth.lower_bound(total)
+            } else {
+                None
+            };
+        }
+        Ok(removed)
+    }
+
+    fn total_qty(&self) -> Result<i64, TreeError> {
+        self.highest_price
+            .map_or(Ok(0), |highest_price| self.depth.query(highest_price))
+    }
+
+    /// Returns quantity resting exactly at `price` tick.
+    pub fn qty_at(&self, price: usize) -> Result<u64, TreeError> {
+        Ok(bucket_sum(&self.depth, price, price)? as u64)
+    }
+
+    /// Returns total quantity resting at all ticks lesser or equal than `price`.
+    pub fn cumulative_qty_up_to(&self, price: usize) -> Result<u64, TreeError> {
+        Ok(self.depth.query(price)? as u64)
+    }
+
+    /// Returns the lowest price tick at which cumulative quantity reaches `qty`, or
+    /// [`None`] if the whole book holds less than `qty`.
+    pub fn price_for_cumulative_qty(&self, qty: u64) -> Result<Option<usize>, TreeError> {
+        let Some(highest_price) = self.highest_price else {
+            return Ok(None);
+        };
+
+        let price = self
+            .depth
+            .partition_point(|cumulative_qty| (*cumulative_qty as u64) < qty);
+
+        if price > highest_price {
+            return Ok(None);
+        }
+
+        Ok(Some(price))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::order_book_depth::OrderBookDepth;
+    use crate::TreeError;
+
+    #[test]
+    fn empty_book() {
+        let book = OrderBookDepth::new();
+        assert_eq!(book.cumulative_qty_up_to(100).unwrap(), 0);
+        assert_eq!(book.price_for_cumulative_qty(1).unwrap(), None);
+    }
+
+    #[test]
+    fn add_and_remove_levels() {
+        let mut book = OrderBookDepth::new();
+        book.add(100, 5).unwrap();
+        book.add(101, 3).unwrap();
+        book.add(105, 2).unwrap();
+
+        assert_eq!(book.cumulative_qty_up_to(99).unwrap(), 0);
+        assert_eq!(book.cumulative_qty_up_to(101).unwrap(), 8);
+        assert_eq!(book.cumulative_qty_up_to(200).unwrap(), 10);
+
+        assert_eq!(book.remove(101, 1).unwrap(), 1);
+        assert_eq!(book.remove(105, 10).unwrap(), 2);
+        assert_eq!(book.remove(110, 10).unwrap(), 0);
+
+        assert_eq!(book.qty_at(101).unwrap(), 2);
+        assert_eq!(book.qty_at(105).unwrap(), 0);
+        assert_eq!(book.cumulative_qty_up_to(200).unwrap(), 7);
+    }
+
+    #[test]
+    fn price_for_cumulative_qty() {
+        let mut book = OrderBookDepth::new();
+        book.add(100, 5).unwrap();
+        book.add(101, 3).unwrap();
+        book.add(105, 2).unwrap();
+
+        assert_eq!(book.price_for_cumulative_qty(1).unwrap(), Some(100));
+        assert_eq!(book.price_for_cumulative_qty(5).unwrap(), Some(100));
+        assert_eq!(book.price_for_cumulative_qty(6).unwrap(), Some(101));
+        assert_eq!(book.price_for_cumulative_qty(9).unwrap(), Some(105));
+        assert_eq!(book.price_for_cumulative_qty(11).unwrap(), None);
+
+        book.remove(101, 3).unwrap();
+        assert_eq!(book.price_for_cumulative_qty(6).unwrap(), Some(105));
+    }
+
+    #[test]
+    fn emptied_highest_level() {
+        let mut book = OrderBookDepth::new();
+        book.add(100, 5).unwrap();
+        book.add(105, 2).unwrap();
+
+        book.remove(105, 2).unwrap();
+        assert_eq!(book.highest_price, Some(100));
+        assert_eq!(book.price_for_cumulative_qty(6).unwrap(), None);
+
+        book.remove(100, 5).unwrap();
+        assert_eq!(book.highest_price, None);
+        assert_eq!(book.price_for_cumulative_qty(1).unwrap(), None);
+    }
+
+    #[test]
+    fn empty_add_keeps_highest_price() {
+        let mut book = OrderBookDepth::new();
+        book.add(100, 5).unwrap();
+        book.add(200, 0).unwrap();
+
+        assert_eq!(book.highest_price, Some(100));
+        assert_eq!(book.price_for_cumulative_qty(6).unwrap(), None);
+    }
+
+    #[test]
+    fn quantity_overflow() {
+        let mut book = OrderBookDepth::new();
+        assert_eq!(book.add(100, u64::MAX), Err(TreeError::Overflow));
+
+        book.add(100, i64::MAX as u64).unwrap();
+        assert_eq!(book.add(101, 1), Err(TreeError::Overflow));
+        assert_eq!(book.cumulative_qty_up_to(200).unwrap(), i64::MAX as u64);
+    }
+}
@@ -0,0 +1,189 @@
+use crate::{FenwickReader, FenwickWriter, FixedSizeFenwickTree, TreeError};
+
+/// Multiset of keys within `0..size`, such as scores or latencies in milliseconds,
+/// answering which key is the `k`-th smallest and how many keys lie below a key, both
+/// in `O(log n)`.
+///
+/// The inner tree holds the number of copies of every key, so the `k`-th smallest key
+/// is the first one which prefix count exceeds `k`.
+///
+/// ```
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut scores = OrderStatTree::new(100);
+/// for score in [70, 15, 42, 42, 99] {
+///     scores.insert(score).unwrap();
+/// }
+///
+/// assert_eq!(scores.kth(0), Some(15));
+/// assert_eq!(scores.kth(2), Some(42));
+/// assert_eq!(scores.rank(50).unwrap(), 3);
+///
+/// scores.remove(15).unwrap();
+/// assert_eq!(scores.kth(0), Some(42));
+/// ```
+#[derive(Clone)]
+pub struct OrderStatTree {
+    counts: FixedSizeFenwickTree<i64>,
+    len: usize,
+}
+
+impl OrderStatTree {
+    /// Creates empty multiset of keys within `0..size`.
+    pub fn new(size: usize) -> Self {
+        Self {
+            counts: FixedSizeFenwickTree::new(size),
+            len: 0,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.counts.data.len() - 1
+    }
+
+    /// Returns number of keys inserted, counting every copy.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Inserts a copy of `key`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `key` is out of bounds.
+    ///
+    pub fn insert(&mut self, key: usize) -> Result<(), TreeError> {
+        self.counts.update(key, 1)?;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes a copy of `key`, returns whether there was one.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `key` is out of bounds.
+    ///
+    pub fn remove(&mut self, key: usize) -> Result<bool, TreeError> {
+        if self.count(key)? == 0 {
+            return Ok(false);
+        }
+        self.counts.update(key, -1)?;
+        self.len -= 1;
+        Ok(true)
+    }
+
+    /// Returns number of copies of `key`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `key` is out of bounds.
+    ///
+    pub fn count(&self, key: usize) -> Result<usize, TreeError> {
+        Ok((self.counts.query(key)? - self.count_below(key)?) as usize)
+    }
+
+    /// Returns number of keys strictly smaller than `key`, which is the position of
+    /// its first copy in sorted order.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `key` is out of bounds.
+    ///
+    pub fn rank(&self, key: usize) -> Result<usize, TreeError> {
+        Ok(self.count_below(key)? as usize)
+    }
+
+    /// Returns the `k`-th smallest key (counting from 0, copies counted separately), or
+    /// [`None`] if there are no more than `k` keys.
+    pub fn kth(&self, k: usize) -> Option<usize> {
+        if k >= self.len {
+            return None;
+        }
+        self.counts.lower_bound(k as i64 + 1)
+    }
+
+    fn count_below(&self, key: usize) -> Result<i64, TreeError> {
+        if key >= self.size() {
+            return Err(TreeError::IndexOutOfBounds {
+                index: key,
+                bound: self.size(),
+            });
+        }
+        match key {
+            0 => Ok(0),
+            _ => self.counts.query(key - 1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::order_stat_tree::OrderStatTree;
+    use crate::workload::Workload;
+    use crate::TreeError;
+
+    #[test]
+    fn matches_sorted_keys() {
+        let mut tree = OrderStatTree::new(40);
+        let mut sorted = Vec::new();
+        for (key, coin) in Workload::new(1, 40).updates().take(200) {
+            if coin < 6 {
+                tree.insert(key).unwrap();
+                let at = sorted.partition_point(|other| *other < key);
+                sorted.insert(at, key);
+            } else {
+                let present = sorted.contains(&key);
+                assert_eq!(tree.remove(key).unwrap(), present);
+                if present {
+                    let at = sorted.partition_point(|other| *other < key);
+                    sorted.remove(at);
+                }
+            }
+        }
+
+        assert_eq!(tree.len(), sorted.len());
+        for (k, key) in sorted.iter().enumerate() {
+            assert_eq!(tree.kth(k), Some(*key));
+        }
+        assert_eq!(tree.kth(sorted.len()), None);
+        for key in 0..40 {
+            let rank = sorted.partition_point(|other| *other < key);
+            let count = sorted.iter().filter(|other| **other == key).count();
+            assert_eq!(tree.rank(key).unwrap(), rank);
+            assert_eq!(tree.count(key).unwrap(), count);
+        }
+    }
+
+    #[test]
+    fn bounds() {
+        let mut tree = OrderStatTree::new(3);
+        assert!(tree.is_empty());
+        assert_eq!(tree.size(), 3);
+        assert_eq!(tree.kth(0), None);
+        assert_eq!(tree.remove(2), Ok(false));
+        assert_eq!(
+            tree.insert(3),
+            Err(TreeError::IndexOutOfBounds { index: 3, bound: 3 })
+        );
+        assert_eq!(
+            tree.rank(3),
+            Err(TreeError::IndexOutOfBounds { index: 3, bound: 3 })
+        );
+
+        tree.insert(2).unwrap();
+        tree.insert(2).unwrap();
+        assert_eq!(tree.kth(1), Some(2));
+        assert_eq!(tree.rank(2).unwrap(), 0);
+        assert!(tree.remove(2).unwrap());
+        assert_eq!(tree.count(2).unwrap(), 1);
+        assert_eq!(tree.len(), 1);
+
+        let empty = OrderStatTree::new(0);
+        assert_eq!(empty.kth(0), None);
+    }
+}
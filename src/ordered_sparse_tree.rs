@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+
+use crate::{least_significant_bit, FenwickReader, FenwickTreeValue, FenwickWriter, TreeError};
+
+/// Tree over a huge key space `[0, size)` storing only populated nodes in a
+/// [`BTreeMap`], so memory depends on the number of updated indexes rather than on
+/// `size`. Every update touches at most `log2(size)` nodes.
+///
+/// Values stored at every index are kept as well, in order, so populated indexes can
+/// be iterated with [`OrderedSparseFenwickTree::iter`] and the lowest and highest of
+/// them are found without scanning. Nodes and values aggregating to
+/// [`FenwickTreeValue::is_identity`] are dropped.
+///
+/// ```
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut tree = OrderedSparseFenwickTree::<i64>::new(1 << 40);
+/// tree.update(1 << 39, 3).unwrap();
+/// tree.update(17, 2).unwrap();
+///
+/// assert_eq!(tree.query((1 << 40) - 1).unwrap(), 5);
+/// assert_eq!(tree.first_index(), Some(17));
+/// assert_eq!(tree.last_index(), Some(1 << 39));
+/// ```
+#[derive(Debug, Clone)]
+pub struct OrderedSparseFenwickTree<T: FenwickTreeValue> {
+    nodes: BTreeMap<usize, T>,
+    points: BTreeMap<usize, T>,
+    size: usize,
+}
+
+impl<T: FenwickTreeValue> OrderedSparseFenwickTree<T> {
+    pub fn new(size: usize) -> Self {
+        Self {
+            nodes: BTreeMap::new(),
+            points: BTreeMap::new(),
+            size,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns number of populated indexes.
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Returns lowest populated index.
+    pub fn first_index(&self) -> Option<usize> {
+        self.points.keys().next().copied()
+    }
+
+    /// Returns highest populated index.
+    pub fn last_index(&self) -> Option<usize> {
+        self.points.keys().next_back().copied()
+    }
+
+    /// Iterates over populated indexes and values stored at them, in ascending order
+    /// of indexes.
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (usize, &T)> + '_ {
+        self.points.iter().map(|(idx, value)| (*idx, value))
+    }
+
+    fn check_bounds(&self, idx: usize) -> Result<(), TreeError> {
+        if idx >= self.size {
+            return Err(TreeError::IndexOutOfBounds {
+                index: idx,
+                bound: self.size,
+            });
+        }
+        Ok(())
+    }
+}
+
+fn store_into<T: FenwickTreeValue>(map: &mut BTreeMap<usize, T>, key: usize, value: &T) {
+    let entry = map.entry(key).or_default();
+    entry.store_value(value);
+    if entry.is_identity() {
+        map.remove(&key);
+    }
+}
+
+impl<T: FenwickTreeValue> FenwickReader for OrderedSparseFenwickTree<T> {
+    type Value = T;
+
+    fn query(&self, idx: usize) -> Result<T, TreeError> {
+        self.check_bounds(idx)?;
+
+        let mut res = T::default();
+        let mut position = idx + 1;
+        while position > 0 {
+            if let Some(node) = self.nodes.get(&position) {
+                res.store_value(node);
+            }
+            position -= least_significant_bit(position);
+        }
+
+        Ok(res)
+    }
+}
+
+impl<T: FenwickTreeValue> FenwickWriter for OrderedSparseFenwickTree<T> {
+    fn update(&mut self, idx: usize, value: T) -> Result<(), TreeError> {
+        self.check_bounds(idx)?;
+
+        if !value.is_valid() {
+            return Err(TreeError::InvalidValue);
+        }
+        if value.is_identity() {
+            return Ok(());
+        }
+
+        store_into(&mut self.points, idx, &value);
+
+        let mut position = idx + 1;
+        while position <= self.size {
+            store_into(&mut self.nodes, position, &value);
+            match position.checked_add(least_significant_bit(position)) {
+                Some(next) => position = next,
+                None => break,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ordered_sparse_tree::OrderedSparseFenwickTree;
+    use crate::{FenwickReader, FenwickWriter, FixedSizeFenwickTree, TreeError};
+
+    #[test]
+    fn matches_fixed_size_tree() {
+        let mut sparse = OrderedSparseFenwickTree::<i32>::new(64);
+        let mut fixed = FixedSizeFenwickTree::<i32>::new(64);
+        for (idx, value) in [(5, 3), (63, -1), (0, 2), (31, 7), (5, -3)] {
+            sparse.update(idx, value).unwrap();
+            fixed.update(idx, value).unwrap();
+        }
+
+        for idx in 0..64 {
+            assert_eq!(sparse.query(idx).unwrap(), fixed.query(idx).unwrap());
+        }
+        assert_eq!(sparse.range_query(0, 31).unwrap(), 7);
+    }
+
+    #[test]
+    fn ordered_points() {
+        let mut tree = OrderedSparseFenwickTree::<i64>::new(usize::MAX);
+        assert!(tree.is_empty());
+        assert_eq!(tree.first_index(), None);
+
+        tree.update(usize::MAX - 1, 4).unwrap();
+        tree.update(1 << 40, 2).unwrap();
+        tree.update(3, 1).unwrap();
+        tree.update(3, -1).unwrap();
+
+        assert_eq!(tree.len(), 2);
+        assert_eq!(tree.first_index(), Some(1 << 40));
+        assert_eq!(tree.last_index(), Some(usize::MAX - 1));
+        assert_eq!(
+            tree.iter().collect::<Vec<_>>(),
+            vec![(1 << 40, &2), (usize::MAX - 1, &4)]
+        );
+        assert_eq!(tree.query(usize::MAX - 1).unwrap(), 6);
+        assert_eq!(tree.query(1 << 40).unwrap(), 2);
+        assert_eq!(tree.query((1 << 40) - 1).unwrap(), 0);
+        assert_eq!(
+            tree.update(usize::MAX, 1),
+            Err(TreeError::IndexOutOfBounds {
+                index: usize::MAX,
+                bound: usize::MAX
+            })
+        );
+    }
+
+    #[test]
+    fn drops_cancelled_nodes() {
+        let mut tree = OrderedSparseFenwickTree::<i32>::new(1 << 20);
+        tree.update(12345, 5).unwrap();
+        tree.update(12345, -5).unwrap();
+
+        assert!(tree.is_empty());
+        assert!(tree.nodes.is_empty());
+    }
+}
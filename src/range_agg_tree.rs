@@ -0,0 +1,228 @@
+use crate::{FenwickReader, FenwickTreeValue, FenwickWriter, TreeError};
+
+/// Segment tree companion of Fenwick trees, answering aggregates over arbitrary
+/// `[from, to]` ranges for values which can't be inverted, like [`crate::Max`] or
+/// [`crate::Gcd`], in `O(log n)`. Values can be overwritten with
+/// [`RangeAggTree::set`], not only combined with [`FenwickWriter::update`].
+///
+/// Same [`FenwickTreeValue`] values are stored as in Fenwick trees, default value
+/// being the identity of aggregation. Leaves are stored after internal nodes in a
+/// single vector, so tree takes twice the memory of a Fenwick tree.
+///
+/// ```
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut tree = RangeAggTree::from_fn(6, |i| Max([5, 1, 4, 2, 8, 3][i]));
+/// assert_eq!(tree.aggregate(1, 3).unwrap(), Max(4));
+///
+/// tree.set(2, Max(0)).unwrap();
+/// assert_eq!(tree.aggregate(1, 3).unwrap(), Max(2));
+/// assert_eq!(tree.query(5).unwrap(), Max(8));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RangeAggTree<T: FenwickTreeValue> {
+    /// Node `i` aggregates nodes `2 * i` and `2 * i + 1`, leaves start at `size`.
+    data: Vec<T>,
+}
+
+impl<T: FenwickTreeValue> RangeAggTree<T> {
+    pub fn new(size: usize) -> Self {
+        Self {
+            data: vec![T::default(); 2 * size],
+        }
+    }
+
+    /// Creates tree of `size` indexes holding `f(i)` at index `i`, in `O(n)`.
+    pub fn from_fn(size: usize, f: impl FnMut(usize) -> T) -> Self {
+        let mut data = vec![T::default(); size];
+        data.extend((0..size).map(f));
+        for position in (1..size).rev() {
+            refresh_node(&mut data, position);
+        }
+        Self { data }
+    }
+
+    pub fn size(&self) -> usize {
+        self.data.len() / 2
+    }
+
+    /// Returns value stored at `idx`, in `O(1)`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `idx` is out of bounds.
+    ///
+    pub fn get(&self, idx: usize) -> Result<&T, TreeError> {
+        self.check_idx(idx)?;
+        Ok(&self.data[self.size() + idx])
+    }
+
+    /// Replaces value stored at `idx` with `value`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `idx` is out of bounds, or `value` is
+    /// rejected by [`FenwickTreeValue::is_valid`].
+    ///
+    pub fn set(&mut self, idx: usize, value: T) -> Result<(), TreeError> {
+        self.check_idx(idx)?;
+        if !value.is_valid() {
+            return Err(TreeError::InvalidValue);
+        }
+        let position = self.size() + idx;
+        self.data[position] = value;
+        self.refresh_parents(position);
+        Ok(())
+    }
+
+    /// Returns aggregate of values stored between `from` and `to` (including edges).
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `from` is greater than `to`, or `to` is
+    /// out of bounds.
+    ///
+    pub fn aggregate(&self, from: usize, to: usize) -> Result<T, TreeError> {
+        if from > to {
+            return Err(TreeError::InvalidRange { from, to });
+        }
+        self.check_idx(to)?;
+
+        let mut res = T::default();
+        let (mut lower, mut upper) = (self.size() + from, self.size() + to + 1);
+        while lower < upper {
+            if lower & 1 == 1 {
+                res.store_value(&self.data[lower]);
+                lower += 1;
+            }
+            if upper & 1 == 1 {
+                upper -= 1;
+                res.store_value(&self.data[upper]);
+            }
+            lower /= 2;
+            upper /= 2;
+        }
+        Ok(res)
+    }
+
+    fn refresh_parents(&mut self, mut position: usize) {
+        while position > 1 {
+            position /= 2;
+            refresh_node(&mut self.data, position);
+        }
+    }
+
+    fn check_idx(&self, idx: usize) -> Result<(), TreeError> {
+        if idx >= self.size() {
+            return Err(TreeError::IndexOutOfBounds {
+                index: idx,
+                bound: self.size(),
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Recomputes internal node at `position` from its children.
+fn refresh_node<T: FenwickTreeValue>(data: &mut [T], position: usize) {
+    let (parents, children) = data.split_at_mut(2 * position);
+    parents[position].clone_from(&children[0]);
+    parents[position].store_value(&children[1]);
+}
+
+impl<T: FenwickTreeValue> FenwickReader for RangeAggTree<T> {
+    type Value = T;
+
+    fn query(&self, idx: usize) -> Result<T, TreeError> {
+        self.aggregate(0, idx)
+    }
+}
+
+impl<T: FenwickTreeValue> FenwickWriter for RangeAggTree<T> {
+    fn update(&mut self, idx: usize, value: T) -> Result<(), TreeError> {
+        self.check_idx(idx)?;
+        if !value.is_valid() {
+            return Err(TreeError::InvalidValue);
+        }
+        if value.is_identity() {
+            return Ok(());
+        }
+        let position = self.size() + idx;
+        self.data[position].absorb(value);
+        self.refresh_parents(position);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::range_agg_tree::RangeAggTree;
+    use crate::workload::Workload;
+    use crate::{FenwickReader, FenwickWriter, Gcd, Max, TreeError};
+
+    #[test]
+    fn matches_naive_ranges() {
+        for size in [1, 2, 7, 16, 33] {
+            let seed = size as u64;
+            let mut naive: Vec<i32> = Workload::new(seed, 1)
+                .deltas(0..100)
+                .updates()
+                .map(|(_, value)| value as i32)
+                .take(size)
+                .collect();
+            let mut tree = RangeAggTree::from_fn(size, |i| Max(naive[i]));
+
+            let updates = Workload::new(seed, size).deltas(0..100).updates();
+            let coins = Workload::new(seed, 2).updates();
+            for ((idx, value), (coin, _)) in updates.zip(coins).take(20) {
+                let value = value as i32;
+                if coin == 0 {
+                    tree.set(idx, Max(value)).unwrap();
+                    naive[idx] = value;
+                } else {
+                    tree.update(idx, Max(value)).unwrap();
+                    naive[idx] = naive[idx].max(value);
+                }
+            }
+
+            for from in 0..size {
+                assert_eq!(tree.get(from).unwrap(), &Max(naive[from]));
+                for to in from..size {
+                    let expected = *naive[from..=to].iter().max().unwrap();
+                    assert_eq!(tree.aggregate(from, to).unwrap(), Max(expected));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn gcd_ranges() {
+        let mut tree = RangeAggTree::<Gcd<u64>>::new(5);
+        for (idx, value) in [12, 18, 7, 35, 21].into_iter().enumerate() {
+            tree.update(idx, Gcd(value)).unwrap();
+        }
+        assert_eq!(tree.aggregate(0, 1).unwrap(), Gcd(6));
+        assert_eq!(tree.aggregate(2, 4).unwrap(), Gcd(7));
+        assert_eq!(tree.query(4).unwrap(), Gcd(1));
+    }
+
+    #[test]
+    fn bounds() {
+        let mut tree = RangeAggTree::<Max<u8>>::new(4);
+        assert_eq!(tree.size(), 4);
+        assert_eq!(
+            tree.aggregate(3, 2),
+            Err(TreeError::InvalidRange { from: 3, to: 2 })
+        );
+        assert_eq!(
+            tree.aggregate(0, 4),
+            Err(TreeError::IndexOutOfBounds { index: 4, bound: 4 })
+        );
+        assert_eq!(
+            tree.set(4, Max(1)),
+            Err(TreeError::IndexOutOfBounds { index: 4, bound: 4 })
+        );
+        assert!(tree.update(4, Max(1)).is_err());
+        assert!(RangeAggTree::<Max<u8>>::new(0).query(0).is_err());
+    }
+}
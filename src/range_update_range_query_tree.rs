@@ -0,0 +1,208 @@
+use crate::{
+    FenwickReader, FenwickTreeValue, FenwickWriter, FixedSizeFenwickTree, InvertibleValue,
+    TreeError,
+};
+
+/// Fixed size tree supporting additions to whole ranges and range sums, both in
+/// `O(log n)`. Plain [`FenwickWriter::range_update`] goes through ranges index by
+/// index, this tree doesn't.
+///
+/// Classic construction of two trees: one holds changes at range edges, the other holds
+/// the same changes multiplied by their index, which turns prefix sums into a
+/// difference of two prefix queries. Changes are negated at range ends, so values
+/// should be signed. Multiplying by an index aggregates a value with itself
+/// `O(log n)` times, so any [`InvertibleValue`] works.
+///
+/// ```
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut tree = RangeUpdateRangeQueryFenwickTree::<i64>::new(10);
+/// tree.update_range(2, 6, 3).unwrap();
+/// tree.update(4, 10).unwrap();
+///
+/// assert_eq!(tree.point_query(4).unwrap(), 13);
+/// assert_eq!(tree.query(9).unwrap(), 25);
+/// assert_eq!(tree.range_query(3, 5).unwrap(), 16);
+/// ```
+#[derive(Clone)]
+pub struct RangeUpdateRangeQueryFenwickTree<T: InvertibleValue> {
+    changes: FixedSizeFenwickTree<T>,
+    /// Changes multiplied by index they are stored at.
+    weighted_changes: FixedSizeFenwickTree<T>,
+}
+
+impl<T: InvertibleValue> RangeUpdateRangeQueryFenwickTree<T> {
+    pub fn new(size: usize) -> Self {
+        Self {
+            changes: FixedSizeFenwickTree::new(size),
+            weighted_changes: FixedSizeFenwickTree::new(size),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.changes.data.len() - 1
+    }
+
+    /// Adds `delta` to every index in between `from` and `to` (including edges).
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `from` is greater than `to`, `to` is out
+    /// of bounds, or `delta` is rejected by [`FenwickTreeValue::is_valid`]. The tree is
+    /// left unchanged in those cases.
+    ///
+    pub fn update_range(&mut self, from: usize, to: usize, delta: T) -> Result<(), TreeError> {
+        if from > to {
+            return Err(TreeError::InvalidRange { from, to });
+        }
+        if to >= self.size() {
+            return Err(TreeError::IndexOutOfBounds {
+                index: to,
+                bound: self.size(),
+            });
+        }
+        if !delta.is_valid() {
+            return Err(TreeError::InvalidValue);
+        }
+
+        // Change after the last index would only affect indexes beyond the tree.
+        if to + 1 < self.size() {
+            let negated = T::default().substract(delta.clone());
+            self.weighted_changes
+                .update(to + 1, repeated(&negated, to + 1))?;
+            self.changes.update(to + 1, negated)?;
+        }
+        self.weighted_changes.update(from, repeated(&delta, from))?;
+        self.changes.update(from, delta)
+    }
+
+    /// Returns value stored at `idx`, in `O(log n)`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `idx` is out of bounds.
+    ///
+    pub fn point_query(&self, idx: usize) -> Result<T, TreeError> {
+        self.changes.query(idx)
+    }
+}
+
+/// Returns `value` aggregated with itself `times` times, in `O(log times)`.
+fn repeated<T: FenwickTreeValue>(value: &T, mut times: usize) -> T {
+    let mut res = T::default();
+    let mut power = value.clone();
+    while times > 0 {
+        if times & 1 == 1 {
+            res.store_value(&power);
+        }
+        times >>= 1;
+        if times > 0 {
+            let doubled = power.clone();
+            power.store_value(&doubled);
+        }
+    }
+    res
+}
+
+impl<T: InvertibleValue> FenwickReader for RangeUpdateRangeQueryFenwickTree<T> {
+    type Value = T;
+
+    fn query(&self, idx: usize) -> Result<T, TreeError> {
+        let changes = self.changes.query(idx)?;
+        let weighted_changes = self.weighted_changes.query(idx)?;
+        Ok(repeated(&changes, idx + 1).substract(weighted_changes))
+    }
+}
+
+impl<T: InvertibleValue> FenwickWriter for RangeUpdateRangeQueryFenwickTree<T> {
+    fn update(&mut self, idx: usize, value: T) -> Result<(), TreeError> {
+        self.update_range(idx, idx, value)
+    }
+
+    /// Same as [`RangeUpdateRangeQueryFenwickTree::update_range`] of indexes after
+    /// `from` up to `to`. Unlike default implementation, ranges are applied as a
+    /// whole or not at all.
+    fn range_update(&mut self, from: usize, to: usize, value: T) -> Result<(), TreeError>
+    where
+        T: InvertibleValue,
+    {
+        if from > to {
+            return Err(TreeError::InvalidRange { from, to });
+        }
+        if from == to {
+            return Ok(());
+        }
+        self.update_range(from + 1, to, value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::range_update_range_query_tree::{repeated, RangeUpdateRangeQueryFenwickTree};
+    use crate::workload::Workload;
+    use crate::{FenwickReader, FenwickWriter, ModInt, TreeError};
+
+    #[test]
+    fn matches_naive_ranges() {
+        for size in [1, 2, 13, 64] {
+            let mut tree = RangeUpdateRangeQueryFenwickTree::<i64>::new(size);
+            let mut naive = vec![0i64; size];
+            let ranges = Workload::new(size as u64, size).ranges();
+            let deltas = Workload::new(size as u64, 5).deltas(-20..20).updates();
+            for ((from, to), (coin, delta)) in ranges.zip(deltas).take(50) {
+                if coin < 4 {
+                    tree.range_update(from, to, delta).unwrap();
+                    naive[from + 1..=to]
+                        .iter_mut()
+                        .for_each(|value| *value += delta);
+                } else {
+                    tree.update(to, delta).unwrap();
+                    naive[to] += delta;
+                }
+            }
+            for (idx, value) in naive.iter().enumerate() {
+                assert_eq!(tree.point_query(idx).unwrap(), *value);
+            }
+
+            for to in 0..size {
+                let expected: i64 = naive[..=to].iter().sum();
+                assert_eq!(tree.query(to).unwrap(), expected);
+                for from in 0..=to {
+                    let expected: i64 = naive[from + 1..=to].iter().sum();
+                    assert_eq!(tree.range_query(from, to).unwrap(), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn modular_values() {
+        let mut tree = RangeUpdateRangeQueryFenwickTree::<ModInt<7>>::new(6);
+        tree.update_range(1, 4, ModInt::new(3)).unwrap();
+        assert_eq!(tree.query(5).unwrap(), ModInt::new(5));
+        assert_eq!(tree.range_query(0, 2).unwrap(), ModInt::new(6));
+        assert_eq!(repeated(&ModInt::<7>::new(3), 5), ModInt::new(1));
+        assert_eq!(repeated(&4i64, 0), 0);
+    }
+
+    #[test]
+    fn bounds() {
+        let mut tree = RangeUpdateRangeQueryFenwickTree::<f64>::new(4);
+        assert_eq!(tree.size(), 4);
+        assert_eq!(
+            tree.update_range(2, 1, 1.0),
+            Err(TreeError::InvalidRange { from: 2, to: 1 })
+        );
+        assert_eq!(
+            tree.range_update(1, 4, 1.0),
+            Err(TreeError::IndexOutOfBounds { index: 4, bound: 4 })
+        );
+        assert_eq!(tree.update(0, f64::NAN), Err(TreeError::InvalidValue));
+        assert_eq!(
+            tree.query(4),
+            Err(TreeError::IndexOutOfBounds { index: 4, bound: 4 })
+        );
+        tree.update_range(0, 3, 0.5).unwrap();
+        assert_eq!(tree.range_query(0, 3).unwrap(), 1.5);
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{FenwickReader, FenwickWriter, FixedSizeFenwickTree, InvertibleValue, TreeError};
+
+/// Fixed size tree of `size` values supporting additions to whole ranges and point
+/// reads, both in `O(log n)`, such as rate limits applied over time windows.
+///
+/// Classic dual construction: the inner tree holds changes at range edges, so prefix
+/// sum of changes up to an index is the value stored there.
+///
+/// ```
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut limits = RangeUpdateFenwickTree::<i64>::new(24);
+/// limits.update_range(8, 17, 100).unwrap();
+/// limits.update_range(12, 23, -30).unwrap();
+///
+/// assert_eq!(limits.point_query(7).unwrap(), 0);
+/// assert_eq!(limits.point_query(12).unwrap(), 70);
+/// assert_eq!(limits.point_query(20).unwrap(), -30);
+/// ```
+#[derive(Clone)]
+pub struct RangeUpdateFenwickTree<T: InvertibleValue> {
+    changes: FixedSizeFenwickTree<T>,
+}
+
+impl<T: InvertibleValue> RangeUpdateFenwickTree<T> {
+    pub fn new(size: usize) -> Self {
+        Self {
+            changes: FixedSizeFenwickTree::new(size),
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.changes.data.len() - 1
+    }
+
+    /// Adds `delta` to every index in between `from` and `to` (including edges).
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `from` is greater than `to`, `to` is out
+    /// of bounds, or `delta` is rejected by [`crate::FenwickTreeValue::is_valid`]. The
+    /// tree is left unchanged in those cases.
+    ///
+    pub fn update_range(&mut self, from: usize, to: usize, delta: T) -> Result<(), TreeError> {
+        if from > to {
+            return Err(TreeError::InvalidRange { from, to });
+        }
+        if to >= self.size() {
+            return Err(TreeError::IndexOutOfBounds {
+                index: to,
+                bound: self.size(),
+            });
+        }
+        if !delta.is_valid() {
+            return Err(TreeError::InvalidValue);
+        }
+
+        // Change after the last index would only affect indexes beyond the tree.
+        if to + 1 < self.size() {
+            self.changes
+                .update(to + 1, T::default().substract(delta.clone()))?;
+        }
+        self.changes.update(from, delta)
+    }
+
+    /// Returns value stored at `idx`, sum of deltas of every range covering it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `idx` is out of bounds.
+    ///
+    pub fn point_query(&self, idx: usize) -> Result<T, TreeError> {
+        self.changes.query(idx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::range_update_tree::RangeUpdateFenwickTree;
+    use crate::workload::Workload;
+    use crate::{ModInt, TreeError};
+
+    #[test]
+    fn matches_naive_ranges() {
+        for size in [1, 2, 9, 64] {
+            let mut tree = RangeUpdateFenwickTree::<i64>::new(size);
+            let mut naive = vec![0i64; size];
+            let ranges = Workload::new(size as u64, size).ranges();
+            let deltas = Workload::new(size as u64, 1).deltas(-20..20).updates();
+            for ((from, to), (_, delta)) in ranges.zip(deltas).take(50) {
+                tree.update_range(from, to, delta).unwrap();
+                naive[from..=to]
+                    .iter_mut()
+                    .for_each(|value| *value += delta);
+            }
+            for (idx, value) in naive.into_iter().enumerate() {
+                assert_eq!(tree.point_query(idx).unwrap(), value);
+            }
+        }
+    }
+
+    #[test]
+    fn modular_values() {
+        let mut tree = RangeUpdateFenwickTree::<ModInt<7>>::new(5);
+        tree.update_range(0, 4, ModInt::new(5)).unwrap();
+        tree.update_range(2, 3, ModInt::new(4)).unwrap();
+        assert_eq!(tree.point_query(1).unwrap(), ModInt::new(5));
+        assert_eq!(tree.point_query(3).unwrap(), ModInt::new(2));
+        assert_eq!(tree.point_query(4).unwrap(), ModInt::new(5));
+    }
+
+    #[test]
+    fn bounds() {
+        let mut tree = RangeUpdateFenwickTree::<f64>::new(4);
+        assert_eq!(tree.size(), 4);
+        assert_eq!(
+            tree.update_range(2, 1, 1.0),
+            Err(TreeError::InvalidRange { from: 2, to: 1 })
+        );
+        assert_eq!(
+            tree.update_range(1, 4, 1.0),
+            Err(TreeError::IndexOutOfBounds { index: 4, bound: 4 })
+        );
+        assert_eq!(
+            tree.update_range(0, 1, f64::NAN),
+            Err(TreeError::InvalidValue)
+        );
+        assert_eq!(
+            tree.point_query(4),
+            Err(TreeError::IndexOutOfBounds { index: 4, bound: 4 })
+        );
+        assert_eq!(tree.point_query(3).unwrap(), 0.0);
+    }
+}
@@ -0,0 +1,157 @@
+//! Low-level traversal of Fenwick tree nodes, which trees of this crate are built on,
+//! for custom operations over caller owned node storage, like updating several trees
+//! in a single pass.
+//!
+//! Nodes are addressed by 1-based positions: index `idx` is stored at position
+//! `idx + 1`, node at `position` holds aggregate of [`least_significant_bit`] indexes
+//! ending at `position - 1`, and position 0 is never visited.
+//!
+//! ```
+//! use fenwick_bit_tree::raw::{lsb_ascending, lsb_descending};
+//!
+//! // Count and total of trades of 8 price levels, node 0 is unused.
+//! let (mut counts, mut totals) = (vec![0u32; 9], vec![0i64; 9]);
+//! for (level, amount) in [(2, 10), (5, 7), (2, 3)] {
+//!     for position in lsb_ascending(level + 1, 8) {
+//!         counts[position] += 1;
+//!         totals[position] += amount;
+//!     }
+//! }
+//!
+//! let (count, total) = lsb_descending(4 + 1).fold((0, 0), |(count, total), position| {
+//!     (count + counts[position], total + totals[position])
+//! });
+//! assert_eq!((count, total), (2, 13));
+//! ```
+
+use std::iter::FusedIterator;
+
+/// Returns the lowest set bit of `position`, the number of indexes node at `position`
+/// aggregates. Returns 0 for 0.
+pub fn least_significant_bit(position: usize) -> usize {
+    position & position.wrapping_neg()
+}
+
+/// Returns positions of nodes a prefix query up to `position` aggregates, from
+/// `position` down by removing the lowest set bit.
+pub fn lsb_descending(position: usize) -> LsbDescending {
+    LsbDescending { position }
+}
+
+/// Returns positions of nodes an update of `position` changes, from `position` up by
+/// adding the lowest set bit, while not greater than `upper_bound`. Nothing is
+/// returned for position 0.
+pub fn lsb_ascending(position: usize, upper_bound: usize) -> LsbAscending {
+    LsbAscending {
+        position,
+        upper_bound,
+    }
+}
+
+/// Iterator returned by [`lsb_descending`].
+#[derive(Debug, Clone)]
+pub struct LsbDescending {
+    position: usize,
+}
+
+impl Iterator for LsbDescending {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.position == 0 {
+            return None;
+        }
+        let position = self.position;
+        self.position -= least_significant_bit(position);
+        Some(position)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.position.count_ones() as usize;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for LsbDescending {}
+
+impl FusedIterator for LsbDescending {}
+
+/// Iterator returned by [`lsb_ascending`].
+#[derive(Debug, Clone)]
+pub struct LsbAscending {
+    position: usize,
+    upper_bound: usize,
+}
+
+impl Iterator for LsbAscending {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        if self.position == 0 || self.position > self.upper_bound {
+            return None;
+        }
+        let position = self.position;
+        // Past the last representable position iteration stops.
+        self.position = position
+            .checked_add(least_significant_bit(position))
+            .unwrap_or(0);
+        Some(position)
+    }
+}
+
+impl FusedIterator for LsbAscending {}
+
+#[cfg(test)]
+mod tests {
+    use crate::raw::{least_significant_bit, lsb_ascending, lsb_descending};
+    use crate::{FenwickReader, FenwickWriter, FixedSizeFenwickTree};
+
+    #[test]
+    fn descending_positions() {
+        assert_eq!(lsb_descending(6).collect::<Vec<_>>(), vec![6, 4]);
+        assert_eq!(lsb_descending(7).collect::<Vec<_>>(), vec![7, 6, 4]);
+        assert_eq!(lsb_descending(8).collect::<Vec<_>>(), vec![8]);
+        assert_eq!(lsb_descending(0).count(), 0);
+        assert_eq!(lsb_descending(usize::MAX).len(), usize::BITS as usize);
+    }
+
+    #[test]
+    fn ascending_positions() {
+        assert_eq!(
+            lsb_ascending(1, 64).collect::<Vec<_>>(),
+            vec![1, 2, 4, 8, 16, 32, 64]
+        );
+        assert_eq!(lsb_ascending(5, 12).collect::<Vec<_>>(), vec![5, 6, 8]);
+        assert_eq!(lsb_ascending(6, 0).count(), 0);
+        assert_eq!(lsb_ascending(0, 64).count(), 0);
+
+        let top = 1 << (usize::BITS - 1);
+        assert_eq!(
+            lsb_ascending(top, usize::MAX).collect::<Vec<_>>(),
+            vec![top]
+        );
+        assert_eq!(least_significant_bit(12), 4);
+        assert_eq!(least_significant_bit(0), 0);
+    }
+
+    #[test]
+    fn matches_tree_traversal() {
+        let size = 13;
+        let mut tree = FixedSizeFenwickTree::<i64>::new(size);
+        let mut nodes = vec![0i64; size + 1];
+        for idx in 0..size {
+            let value = (idx * idx % 7) as i64;
+            tree.update(idx, value).unwrap();
+            for position in lsb_ascending(idx + 1, size) {
+                nodes[position] += value;
+            }
+        }
+
+        for idx in 0..size {
+            let prefix: i64 = lsb_descending(idx + 1)
+                .map(|position| nodes[position])
+                .sum();
+            assert_eq!(prefix, tree.query(idx).unwrap());
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use crate::iterators::PrefixSums;
+use crate::{least_significant_bit, FenwickTreeValue, InvertibleValue};
+
+/// Returns number of leading prefixes satisfying `pred`, found by descending through
+/// raw internal representation (node 0 unused) in `O(log n)`.
+///
+/// `pred` must be monotone: once it is `false` for some prefix, it must stay `false`
+/// for all longer prefixes.
+pub(crate) fn partition_point<T: FenwickTreeValue>(data: &[T], pred: impl Fn(&T) -> bool) -> usize {
+    let size = data.len().saturating_sub(1);
+    if size == 0 {
+        return 0;
+    }
+
+    let mut position = 0;
+    let mut aggregate = T::default();
+    let mut step = 1 << size.ilog2();
+
+    while step > 0 {
+        if position + step <= size {
+            let mut candidate = aggregate.clone();
+            candidate.store_value(&data[position + step]);
+            if pred(&candidate) {
+                position += step;
+                aggregate = candidate;
+            }
+        }
+        step >>= 1;
+    }
+
+    position
+}
+
+/// Returns number of leading points holding at most `k` zero counts, together with
+/// the number of zeros among them, found by descending through raw internal
+/// representation in `O(log n)`. Points are expected to hold counts of 0 or 1, so a
+/// node covering `step` points has `step - count` zeros.
+pub(crate) fn kth_missing<T>(data: &[T], k: usize, count: impl Fn(&T) -> usize) -> (usize, usize) {
+    let size = data.len().saturating_sub(1);
+    if size == 0 {
+        return (0, 0);
+    }
+
+    let mut position = 0;
+    let mut missing = 0;
+    let mut step = 1 << size.ilog2();
+
+    while step > 0 {
+        if position + step <= size {
+            let candidate = missing + step.saturating_sub(count(&data[position + step]));
+            if candidate <= k {
+                position += step;
+                missing = candidate;
+            }
+        }
+        step >>= 1;
+    }
+
+    (position, missing)
+}
+
+/// Returns the shortest `[from, to]` range of points which sum reaches `target`,
+/// the leftmost one among equally short ranges. Every range end is matched with the
+/// latest start found by prefix search, in `O(n log n)`. Values are expected to be
+/// non-negative.
+pub(crate) fn min_window_with_sum<T>(data: &[T], target: &T) -> Option<(usize, usize)>
+where
+    T: InvertibleValue + PartialOrd,
+{
+    let mut best: Option<(usize, usize)> = None;
+    for (to, sum) in PrefixSums::new(data, 0..=usize::MAX) {
+        if sum < *target {
+            continue;
+        }
+        let before = sum.substract(target.clone());
+        let from = partition_point(data, |prefix| *prefix <= before).min(to);
+        match best {
+            Some((best_from, best_to)) if best_to - best_from <= to - from => {}
+            _ => best = Some((from, to)),
+        }
+    }
+    best
+}
+
+/// Answers `partition_point(|sum| sum < target)` for every target at once. Targets are
+/// processed in ascending order, so all targets sharing a descent path are moved down
+/// the tree together and every node is visited once per group rather than once per
+/// target. Returns [`None`] for targets not reached by the total sum.
+///
+/// Sums are compared with targets through `key` projection.
+pub(crate) fn select_many<T, K>(
+    data: &[T],
+    targets: &[K],
+    key: impl Fn(&T) -> &K + Copy,
+) -> Vec<Option<usize>>
+where
+    T: FenwickTreeValue,
+    K: PartialOrd,
+{
+    let size = data.len().saturating_sub(1);
+    let mut order: Vec<usize> = (0..targets.len()).collect();
+    order.sort_by(|left, right| {
+        targets[*left]
+            .partial_cmp(&targets[*right])
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let mut positions = vec![0; targets.len()];
+    if size > 0 {
+        descend(
+            data,
+            targets,
+            &order,
+            &mut positions,
+            (0, T::default()),
+            1 << size.ilog2(),
+            key,
+        );
+    }
+
+    positions
+        .into_iter()
+        .map(|position| (position < size).then_some(position))
+        .collect()
+}
+
+fn descend<T, K>(
+    data: &[T],
+    targets: &[K],
+    group: &[usize],
+    positions: &mut [usize],
+    (position, aggregate): (usize, T),
+    step: usize,
+    key: impl Fn(&T) -> &K + Copy,
+) where
+    T: FenwickTreeValue,
+    K: PartialOrd,
+{
+    if group.is_empty() {
+        return;
+    }
+
+    if step == 0 {
+        for target in group {
+            positions[*target] = position;
+        }
+        return;
+    }
+
+    let next_step = step >> 1;
+
+    if position + step > data.len() - 1 {
+        return descend(
+            data,
+            targets,
+            group,
+            positions,
+            (position, aggregate),
+            next_step,
+            key,
+        );
+    }
+
+    let mut candidate = aggregate.clone();
+    candidate.store_value(&data[position + step]);
+
+    // Targets are sorted, so the ones already reached by candidate form a prefix.
+    let split = group.partition_point(|target| targets[*target] <= *key(&candidate));
+    let (stay, advance) = group.split_at(split);
+
+    descend(
+        data,
+        targets,
+        stay,
+        positions,
+        (position, aggregate),
+        next_step,
+        key,
+    );
+    descend(
+        data,
+        targets,
+        advance,
+        positions,
+        (position + step, candidate),
+        next_step,
+        key,
+    );
+}
+
+/// Aggregate of all values stored in raw internal representation.
+pub(crate) fn total<T: FenwickTreeValue>(data: &[T]) -> T {
+    prefix(data, data.len().saturating_sub(1))
+}
+
+/// Aggregate of the first `points` values stored in raw internal representation.
+pub(crate) fn prefix<T: FenwickTreeValue>(data: &[T], points: usize) -> T {
+    let mut res = T::default();
+    let mut node = points;
+    while node > 0 {
+        res.store_value(&data[node]);
+        node -= least_significant_bit(node);
+    }
+    res
+}
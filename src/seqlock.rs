@@ -0,0 +1,131 @@
+#[cfg(loom)]
+use loom::{
+    hint,
+    sync::atomic::{fence, AtomicU64, Ordering},
+};
+#[cfg(not(loom))]
+use std::{
+    hint,
+    sync::atomic::{fence, AtomicU64, Ordering},
+};
+
+/// Runs `write` under sequence lock `sequence`, which is odd while it runs. Only one
+/// writer may hold the lock at a time, and nodes touched by `write` have to be atomics,
+/// accessed with any ordering.
+pub(crate) fn write<R>(sequence: &AtomicU64, write: impl FnOnce() -> R) -> R {
+    sequence.fetch_add(1, Ordering::Relaxed);
+    fence(Ordering::Release);
+    let res = write();
+    sequence.fetch_add(1, Ordering::Release);
+    res
+}
+
+/// Runs `read` until it completes while no writer holds sequence lock `sequence`, so
+/// it observes either none or all of the nodes touched by every write. Spins while a
+/// write is in progress, which is forever if the writer never completes it. Errors of
+/// `read` are returned right away.
+pub(crate) fn read<R, E>(
+    sequence: &AtomicU64,
+    mut read: impl FnMut() -> Result<R, E>,
+) -> Result<R, E> {
+    loop {
+        let before = sequence.load(Ordering::Acquire);
+        if before % 2 == 0 {
+            let res = read()?;
+            fence(Ordering::Acquire);
+            if sequence.load(Ordering::Relaxed) == before {
+                return Ok(res);
+            }
+        }
+        hint::spin_loop();
+    }
+}
+
+// Model checked with `RUSTFLAGS="--cfg loom" cargo test --release --lib seqlock`.
+#[cfg(all(test, loom))]
+mod tests {
+    use std::convert::Infallible;
+
+    use loom::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+    use loom::sync::Arc;
+    use loom::thread;
+
+    use crate::seqlock;
+
+    /// Nodes of a tree of two indexes, both of which are touched by update of index 0.
+    struct Tree {
+        sequence: AtomicU64,
+        nodes: [AtomicI64; 2],
+    }
+
+    impl Tree {
+        fn new() -> Self {
+            Self {
+                sequence: AtomicU64::new(0),
+                nodes: [AtomicI64::new(0), AtomicI64::new(0)],
+            }
+        }
+
+        fn update(&self, value: i64) {
+            seqlock::write(&self.sequence, || {
+                for node in &self.nodes {
+                    node.fetch_add(value, Ordering::Relaxed);
+                }
+            });
+        }
+
+        fn query(&self) -> (u64, [i64; 2]) {
+            let read = seqlock::read(&self.sequence, || {
+                Ok::<_, Infallible>([
+                    self.nodes[0].load(Ordering::Relaxed),
+                    self.nodes[1].load(Ordering::Relaxed),
+                ])
+            });
+            let version = self.sequence.load(Ordering::Acquire) / 2;
+            (version, read.unwrap())
+        }
+    }
+
+    #[test]
+    fn query_observes_whole_updates() {
+        loom::model(|| {
+            let tree = Arc::new(Tree::new());
+            let writer = {
+                let tree = Arc::clone(&tree);
+                thread::spawn(move || {
+                    tree.update(1);
+                    tree.update(2);
+                })
+            };
+
+            let (version, [first, second]) = tree.query();
+            assert_eq!(first, second);
+            // Nodes hold sums of the first `applied` updates. Version is read after
+            // them, so it never lags behind.
+            let applied = [0, 1, 3].iter().position(|sum| *sum == first).unwrap();
+            assert!(version >= applied as u64);
+
+            writer.join().unwrap();
+            assert_eq!(tree.query(), (2, [3, 3]));
+        });
+    }
+
+    #[test]
+    fn queries_never_go_back() {
+        loom::model(|| {
+            let tree = Arc::new(Tree::new());
+            let reader = {
+                let tree = Arc::clone(&tree);
+                thread::spawn(move || {
+                    let (_, [first, _]) = tree.query();
+                    let (_, [second, _]) = tree.query();
+                    assert!(first <= second);
+                })
+            };
+
+            tree.update(1);
+            tree.update(2);
+            reader.join().unwrap();
+        });
+    }
+}
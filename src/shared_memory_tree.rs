@@ -0,0 +1,378 @@
+use std::ffi::CString;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
+use crate::{least_significant_bit, seqlock, FenwickReader, FenwickWriter, TreeError};
+
+const MAGIC: u64 = u64::from_le_bytes(*b"fenwick1");
+/// Magic, size and sequence words preceding tree nodes.
+const HEADER_WORDS: usize = 3;
+
+/// Tree which nodes live in a named POSIX shared memory segment, so one writer process
+/// and any number of [`SharedMemoryReader`] processes share live sums without
+/// serializing them. Values are [`i64`], sums wrap on overflow.
+///
+/// Readers never block the writer. Updates are guarded by a sequence lock: a query
+/// retries until it reads no update in progress, so it observes either none or all of
+/// the nodes touched by an update. If the writer process dies in the middle of an
+/// update, the update never completes and queries of its readers spin forever.
+///
+/// The segment is removed when the tree is dropped, readers which have it open keep
+/// their mapping.
+///
+/// ```
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let name = format!("/fenwick-doc-{}", std::process::id());
+/// let mut tree = SharedMemoryFenwickTree::create(&name, 8).unwrap();
+/// tree.update(3, 5).unwrap();
+///
+/// // Usually done in another process.
+/// let reader = SharedMemoryReader::open(&name).unwrap();
+/// assert_eq!(reader.query(7).unwrap(), 5);
+/// assert_eq!(reader.version(), 1);
+/// ```
+pub struct SharedMemoryFenwickTree {
+    segment: Segment,
+    name: CString,
+}
+
+/// Read-only view of a [`SharedMemoryFenwickTree`] opened by name, usually from
+/// another process.
+pub struct SharedMemoryReader {
+    segment: Segment,
+}
+
+impl SharedMemoryFenwickTree {
+    /// Creates segment `name` holding a tree of `size` indexes. Names follow
+    /// `shm_open` rules, portable ones start with `/` and contain no other slashes.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if segment exists already, or can't be
+    /// created or mapped.
+    ///
+    pub fn create(name: &str, size: usize) -> Result<Self, TreeError> {
+        let name = c_name(name)?;
+        let words = size
+            .checked_add(HEADER_WORDS + 1)
+            .ok_or(TreeError::Overflow)?;
+        let segment = Segment::create(&name, words)?;
+        segment.word(1)?.store(size as u64, Ordering::Relaxed);
+        segment.word(0)?.store(MAGIC, Ordering::Release);
+        Ok(Self { segment, name })
+    }
+
+    pub fn size(&self) -> usize {
+        self.segment.size().unwrap_or_default()
+    }
+}
+
+impl Drop for SharedMemoryFenwickTree {
+    fn drop(&mut self) {
+        // SAFETY: `name` is a valid C string.
+        unsafe {
+            libc::shm_unlink(self.name.as_ptr());
+        }
+    }
+}
+
+impl SharedMemoryReader {
+    /// Opens segment `name` created by [`SharedMemoryFenwickTree::create`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if segment can't be opened or mapped, or
+    /// doesn't hold a tree.
+    ///
+    pub fn open(name: &str) -> Result<Self, TreeError> {
+        let segment = Segment::open(&c_name(name)?)?;
+        if segment.word(0)?.load(Ordering::Acquire) != MAGIC {
+            return Err(TreeError::CorruptSnapshot);
+        }
+        if segment.size()? >= segment.words - HEADER_WORDS {
+            return Err(TreeError::CorruptSnapshot);
+        }
+        Ok(Self { segment })
+    }
+
+    /// Returns size of the tree, or 0 if the segment no longer holds a valid one.
+    pub fn size(&self) -> usize {
+        self.segment.size().unwrap_or_default()
+    }
+
+    /// Returns number of updates applied by the writer so far.
+    pub fn version(&self) -> u64 {
+        self.segment
+            .word(2)
+            .map_or(0, |sequence| sequence.load(Ordering::Acquire) / 2)
+    }
+}
+
+impl FenwickReader for SharedMemoryFenwickTree {
+    type Value = i64;
+
+    fn query(&self, idx: usize) -> Result<i64, TreeError> {
+        self.segment.query(idx)
+    }
+}
+
+impl FenwickWriter for SharedMemoryFenwickTree {
+    fn update(&mut self, idx: usize, value: i64) -> Result<(), TreeError> {
+        let size = self.segment.check_bounds(idx)?;
+
+        // Sequence is released even if a node is missing, so readers never hang.
+        seqlock::write(self.segment.word(2)?, || {
+            self.segment.add_to_nodes(idx, size, value)
+        })
+    }
+}
+
+impl FenwickReader for SharedMemoryReader {
+    type Value = i64;
+
+    fn query(&self, idx: usize) -> Result<i64, TreeError> {
+        self.segment.query(idx)
+    }
+}
+
+fn c_name(name: &str) -> Result<CString, TreeError> {
+    CString::new(name).map_err(|_| TreeError::SharedMemory {
+        errno: libc::EINVAL,
+    })
+}
+
+fn last_error() -> TreeError {
+    TreeError::SharedMemory {
+        errno: std::io::Error::last_os_error()
+            .raw_os_error()
+            .unwrap_or_default(),
+    }
+}
+
+/// Mapping of a segment, only accessed through atomics.
+struct Segment {
+    ptr: NonNull<u64>,
+    words: usize,
+}
+
+// SAFETY: mapping is owned by the segment and only accessed through atomics.
+unsafe impl Send for Segment {}
+unsafe impl Sync for Segment {}
+
+impl Segment {
+    fn create(name: &CString, words: usize) -> Result<Self, TreeError> {
+        let len = words.checked_mul(8).ok_or(TreeError::Overflow)?;
+        let len_off = libc::off_t::try_from(len).map_err(|_| TreeError::Overflow)?;
+
+        // SAFETY: `name` is a valid C string, the descriptor is closed before
+        // returning, the segment is unlinked if it can't be mapped.
+        unsafe {
+            let fd = libc::shm_open(
+                name.as_ptr(),
+                libc::O_CREAT | libc::O_EXCL | libc::O_RDWR,
+                0o600,
+            );
+            if fd < 0 {
+                return Err(last_error());
+            }
+            let segment = if libc::ftruncate(fd, len_off) == 0 {
+                Self::map(fd, words, libc::PROT_READ | libc::PROT_WRITE)
+            } else {
+                Err(last_error())
+            };
+            libc::close(fd);
+            if segment.is_err() {
+                libc::shm_unlink(name.as_ptr());
+            }
+            segment
+        }
+    }
+
+    fn open(name: &CString) -> Result<Self, TreeError> {
+        // SAFETY: `name` is a valid C string, `stat` is plain data, the descriptor is
+        // closed before returning.
+        unsafe {
+            let fd = libc::shm_open(name.as_ptr(), libc::O_RDONLY, 0);
+            if fd < 0 {
+                return Err(last_error());
+            }
+            let mut stat: libc::stat = std::mem::zeroed();
+            let segment = if libc::fstat(fd, &mut stat) != 0 {
+                Err(last_error())
+            } else {
+                match usize::try_from(stat.st_size) {
+                    Ok(len) if len / 8 > HEADER_WORDS => Self::map(fd, len / 8, libc::PROT_READ),
+                    _ => Err(TreeError::CorruptSnapshot),
+                }
+            };
+            libc::close(fd);
+            segment
+        }
+    }
+
+    /// # Safety
+    ///
+    /// `fd` must be an open shared memory descriptor at least `words` words long.
+    unsafe fn map(fd: libc::c_int, words: usize, prot: libc::c_int) -> Result<Self, TreeError> {
+        let ptr = libc::mmap(
+            std::ptr::null_mut(),
+            words * 8,
+            prot,
+            libc::MAP_SHARED,
+            fd,
+            0,
+        );
+        if ptr == libc::MAP_FAILED {
+            return Err(last_error());
+        }
+        let ptr = NonNull::new(ptr.cast()).ok_or(TreeError::AllocationFailed)?;
+        Ok(Self { ptr, words })
+    }
+
+    /// Returns word `idx` of the mapping, failing if the mapping is shorter.
+    fn word(&self, idx: usize) -> Result<&AtomicU64, TreeError> {
+        if idx >= self.words {
+            return Err(TreeError::CorruptSnapshot);
+        }
+        // SAFETY: mapping is page aligned and `idx` lies within it.
+        Ok(unsafe { AtomicU64::from_ptr(self.ptr.as_ptr().add(idx)) })
+    }
+
+    fn node(&self, position: usize) -> Result<&AtomicI64, TreeError> {
+        let idx = position
+            .checked_add(HEADER_WORDS)
+            .filter(|idx| *idx < self.words)
+            .ok_or(TreeError::CorruptSnapshot)?;
+        // SAFETY: mapping is page aligned and `idx` lies within it.
+        Ok(unsafe { AtomicI64::from_ptr(self.ptr.as_ptr().add(idx).cast()) })
+    }
+
+    fn size(&self) -> Result<usize, TreeError> {
+        usize::try_from(self.word(1)?.load(Ordering::Relaxed))
+            .map_err(|_| TreeError::CorruptSnapshot)
+    }
+
+    /// Returns size of the tree once `idx` is known to lie within it. Size stored in
+    /// the segment is checked against its length, as another process may change it.
+    fn check_bounds(&self, idx: usize) -> Result<usize, TreeError> {
+        let size = self.size()?;
+        if size >= self.words - HEADER_WORDS {
+            return Err(TreeError::CorruptSnapshot);
+        }
+        if idx >= size {
+            return Err(TreeError::IndexOutOfBounds {
+                index: idx,
+                bound: size,
+            });
+        }
+        Ok(size)
+    }
+
+    fn add_to_nodes(&self, idx: usize, size: usize, value: i64) -> Result<(), TreeError> {
+        let mut position = idx + 1;
+        while position <= size {
+            self.node(position)?.fetch_add(value, Ordering::Relaxed);
+            position += least_significant_bit(position);
+        }
+        Ok(())
+    }
+
+    fn query(&self, idx: usize) -> Result<i64, TreeError> {
+        self.check_bounds(idx)?;
+
+        seqlock::read(self.word(2)?, || {
+            let mut res = 0i64;
+            let mut position = idx + 1;
+            while position > 0 {
+                res = res.wrapping_add(self.node(position)?.load(Ordering::Relaxed));
+                position -= least_significant_bit(position);
+            }
+            Ok(res)
+        })
+    }
+}
+
+impl Drop for Segment {
+    fn drop(&mut self) {
+        // SAFETY: the mapping was created by `map` with the same length.
+        unsafe {
+            libc::munmap(self.ptr.as_ptr().cast(), self.words * 8);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::shared_memory_tree::{SharedMemoryFenwickTree, SharedMemoryReader};
+    use crate::{FenwickReader, FenwickWriter, TreeError};
+
+    fn name(test: &str) -> String {
+        format!("/fenwick-test-{}-{test}", std::process::id())
+    }
+
+    #[test]
+    fn reader_sees_writer_updates() {
+        let name = name("updates");
+        let mut tree = SharedMemoryFenwickTree::create(&name, 16).unwrap();
+        let reader = SharedMemoryReader::open(&name).unwrap();
+        assert_eq!(reader.size(), 16);
+
+        tree.update(3, 5).unwrap();
+        tree.update(15, -2).unwrap();
+        assert_eq!(reader.query(2).unwrap(), 0);
+        assert_eq!(reader.query(15).unwrap(), 3);
+        assert_eq!(reader.range_query(3, 15).unwrap(), -2);
+        assert_eq!(reader.version(), 2);
+        assert_eq!(tree.query(15).unwrap(), 3);
+        assert_eq!(
+            reader.query(16),
+            Err(TreeError::IndexOutOfBounds {
+                index: 16,
+                bound: 16
+            })
+        );
+    }
+
+    #[test]
+    fn segment_lifetime() {
+        let name = name("lifetime");
+        let tree = SharedMemoryFenwickTree::create(&name, 4).unwrap();
+        assert!(matches!(
+            SharedMemoryFenwickTree::create(&name, 4),
+            Err(TreeError::SharedMemory { .. })
+        ));
+
+        let reader = SharedMemoryReader::open(&name).unwrap();
+        drop(tree);
+        assert!(matches!(
+            SharedMemoryReader::open(&name),
+            Err(TreeError::SharedMemory { .. })
+        ));
+        assert_eq!(reader.query(3).unwrap(), 0);
+    }
+
+    #[test]
+    fn consistent_reads_during_updates() {
+        let name = name("consistency");
+        let mut tree = SharedMemoryFenwickTree::create(&name, 1024).unwrap();
+        let reader = SharedMemoryReader::open(&name).unwrap();
+
+        let writer = thread::spawn(move || {
+            for idx in 0..1000 {
+                tree.update(idx, 1).unwrap();
+            }
+            tree
+        });
+        let mut last = 0;
+        while last < 1000 {
+            let version = reader.version();
+            let total = reader.query(1023).unwrap();
+            assert!(total >= last && total as u64 >= version);
+            last = total;
+        }
+        drop(writer.join().unwrap());
+    }
+}
@@ -0,0 +1,520 @@
+use std::io::{self, ErrorKind, Read, Write};
+
+use crate::raw::lsb_ascending;
+use crate::{
+    add_to_nodes, FenwickReader, FenwickTreeValue, FenwickWriter, FixedSizeFenwickTree,
+    InvertibleValue, TreeError,
+};
+
+const MAGIC: [u8; 4] = *b"FWTS";
+const FULL: u8 = 0;
+const PATCH: u8 = 1;
+
+/// Values which can be saved by [`SnapshotFenwickTree`], encoded in a fixed number of
+/// little-endian bytes. Implemented for fixed width primitive numbers.
+pub trait SnapshotValue: FenwickTreeValue {
+    /// Number of bytes value is encoded in.
+    const ENCODED_LEN: usize;
+
+    /// Writes value to `buf` of [`Self::ENCODED_LEN`] bytes.
+    fn encode(&self, buf: &mut [u8]);
+
+    /// Reads value from `buf` of [`Self::ENCODED_LEN`] bytes.
+    fn decode(buf: &[u8]) -> Self;
+}
+
+macro_rules! impl_snapshot_value {
+    ($($t:ty),*) => {$(
+        impl SnapshotValue for $t {
+            const ENCODED_LEN: usize = std::mem::size_of::<$t>();
+
+            fn encode(&self, buf: &mut [u8]) {
+                buf.copy_from_slice(&self.to_le_bytes());
+            }
+
+            fn decode(buf: &[u8]) -> Self {
+                let mut bytes = [0; std::mem::size_of::<$t>()];
+                bytes.copy_from_slice(buf);
+                <$t>::from_le_bytes(bytes)
+            }
+        }
+    )*};
+}
+
+impl_snapshot_value!(i8, i16, i32, i64, i128, u8, u16, u32, u64, u128, f32, f64);
+
+/// Tree remembering which nodes changed since it was last saved, so besides full
+/// snapshots it can write small patches holding only those nodes. Patch is applied
+/// on top of a tree restored from the previous snapshot or patch, which makes frequent
+/// saves of huge trees cheap when few indexes are updated in between.
+///
+/// Every save bumps the generation of the tree, which is recorded in snapshots, and
+/// patches are only accepted by trees at the generation they were written since.
+/// Wrapping a tree starts at generation 0, which is an empty tree, so the first patch
+/// holds every node storing a value. Out of bounds policy of the tree isn't saved.
+///
+/// ```rust
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut tree = SnapshotFenwickTree::new(FixedSizeFenwickTree::<i64>::new(1_000));
+/// for idx in 0..1_000 {
+///     tree.update(idx, 1).unwrap();
+/// }
+/// let mut snapshot = vec![];
+/// tree.serialize(&mut snapshot).unwrap();
+///
+/// tree.update(500, 10).unwrap();
+/// let mut patch = vec![];
+/// tree.serialize_incremental(&mut patch).unwrap();
+/// assert!(patch.len() < snapshot.len() / 50);
+///
+/// let mut restored = SnapshotFenwickTree::<i64>::deserialize(snapshot.as_slice()).unwrap();
+/// restored.apply_incremental(patch.as_slice()).unwrap();
+/// assert_eq!(restored.query(999).unwrap(), 1_010);
+/// assert_eq!(restored.generation(), tree.generation());
+/// ```
+pub struct SnapshotFenwickTree<T: SnapshotValue> {
+    tree: FixedSizeFenwickTree<T>,
+    /// Bit per node position, set for nodes changed since the last save.
+    dirty: Vec<u64>,
+    generation: u64,
+}
+
+struct Header {
+    kind: u8,
+    since: u64,
+    generation: u64,
+    size: usize,
+}
+
+impl<T: SnapshotValue> SnapshotFenwickTree<T> {
+    /// Wraps `tree` at generation 0, nodes storing a value are considered changed.
+    pub fn new(tree: FixedSizeFenwickTree<T>) -> Self {
+        let mut res = Self {
+            tree,
+            dirty: vec![],
+            generation: 0,
+        };
+        for position in 1..res.tree.data.len() {
+            if !res.tree.data[position].is_identity() {
+                res.mark(position);
+            }
+        }
+        res
+    }
+
+    /// Returns number of saves, restored trees take generation of their snapshot.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Returns number of nodes changed since the last save, which is the number of
+    /// nodes the next patch holds.
+    pub fn dirty_nodes(&self) -> usize {
+        self.dirty
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    /// Writes every node of the tree to `writer`, in `O(n)`. Writes are small, so
+    /// `writer` is better buffered.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if writing fails, changed nodes are kept
+    /// for the next save in that case.
+    ///
+    pub fn serialize(&mut self, mut writer: impl Write) -> Result<(), TreeError> {
+        let generation = self.generation + 1;
+        let size = self.tree.data.len() - 1;
+        write_header::<T>(&mut writer, FULL, self.generation, generation, size)?;
+
+        let mut buf = vec![0; T::ENCODED_LEN];
+        for node in &self.tree.data[1..] {
+            node.encode(&mut buf);
+            writer.write_all(&buf).map_err(io_error)?;
+        }
+        writer.flush().map_err(io_error)?;
+
+        self.saved(generation);
+        Ok(())
+    }
+
+    /// Writes nodes changed since the last save to `writer`, in `O(n / 64 + k)` for
+    /// `k` changed nodes. Writes are small, so `writer` is better buffered.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if writing fails, changed nodes are kept
+    /// for the next save in that case.
+    ///
+    pub fn serialize_incremental(&mut self, mut writer: impl Write) -> Result<(), TreeError> {
+        let generation = self.generation + 1;
+        let size = self.tree.data.len() - 1;
+        write_header::<T>(&mut writer, PATCH, self.generation, generation, size)?;
+        write_u64(&mut writer, self.dirty_nodes() as u64)?;
+
+        let mut buf = vec![0; T::ENCODED_LEN];
+        for position in self.dirty_positions() {
+            write_u64(&mut writer, position as u64)?;
+            self.tree.data[position].encode(&mut buf);
+            writer.write_all(&buf).map_err(io_error)?;
+        }
+        writer.flush().map_err(io_error)?;
+
+        self.saved(generation);
+        Ok(())
+    }
+
+    /// Restores tree written by [`Self::serialize`].
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if reading fails, memory for the tree can't
+    /// be allocated, or `reader` doesn't hold a snapshot of a tree of `T` values.
+    ///
+    pub fn deserialize(mut reader: impl Read) -> Result<Self, TreeError> {
+        let header = read_header::<T>(&mut reader)?;
+        if header.kind != FULL {
+            return Err(TreeError::CorruptSnapshot);
+        }
+
+        let len = header
+            .size
+            .checked_add(1)
+            .ok_or(TreeError::CorruptSnapshot)?;
+        let mut data = Vec::new();
+        data.try_reserve_exact(len)
+            .map_err(|_| TreeError::AllocationFailed)?;
+        data.push(T::default());
+        let mut buf = vec![0; T::ENCODED_LEN];
+        for _ in 0..header.size {
+            data.push(read_value(&mut reader, &mut buf)?);
+        }
+
+        let mut tree = FixedSizeFenwickTree::new(0);
+        tree.data = data;
+        Ok(Self {
+            tree,
+            dirty: vec![],
+            generation: header.generation,
+        })
+    }
+
+    /// Applies patch written by [`Self::serialize_incremental`], which moves the tree
+    /// to its generation. Patch overwrites nodes it holds, so updates made to this tree
+    /// since the last save are lost.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if patch was written since a generation other
+    /// than the current one, reading fails, or `reader` doesn't hold a patch of a tree
+    /// of `T` values. The tree is left unchanged in those cases.
+    ///
+    pub fn apply_incremental(&mut self, mut reader: impl Read) -> Result<(), TreeError> {
+        let header = read_header::<T>(&mut reader)?;
+        if header.kind != PATCH || header.size < self.tree.data.len() - 1 {
+            return Err(TreeError::CorruptSnapshot);
+        }
+        if header.since != self.generation {
+            return Err(TreeError::VersionMismatch {
+                expected: self.generation,
+                found: header.since,
+            });
+        }
+
+        let count = read_u64(&mut reader)?;
+        let mut nodes = vec![];
+        let mut buf = vec![0; T::ENCODED_LEN];
+        for _ in 0..count {
+            let position = usize::try_from(read_u64(&mut reader)?)
+                .ok()
+                .filter(|position| (1..=header.size).contains(position))
+                .ok_or(TreeError::CorruptSnapshot)?;
+            nodes.push((position, read_value(&mut reader, &mut buf)?));
+        }
+
+        let data = &mut self.tree.data;
+        data.try_reserve(header.size + 1 - data.len())
+            .map_err(|_| TreeError::AllocationFailed)?;
+        data.resize(header.size + 1, T::default());
+        for (position, node) in nodes {
+            data[position] = node;
+        }
+        self.saved(header.generation);
+        Ok(())
+    }
+
+    pub fn tree(&self) -> &FixedSizeFenwickTree<T> {
+        &self.tree
+    }
+
+    pub fn into_inner(self) -> FixedSizeFenwickTree<T> {
+        self.tree
+    }
+
+    fn mark(&mut self, position: usize) {
+        let word = position / 64;
+        if word >= self.dirty.len() {
+            self.dirty.resize(word + 1, 0);
+        }
+        self.dirty[word] |= 1 << (position % 64);
+    }
+
+    fn dirty_positions(&self) -> impl Iterator<Item = usize> + '_ {
+        self.dirty.iter().enumerate().flat_map(|(word_idx, word)| {
+            let mut bits = *word;
+            std::iter::from_fn(move || {
+                if bits == 0 {
+                    return None;
+                }
+                let bit = bits.trailing_zeros() as usize;
+                bits &= bits - 1;
+                Some(word_idx * 64 + bit)
+            })
+        })
+    }
+
+    fn saved(&mut self, generation: u64) {
+        self.dirty.iter_mut().for_each(|word| *word = 0);
+        self.generation = generation;
+    }
+}
+
+impl<T: SnapshotValue> FenwickReader for SnapshotFenwickTree<T> {
+    type Value = T;
+
+    fn query(&self, idx: usize) -> Result<T, TreeError> {
+        self.tree.query(idx)
+    }
+
+    fn range_query(&self, from: usize, to: usize) -> Result<T, TreeError>
+    where
+        T: InvertibleValue,
+    {
+        self.tree.range_query(from, to)
+    }
+
+    fn find_prefix(&self, target: T) -> Option<usize>
+    where
+        T: PartialOrd,
+    {
+        self.tree.find_prefix(target)
+    }
+}
+
+impl<T: SnapshotValue> FenwickWriter for SnapshotFenwickTree<T> {
+    fn update(&mut self, idx: usize, value: T) -> Result<(), TreeError> {
+        let size = self.tree.data.len() - 1;
+        if let Some(position) = self.tree.update_position(idx, &value)? {
+            let grown = self.tree.data.len() - 1;
+            for new_node in size + 1..=grown {
+                self.mark(new_node);
+            }
+            for changed in lsb_ascending(position, grown) {
+                self.mark(changed);
+            }
+            add_to_nodes(&mut self.tree.data, position, value);
+        }
+        Ok(())
+    }
+
+    fn update_target(&self, idx: usize) -> Result<Option<usize>, TreeError> {
+        self.tree.update_target(idx)
+    }
+}
+
+fn io_error(err: io::Error) -> TreeError {
+    match err.kind() {
+        ErrorKind::UnexpectedEof => TreeError::CorruptSnapshot,
+        kind => TreeError::Io { kind },
+    }
+}
+
+fn write_u64(writer: &mut impl Write, value: u64) -> Result<(), TreeError> {
+    writer.write_all(&value.to_le_bytes()).map_err(io_error)
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64, TreeError> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes).map_err(io_error)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+fn read_value<T: SnapshotValue>(reader: &mut impl Read, buf: &mut [u8]) -> Result<T, TreeError> {
+    reader.read_exact(buf).map_err(io_error)?;
+    let value = T::decode(buf);
+    if !value.is_valid() {
+        return Err(TreeError::CorruptSnapshot);
+    }
+    Ok(value)
+}
+
+fn write_header<T: SnapshotValue>(
+    writer: &mut impl Write,
+    kind: u8,
+    since: u64,
+    generation: u64,
+    size: usize,
+) -> Result<(), TreeError> {
+    writer.write_all(&MAGIC).map_err(io_error)?;
+    writer
+        .write_all(&[kind, T::ENCODED_LEN as u8])
+        .map_err(io_error)?;
+    write_u64(writer, since)?;
+    write_u64(writer, generation)?;
+    write_u64(writer, size as u64)
+}
+
+fn read_header<T: SnapshotValue>(reader: &mut impl Read) -> Result<Header, TreeError> {
+    let mut bytes = [0; 6];
+    reader.read_exact(&mut bytes).map_err(io_error)?;
+    if bytes[..4] != MAGIC || bytes[5] as usize != T::ENCODED_LEN {
+        return Err(TreeError::CorruptSnapshot);
+    }
+    Ok(Header {
+        kind: bytes[4],
+        since: read_u64(reader)?,
+        generation: read_u64(reader)?,
+        size: usize::try_from(read_u64(reader)?).map_err(|_| TreeError::CorruptSnapshot)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, ErrorKind, Write};
+
+    use crate::snapshot_tree::SnapshotFenwickTree;
+    use crate::workload::Workload;
+    use crate::{FenwickReader, FenwickWriter, FixedSizeFenwickTree, OutOfBoundsPolicy, TreeError};
+
+    fn assert_same(left: &SnapshotFenwickTree<i64>, right: &SnapshotFenwickTree<i64>) {
+        for idx in 0..left.tree().data.len() - 1 {
+            assert_eq!(left.query(idx).unwrap(), right.query(idx).unwrap());
+        }
+        assert_eq!(left.generation(), right.generation());
+    }
+
+    #[test]
+    fn snapshot_and_patches() {
+        let mut tree = SnapshotFenwickTree::new(FixedSizeFenwickTree::<i64>::new(10_000));
+        let values = Workload::new(1, 1).deltas(-100..100).updates();
+        for (idx, (_, value)) in values.take(10_000).enumerate() {
+            tree.update(idx, value).unwrap();
+        }
+        let mut snapshot = vec![];
+        tree.serialize(&mut snapshot).unwrap();
+        assert_eq!(tree.dirty_nodes(), 0);
+
+        let mut restored = SnapshotFenwickTree::<i64>::deserialize(snapshot.as_slice()).unwrap();
+        assert_same(&tree, &restored);
+
+        let mut updates = Workload::new(2, 10_000).deltas(1..100).updates();
+        for _ in 0..3 {
+            for (idx, value) in updates.by_ref().take(5) {
+                tree.update(idx, value).unwrap();
+            }
+            assert!(tree.dirty_nodes() <= 5 * 14);
+            let mut patch = vec![];
+            tree.serialize_incremental(&mut patch).unwrap();
+            assert!(patch.len() < snapshot.len() / 50);
+
+            restored.apply_incremental(patch.as_slice()).unwrap();
+            assert_same(&tree, &restored);
+        }
+    }
+
+    #[test]
+    fn patches_of_growing_tree() {
+        let tree = FixedSizeFenwickTree::<i64>::new(3).with_out_of_bounds(OutOfBoundsPolicy::Grow);
+        let mut tree = SnapshotFenwickTree::new(tree);
+        let mut replica = SnapshotFenwickTree::new(FixedSizeFenwickTree::new(0));
+
+        for (idx, value) in [(1, 4), (2, 1), (40, 7), (17, 2), (100, 3)] {
+            tree.update(idx, value).unwrap();
+            let mut patch = vec![];
+            tree.serialize_incremental(&mut patch).unwrap();
+            replica.apply_incremental(patch.as_slice()).unwrap();
+            assert_same(&tree, &replica);
+        }
+        assert_eq!(replica.query(100).unwrap(), 17);
+    }
+
+    #[test]
+    fn wrapped_values_are_part_of_first_patch() {
+        let mut tree = SnapshotFenwickTree::new(FixedSizeFenwickTree::from_fn(8, |i| i as i64 % 2));
+        assert_eq!(tree.dirty_nodes(), 4);
+
+        let mut patch = vec![];
+        tree.serialize_incremental(&mut patch).unwrap();
+        let mut replica = SnapshotFenwickTree::new(FixedSizeFenwickTree::new(0));
+        replica.apply_incremental(patch.as_slice()).unwrap();
+        assert_same(&tree, &replica);
+    }
+
+    #[test]
+    fn rejected_patches() {
+        let mut tree = SnapshotFenwickTree::new(FixedSizeFenwickTree::<i64>::new(16));
+        tree.update(3, 1).unwrap();
+        let mut snapshot = vec![];
+        tree.serialize(&mut snapshot).unwrap();
+        let mut replica = SnapshotFenwickTree::<i64>::deserialize(snapshot.as_slice()).unwrap();
+
+        let mut stale = vec![];
+        tree.serialize_incremental(&mut stale).unwrap();
+        let mut patch = vec![];
+        tree.update(5, 2).unwrap();
+        tree.serialize_incremental(&mut patch).unwrap();
+        assert_eq!(
+            replica.apply_incremental(patch.as_slice()),
+            Err(TreeError::VersionMismatch {
+                expected: 1,
+                found: 2
+            })
+        );
+        assert_eq!(
+            replica.apply_incremental(&stale[..stale.len() - 1]),
+            Err(TreeError::CorruptSnapshot)
+        );
+        assert_eq!(
+            replica.apply_incremental(snapshot.as_slice()),
+            Err(TreeError::CorruptSnapshot)
+        );
+        assert_eq!(
+            SnapshotFenwickTree::<i32>::deserialize(snapshot.as_slice()).err(),
+            Some(TreeError::CorruptSnapshot)
+        );
+        assert_eq!(replica.query(15).unwrap(), 1);
+
+        replica.apply_incremental(stale.as_slice()).unwrap();
+        replica.apply_incremental(patch.as_slice()).unwrap();
+        assert_eq!(replica.query(15).unwrap(), 3);
+    }
+
+    #[test]
+    fn failed_write_keeps_changes() {
+        struct Broken;
+
+        impl Write for Broken {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::from(ErrorKind::BrokenPipe))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut tree = SnapshotFenwickTree::new(FixedSizeFenwickTree::<f64>::new(4));
+        tree.update(1, 0.5).unwrap();
+        assert_eq!(
+            tree.serialize_incremental(Broken),
+            Err(TreeError::Io {
+                kind: ErrorKind::BrokenPipe
+            })
+        );
+        assert_eq!(tree.dirty_nodes(), 2);
+        assert_eq!(tree.generation(), 0);
+    }
+}
@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+
+use crate::{FenwickReader, FenwickWriter, InvertibleValue, TreeError};
+
+/// Tree recording the stamp of the last update of every index, such as a timestamp or
+/// a caller supplied tag, which allows detecting dead buckets and picking indexes to
+/// evict.
+///
+/// Plain [`FenwickWriter::update`] stamps indexes with `clock` passed to
+/// [`StampedFenwickTree::new`], [`StampedFenwickTree::update_stamped`] takes the stamp
+/// explicitly. Stamps are kept only for indexes updated after wrapping, in a sparse
+/// map.
+///
+/// ```rust
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut tree = StampedFenwickTree::new(GrowingFenwickTree::<i32>::new(0), || 0u64);
+/// tree.update_stamped(3, 1, 10).unwrap();
+/// tree.update_stamped(5, 1, 20).unwrap();
+/// tree.update_stamped(3, 1, 30).unwrap();
+///
+/// assert_eq!(tree.last_updated(3), Some(&30));
+/// assert_eq!(tree.stale_indexes(&25).collect::<Vec<_>>(), vec![5]);
+/// assert_eq!(tree.query(5).unwrap(), 3);
+/// ```
+pub struct StampedFenwickTree<F: FenwickWriter, S> {
+    tree: F,
+    stamps: BTreeMap<usize, S>,
+    clock: fn() -> S,
+}
+
+impl<F: FenwickWriter, S: Ord> StampedFenwickTree<F, S> {
+    /// Wraps `tree`, stamping plain updates with `clock`, e.g. [`std::time::Instant::now`].
+    pub fn new(tree: F, clock: fn() -> S) -> Self {
+        Self {
+            tree,
+            stamps: BTreeMap::new(),
+            clock,
+        }
+    }
+
+    /// Same as [`FenwickWriter::update`], stamping the index update lands at under out
+    /// of bounds policy of the tree (see [`FenwickWriter::update_target`]) with
+    /// `stamp`. Stamp is only recorded if the update succeeds and isn't dropped.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the update of the underlying tree fails.
+    ///
+    pub fn update_stamped(
+        &mut self,
+        idx: usize,
+        value: F::Value,
+        stamp: S,
+    ) -> Result<(), TreeError> {
+        let target = self.tree.update_target(idx)?;
+        self.tree.update(idx, value)?;
+        if let Some(target) = target {
+            self.stamps.insert(target, stamp);
+        }
+        Ok(())
+    }
+
+    /// Returns stamp of the last update of `idx`, or [`None`] if it wasn't updated.
+    pub fn last_updated(&self, idx: usize) -> Option<&S> {
+        self.stamps.get(&idx)
+    }
+
+    /// Returns indexes last updated before `older_than`, in ascending index order, in
+    /// `O(k)` for `k` updated indexes.
+    pub fn stale_indexes<'a>(&'a self, older_than: &'a S) -> impl Iterator<Item = usize> + 'a {
+        self.stamps
+            .iter()
+            .filter(move |(_, stamp)| *stamp < older_than)
+            .map(|(idx, _)| *idx)
+    }
+
+    /// Drops stamp of `idx`, e.g. once its bucket is evicted, and returns it.
+    pub fn forget(&mut self, idx: usize) -> Option<S> {
+        self.stamps.remove(&idx)
+    }
+
+    pub fn tree(&self) -> &F {
+        &self.tree
+    }
+
+    pub fn into_inner(self) -> F {
+        self.tree
+    }
+}
+
+impl<F: FenwickWriter, S> FenwickReader for StampedFenwickTree<F, S> {
+    type Value = F::Value;
+
+    fn query(&self, idx: usize) -> Result<F::Value, TreeError> {
+        self.tree.query(idx)
+    }
+
+    fn range_query(&self, from: usize, to: usize) -> Result<F::Value, TreeError>
+    where
+        F::Value: InvertibleValue,
+    {
+        self.tree.range_query(from, to)
+    }
+
+    fn find_prefix(&self, target: F::Value) -> Option<usize>
+    where
+        F::Value: PartialOrd,
+    {
+        self.tree.find_prefix(target)
+    }
+}
+
+impl<F: FenwickWriter, S: Ord> FenwickWriter for StampedFenwickTree<F, S> {
+    fn update(&mut self, idx: usize, value: F::Value) -> Result<(), TreeError> {
+        let stamp = (self.clock)();
+        self.update_stamped(idx, value, stamp)
+    }
+
+    fn update_target(&self, idx: usize) -> Result<Option<usize>, TreeError> {
+        self.tree.update_target(idx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use crate::stamped_tree::StampedFenwickTree;
+    use crate::{
+        FenwickReader, FenwickWriter, FixedSizeFenwickTree, GrowingFenwickTree, OutOfBoundsPolicy,
+        TreeError,
+    };
+
+    #[test]
+    fn stamps_last_updates() {
+        let mut tree = StampedFenwickTree::new(FixedSizeFenwickTree::<i32>::new(8), || 0);
+        for (idx, stamp) in [(1, 5), (4, 2), (6, 9), (1, 1), (7, 3)] {
+            tree.update_stamped(idx, 1, stamp).unwrap();
+        }
+
+        assert_eq!(tree.last_updated(1), Some(&1));
+        assert_eq!(tree.last_updated(0), None);
+        assert_eq!(tree.stale_indexes(&3).collect::<Vec<_>>(), vec![1, 4]);
+        assert_eq!(tree.stale_indexes(&0).count(), 0);
+
+        assert_eq!(tree.forget(4), Some(2));
+        assert_eq!(tree.stale_indexes(&3).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(tree.into_inner().query(7).unwrap(), 5);
+    }
+
+    #[test]
+    fn rejected_updates_are_not_stamped() {
+        let mut tree = StampedFenwickTree::new(FixedSizeFenwickTree::<i32>::new(4), || 7);
+        assert_eq!(
+            tree.update(4, 1),
+            Err(TreeError::IndexOutOfBounds { index: 4, bound: 4 })
+        );
+        assert_eq!(tree.last_updated(4), None);
+
+        tree.update(2, 1).unwrap();
+        assert_eq!(tree.last_updated(2), Some(&7));
+        assert_eq!(tree.range_query(1, 3).unwrap(), 1);
+    }
+
+    #[test]
+    fn stamps_follow_out_of_bounds_policy() {
+        let tree =
+            FixedSizeFenwickTree::<i32>::new(4).with_out_of_bounds(OutOfBoundsPolicy::TreatAsZero);
+        let mut tree = StampedFenwickTree::new(tree, || 0);
+        tree.update_stamped(100, 5, 7).unwrap();
+        assert_eq!(tree.last_updated(100), None);
+        assert_eq!(tree.stale_indexes(&8).count(), 0);
+        assert_eq!(tree.query(3).unwrap(), 0);
+
+        let tree =
+            FixedSizeFenwickTree::<i32>::new(4).with_out_of_bounds(OutOfBoundsPolicy::ClampToLast);
+        let mut tree = StampedFenwickTree::new(tree, || 0);
+        tree.update_stamped(100, 5, 7).unwrap();
+        assert_eq!(tree.last_updated(100), None);
+        assert_eq!(tree.last_updated(3), Some(&7));
+
+        let tree =
+            GrowingFenwickTree::<i32>::new(4).with_out_of_bounds(OutOfBoundsPolicy::ClampToLast);
+        let mut tree = StampedFenwickTree::new(tree, || 0);
+        tree.update_stamped(100, 5, 7).unwrap();
+        assert_eq!(tree.stale_indexes(&8).collect::<Vec<_>>(), vec![3]);
+        assert_eq!(tree.query(3).unwrap(), 5);
+
+        let mut tree = StampedFenwickTree::new(GrowingFenwickTree::<i32>::new(4), || 0);
+        tree.update_stamped(100, 5, 7).unwrap();
+        assert_eq!(tree.last_updated(100), Some(&7));
+    }
+
+    #[test]
+    fn clock_stamps() {
+        let mut tree = StampedFenwickTree::new(FixedSizeFenwickTree::<i32>::new(4), Instant::now);
+        tree.update(0, 1).unwrap();
+        let between = Instant::now();
+        tree.update(1, 1).unwrap();
+
+        assert!(tree.last_updated(0) <= tree.last_updated(1));
+        assert!(tree.stale_indexes(&between).all(|idx| idx == 0));
+        assert_eq!(tree.tree().query(3).unwrap(), 2);
+    }
+}
@@ -0,0 +1,122 @@
+use std::sync::Arc;
+
+use crate::{FenwickReader, InvertibleValue, TreeError};
+
+/// Read-only handle sharing a tree between threads without a lock. Cloning the handle
+/// is cheap, every clone queries the same tree. Implements [`FenwickReader`] half of
+/// the tree API only.
+///
+/// Trees are [`Send`] and [`Sync`] whenever their values are, so the handle is too.
+/// Once all other handles are dropped the tree can be taken back for updates with
+/// [`SyncFenwickTree::try_into_inner`].
+pub struct SyncFenwickTree<F: FenwickReader> {
+    tree: Arc<F>,
+}
+
+impl<F: FenwickReader> Clone for SyncFenwickTree<F> {
+    fn clone(&self) -> Self {
+        Self {
+            tree: Arc::clone(&self.tree),
+        }
+    }
+}
+
+impl<F: FenwickReader> From<F> for SyncFenwickTree<F> {
+    fn from(tree: F) -> Self {
+        Self::new(tree)
+    }
+}
+
+impl<F: FenwickReader> SyncFenwickTree<F> {
+    pub fn new(tree: F) -> Self {
+        Self {
+            tree: Arc::new(tree),
+        }
+    }
+
+    /// Returns the tree if this is the only handle left, otherwise the handle itself.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if other handles to the tree still exist.
+    ///
+    pub fn try_into_inner(self) -> Result<F, Self> {
+        Arc::try_unwrap(self.tree).map_err(|tree| Self { tree })
+    }
+}
+
+impl<F: FenwickReader> FenwickReader for SyncFenwickTree<F> {
+    type Value = F::Value;
+
+    fn query(&self, idx: usize) -> Result<F::Value, TreeError> {
+        self.tree.query(idx)
+    }
+
+    fn range_query(&self, from: usize, to: usize) -> Result<F::Value, TreeError>
+    where
+        F::Value: InvertibleValue,
+    {
+        self.tree.range_query(from, to)
+    }
+
+    fn find_prefix(&self, target: F::Value) -> Option<usize>
+    where
+        F::Value: PartialOrd,
+    {
+        self.tree.find_prefix(target)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use crate::sync_tree::SyncFenwickTree;
+    use crate::{
+        AnyFenwickTree, FenwickReader, FenwickWriter, FixedSizeFenwickTree, GrowingFenwickTree,
+        InventoryTree, MultiResolutionTree, OrderBookDepth, VwapTracker,
+    };
+
+    fn assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn trees_are_send_and_sync() {
+        assert_send_sync::<FixedSizeFenwickTree<i64>>();
+        assert_send_sync::<GrowingFenwickTree<i64>>();
+        assert_send_sync::<AnyFenwickTree<i64>>();
+        assert_send_sync::<MultiResolutionTree<i64>>();
+        assert_send_sync::<OrderBookDepth>();
+        assert_send_sync::<InventoryTree>();
+        assert_send_sync::<VwapTracker>();
+        assert_send_sync::<SyncFenwickTree<GrowingFenwickTree<i64>>>();
+    }
+
+    #[test]
+    fn handles_query_from_threads() {
+        let mut tree = FixedSizeFenwickTree::<i64>::new(100);
+        for i in 0..100 {
+            tree.update(i, 1).unwrap();
+        }
+        let handle = SyncFenwickTree::new(tree);
+
+        let workers: Vec<_> = (0..4)
+            .map(|worker| {
+                let handle = handle.clone();
+                thread::spawn(move || handle.query(worker * 10).unwrap())
+            })
+            .collect();
+        let sums: Vec<i64> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+        assert_eq!(sums, vec![1, 11, 21, 31]);
+
+        let copy = handle.clone();
+        let Err(handle) = handle.try_into_inner() else {
+            panic!("other handle is still alive");
+        };
+        drop(copy);
+        let Ok(mut tree) = handle.try_into_inner() else {
+            panic!("handle is the last one");
+        };
+        tree.update(0, 1).unwrap();
+        assert_eq!(tree.range_query(0, 99).unwrap(), 99);
+    }
+}
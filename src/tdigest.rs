@@ -0,0 +1,254 @@
+use crate::FenwickTreeValue;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Centroid {
+    mean: f64,
+    weight: f64,
+}
+
+/// Merging t-digest sketch estimating quantiles of observed values. `C` is a
+/// compression parameter: bigger values give more precise estimates at the cost of
+/// keeping more centroids.
+///
+/// Digests are merged by combining their centroids, which can't be reverted, so trees
+/// storing digests answer prefix queries only: quantiles of all observations stored up
+/// to the queried index.
+///
+/// ```rust
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut tree = GrowingFenwickTree::<TDigest>::new(0);
+/// for latency in 1..=100 {
+///     tree.update(latency as usize / 10, TDigest::from_value(latency as f64)).unwrap();
+/// }
+///
+/// let median = tree.query(10).unwrap().quantile(0.5).unwrap();
+/// assert!((median - 50.5).abs() < 1.0);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct TDigest<const C: usize = 100> {
+    centroids: Vec<Centroid>,
+    total_weight: f64,
+    min: f64,
+    max: f64,
+}
+
+impl<const C: usize> Default for TDigest<C> {
+    fn default() -> Self {
+        Self {
+            centroids: vec![],
+            total_weight: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+}
+
+impl<const C: usize> TDigest<C> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates digest containing single observation.
+    pub fn from_value(value: f64) -> Self {
+        let mut res = Self::new();
+        res.insert(value);
+        res
+    }
+
+    pub fn insert(&mut self, value: f64) {
+        self.centroids.push(Centroid {
+            mean: value,
+            weight: 1.0,
+        });
+        self.total_weight += 1.0;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+
+        if self.centroids.len() > 4 * C {
+            self.compress();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.centroids.is_empty()
+    }
+
+    /// Returns number of observations in the digest.
+    pub fn count(&self) -> f64 {
+        self.total_weight
+    }
+
+    /// Returns estimated value below which `q` fraction of observations lies, or
+    /// [`None`] if digest is empty. `q` is clamped into `0.0..=1.0` range.
+    pub fn quantile(&self, q: f64) -> Option<f64> {
+        if self.centroids.is_empty() {
+            return None;
+        }
+
+        let mut digest = self.clone();
+        digest.compress();
+        let centroids = &digest.centroids;
+
+        let target = q.clamp(0.0, 1.0) * self.total_weight;
+
+        // Every centroid is considered to be located at the middle of the weight it covers.
+        let mut previous = (0.0, self.min);
+        let mut cumulative = 0.0;
+        for centroid in centroids {
+            let center = cumulative + centroid.weight / 2.0;
+            if target <= center {
+                return Some(interpolate(previous, (center, centroid.mean), target));
+            }
+            previous = (center, centroid.mean);
+            cumulative += centroid.weight;
+        }
+
+        Some(interpolate(previous, (self.total_weight, self.max), target))
+    }
+
+    fn compress(&mut self) {
+        if self.centroids.len() <= 1 {
+            return;
+        }
+
+        self.centroids
+            .sort_by(|left, right| left.mean.total_cmp(&right.mean));
+
+        let mut compressed = Vec::with_capacity(C);
+        let mut current = self.centroids[0];
+        let mut weight_before = 0.0;
+
+        for centroid in self.centroids.iter().skip(1) {
+            let proposed_weight = current.weight + centroid.weight;
+            let q = (weight_before + proposed_weight / 2.0) / self.total_weight;
+            let weight_limit = 4.0 * self.total_weight * q * (1.0 - q) / C as f64;
+
+            if proposed_weight <= weight_limit {
+                current.mean = (current.mean * current.weight + centroid.mean * centroid.weight)
+                    / proposed_weight;
+                current.weight = proposed_weight;
+            } else {
+                weight_before += current.weight;
+                compressed.push(current);
+                current = *centroid;
+            }
+        }
+        compressed.push(current);
+
+        self.centroids = compressed;
+    }
+}
+
+fn interpolate(from: (f64, f64), to: (f64, f64), at: f64) -> f64 {
+    let (from_weight, from_value) = from;
+    let (to_weight, to_value) = to;
+    if to_weight <= from_weight {
+        return to_value;
+    }
+    from_value + (to_value - from_value) * (at - from_weight) / (to_weight - from_weight)
+}
+
+impl<const C: usize> FenwickTreeValue for TDigest<C> {
+    const CANCELS: bool = false;
+
+    fn store_value(&mut self, other: &Self) {
+        if other.centroids.is_empty() {
+            return;
+        }
+
+        self.centroids.extend_from_slice(&other.centroids);
+        self.total_weight += other.total_weight;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+
+        if self.centroids.len() > 4 * C {
+            self.compress();
+        }
+    }
+
+    fn absorb(&mut self, other: Self) {
+        if self.centroids.is_empty() {
+            *self = other;
+            return;
+        }
+        self.store_value(&other);
+    }
+
+    fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::tdigest::TDigest;
+    use crate::workload::Workload;
+    use crate::{FenwickReader, FenwickTreeValue, FenwickWriter, GrowingFenwickTree};
+
+    #[test]
+    fn empty_digest() {
+        let digest = TDigest::<100>::new();
+        assert!(digest.is_empty());
+        assert_eq!(digest.quantile(0.5), None);
+    }
+
+    #[test]
+    fn single_value_digest() {
+        let digest = TDigest::<100>::from_value(42.0);
+        assert_eq!(digest.quantile(0.0), Some(42.0));
+        assert_eq!(digest.quantile(0.5), Some(42.0));
+        assert_eq!(digest.quantile(1.0), Some(42.0));
+    }
+
+    #[test]
+    fn quantiles_of_shuffled_values() {
+        let mut values: Vec<f64> = (0..10_000).map(|i| i as f64).collect();
+        let mut order = Workload::new(1, usize::MAX).updates();
+        values.sort_by_cached_key(|_| order.next());
+
+        let mut digest = TDigest::<100>::new();
+        for value in values {
+            digest.insert(value);
+        }
+
+        assert_eq!(digest.count(), 10_000.0);
+        assert_eq!(digest.quantile(0.0), Some(0.0));
+        assert_eq!(digest.quantile(1.0), Some(9_999.0));
+        for q in [0.01, 0.1, 0.5, 0.9, 0.99] {
+            let estimate = digest.quantile(q).unwrap();
+            assert!((estimate - q * 10_000.0).abs() < 50.0, "q {q}: {estimate}");
+        }
+    }
+
+    #[test]
+    fn merged_digests() {
+        let mut left = TDigest::<100>::new();
+        let mut right = TDigest::<100>::new();
+        for i in 0..1_000 {
+            left.insert(i as f64);
+            right.insert((i + 1_000) as f64);
+        }
+        left.store_value(&right);
+
+        let median = left.quantile(0.5).unwrap();
+        assert!((median - 1_000.0).abs() < 20.0);
+    }
+
+    #[test]
+    fn prefix_percentiles() {
+        let mut tree = GrowingFenwickTree::<TDigest>::new(0);
+        for minute in 0..10usize {
+            for i in 0..100 {
+                tree.update(minute, TDigest::from_value((minute * 100 + i) as f64))
+                    .unwrap();
+            }
+        }
+
+        for minute in 0..10usize {
+            let p90 = tree.query(minute).unwrap().quantile(0.9).unwrap();
+            let expected = (minute + 1) as f64 * 100.0 * 0.9;
+            assert!((p90 - expected).abs() < 10.0);
+        }
+    }
+}
@@ -4,7 +4,7 @@
 mod more_complex_tests {
     use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
-    use rand::{seq::IteratorRandom, Rng};
+    use crate::workload::Workload;
 
     enum FlightStatus {
         LANDED,
@@ -18,17 +18,25 @@ mod more_complex_tests {
         status: FlightStatus,
     }
 
-    fn generate_flights(start: SystemTime, flight: String, segment_count: usize) -> Vec<LogRecord> {
+    fn generate_flights(
+        start: SystemTime,
+        flight: String,
+        segment_count: usize,
+        seed: u64,
+    ) -> Vec<LogRecord> {
         let mut res = vec![];
 
         let mut fuel = 0;
 
         let mut current_timestamp = start.duration_since(UNIX_EPOCH).unwrap().as_secs();
 
-        let mut rng = rand::thread_rng();
+        let mut amounts = Workload::new(seed, 1)
+            .deltas(0..10)
+            .updates()
+            .map(|(_, delta)| delta as i32 * 100);
         for _i in 0..segment_count {
-            let next_flight_fuel = (rng.gen::<f32>() * 10.0) as i32 * 100;
-            let some_additional = (rng.gen::<f32>() * 10.0) as i32 * 100;
+            let next_flight_fuel = amounts.next().unwrap();
+            let some_additional = amounts.next().unwrap();
             let time_delta = Duration::from_secs(next_flight_fuel as u64 * 72);
 
             if next_flight_fuel < fuel {
@@ -61,13 +69,19 @@ mod more_complex_tests {
         let mut res = vec![];
         let letters = "ABCDEFGHIJKLMNOPQRSTUVWXYZ";
 
-        let mut rng = rand::thread_rng();
+        let mut names = Workload::new(0, letters.len() * 100).updates();
         let start = SystemTime::now();
 
         for i in 0..flight_count {
-            let num = (rng.gen::<f32>() * 100.0) as i32;
-            let flight_name = format!("{:?}{:?}", letters.chars().choose(&mut rng), num);
-            res.append(&mut generate_flights(start, flight_name, segment_count));
+            let (name, _) = names.next().unwrap();
+            let num = (name % 100) as i32;
+            let flight_name = format!("{:?}{:?}", letters.chars().nth(name / 100), num);
+            res.append(&mut generate_flights(
+                start,
+                flight_name,
+                segment_count,
+                i as u64,
+            ));
         }
 
         return res;
@@ -0,0 +1,889 @@
+use std::time::Duration;
+
+use crate::{FenwickTreeValue, InvertibleValue};
+
+/// Integer modulo `M`. Addition and subtraction wrap around the modulus, so prefix sums
+/// are calculated modulo `M` without overflows.
+///
+/// ```rust
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut tree = FixedSizeFenwickTree::<ModInt<7>>::new(4);
+/// tree.update(0, ModInt::new(5)).unwrap();
+/// tree.update(1, ModInt::new(4)).unwrap();
+/// assert_eq!(tree.query(1).unwrap().value(), 2);
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ModInt<const M: u64> {
+    value: u64,
+}
+
+impl<const M: u64> ModInt<M> {
+    const NON_ZERO_MODULUS: () = assert!(M > 0, "Modulus must be greater than zero.");
+
+    pub fn new(value: u64) -> Self {
+        #[allow(clippy::let_unit_value)]
+        let () = Self::NON_ZERO_MODULUS;
+        Self { value: value % M }
+    }
+
+    pub fn value(self) -> u64 {
+        self.value
+    }
+}
+
+impl<const M: u64> From<u64> for ModInt<M> {
+    fn from(value: u64) -> Self {
+        Self::new(value)
+    }
+}
+
+impl<const M: u64> std::ops::AddAssign for ModInt<M> {
+    fn add_assign(&mut self, other: Self) {
+        if self.value >= M - other.value {
+            self.value -= M - other.value;
+        } else {
+            self.value += other.value;
+        }
+    }
+}
+
+impl<const M: u64> std::ops::Sub for ModInt<M> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        if self.value >= other.value {
+            Self {
+                value: self.value - other.value,
+            }
+        } else {
+            Self {
+                value: M - (other.value - self.value),
+            }
+        }
+    }
+}
+
+/// Wrapper that aggregates values with bitwise XOR. XOR is its own inverse, so range
+/// queries return XOR of all values stored between two indexes.
+///
+/// ```rust
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut tree = FixedSizeFenwickTree::<XorValue<u8>>::new(4);
+/// tree.update(0, XorValue(0b0110)).unwrap();
+/// tree.update(1, XorValue(0b0011)).unwrap();
+/// assert_eq!(tree.query(1).unwrap(), XorValue(0b0101));
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct XorValue<T>(pub T);
+
+impl<T> FenwickTreeValue for XorValue<T>
+where
+    T: Default + Copy + PartialEq + std::ops::BitXor<Output = T>,
+{
+    fn store_value(&mut self, other: &Self) {
+        self.0 = self.0 ^ other.0
+    }
+
+    fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl<T> InvertibleValue for XorValue<T>
+where
+    T: Default + Copy + PartialEq + std::ops::BitXor<Output = T>,
+{
+    fn substract(self, other: Self) -> Self {
+        XorValue(self.0 ^ other.0)
+    }
+}
+
+/// Wrapper that aggregates values by greatest common divisor. Gcd can't be reverted,
+/// so trees storing it answer prefix queries only and `range_query` is not available.
+///
+/// ```rust
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut tree = FixedSizeFenwickTree::<Gcd<u32>>::new(4);
+/// tree.update(0, Gcd(12)).unwrap();
+/// tree.update(1, Gcd(18)).unwrap();
+/// tree.update(2, Gcd(5)).unwrap();
+/// assert_eq!(tree.query(1).unwrap(), Gcd(6));
+/// assert_eq!(tree.query(2).unwrap(), Gcd(1));
+/// ```
+///
+/// ```compile_fail
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let tree = FixedSizeFenwickTree::<Gcd<u32>>::new(4);
+/// tree.range_query(0, 1).unwrap();
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Gcd<T>(pub T);
+
+impl<T> FenwickTreeValue for Gcd<T>
+where
+    T: Default + Copy + PartialEq + std::ops::Rem<Output = T>,
+{
+    const CANCELS: bool = false;
+
+    fn store_value(&mut self, other: &Self) {
+        let (mut a, mut b) = (self.0, other.0);
+        while b != T::default() {
+            (a, b) = (b, a % b);
+        }
+        self.0 = a
+    }
+
+    fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Value aggregated with maximum, so prefix query returns the greatest value stored
+/// up to an index. Default value is the identity, so values below it are never
+/// reported. Values can only grow. Prefix queries only.
+///
+/// ```rust
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut tree = FixedSizeFenwickTree::<Max<u32>>::new(8);
+/// tree.update(2, Max(7)).unwrap();
+/// tree.update(5, Max(3)).unwrap();
+/// assert_eq!(tree.query(1).unwrap(), Max(0));
+/// assert_eq!(tree.query(5).unwrap(), Max(7));
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Max<T>(pub T);
+
+impl<T: Default + Clone + PartialOrd> FenwickTreeValue for Max<T> {
+    fn store_value(&mut self, other: &Self) {
+        if other.0 > self.0 {
+            self.0.clone_from(&other.0);
+        }
+    }
+
+    fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Flag aggregated with logical OR. Prefix query answers whether any flag was set
+/// within the first buckets. Prefix queries only.
+///
+/// ```rust
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut tree = FixedSizeFenwickTree::<AnyTrue>::new(8);
+/// tree.update(5, AnyTrue(true)).unwrap();
+/// assert_eq!(tree.query(4).unwrap(), AnyTrue(false));
+/// assert_eq!(tree.query(5).unwrap(), AnyTrue(true));
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AnyTrue(pub bool);
+
+impl FenwickTreeValue for AnyTrue {
+    const CANCELS: bool = false;
+
+    fn store_value(&mut self, other: &Self) {
+        self.0 |= other.0
+    }
+
+    fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Flag aggregated with logical AND. Empty buckets are considered `true`, so prefix
+/// query answers whether no bucket within the first ones was marked with `false`.
+/// Prefix queries only.
+///
+/// ```rust
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut tree = FixedSizeFenwickTree::<AllTrue>::new(8);
+/// tree.update(5, AllTrue(false)).unwrap();
+/// assert_eq!(tree.query(4).unwrap(), AllTrue(true));
+/// assert_eq!(tree.query(5).unwrap(), AllTrue(false));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AllTrue(pub bool);
+
+impl Default for AllTrue {
+    fn default() -> Self {
+        AllTrue(true)
+    }
+}
+
+impl FenwickTreeValue for AllTrue {
+    const CANCELS: bool = false;
+
+    fn store_value(&mut self, other: &Self) {
+        self.0 &= other.0
+    }
+
+    fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Fixed size bitset of `WORDS * 64` bits aggregated with bitwise OR, so prefix
+/// query returns union of all sets stored up to an index. Prefix queries only.
+///
+/// ```rust
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut tree = FixedSizeFenwickTree::<BitsetValue<2>>::new(8);
+/// tree.update(1, BitsetValue::from_bit(3)).unwrap();
+/// tree.update(4, BitsetValue::from_bit(100)).unwrap();
+///
+/// let categories = tree.query(4).unwrap();
+/// assert!(categories.contains(3));
+/// assert!(categories.contains(100));
+/// assert!(!tree.query(3).unwrap().contains(100));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct BitsetValue<const WORDS: usize> {
+    words: [u64; WORDS],
+}
+
+impl<const WORDS: usize> Default for BitsetValue<WORDS> {
+    fn default() -> Self {
+        Self { words: [0; WORDS] }
+    }
+}
+
+impl<const WORDS: usize> BitsetValue<WORDS> {
+    const BITS: usize = WORDS * 64;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates set containing single `bit`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bit` doesn't fit into `WORDS * 64` bits.
+    pub fn from_bit(bit: usize) -> Self {
+        let mut res = Self::default();
+        res.insert(bit);
+        res
+    }
+
+    /// Adds `bit` to the set.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bit` doesn't fit into `WORDS * 64` bits.
+    pub fn insert(&mut self, bit: usize) {
+        assert!(bit < Self::BITS, "Bit {bit} is out of bitset bounds.");
+        self.words[bit / 64] |= 1 << (bit % 64);
+    }
+
+    pub fn contains(&self, bit: usize) -> bool {
+        bit < Self::BITS && self.words[bit / 64] & (1 << (bit % 64)) != 0
+    }
+
+    /// Returns number of bits in the set.
+    pub fn len(&self) -> usize {
+        self.words
+            .iter()
+            .map(|word| word.count_ones() as usize)
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.iter().all(|word| *word == 0)
+    }
+}
+
+impl<const WORDS: usize> FenwickTreeValue for BitsetValue<WORDS> {
+    const CANCELS: bool = false;
+
+    fn store_value(&mut self, other: &Self) {
+        for (word, other_word) in self.words.iter_mut().zip(other.words.iter()) {
+            *word |= other_word;
+        }
+    }
+
+    fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Elapsed time aggregated with saturating arithmetic. [`Duration`] can't be negative,
+/// so subtraction saturates at zero instead of panicking on underflow.
+///
+/// ```rust
+/// use std::time::Duration;
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut tree = FixedSizeFenwickTree::<DurationValue>::new(4);
+/// tree.update(1, DurationValue(Duration::from_millis(1500))).unwrap();
+/// tree.update(3, DurationValue(Duration::from_secs(2))).unwrap();
+/// assert_eq!(tree.range_query(1, 3).unwrap(), DurationValue(Duration::from_secs(2)));
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DurationValue(pub Duration);
+
+impl FenwickTreeValue for DurationValue {
+    const CANCELS: bool = false;
+
+    fn store_value(&mut self, other: &Self) {
+        self.0 = self.0.saturating_add(other.0);
+    }
+
+    fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+impl InvertibleValue for DurationValue {
+    fn substract(self, other: Self) -> Self {
+        Self(self.0.saturating_sub(other.0))
+    }
+}
+
+/// Wrapper remembering whether any negative value was ever stored. Prefix searches
+/// only give correct answers for non-negative values, so trees storing that wrapper
+/// provide `checked_*` search methods which return [`crate::TreeError::NegativeValue`]
+/// instead of silently wrong answers.
+///
+/// ```rust
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut tree = FixedSizeFenwickTree::<NonNegative<i32>>::new(8);
+/// tree.update(1, NonNegative::new(5)).unwrap();
+/// assert_eq!(tree.checked_lower_bound(3), Ok(Some(1)));
+///
+/// tree.update(2, NonNegative::new(-1)).unwrap();
+/// assert_eq!(tree.checked_lower_bound(3), Err(TreeError::NegativeValue));
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct NonNegative<T> {
+    value: T,
+    negative_applied: bool,
+}
+
+impl<T: PartialOrd + Default> NonNegative<T> {
+    pub fn new(value: T) -> Self {
+        let negative_applied = value < T::default();
+        Self {
+            value,
+            negative_applied,
+        }
+    }
+}
+
+impl<T> NonNegative<T> {
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// Returns `true` if any negative value was aggregated into that one.
+    pub fn negative_applied(&self) -> bool {
+        self.negative_applied
+    }
+}
+
+impl<T: FenwickTreeValue> FenwickTreeValue for NonNegative<T> {
+    const CANCELS: bool = T::CANCELS;
+
+    fn store_value(&mut self, other: &Self) {
+        self.value.store_value(&other.value);
+        self.negative_applied |= other.negative_applied;
+    }
+
+    fn is_identity(&self) -> bool {
+        self.value.is_identity() && !self.negative_applied
+    }
+
+    fn is_valid(&self) -> bool {
+        self.value.is_valid()
+    }
+}
+
+impl<T: InvertibleValue> InvertibleValue for NonNegative<T> {
+    fn substract(self, other: Self) -> Self {
+        Self {
+            value: self.value.substract(other.value),
+            negative_applied: self.negative_applied || other.negative_applied,
+        }
+    }
+}
+
+/// Counts of `N` buckets aggregated bucket-wise, so every tree node holds a small
+/// histogram and a single tree answers questions like distribution of response codes
+/// between two points in time, instead of `N` parallel trees.
+///
+/// ```rust
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut tree = FixedSizeFenwickTree::<Histogram<3>>::new(8);
+/// tree.update(1, Histogram([1, 0, 0])).unwrap();
+/// tree.update(4, Histogram([0, 2, 0])).unwrap();
+/// tree.update(6, Histogram([1, 0, 3])).unwrap();
+/// assert_eq!(tree.range_query(2, 7).unwrap(), Histogram([1, 2, 3]));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Histogram<const N: usize>(pub [u64; N]);
+
+impl<const N: usize> Default for Histogram<N> {
+    fn default() -> Self {
+        Self([0; N])
+    }
+}
+
+impl<const N: usize> FenwickTreeValue for Histogram<N> {
+    // Counts only reach zero by overflowing.
+    const CANCELS: bool = false;
+
+    fn store_value(&mut self, other: &Self) {
+        for (count, other_count) in self.0.iter_mut().zip(other.0) {
+            *count += other_count;
+        }
+    }
+
+    fn is_identity(&self) -> bool {
+        self.0.iter().all(|count| *count == 0)
+    }
+}
+
+impl<const N: usize> InvertibleValue for Histogram<N> {
+    fn substract(mut self, other: Self) -> Self {
+        for (count, other_count) in self.0.iter_mut().zip(other.0) {
+            *count -= other_count;
+        }
+        self
+    }
+}
+
+/// Wrapper aggregating tuples of up to 4 values component-wise, so counts can be
+/// paired with sums (or two unrelated counters kept together) without a hand-rolled
+/// struct. Range queries are available if every component is invertible.
+///
+/// ```rust
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut tree = FixedSizeFenwickTree::<Tuple<(u32, f64)>>::new(4);
+/// tree.update(0, Tuple((1, 2.5))).unwrap();
+/// tree.update(3, Tuple((1, 4.0))).unwrap();
+/// tree.update(3, Tuple((1, 1.5))).unwrap();
+/// assert_eq!(tree.range_query(1, 3).unwrap(), Tuple((2, 5.5)));
+/// ```
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Tuple<T>(pub T);
+
+macro_rules! impl_tuple_value {
+    ($(($t:ident, $idx:tt)),*) => {
+        impl<$($t: FenwickTreeValue),*> FenwickTreeValue for Tuple<($($t,)*)> {
+            const CANCELS: bool = false $(|| $t::CANCELS)*;
+
+            fn store_value(&mut self, other: &Self) {
+                $(self.0.$idx.store_value(&other.0.$idx);)*
+            }
+
+            fn absorb(&mut self, other: Self) {
+                $(self.0.$idx.absorb(other.0.$idx);)*
+            }
+
+            fn is_identity(&self) -> bool {
+                true $(&& self.0.$idx.is_identity())*
+            }
+
+            fn is_valid(&self) -> bool {
+                true $(&& self.0.$idx.is_valid())*
+            }
+        }
+
+        impl<$($t: InvertibleValue),*> InvertibleValue for Tuple<($($t,)*)> {
+            fn substract(self, other: Self) -> Self {
+                Tuple(($(self.0.$idx.substract(other.0.$idx),)*))
+            }
+        }
+    };
+}
+
+impl_tuple_value!((A, 0));
+impl_tuple_value!((A, 0), (B, 1));
+impl_tuple_value!((A, 0), (B, 1), (C, 2));
+impl_tuple_value!((A, 0), (B, 1), (C, 2), (D, 3));
+
+#[cfg(test)]
+mod tests {
+    use std::num::Wrapping;
+    use std::time::Duration;
+
+    use crate::values::{
+        AllTrue, AnyTrue, BitsetValue, DurationValue, Gcd, Histogram, Max, ModInt, Tuple, XorValue,
+    };
+    use crate::workload::Workload;
+    use crate::{
+        FenwickReader, FenwickTreeValue, FenwickWriter, GrowingFenwickTree, InvertibleValue,
+        TreeError,
+    };
+
+    const PRIME: u64 = 1_000_000_007;
+
+    #[test]
+    fn mod_int_arithmetic() {
+        let mut value = ModInt::<PRIME>::new(PRIME - 1);
+        value += ModInt::new(2);
+        assert_eq!(value.value(), 1);
+        assert_eq!((value - ModInt::new(3)).value(), PRIME - 2);
+        assert_eq!(ModInt::<PRIME>::new(PRIME + 5).value(), 5);
+    }
+
+    #[test]
+    fn mod_int_large_modulus_does_not_overflow() {
+        let mut value = ModInt::<{ u64::MAX }>::new(u64::MAX - 1);
+        value += ModInt::new(u64::MAX - 1);
+        assert_eq!(value.value(), u64::MAX - 2);
+    }
+
+    #[test]
+    fn mod_int_prefix_sums() {
+        let mut tree = GrowingFenwickTree::<ModInt<PRIME>>::new(0);
+        let input: Vec<u64> = Workload::new(1, 1)
+            .deltas(0..PRIME as i64)
+            .updates()
+            .map(|(_, value)| value as u64)
+            .take(100)
+            .collect();
+
+        for (i, value) in input.iter().enumerate() {
+            tree.update(i, ModInt::new(*value)).unwrap();
+        }
+
+        let mut sum = 0;
+        for (i, value) in input.iter().enumerate() {
+            sum = (sum + value) % PRIME;
+            assert_eq!(tree.query(i).unwrap().value(), sum);
+        }
+
+        let expected = (input[11..=20].iter().sum::<u64>()) % PRIME;
+        assert_eq!(tree.range_query(10, 20).unwrap().value(), expected);
+    }
+
+    #[test]
+    fn xor_prefix_and_range_queries() {
+        let mut tree = GrowingFenwickTree::<XorValue<u32>>::new(0);
+        let input: Vec<u32> = Workload::new(2, 1)
+            .deltas(0..1 << 32)
+            .updates()
+            .map(|(_, value)| value as u32)
+            .take(100)
+            .collect();
+
+        for (i, value) in input.iter().enumerate() {
+            tree.update(i, XorValue(*value)).unwrap();
+        }
+
+        let mut prefix = 0;
+        for (i, value) in input.iter().enumerate() {
+            prefix ^= value;
+            assert_eq!(tree.query(i).unwrap(), XorValue(prefix));
+        }
+
+        let expected = input[11..=20].iter().fold(0, |acc, value| acc ^ value);
+        assert_eq!(tree.range_query(10, 20).unwrap(), XorValue(expected));
+    }
+
+    #[test]
+    fn xor_toggling_same_value_twice_clears_it() {
+        let mut tree = GrowingFenwickTree::<XorValue<bool>>::new(8);
+        tree.update(3, XorValue(true)).unwrap();
+        assert_eq!(tree.query(7).unwrap(), XorValue(true));
+        tree.update(3, XorValue(true)).unwrap();
+        assert_eq!(tree.query(7).unwrap(), XorValue(false));
+    }
+
+    #[test]
+    fn max_prefix_queries() {
+        let mut tree = GrowingFenwickTree::<Max<f64>>::new(0);
+        for (i, value) in [1.5, -2.0, 4.0, 3.0].iter().enumerate() {
+            tree.update(i, Max(*value)).unwrap();
+        }
+
+        let expected = [1.5, 1.5, 4.0, 4.0];
+        for (i, value) in expected.iter().enumerate() {
+            assert_eq!(tree.query(i).unwrap(), Max(*value));
+        }
+    }
+
+    #[test]
+    fn gcd_prefix_queries() {
+        let mut tree = GrowingFenwickTree::<Gcd<u64>>::new(0);
+        let input = [0, 84, 36, 0, 60, 18, 7];
+
+        for (i, value) in input.iter().enumerate() {
+            tree.update(i, Gcd(*value)).unwrap();
+        }
+
+        let expected = [0, 84, 12, 12, 12, 6, 1];
+        for (i, value) in expected.iter().enumerate() {
+            assert_eq!(tree.query(i).unwrap(), Gcd(*value));
+        }
+    }
+
+    #[test]
+    fn any_true_prefix_queries() {
+        let mut tree = GrowingFenwickTree::<AnyTrue>::new(0);
+        tree.update(10, AnyTrue(false)).unwrap();
+        tree.update(20, AnyTrue(true)).unwrap();
+        tree.update(30, AnyTrue(false)).unwrap();
+
+        for i in 0..=40 {
+            assert_eq!(tree.query(i).unwrap(), AnyTrue(i >= 20));
+        }
+    }
+
+    #[test]
+    fn all_true_prefix_queries() {
+        let mut tree = GrowingFenwickTree::<AllTrue>::new(0);
+        tree.update(10, AllTrue(true)).unwrap();
+        tree.update(20, AllTrue(false)).unwrap();
+        tree.update(30, AllTrue(true)).unwrap();
+
+        for i in 0..=40 {
+            assert_eq!(tree.query(i).unwrap(), AllTrue(i < 20));
+        }
+    }
+
+    #[test]
+    fn bitset_union_of_categories() {
+        let mut tree = GrowingFenwickTree::<BitsetValue<2>>::new(0);
+        let input: Vec<usize> = Workload::new(3, 128)
+            .updates()
+            .map(|(category, _)| category)
+            .take(100)
+            .collect();
+
+        for (i, category) in input.iter().enumerate() {
+            tree.update(i, BitsetValue::from_bit(*category)).unwrap();
+        }
+
+        for i in 0..input.len() {
+            let seen = tree.query(i).unwrap();
+            for category in 0..128 {
+                assert_eq!(seen.contains(category), input[..=i].contains(&category));
+            }
+        }
+    }
+
+    #[test]
+    fn bitset_operations() {
+        let mut set = BitsetValue::<1>::new();
+        assert!(set.is_empty());
+        set.insert(0);
+        set.insert(63);
+        set.insert(63);
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(63));
+        assert!(!set.contains(64));
+    }
+
+    #[test]
+    #[should_panic]
+    fn bitset_insert_out_of_bounds() {
+        BitsetValue::<1>::new().insert(64);
+    }
+
+    #[test]
+    fn duration_subtraction_saturates() {
+        let mut tree = GrowingFenwickTree::<DurationValue>::new(0);
+        tree.update(2, DurationValue(Duration::from_secs(3)))
+            .unwrap();
+        tree.update(5, DurationValue(Duration::from_millis(250)))
+            .unwrap();
+
+        assert_eq!(
+            tree.range_query(2, 5).unwrap(),
+            DurationValue(Duration::from_millis(250))
+        );
+        assert_eq!(
+            DurationValue(Duration::from_secs(1)).substract(DurationValue(Duration::MAX)),
+            DurationValue(Duration::ZERO)
+        );
+
+        let mut total = DurationValue(Duration::MAX);
+        total.store_value(&DurationValue(Duration::from_secs(1)));
+        assert_eq!(total, DurationValue(Duration::MAX));
+    }
+
+    #[test]
+    fn non_cancelling_values_are_pruned() {
+        const _: () = assert!(!<DurationValue as FenwickTreeValue>::CANCELS);
+        const _: () = assert!(!<AnyTrue as FenwickTreeValue>::CANCELS);
+        const _: () = assert!(!<AllTrue as FenwickTreeValue>::CANCELS);
+        const _: () = assert!(!<Gcd<u32> as FenwickTreeValue>::CANCELS);
+        const _: () = assert!(!<BitsetValue<1> as FenwickTreeValue>::CANCELS);
+        const _: () = assert!(!<Histogram<4> as FenwickTreeValue>::CANCELS);
+        const _: () = assert!(<ModInt<PRIME> as FenwickTreeValue>::CANCELS);
+
+        let mut tree = GrowingFenwickTree::<DurationValue>::new(1 << 20);
+        tree.update(3, DurationValue(Duration::from_secs(1))).unwrap();
+        tree.update(70_000, DurationValue(Duration::from_secs(2))).unwrap();
+        assert_eq!(
+            tree.iter_nonzero().map(|(idx, _)| idx).collect::<Vec<_>>(),
+            vec![3, 70_000]
+        );
+    }
+
+    #[test]
+    fn plain_durations_and_wrapping_integers() {
+        let mut tree = GrowingFenwickTree::<Duration>::new(0);
+        tree.update(1, Duration::from_secs(2)).unwrap();
+        tree.update(3, Duration::from_millis(500)).unwrap();
+        assert_eq!(tree.range_query(1, 3).unwrap(), Duration::from_millis(500));
+
+        let mut tree = GrowingFenwickTree::<Wrapping<u8>>::new(0);
+        tree.update(0, Wrapping(200)).unwrap();
+        tree.update(2, Wrapping(100)).unwrap();
+        assert_eq!(tree.query(2).unwrap(), Wrapping(44));
+        assert_eq!(tree.range_query(0, 2).unwrap(), Wrapping(100));
+    }
+
+    #[test]
+    fn histogram_range_queries() {
+        let mut tree = GrowingFenwickTree::<Histogram<3>>::new(0);
+        tree.update(0, Histogram([1, 0, 0])).unwrap();
+        tree.update(1, Histogram([0, 2, 0])).unwrap();
+        tree.update(1, Histogram([1, 0, 0])).unwrap();
+        tree.update(5, Histogram([0, 0, 4])).unwrap();
+
+        assert_eq!(tree.query(0).unwrap(), Histogram([1, 0, 0]));
+        assert_eq!(tree.query(1).unwrap(), Histogram([2, 2, 0]));
+        assert_eq!(tree.query(10).unwrap(), Histogram([2, 2, 4]));
+        assert_eq!(tree.range_query(0, 5).unwrap(), Histogram([1, 2, 4]));
+        assert_eq!(
+            tree.iter_nonzero().map(|(idx, _)| idx).collect::<Vec<_>>(),
+            vec![0, 1, 5]
+        );
+    }
+
+    #[test]
+    fn wide_histograms() {
+        let mut tree = GrowingFenwickTree::<Histogram<64>>::new(0);
+        let mut counts = [0; 64];
+        counts[63] = 2;
+        tree.update(3, Histogram(counts)).unwrap();
+        assert_eq!(tree.query(3).unwrap().0[63], 2);
+    }
+
+    #[test]
+    fn tuple_of_count_and_sum() {
+        let mut tree = GrowingFenwickTree::<Tuple<(u32, f64)>>::new(0);
+        tree.update(0, Tuple((1, 2.5))).unwrap();
+        tree.update(3, Tuple((1, 4.0))).unwrap();
+        tree.update(3, Tuple((1, 1.5))).unwrap();
+
+        assert_eq!(tree.query(2).unwrap(), Tuple((1, 2.5)));
+        assert_eq!(tree.query(3).unwrap(), Tuple((3, 8.0)));
+        assert_eq!(tree.range_query(0, 3).unwrap(), Tuple((2, 5.5)));
+        assert_eq!(
+            tree.update(1, Tuple((1, f64::NAN))),
+            Err(TreeError::InvalidValue)
+        );
+    }
+
+    #[test]
+    fn tuple_mixing_invertible_and_prefix_only_values() {
+        let mut tree = GrowingFenwickTree::<Tuple<(i64, Gcd<u64>, AnyTrue, XorValue<u8>)>>::new(0);
+        tree.update(1, Tuple((5, Gcd(12), AnyTrue(false), XorValue(1))))
+            .unwrap();
+        tree.update(2, Tuple((-2, Gcd(8), AnyTrue(true), XorValue(3))))
+            .unwrap();
+
+        assert_eq!(
+            tree.query(2).unwrap(),
+            Tuple((3, Gcd(4), AnyTrue(true), XorValue(2)))
+        );
+    }
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod derive_tests {
+    use crate::prelude::*;
+
+    #[derive(Debug, Default, Clone, PartialEq, FenwickTreeValue, InvertibleValue)]
+    struct Stats {
+        count: u32,
+        sum: f64,
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, FenwickTreeValue)]
+    struct PrefixStats<T> {
+        total: T,
+        divisor: Gcd<u64>,
+    }
+
+    #[derive(Debug, Default, Clone, PartialEq, FenwickTreeValue, InvertibleValue)]
+    struct Pair(i32, i32);
+
+    #[test]
+    fn derived_cancels_if_any_field_does() {
+        const _: () = assert!(<Stats as FenwickTreeValue>::CANCELS);
+        const _: () = assert!(!<PrefixStats<DurationValue> as FenwickTreeValue>::CANCELS);
+        const _: () = assert!(<PrefixStats<i32> as FenwickTreeValue>::CANCELS);
+    }
+
+    #[test]
+    fn derived_named_struct() {
+        let mut tree = GrowingFenwickTree::<Stats>::new(0);
+        tree.update(0, Stats { count: 1, sum: 2.0 }).unwrap();
+        tree.update(4, Stats { count: 2, sum: 3.5 }).unwrap();
+
+        assert_eq!(tree.query(4).unwrap(), Stats { count: 3, sum: 5.5 });
+        assert_eq!(
+            tree.range_query(0, 4).unwrap(),
+            Stats { count: 2, sum: 3.5 }
+        );
+    }
+
+    #[test]
+    fn derived_generic_prefix_only_struct() {
+        let mut tree = GrowingFenwickTree::<PrefixStats<i64>>::new(0);
+        tree.update(
+            0,
+            PrefixStats {
+                total: 1,
+                divisor: Gcd(6),
+            },
+        )
+        .unwrap();
+        tree.update(
+            1,
+            PrefixStats {
+                total: 2,
+                divisor: Gcd(4),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(
+            tree.query(1).unwrap(),
+            PrefixStats {
+                total: 3,
+                divisor: Gcd(2)
+            }
+        );
+    }
+
+    #[test]
+    fn derived_tuple_struct() {
+        let mut tree = GrowingFenwickTree::<Pair>::new(0);
+        tree.update(0, Pair(1, -1)).unwrap();
+        tree.update(1, Pair(2, -2)).unwrap();
+
+        assert_eq!(tree.query(1).unwrap(), Pair(3, -3));
+        assert_eq!(tree.range_query(0, 1).unwrap(), Pair(2, -2));
+    }
+}
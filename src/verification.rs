@@ -0,0 +1,65 @@
+//! Kani proof harnesses, run with `cargo kani`. Sizes are bounded, so proofs cover
+//! every update sequence of up to `UPDATES` updates on trees of up to `MAX_SIZE`
+//! indexes.
+
+use crate::{FenwickReader, FenwickWriter, FixedSizeFenwickTree, GrowingFenwickTree};
+
+const MAX_SIZE: usize = 8;
+const UPDATES: usize = 3;
+
+fn any_index(bound: usize) -> usize {
+    let idx: usize = kani::any();
+    kani::assume(idx < bound);
+    idx
+}
+
+#[kani::proof]
+#[kani::unwind(10)]
+fn fixed_size_tree_query_matches_naive_sum() {
+    let mut tree = FixedSizeFenwickTree::<i32>::new(MAX_SIZE);
+    let mut points = [0i32; MAX_SIZE];
+
+    for _ in 0..UPDATES {
+        let idx = any_index(MAX_SIZE);
+        let value: i8 = kani::any();
+        tree.update(idx, i32::from(value)).unwrap();
+        points[idx] += i32::from(value);
+    }
+
+    let idx = any_index(MAX_SIZE);
+    let expected: i32 = points[..=idx].iter().sum();
+    assert_eq!(tree.query(idx), Ok(expected));
+}
+
+#[kani::proof]
+#[kani::unwind(10)]
+fn fixed_size_tree_never_panics() {
+    let size = any_index(MAX_SIZE + 1);
+    let mut tree = FixedSizeFenwickTree::<i32>::new(size);
+
+    let _ = tree.update(kani::any(), i32::from(kani::any::<i8>()));
+    let _ = tree.query(kani::any());
+    let _ = tree.range_query(kani::any(), kani::any());
+}
+
+#[kani::proof]
+#[kani::unwind(18)]
+fn growing_tree_resize_preserves_prefix_sums() {
+    let mut tree = GrowingFenwickTree::<i32>::new(0);
+    let mut points = [0i32; 2 * MAX_SIZE];
+
+    for _ in 0..UPDATES {
+        let idx = any_index(MAX_SIZE);
+        let value: i8 = kani::any();
+        tree.update(idx, i32::from(value)).unwrap();
+        points[idx] += i32::from(value);
+    }
+
+    let new_len = any_index(2 * MAX_SIZE + 1);
+    tree.resize(new_len).unwrap();
+
+    let idx = any_index(2 * MAX_SIZE);
+    let expected: i32 = points[..=idx.min(new_len.saturating_sub(1))].iter().sum();
+    let expected = if new_len == 0 { 0 } else { expected };
+    assert_eq!(tree.query(idx), Ok(expected));
+}
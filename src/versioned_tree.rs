@@ -0,0 +1,278 @@
+use std::collections::BTreeMap;
+
+use crate::{FenwickReader, FenwickTreeValue, FenwickWriter, InvertibleValue, TreeError};
+
+/// Tree counting its updates, so a replica in another process can be kept in sync by
+/// shipping only the changes made since the version it holds.
+///
+/// ```rust
+/// use fenwick_bit_tree::prelude::*;
+///
+/// let mut primary = VersionedFenwickTree::new(GrowingFenwickTree::<i32>::new(0));
+/// let mut replica = VersionedFenwickTree::new(GrowingFenwickTree::<i32>::new(0));
+///
+/// primary.update(3, 1).unwrap();
+/// primary.update(3, 2).unwrap();
+/// let deltas = primary.export_deltas_since(replica.version()).unwrap();
+/// assert_eq!(deltas.deltas, vec![(3, 3)]);
+///
+/// replica.apply_deltas(deltas).unwrap();
+/// assert_eq!(replica.version(), primary.version());
+/// assert_eq!(replica.query(3).unwrap(), 3);
+/// ```
+pub struct VersionedFenwickTree<F: FenwickWriter> {
+    tree: F,
+    version: u64,
+    oldest: u64,
+    log: Vec<(u64, usize, F::Value)>,
+}
+
+/// Changes made to a [`VersionedFenwickTree`] between two versions, merged per index.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DeltaSet<T> {
+    /// Version the changes have to be applied on top of.
+    pub since: u64,
+    /// Version of the tree once the changes are applied.
+    pub version: u64,
+    /// Values added to every changed index, in ascending index order.
+    pub deltas: Vec<(usize, T)>,
+}
+
+impl<F: FenwickWriter> VersionedFenwickTree<F> {
+    /// Wraps `tree` at version 0. Values stored in `tree` before wrapping are not part
+    /// of any delta set.
+    pub fn new(tree: F) -> Self {
+        Self {
+            tree,
+            version: 0,
+            oldest: 0,
+            log: vec![],
+        }
+    }
+
+    /// Returns the current version, which is incremented by every update.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Returns changes made after `version`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `version` is newer than the current one or
+    /// changes made after it were already forgotten.
+    ///
+    pub fn export_deltas_since(&self, version: u64) -> Result<DeltaSet<F::Value>, TreeError> {
+        if version < self.oldest || version > self.version {
+            return Err(TreeError::VersionMismatch {
+                expected: version.clamp(self.oldest, self.version),
+                found: version,
+            });
+        }
+
+        let first = self
+            .log
+            .partition_point(|(logged, _, _)| *logged <= version);
+        let mut deltas = BTreeMap::<usize, F::Value>::new();
+        for (_, idx, value) in &self.log[first..] {
+            deltas.entry(*idx).or_default().store_value(value);
+        }
+
+        Ok(DeltaSet {
+            since: version,
+            version: self.version,
+            deltas: deltas.into_iter().collect(),
+        })
+    }
+
+    /// Applies changes exported from another tree, which moves this tree to their
+    /// version.
+    ///
+    /// Changes are applied to a copy of the underlying tree, in `O(n + k log n)`, which
+    /// replaces the tree once all of them succeed. So either all changes are applied or
+    /// none is, and a failed delta set can be safely applied again.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if changes were exported since a version other
+    /// than the current one, or any update of the underlying tree fails. The tree and
+    /// its version are left unchanged in those cases.
+    ///
+    pub fn apply_deltas(&mut self, deltas: DeltaSet<F::Value>) -> Result<(), TreeError>
+    where
+        F: Clone,
+    {
+        if deltas.since != self.version {
+            return Err(TreeError::VersionMismatch {
+                expected: self.version,
+                found: deltas.since,
+            });
+        }
+
+        let mut staged = self.tree.clone();
+        for (idx, value) in &deltas.deltas {
+            staged.update_ref(*idx, value)?;
+        }
+
+        self.tree = staged;
+        self.log.extend(
+            deltas
+                .deltas
+                .into_iter()
+                .map(|(idx, value)| (deltas.version, idx, value)),
+        );
+        self.version = deltas.version;
+
+        Ok(())
+    }
+
+    /// Drops changes made up to `version` (including it), so only later versions can be
+    /// exported from now on.
+    pub fn forget_until(&mut self, version: u64) {
+        let version = version.min(self.version);
+        if version <= self.oldest {
+            return;
+        }
+        let first = self
+            .log
+            .partition_point(|(logged, _, _)| *logged <= version);
+        self.log.drain(..first);
+        self.oldest = version;
+    }
+
+    pub fn into_inner(self) -> F {
+        self.tree
+    }
+}
+
+impl<F: FenwickWriter> FenwickReader for VersionedFenwickTree<F> {
+    type Value = F::Value;
+
+    fn query(&self, idx: usize) -> Result<F::Value, TreeError> {
+        self.tree.query(idx)
+    }
+
+    fn range_query(&self, from: usize, to: usize) -> Result<F::Value, TreeError>
+    where
+        F::Value: InvertibleValue,
+    {
+        self.tree.range_query(from, to)
+    }
+
+    fn find_prefix(&self, target: F::Value) -> Option<usize>
+    where
+        F::Value: PartialOrd,
+    {
+        self.tree.find_prefix(target)
+    }
+}
+
+impl<F: FenwickWriter> FenwickWriter for VersionedFenwickTree<F> {
+    fn update(&mut self, idx: usize, value: F::Value) -> Result<(), TreeError> {
+        self.tree.update(idx, value.clone())?;
+        self.version += 1;
+        self.log.push((self.version, idx, value));
+        Ok(())
+    }
+
+    fn update_target(&self, idx: usize) -> Result<Option<usize>, TreeError> {
+        self.tree.update_target(idx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::versioned_tree::{DeltaSet, VersionedFenwickTree};
+    use crate::workload::Workload;
+    use crate::{
+        FenwickReader, FenwickWriter, FixedSizeFenwickTree, GrowingFenwickTree, TreeError,
+    };
+
+    #[test]
+    fn replica_follows_primary() {
+        let mut primary = VersionedFenwickTree::new(GrowingFenwickTree::<i64>::new(0));
+        let mut replica = VersionedFenwickTree::new(GrowingFenwickTree::<i64>::new(0));
+        let mut updates = Workload::new(0, 100).query_share(0.0).deltas(-10..10);
+
+        for _round in 0..10 {
+            for operation in updates.by_ref().take(50) {
+                operation.apply(&mut primary).unwrap();
+            }
+            let deltas = primary.export_deltas_since(replica.version()).unwrap();
+            assert!(deltas.deltas.len() <= 50);
+            replica.apply_deltas(deltas).unwrap();
+
+            for i in 0..100 {
+                assert_eq!(replica.query(i).unwrap(), primary.query(i).unwrap());
+            }
+        }
+        assert_eq!(replica.version(), 500);
+    }
+
+    #[test]
+    fn versions_are_checked() {
+        let mut primary = VersionedFenwickTree::new(FixedSizeFenwickTree::<i32>::new(8));
+        let mut replica = VersionedFenwickTree::new(FixedSizeFenwickTree::<i32>::new(8));
+        primary.update(1, 1).unwrap();
+        primary.update(2, 1).unwrap();
+
+        let stale = primary.export_deltas_since(1).unwrap();
+        assert_eq!(
+            stale,
+            DeltaSet {
+                since: 1,
+                version: 2,
+                deltas: vec![(2, 1)]
+            }
+        );
+        assert_eq!(
+            replica.apply_deltas(stale),
+            Err(TreeError::VersionMismatch {
+                expected: 0,
+                found: 1
+            })
+        );
+        assert_eq!(
+            primary.export_deltas_since(3),
+            Err(TreeError::VersionMismatch {
+                expected: 2,
+                found: 3
+            })
+        );
+
+        primary.forget_until(1);
+        assert_eq!(
+            primary.export_deltas_since(0),
+            Err(TreeError::VersionMismatch {
+                expected: 1,
+                found: 0
+            })
+        );
+        replica.update(0, 5).unwrap();
+        replica
+            .apply_deltas(primary.export_deltas_since(1).unwrap())
+            .unwrap();
+        assert_eq!(replica.query(7).unwrap(), 6);
+        assert_eq!(replica.into_inner().query(1).unwrap(), 5);
+    }
+
+    #[test]
+    fn failed_deltas_are_not_applied() {
+        let mut replica = VersionedFenwickTree::new(FixedSizeFenwickTree::<i32>::new(4));
+        let deltas = DeltaSet {
+            since: 0,
+            version: 2,
+            deltas: vec![(1, 5), (9, 1)],
+        };
+
+        for _attempt in 0..2 {
+            assert_eq!(
+                replica.apply_deltas(deltas.clone()),
+                Err(TreeError::IndexOutOfBounds { index: 9, bound: 4 })
+            );
+            assert_eq!(replica.query(3).unwrap(), 0);
+            assert_eq!(replica.version(), 0);
+        }
+        assert_eq!(replica.export_deltas_since(0).unwrap().deltas, vec![]);
+    }
+}
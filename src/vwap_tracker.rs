@@ -0,0 +1,115 @@
+use crate::{bucket_sum, FenwickWriter, GrowingFenwickTree, TreeError};
+
+/// Volume weighted average price tracker.
+///
+/// Maintains a pair of trees keyed by time bucket: one aggregates `price * volume`
+/// and another one aggregates `volume`. VWAP of any bucket range is then a ratio of
+/// two range sums.
+pub struct VwapTracker {
+    notional: GrowingFenwickTree<f64>,
+    volume: GrowingFenwickTree<f64>,
+}
+
+impl Default for VwapTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VwapTracker {
+    pub fn new() -> Self {
+        Self {
+            notional: GrowingFenwickTree::new(0),
+            volume: GrowingFenwickTree::new(0),
+        }
+    }
+
+    /// Records a trade of `volume` units at `price` within time `bucket`.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `price * volume` or `volume` is NaN, if
+    /// `bucket` is `usize::MAX`, or if memory for new buckets can't be allocated. Both
+    /// trees are left untouched then.
+    ///
+    pub fn record(&mut self, bucket: usize, price: f64, volume: f64) -> Result<(), TreeError> {
+        let notional = price * volume;
+        // Both trees are checked and grown first, so updates below can't fail half way.
+        self.notional.grow_for(bucket, &notional)?;
+        self.volume.grow_for(bucket, &volume)?;
+        self.notional.update(bucket, notional)?;
+        self.volume.update(bucket, volume)
+    }
+
+    /// Returns VWAP of all trades recorded in buckets between `from` and `to`
+    /// (including edges), or [`None`] if no volume was traded in that range.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `from` is greater than `to`.
+    ///
+    pub fn vwap_between(&self, from: usize, to: usize) -> Result<Option<f64>, TreeError> {
+        if from > to {
+            return Err(TreeError::InvalidRange { from, to });
+        }
+
+        let volume = bucket_sum(&self.volume, from, to)?;
+        if volume == 0.0 {
+            return Ok(None);
+        }
+
+        let notional = bucket_sum(&self.notional, from, to)?;
+        Ok(Some(notional / volume))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vwap_tracker::VwapTracker;
+    use crate::TreeError;
+
+    #[test]
+    fn empty_tracker_has_no_vwap() {
+        let tracker = VwapTracker::new();
+        assert_eq!(tracker.vwap_between(0, 100).unwrap(), None);
+    }
+
+    #[test]
+    fn vwap_between_buckets() {
+        let mut tracker = VwapTracker::new();
+        tracker.record(0, 10.0, 1.0).unwrap();
+        tracker.record(1, 20.0, 3.0).unwrap();
+        tracker.record(1, 30.0, 1.0).unwrap();
+        tracker.record(5, 12.0, 2.0).unwrap();
+
+        assert_eq!(tracker.vwap_between(0, 0).unwrap(), Some(10.0));
+        assert_eq!(tracker.vwap_between(1, 1).unwrap(), Some(22.5));
+        assert_eq!(tracker.vwap_between(0, 1).unwrap(), Some(20.0));
+        assert_eq!(tracker.vwap_between(2, 4).unwrap(), None);
+        assert_eq!(
+            tracker.vwap_between(1, 0),
+            Err(TreeError::InvalidRange { from: 1, to: 0 })
+        );
+        assert_eq!(tracker.vwap_between(0, 10).unwrap(), Some(124.0 / 7.0));
+    }
+
+    #[test]
+    fn rejected_trades_leave_both_trees_untouched() {
+        let mut tracker = VwapTracker::new();
+        tracker.record(0, 10.0, 2.0).unwrap();
+
+        assert_eq!(
+            tracker.record(0, f64::INFINITY, 0.0),
+            Err(TreeError::InvalidValue)
+        );
+        assert_eq!(
+            tracker.record(1, 10.0, f64::NAN),
+            Err(TreeError::InvalidValue)
+        );
+        assert_eq!(
+            tracker.record(usize::MAX, 10.0, 1.0),
+            Err(TreeError::Overflow)
+        );
+        assert_eq!(tracker.vwap_between(0, 0).unwrap(), Some(10.0));
+    }
+}
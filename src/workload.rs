@@ -0,0 +1,290 @@
+//! Reproducible random workloads for stress-testing and benchmarking trees and
+//! wrappers around them, available with `workload` feature. Sequences are generated
+//! from a seed, so a failing run can be replayed.
+//!
+//! ```rust
+//! use fenwick_bit_tree::prelude::*;
+//! use fenwick_bit_tree::workload::{NaiveTree, Pattern, Workload};
+//!
+//! let mut tree = GrowingFenwickTree::<i64>::new(0);
+//! let mut naive = NaiveTree::new(64);
+//!
+//! let workload = Workload::new(7, 64).pattern(Pattern::HotIndexes { hot: 4, share: 0.9 });
+//! for operation in workload.take(1_000) {
+//!     assert_eq!(operation.apply(&mut tree), operation.apply(&mut naive));
+//! }
+//! ```
+
+use std::ops::Range;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::{FenwickReader, FenwickTree, FenwickWriter, InvertibleValue, TreeError};
+
+/// Defines which indexes operations of a workload touch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pattern {
+    /// Every index is equally likely.
+    Uniform,
+    /// Updates never go below the previously updated index, like appends to a time
+    /// series, queries go up to it.
+    MonotoneGrowth,
+    /// `share` of operations touch one of `hot` indexes picked up front.
+    HotIndexes { hot: usize, share: f64 },
+}
+
+/// Single operation of a workload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Update { idx: usize, delta: i64 },
+    Query { idx: usize },
+    RangeQuery { from: usize, to: usize },
+}
+
+impl Operation {
+    /// Applies operation to `tree`, returning result of queries.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if `tree` rejects the operation.
+    ///
+    pub fn apply<F: FenwickTree<Value = i64> + ?Sized>(
+        &self,
+        tree: &mut F,
+    ) -> Result<Option<i64>, TreeError> {
+        match *self {
+            Operation::Update { idx, delta } => tree.update(idx, delta).map(|_| None),
+            Operation::Query { idx } => tree.query(idx).map(Some),
+            Operation::RangeQuery { from, to } => tree.range_query(from, to).map(Some),
+        }
+    }
+}
+
+/// Endless seeded sequence of [`Operation`]s over indexes `0..size`. Nothing is
+/// generated for size 0.
+pub struct Workload {
+    rng: StdRng,
+    size: usize,
+    pattern: Pattern,
+    hot: Vec<usize>,
+    frontier: usize,
+    query_share: f64,
+    deltas: Range<i64>,
+}
+
+impl Workload {
+    /// Creates uniform workload of equally many updates and queries, with deltas
+    /// within `0..10`, so trees stay non-negative.
+    pub fn new(seed: u64, size: usize) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            size,
+            pattern: Pattern::Uniform,
+            hot: vec![],
+            frontier: 0,
+            query_share: 0.5,
+            deltas: 0..10,
+        }
+    }
+
+    pub fn pattern(mut self, pattern: Pattern) -> Self {
+        if let Pattern::HotIndexes { hot, .. } = pattern {
+            let size = self.size.max(1);
+            self.hot = (0..hot.max(1))
+                .map(|_| self.rng.gen_range(0..size))
+                .collect();
+        }
+        self.pattern = pattern;
+        self
+    }
+
+    /// Share of operations which are queries, half of them are range queries.
+    pub fn query_share(mut self, share: f64) -> Self {
+        self.query_share = share;
+        self
+    }
+
+    /// Range deltas of updates are drawn from.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `deltas` is empty.
+    pub fn deltas(mut self, deltas: Range<i64>) -> Self {
+        assert!(!deltas.is_empty(), "deltas range must not be empty");
+        self.deltas = deltas;
+        self
+    }
+
+    /// Returns updates of the workload as `(idx, delta)` pairs, without any queries.
+    /// Handy for drawing reproducible indexes and values in randomized tests.
+    pub fn updates(self) -> impl Iterator<Item = (usize, i64)> {
+        self.query_share(0.0)
+            .filter_map(|operation| match operation {
+                Operation::Update { idx, delta } => Some((idx, delta)),
+                _ => None,
+            })
+    }
+
+    /// Returns range queries of the workload as `(from, to)` pairs with `from <= to`.
+    pub fn ranges(self) -> impl Iterator<Item = (usize, usize)> {
+        self.query_share(1.0)
+            .filter_map(|operation| match operation {
+                Operation::RangeQuery { from, to } => Some((from, to)),
+                _ => None,
+            })
+    }
+
+    fn index(&mut self, update: bool) -> usize {
+        match self.pattern {
+            Pattern::Uniform => self.rng.gen_range(0..self.size),
+            Pattern::MonotoneGrowth if update => {
+                let idx = self.frontier;
+                self.frontier = (self.frontier + self.rng.gen_range(0..=1)).min(self.size - 1);
+                idx
+            }
+            Pattern::MonotoneGrowth => self.rng.gen_range(0..=self.frontier),
+            Pattern::HotIndexes { share, .. } => match self.rng.gen_bool(share) {
+                true => self.hot[self.rng.gen_range(0..self.hot.len())],
+                false => self.rng.gen_range(0..self.size),
+            },
+        }
+    }
+}
+
+impl Iterator for Workload {
+    type Item = Operation;
+
+    /// # Panics
+    ///
+    /// Panics if query share or share of hot indexes lies outside of `0.0..=1.0`.
+    fn next(&mut self) -> Option<Operation> {
+        if self.size == 0 {
+            return None;
+        }
+        if !self.rng.gen_bool(self.query_share) {
+            let idx = self.index(true);
+            let delta = self.rng.gen_range(self.deltas.clone());
+            return Some(Operation::Update { idx, delta });
+        }
+
+        let idx = self.index(false);
+        if self.rng.gen_bool(0.5) {
+            return Some(Operation::Query { idx });
+        }
+        let other = self.index(false);
+        Some(Operation::RangeQuery {
+            from: idx.min(other),
+            to: idx.max(other),
+        })
+    }
+}
+
+/// Reference tree of plain values with `O(n)` queries, which results of other trees
+/// are compared with. Out of bounds operations are rejected.
+#[derive(Debug, Clone)]
+pub struct NaiveTree<T> {
+    points: Vec<T>,
+}
+
+impl<T: InvertibleValue> NaiveTree<T> {
+    pub fn new(size: usize) -> Self {
+        Self {
+            points: vec![T::default(); size],
+        }
+    }
+
+    fn check_idx(&self, idx: usize) -> Result<(), TreeError> {
+        if idx >= self.points.len() {
+            return Err(TreeError::IndexOutOfBounds {
+                index: idx,
+                bound: self.points.len(),
+            });
+        }
+        Ok(())
+    }
+}
+
+impl<T: InvertibleValue> FenwickReader for NaiveTree<T> {
+    type Value = T;
+
+    fn query(&self, idx: usize) -> Result<T, TreeError> {
+        self.check_idx(idx)?;
+        let mut res = T::default();
+        for point in &self.points[..=idx] {
+            res.store_value(point);
+        }
+        Ok(res)
+    }
+}
+
+impl<T: InvertibleValue> FenwickWriter for NaiveTree<T> {
+    fn update(&mut self, idx: usize, value: T) -> Result<(), TreeError> {
+        self.check_idx(idx)?;
+        self.points[idx].absorb(value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::workload::{NaiveTree, Operation, Pattern, Workload};
+    use crate::{
+        DequeFenwickTree, FenwickTree, FixedSizeFenwickTree, GrowingFenwickTree,
+        OrderedSparseFenwickTree,
+    };
+
+    fn matches_naive(tree: &mut dyn FenwickTree<Value = i64>, workload: Workload) {
+        let mut naive = NaiveTree::new(100);
+        for operation in workload.take(2_000) {
+            assert_eq!(operation.apply(&mut naive), operation.apply(tree));
+        }
+    }
+
+    #[test]
+    fn trees_match_naive_on_every_pattern() {
+        let patterns = [
+            Pattern::Uniform,
+            Pattern::MonotoneGrowth,
+            Pattern::HotIndexes { hot: 3, share: 0.8 },
+        ];
+        for (seed, pattern) in patterns.into_iter().enumerate() {
+            let workload = || {
+                Workload::new(seed as u64, 100)
+                    .pattern(pattern)
+                    .query_share(0.3)
+                    .deltas(-5..5)
+            };
+            matches_naive(&mut FixedSizeFenwickTree::new(100), workload());
+            matches_naive(&mut GrowingFenwickTree::new(0), workload());
+            matches_naive(&mut DequeFenwickTree::new(50), workload());
+            matches_naive(&mut OrderedSparseFenwickTree::new(100), workload());
+        }
+    }
+
+    #[test]
+    fn seeded_workloads_are_reproducible() {
+        let first: Vec<_> = Workload::new(42, 1_000).take(100).collect();
+        let second: Vec<_> = Workload::new(42, 1_000).take(100).collect();
+        assert_eq!(first, second);
+        assert_ne!(
+            first,
+            Workload::new(43, 1_000).take(100).collect::<Vec<_>>()
+        );
+        assert_eq!(Workload::new(42, 0).next(), None);
+    }
+
+    #[test]
+    fn monotone_growth_updates_never_go_back() {
+        let updates: Vec<usize> = Workload::new(1, 50)
+            .pattern(Pattern::MonotoneGrowth)
+            .take(500)
+            .filter_map(|operation| match operation {
+                Operation::Update { idx, .. } => Some(idx),
+                _ => None,
+            })
+            .collect();
+        assert!(updates.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(updates.last(), Some(&49));
+    }
+}